@@ -0,0 +1,157 @@
+// Baseline timings for the indexing and query pipeline. Runs entirely offline:
+//
+//     cargo bench --bench indexing
+//
+// The tokenizer has no stemming option, so the comparison groups cover the choices the
+// pipeline does have: on-disk index format (JSON vs binary) and scoring through the
+// sparse inverted index vs dense per-document dot products.
+use std::hint::black_box;
+use std::path::Path;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use tempfile::TempDir;
+use voltai::{
+    bm25_scores, cosine_similarity, dot_product, index_dir, load_index, retrieve, tokenize, Index,
+    IndexFormat, QueryOptions,
+};
+
+const VOCAB: &[&str] = &[
+    "kubernetes",
+    "docker",
+    "container",
+    "network",
+    "storage",
+    "volume",
+    "cluster",
+    "node",
+    "deployment",
+    "service",
+    "ingress",
+    "secret",
+    "config",
+    "pod",
+    "namespace",
+    "helm",
+    "contract",
+    "invoice",
+    "delivery",
+    "schedule",
+    "payment",
+    "audit",
+    "report",
+    "budget",
+    "solar",
+    "battery",
+    "inverter",
+    "grid",
+    "turbine",
+    "energy",
+    "forecast",
+    "demand",
+];
+
+const DOCS: usize = 100;
+const WORDS_PER_DOC: usize = 200;
+const QUERY: &str = "kubernetes cluster storage volume";
+
+/// `n` words drawn from a sliding window of `VOCAB`, offset by `seed`, with stop words
+/// mixed in so tokenisation has something to filter.
+fn synthetic_text(seed: usize, n: usize) -> String {
+    let offset = (seed * 7) % VOCAB.len();
+    (0..n)
+        .map(|j| {
+            if j % 5 == 4 {
+                "the"
+            } else {
+                VOCAB[(offset + j * 3) % VOCAB.len()]
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn write_corpus(dir: &Path) {
+    for i in 0..DOCS {
+        std::fs::write(
+            dir.join(format!("doc_{:03}.txt", i)),
+            synthetic_text(i, WORDS_PER_DOC),
+        )
+        .unwrap();
+    }
+}
+
+/// Dense scoring: the query vector (`idf[t]` for query terms) dotted with every row.
+fn dense_scores(idx: &Index, q_toks: &[String]) -> Vec<f32> {
+    let q_vec: Vec<f32> = idx
+        .terms
+        .iter()
+        .zip(&idx.idf)
+        .map(|(t, &w)| if q_toks.contains(t) { w } else { 0.0 })
+        .collect();
+    idx.vectors.iter().map(|v| dot_product(&q_vec, v)).collect()
+}
+
+fn bench_tokenize(c: &mut Criterion) {
+    let text = synthetic_text(0, 1000);
+    c.bench_function("tokenize/1000_words", |b| {
+        b.iter(|| tokenize(black_box(&text)))
+    });
+}
+
+fn bench_index_dir(c: &mut Criterion) {
+    let corpus = TempDir::new().unwrap();
+    write_corpus(corpus.path());
+    let out_dir = TempDir::new().unwrap();
+
+    let mut group = c.benchmark_group("index_dir/100_docs");
+    group.sample_size(20);
+    for (name, format, file) in [
+        ("json", IndexFormat::Json, "index.json"),
+        ("binary", IndexFormat::Binary, "index.bin"),
+    ] {
+        let out = out_dir.path().join(file);
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| index_dir(corpus.path(), &out, format).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_cosine(c: &mut Criterion) {
+    let a: Vec<f32> = (0..10_000).map(|i| (i % 17) as f32 * 0.1).collect();
+    let b: Vec<f32> = (0..10_000).map(|i| (i % 13) as f32 * 0.2).collect();
+    c.bench_function("cosine_sim/10k", |bench| {
+        bench.iter(|| cosine_similarity(black_box(&a), black_box(&b)))
+    });
+}
+
+fn bench_query(c: &mut Criterion) {
+    let corpus = TempDir::new().unwrap();
+    write_corpus(corpus.path());
+    let index_path = corpus.path().join("index.json");
+    index_dir(corpus.path(), &index_path, IndexFormat::Json).unwrap();
+    let idx = load_index(&index_path).unwrap();
+    let q_toks = tokenize(QUERY);
+
+    c.bench_function("query/100_docs", |b| {
+        b.iter(|| retrieve(&idx, black_box(QUERY), &QueryOptions::default()))
+    });
+
+    let mut group = c.benchmark_group("query_scoring/100_docs");
+    group.bench_function("sparse", |b| {
+        b.iter(|| bm25_scores(&idx, black_box(&q_toks)))
+    });
+    group.bench_function("dense", |b| {
+        b.iter(|| dense_scores(&idx, black_box(&q_toks)))
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_tokenize,
+    bench_index_dir,
+    bench_cosine,
+    bench_query
+);
+criterion_main!(benches);
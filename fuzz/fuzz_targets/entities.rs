@@ -0,0 +1,8 @@
+// Arbitrary UTF-8 through the rule-based entity extractor.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|text: &str| {
+    voltai::extract_entities_from_text(text);
+});
@@ -0,0 +1,15 @@
+// Arbitrary bytes as a JSON index file. Decoding may fail, but must not panic; an index
+// that decodes and validates must also survive a query.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use voltai::{retrieve, Index, QueryOptions};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(idx) = serde_json::from_slice::<Index>(data) else {
+        return;
+    };
+    if idx.validate().is_ok() {
+        retrieve(&idx, "alpha beta", &QueryOptions::default());
+    }
+});
@@ -0,0 +1,221 @@
+// Approximate nearest-neighbour candidate generation over document vectors (HNSW, via hnsw_rs).
+// The graph only proposes candidates; callers re-score them exactly with BM25.
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use hnsw_rs::prelude::*;
+
+/// Default search breadth for `--ann-ef`: number of candidates explored (and returned)
+/// per query. Higher values trade latency for recall.
+pub const DEFAULT_ANN_EF: usize = 64;
+
+/// Graph parameters. 16 links per node and `ef_construction = 200` are the usual
+/// HNSW defaults and keep recall well above 90% for corpora of this size.
+const MAX_CONNECTIONS: usize = 16;
+const MAX_LAYERS: usize = 16;
+const EF_CONSTRUCTION: usize = 200;
+
+/// HNSW graph over the rows of `Index::vectors`, keyed by document position.
+pub struct AnnIndex {
+    hnsw: Hnsw<'static, f32, DistCosine>,
+}
+
+impl fmt::Debug for AnnIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AnnIndex")
+            .field("points", &self.hnsw.get_nb_point())
+            .finish()
+    }
+}
+
+impl AnnIndex {
+    /// Builds the graph from document vectors. All-zero vectors (empty documents) are
+    /// left out: cosine distance treats them as closest to everything.
+    pub fn build(vectors: &[Vec<f32>]) -> Self {
+        let mut hnsw = Hnsw::new(
+            MAX_CONNECTIONS,
+            vectors.len().max(1),
+            MAX_LAYERS,
+            EF_CONSTRUCTION,
+            DistCosine {},
+        );
+        let points: Vec<(&Vec<f32>, usize)> = vectors
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.iter().any(|&w| w != 0.0))
+            .map(|(i, v)| (v, i))
+            .collect();
+        hnsw.parallel_insert(&points);
+        hnsw.set_searching_mode(true);
+        AnnIndex { hnsw }
+    }
+
+    /// Returns up to `n` document positions nearest to `query` by cosine distance,
+    /// exploring `ef` candidates (clamped to at least `n`).
+    pub fn search(&self, query: &[f32], n: usize, ef: usize) -> Vec<usize> {
+        if self.hnsw.get_nb_point() == 0 || n == 0 {
+            return Vec::new();
+        }
+        self.hnsw
+            .search(query, n, ef.max(n))
+            .into_iter()
+            .map(|nb| nb.d_id)
+            .collect()
+    }
+
+    /// Writes the graph next to `index_path` as `<file>.hnsw.graph` / `<file>.hnsw.data`,
+    /// replacing any previous dump.
+    pub fn dump(&self, index_path: &Path) -> Result<()> {
+        let (dir, basename) = dump_location(index_path)?;
+        self.hnsw.file_dump(&dir, &basename)?;
+        Ok(())
+    }
+
+    /// Reloads the graph dumped for `index_path`, or `None` when the index has no ANN sidecar.
+    pub fn load(index_path: &Path) -> Result<Option<Self>> {
+        if !graph_path(index_path).exists() {
+            return Ok(None);
+        }
+        let (dir, basename) = dump_location(index_path)?;
+        // The reloaded graph borrows its loader. `HnswIo` only holds a directory and a
+        // basename, so leaking it once per load is cheap and lets the graph be `'static`.
+        let io: &'static mut HnswIo = Box::leak(Box::new(HnswIo::new(&dir, &basename)));
+        let mut hnsw: Hnsw<'static, f32, DistCosine> = io.load_hnsw().map_err(|e| {
+            anyhow!(
+                "failed to load ANN graph for {}: {}",
+                index_path.display(),
+                e
+            )
+        })?;
+        hnsw.set_searching_mode(true);
+        Ok(Some(AnnIndex { hnsw }))
+    }
+}
+
+/// Path of the graph file written by [`AnnIndex::dump`] for `index_path`.
+pub fn graph_path(index_path: &Path) -> PathBuf {
+    let mut name = index_path.file_name().unwrap_or_default().to_os_string();
+    name.push(".hnsw.graph");
+    index_path.with_file_name(name)
+}
+
+/// Deletes the graph and data files dumped for `index_path`, if any.
+pub fn remove_dump(index_path: &Path) -> Result<()> {
+    let graph = graph_path(index_path);
+    let data = graph.with_extension("data");
+    for p in [graph, data] {
+        if p.exists() {
+            std::fs::remove_file(&p)?;
+        }
+    }
+    Ok(())
+}
+
+fn dump_location(index_path: &Path) -> Result<(PathBuf, String)> {
+    let basename = index_path
+        .file_name()
+        .map(|s| s.to_string_lossy().into_owned())
+        .ok_or_else(|| anyhow!("invalid index path: {}", index_path.display()))?;
+    let dir = match index_path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    Ok((dir, basename))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// Deterministic sparse vectors: each row activates a sliding window of dimensions.
+    fn synthetic_vectors(rows: usize, dims: usize) -> Vec<Vec<f32>> {
+        (0..rows)
+            .map(|i| {
+                let mut v = vec![0.0_f32; dims];
+                for j in 0..12 {
+                    let d = (i * 7 + j * 3) % dims;
+                    v[d] += 1.0 + ((i + j) % 5) as f32 * 0.25;
+                }
+                v
+            })
+            .collect()
+    }
+
+    fn cosine(a: &[f32], b: &[f32]) -> f32 {
+        let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        let na: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let nb: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if na == 0.0 || nb == 0.0 {
+            0.0
+        } else {
+            dot / (na * nb)
+        }
+    }
+
+    #[test]
+    fn test_ann_top_k_overlaps_exact_top_k() {
+        let dims = 64;
+        let vectors = synthetic_vectors(500, dims);
+        let ann = AnnIndex::build(&vectors);
+        let k = 10;
+
+        let mut hits = 0;
+        let mut total = 0;
+        for q in 0..20 {
+            let query = &vectors[q * 23];
+            let mut exact: Vec<(usize, f32)> = vectors
+                .iter()
+                .enumerate()
+                .map(|(i, v)| (i, cosine(query, v)))
+                .collect();
+            exact.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+            // Count against the exact k-th score so tied neighbours are not penalised.
+            let kth = exact[k - 1].1;
+            let exact_set: HashSet<usize> = exact
+                .iter()
+                .take_while(|(_, s)| *s >= kth - 1e-6)
+                .map(|(i, _)| *i)
+                .collect();
+            let approx = ann.search(query, k, DEFAULT_ANN_EF);
+            hits += approx.iter().filter(|i| exact_set.contains(i)).count();
+            total += k;
+        }
+        let overlap = hits as f32 / total as f32;
+        assert!(overlap >= 0.9, "ANN overlap {:.2} below 0.9", overlap);
+    }
+
+    #[test]
+    fn test_ann_dump_and_load_roundtrip() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let index_path = dir.path().join("idx.json");
+        let vectors = synthetic_vectors(50, 32);
+        let ann = AnnIndex::build(&vectors);
+        ann.dump(&index_path).unwrap();
+        assert!(graph_path(&index_path).exists());
+
+        let loaded = AnnIndex::load(&index_path)
+            .unwrap()
+            .expect("sidecar present");
+        assert_eq!(
+            loaded.search(&vectors[7], 1, DEFAULT_ANN_EF),
+            ann.search(&vectors[7], 1, DEFAULT_ANN_EF)
+        );
+    }
+
+    #[test]
+    fn test_ann_load_without_sidecar_is_none() {
+        let dir = tempfile::TempDir::new().unwrap();
+        assert!(AnnIndex::load(&dir.path().join("idx.json"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_ann_skips_empty_vectors() {
+        let vectors = vec![vec![0.0, 0.0], vec![1.0, 0.0]];
+        let ann = AnnIndex::build(&vectors);
+        assert_eq!(ann.search(&[1.0, 0.0], 5, DEFAULT_ANN_EF), vec![1]);
+    }
+}
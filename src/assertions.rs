@@ -0,0 +1,169 @@
+// Retrieval assertions for `voltai assert`: queries that must return a given document
+// within the top ranks, checked in CI.
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+use crate::{retrieve, Index, QueryOptions};
+
+/// One entry of an assertions file.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Assertion {
+    pub query: String,
+    /// Path of the expected document, as indexed or relative to the indexed directory.
+    pub expected_doc_path: String,
+    /// Lowest acceptable rank, counting from 1.
+    #[serde(default = "default_min_rank")]
+    pub min_rank: usize,
+}
+
+fn default_min_rank() -> usize {
+    1
+}
+
+/// Outcome of [`run_assertion`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssertionResult {
+    pub assertion: Assertion,
+    /// Rank of the expected document, if it was within `min_rank`.
+    pub rank: Option<usize>,
+}
+
+impl AssertionResult {
+    pub fn passed(&self) -> bool {
+        self.rank.is_some()
+    }
+}
+
+#[derive(Deserialize)]
+struct TomlAssertions {
+    assertions: Vec<Assertion>,
+}
+
+/// Reads a JSON array of assertions, or a TOML file of `[[assertions]]` tables.
+pub fn load_assertions(path: &Path) -> Result<Vec<Assertion>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+    let parsed = if path.extension().is_some_and(|ext| ext == "toml") {
+        toml::from_str::<TomlAssertions>(&text)
+            .map(|t| t.assertions)
+            .map_err(anyhow::Error::from)
+    } else {
+        serde_json::from_str(&text).map_err(anyhow::Error::from)
+    };
+    parsed.map_err(|e| anyhow!("invalid assertions file {}: {}", path.display(), e))
+}
+
+fn same_doc(indexed: &str, expected: &str) -> bool {
+    indexed == expected || Path::new(indexed).ends_with(expected)
+}
+
+/// Retrieves the top `min_rank` documents for the assertion's query and finds the expected
+/// one among them.
+pub fn run_assertion(index: &Index, assertion: &Assertion) -> AssertionResult {
+    let opts = QueryOptions {
+        k: assertion.min_rank,
+        ..Default::default()
+    };
+    let rank = retrieve(index, &assertion.query, &opts)
+        .iter()
+        .position(|r| same_doc(&r.path, &assertion.expected_doc_path))
+        .map(|i| i + 1);
+    AssertionResult {
+        assertion: assertion.clone(),
+        rank,
+    }
+}
+
+/// One `PASS`/`FAIL` line per result, then a summary line.
+pub fn write_assertion_results(results: &[AssertionResult], out: &mut dyn Write) -> Result<()> {
+    for r in results {
+        let a = &r.assertion;
+        match r.rank {
+            Some(rank) => writeln!(
+                out,
+                "PASS  {:?} -> {} (rank {})",
+                a.query, a.expected_doc_path, rank
+            )?,
+            None => writeln!(
+                out,
+                "FAIL  {:?} -> {} (not in top {})",
+                a.query, a.expected_doc_path, a.min_rank
+            )?,
+        }
+    }
+    let failed = results.iter().filter(|r| !r.passed()).count();
+    writeln!(out, "{} passed, {} failed", results.len() - failed, failed)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{index_dir, load_index, IndexFormat};
+
+    #[test]
+    fn test_passing_and_failing_assertions() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("rust.txt"), "rust ownership keeps memory safe")?;
+        std::fs::write(docs.join("sauce.txt"), "slow cooked tomato sauce recipe")?;
+        let index_path = dir.path().join("idx.json");
+        index_dir(&docs, &index_path, IndexFormat::Json)?;
+        let idx = load_index(&index_path)?;
+
+        let file = dir.path().join("assertions.toml");
+        std::fs::write(
+            &file,
+            "[[assertions]]\nquery = \"rust memory\"\nexpected_doc_path = \"rust.txt\"\nmin_rank = 1\n\n\
+             [[assertions]]\nquery = \"tomato sauce\"\nexpected_doc_path = \"rust.txt\"\n",
+        )?;
+        let assertions = load_assertions(&file)?;
+        assert_eq!(assertions[1].min_rank, 1);
+        let results: Vec<AssertionResult> =
+            assertions.iter().map(|a| run_assertion(&idx, a)).collect();
+        assert!(results[0].passed());
+        assert_eq!(results[0].rank, Some(1));
+        assert!(!results[1].passed());
+
+        let mut out: Vec<u8> = Vec::new();
+        write_assertion_results(&results, &mut out)?;
+        let text = String::from_utf8(out)?;
+        assert!(
+            text.starts_with("PASS  \"rust memory\" -> rust.txt (rank 1)\n"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("FAIL  \"tomato sauce\" -> rust.txt (not in top 1)"),
+            "{}",
+            text
+        );
+        assert!(text.ends_with("1 passed, 1 failed\n"), "{}", text);
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_assertions_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("assertions.json");
+        std::fs::write(
+            &file,
+            r#"[{"query": "q", "expected_doc_path": "docs/a.md", "min_rank": 3}]"#,
+        )?;
+        assert_eq!(
+            load_assertions(&file)?,
+            vec![Assertion {
+                query: "q".to_string(),
+                expected_doc_path: "docs/a.md".to_string(),
+                min_rank: 3,
+            }]
+        );
+        assert!(same_doc("/srv/corpus/docs/a.md", "docs/a.md"));
+        assert!(!same_doc("/srv/corpus/docs/ba.md", "a.md"));
+        Ok(())
+    }
+}
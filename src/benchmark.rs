@@ -0,0 +1,177 @@
+// Query latency on a real index for `voltai benchmark`: each query is run repeatedly and
+// its wall-clock retrieval time summarised as percentiles.
+use std::io::Write;
+use std::time::Instant;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::{retrieve, Index, QueryOptions};
+
+/// Output of `voltai benchmark`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BenchmarkFormat {
+    #[default]
+    Markdown,
+    Json,
+}
+
+/// Latency of one query over the timed iterations, in microseconds.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LatencyStats {
+    pub query: String,
+    pub mean_us: f64,
+    pub p50_us: f64,
+    pub p95_us: f64,
+    pub p99_us: f64,
+    pub max_us: f64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct BenchmarkReport {
+    /// Timed runs per query, after one untimed warm-up run.
+    pub iterations: usize,
+    pub queries: Vec<LatencyStats>,
+    /// Timed queries per second of retrieval time.
+    pub queries_per_sec: f64,
+}
+
+/// Nearest-rank percentile of ascending `sorted`, which must not be empty.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Runs each of `queries` once to warm caches, then `iterations` more times, timing each
+/// retrieval.
+pub fn run_benchmark(
+    idx: &Index,
+    queries: &[String],
+    iterations: usize,
+    opts: &QueryOptions,
+) -> BenchmarkReport {
+    let iterations = iterations.max(1);
+    let mut total_secs = 0.0;
+    let stats = queries
+        .iter()
+        .map(|q| {
+            retrieve(idx, q, opts);
+            let mut times: Vec<f64> = (0..iterations)
+                .map(|_| {
+                    let start = Instant::now();
+                    retrieve(idx, q, opts);
+                    start.elapsed().as_secs_f64()
+                })
+                .collect();
+            total_secs += times.iter().sum::<f64>();
+            times.iter_mut().for_each(|t| *t *= 1e6);
+            times.sort_by(f64::total_cmp);
+            LatencyStats {
+                query: q.clone(),
+                mean_us: times.iter().sum::<f64>() / times.len() as f64,
+                p50_us: percentile(&times, 0.50),
+                p95_us: percentile(&times, 0.95),
+                p99_us: percentile(&times, 0.99),
+                max_us: times[times.len() - 1],
+            }
+        })
+        .collect();
+    BenchmarkReport {
+        iterations,
+        queries: stats,
+        queries_per_sec: if total_secs > 0.0 {
+            (queries.len() * iterations) as f64 / total_secs
+        } else {
+            0.0
+        },
+    }
+}
+
+pub fn write_benchmark(
+    report: &BenchmarkReport,
+    format: BenchmarkFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    match format {
+        BenchmarkFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, report)?;
+            writeln!(out)?;
+        }
+        BenchmarkFormat::Markdown => {
+            writeln!(out, "| Query | Mean (µs) | p50 | p95 | p99 | Max |")?;
+            writeln!(out, "|---|---:|---:|---:|---:|---:|")?;
+            for s in &report.queries {
+                writeln!(
+                    out,
+                    "| {} | {:.1} | {:.1} | {:.1} | {:.1} | {:.1} |",
+                    s.query.replace('|', "\\|"),
+                    s.mean_us,
+                    s.p50_us,
+                    s.p95_us,
+                    s.p99_us,
+                    s.max_us
+                )?;
+            }
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{} queries × {} iterations: {:.0} queries/sec",
+                report.queries.len(),
+                report.iterations,
+                report.queries_per_sec
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{index_dir, load_index, IndexFormat};
+
+    #[test]
+    fn test_percentile_nearest_rank() {
+        let sorted: Vec<f64> = (1..=100).map(f64::from).collect();
+        assert_eq!(percentile(&sorted, 0.50), 50.0);
+        assert_eq!(percentile(&sorted, 0.99), 99.0);
+        assert_eq!(percentile(&[7.0], 0.95), 7.0);
+    }
+
+    #[test]
+    fn test_benchmark_reports_every_query() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("rust.txt"), "rust ownership keeps memory safe")?;
+        std::fs::write(docs.join("go.txt"), "go has a garbage collector")?;
+        std::fs::write(docs.join("sauce.txt"), "slow cooked tomato sauce recipe")?;
+        let index_path = dir.path().join("idx.json");
+        index_dir(&docs, &index_path, IndexFormat::Json)?;
+        let idx = load_index(&index_path)?;
+        let queries: Vec<String> = ["rust", "memory", "garbage collector", "tomato", "unknown"]
+            .map(String::from)
+            .to_vec();
+
+        let report = run_benchmark(&idx, &queries, 5, &QueryOptions::default());
+        assert_eq!(report.queries.len(), 5);
+        for s in &report.queries {
+            assert!(s.mean_us > 0.0, "{:?}", s);
+            assert!(s.p99_us >= s.p50_us && s.max_us >= s.p99_us, "{:?}", s);
+        }
+        assert!(report.queries_per_sec > 0.0);
+
+        let mut out: Vec<u8> = Vec::new();
+        write_benchmark(&report, BenchmarkFormat::Markdown, &mut out)?;
+        let table = String::from_utf8(out)?;
+        assert!(table.starts_with("| Query | Mean (µs) |"), "{}", table);
+        assert!(table.contains("| garbage collector |"), "{}", table);
+
+        let mut out: Vec<u8> = Vec::new();
+        write_benchmark(&report, BenchmarkFormat::Json, &mut out)?;
+        let json: serde_json::Value = serde_json::from_slice(&out)?;
+        assert_eq!(json["queries"].as_array().map(Vec::len), Some(5));
+        Ok(())
+    }
+}
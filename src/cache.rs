@@ -0,0 +1,383 @@
+// On-disk cache of retrieval results for `voltai query --cache-dir`, the query history
+// shown by `voltai search-history`, and the LLM response cache of
+// `voltai query --cache-ollama-responses`.
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::QueryResult;
+
+/// Default lifetime of a cache entry for `--cache-ttl`, in seconds.
+pub const DEFAULT_CACHE_TTL_SECS: u64 = 300;
+
+/// One cached query, stored as `<cache-dir>/<key>.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CacheEntry {
+    pub query: String,
+    pub index: String,
+    /// When the results were computed, in seconds since the Unix epoch.
+    pub created_secs: u64,
+    /// Retrieval settings the results were computed with; a lookup with different
+    /// settings is a miss.
+    pub options: String,
+    pub results: Vec<QueryResult>,
+}
+
+/// Cache key for `query` against the index at `index_path`: the hex SHA-256 of the query,
+/// the index path and the index's modification time, so re-indexing invalidates entries.
+pub fn query_cache_key(query: &str, index_path: &Path) -> String {
+    let mtime = std::fs::metadata(index_path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos());
+    let mut hasher = Sha256::new();
+    hasher.update(query.as_bytes());
+    hasher.update([0]);
+    hasher.update(index_path.to_string_lossy().as_bytes());
+    hasher.update([0]);
+    hasher.update(mtime.to_le_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Retrieval cache rooted at `dir`; entries older than `ttl` are ignored.
+#[derive(Debug, Clone)]
+pub struct QueryCache {
+    pub dir: PathBuf,
+    pub ttl: Duration,
+}
+
+impl QueryCache {
+    fn entry_path(&self, query: &str, index_path: &Path) -> PathBuf {
+        self.dir
+            .join(format!("{}.json", query_cache_key(query, index_path)))
+    }
+
+    /// Cached results for `query`, if an entry exists, is younger than the TTL and was
+    /// computed with the same `options`. Unreadable entries count as misses.
+    pub fn get(&self, query: &str, index_path: &Path, options: &str) -> Option<Vec<QueryResult>> {
+        let raw = std::fs::read(self.entry_path(query, index_path)).ok()?;
+        let entry: CacheEntry = serde_json::from_slice(&raw).ok()?;
+        let age = now_secs().saturating_sub(entry.created_secs);
+        (age < self.ttl.as_secs() && entry.options == options).then_some(entry.results)
+    }
+
+    /// Stores `results`, replacing any previous entry for the same query and index.
+    pub fn put(
+        &self,
+        query: &str,
+        index_path: &Path,
+        options: &str,
+        results: &[QueryResult],
+    ) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let entry = CacheEntry {
+            query: query.to_string(),
+            index: index_path.to_string_lossy().into_owned(),
+            created_secs: now_secs(),
+            options: options.to_string(),
+            results: results.to_vec(),
+        };
+        std::fs::write(
+            self.entry_path(query, index_path),
+            serde_json::to_vec(&entry)?,
+        )?;
+        Ok(())
+    }
+}
+
+/// Default file for `query --cache-ollama-responses`.
+pub const DEFAULT_RESPONSE_CACHE_FILE: &str = "voltai_response_cache.json";
+/// Default lifetime of a cached LLM response for `--response-cache-ttl`, in seconds.
+pub const DEFAULT_RESPONSE_CACHE_TTL_SECS: u64 = 86_400;
+
+/// One generated answer in the response cache.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CachedResponse {
+    pub response: String,
+    pub created_secs: u64,
+    /// The user's query the prompt was built for; empty in caches written before it was
+    /// recorded.
+    #[serde(default)]
+    pub query: String,
+}
+
+/// Response cache key: the hex SHA-256 of the model name and the exact prompt.
+pub fn response_cache_key(model: &str, prompt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(model.as_bytes());
+    hasher.update([0]);
+    hasher.update(prompt.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// LLM answers keyed by prompt hash, in a single JSON file; entries older than `ttl` are
+/// ignored and dropped on the next write.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    pub path: PathBuf,
+    pub ttl: Duration,
+}
+
+impl ResponseCache {
+    /// A missing or unreadable cache file is an empty cache.
+    fn load(&self) -> BTreeMap<String, CachedResponse> {
+        std::fs::read(&self.path)
+            .ok()
+            .and_then(|raw| serde_json::from_slice(&raw).ok())
+            .unwrap_or_default()
+    }
+
+    fn is_fresh(&self, entry: &CachedResponse) -> bool {
+        now_secs().saturating_sub(entry.created_secs) < self.ttl.as_secs()
+    }
+
+    /// The cached answer of `model` to `prompt`, if one is younger than the TTL.
+    pub fn get(&self, model: &str, prompt: &str) -> Option<String> {
+        self.load()
+            .remove(&response_cache_key(model, prompt))
+            .filter(|entry| self.is_fresh(entry))
+            .map(|entry| entry.response)
+    }
+
+    /// Stores `response` to the prompt built for `query`, replacing any previous answer to
+    /// the same prompt.
+    pub fn put(&self, model: &str, prompt: &str, query: &str, response: &str) -> Result<()> {
+        let mut entries = self.load();
+        entries.retain(|_, entry| self.is_fresh(entry));
+        entries.insert(
+            response_cache_key(model, prompt),
+            CachedResponse {
+                response: response.to_string(),
+                created_secs: now_secs(),
+                query: query.to_string(),
+            },
+        );
+        std::fs::write(&self.path, serde_json::to_vec_pretty(&entries)?)?;
+        Ok(())
+    }
+
+    /// Every entry with its key, most recent first, expired ones included until the next
+    /// write drops them.
+    pub fn entries(&self) -> Vec<(String, CachedResponse)> {
+        let mut entries: Vec<(String, CachedResponse)> = self.load().into_iter().collect();
+        entries.sort_by_key(|(_, e)| std::cmp::Reverse(e.created_secs));
+        entries
+    }
+
+    /// The entry whose key is `hash` or starts with it, for `query --from-cache`. The TTL
+    /// is not applied: replaying an answer is an explicit request for it.
+    pub fn find(&self, hash: &str) -> Result<CachedResponse> {
+        let hash = hash.trim().to_lowercase();
+        let mut matches: Vec<(String, CachedResponse)> = self
+            .load()
+            .into_iter()
+            .filter(|(key, _)| !hash.is_empty() && key.starts_with(&hash))
+            .collect();
+        match matches.len() {
+            1 => Ok(matches.remove(0).1),
+            0 => Err(anyhow!(
+                "no cached response {} in {}; run `voltai query --list-cache` to see the hashes",
+                hash,
+                self.path.display()
+            )),
+            n => Err(anyhow!(
+                "{} cached responses start with {}; give more of the hash",
+                n,
+                hash
+            )),
+        }
+    }
+}
+
+/// Deletes the response cache at `path`; returns whether there was one.
+pub fn clear_response_cache(path: &Path) -> Result<bool> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Every entry in `dir`, most recent first, expired ones included. Files that are not
+/// cache entries are skipped.
+pub fn history(dir: &Path) -> Result<Vec<CacheEntry>> {
+    let mut entries: Vec<CacheEntry> = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|p| serde_json::from_slice(&std::fs::read(p).ok()?).ok())
+        .collect();
+    entries.sort_by(|a, b| {
+        b.created_secs
+            .cmp(&a.created_secs)
+            .then_with(|| a.query.cmp(&b.query))
+    });
+    Ok(entries)
+}
+
+/// `secs` since the Unix epoch as `YYYY-MM-DD HH:MM:SS UTC`.
+pub fn format_utc(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil-from-days (Howard Hinnant), valid for any date after 1970.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(dir: &Path, ttl_secs: u64) -> QueryCache {
+        QueryCache {
+            dir: dir.to_path_buf(),
+            ttl: Duration::from_secs(ttl_secs),
+        }
+    }
+
+    #[test]
+    fn test_key_changes_with_query_and_index_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = dir.path().join("idx.json");
+        std::fs::write(&index, "{}").unwrap();
+        let key = query_cache_key("rust", &index);
+        assert_eq!(key.len(), 64);
+        assert_eq!(key, query_cache_key("rust", &index));
+        assert_ne!(key, query_cache_key("python", &index));
+
+        let later = SystemTime::now() + Duration::from_secs(60);
+        std::fs::File::options()
+            .write(true)
+            .open(&index)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_ne!(key, query_cache_key("rust", &index));
+    }
+
+    #[test]
+    fn test_get_respects_ttl_and_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = dir.path().join("idx.json");
+        std::fs::write(&index, "{}").unwrap();
+        let c = cache(&dir.path().join("cache"), 60);
+        assert!(c.get("q", &index, "k=3").is_none());
+
+        c.put("q", &index, "k=3", &[]).unwrap();
+        assert_eq!(c.get("q", &index, "k=3"), Some(Vec::new()));
+        assert!(c.get("q", &index, "k=5").is_none());
+        assert!(cache(&c.dir, 0).get("q", &index, "k=3").is_none());
+    }
+
+    #[test]
+    fn test_history_lists_newest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = dir.path().join("idx.json");
+        std::fs::write(&index, "{}").unwrap();
+        let c = cache(dir.path(), 60);
+        c.put("first", &index, "", &[]).unwrap();
+        c.put("second", &index, "", &[]).unwrap();
+        // Age the first entry so the order does not depend on the clock.
+        let path = c.entry_path("first", &index);
+        let mut entry: CacheEntry = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        entry.created_secs -= 10;
+        std::fs::write(&path, serde_json::to_vec(&entry).unwrap()).unwrap();
+        std::fs::write(dir.path().join("notes.json"), "not an entry").unwrap();
+
+        let queries: Vec<String> = history(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|e| e.query)
+            .collect();
+        assert_eq!(queries, vec!["second", "first"]);
+    }
+
+    #[test]
+    fn test_response_cache_round_trip_and_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        let c = ResponseCache {
+            path: dir.path().join(DEFAULT_RESPONSE_CACHE_FILE),
+            ttl: Duration::from_secs(60),
+        };
+        assert!(c.get("llama3", "prompt").is_none());
+        c.put("llama3", "prompt", "q", "answer").unwrap();
+        assert_eq!(c.get("llama3", "prompt").as_deref(), Some("answer"));
+        // The same prompt to another model, or an expired entry, is a miss.
+        assert!(c.get("mistral", "prompt").is_none());
+        let expired = ResponseCache {
+            ttl: Duration::ZERO,
+            ..c.clone()
+        };
+        assert!(expired.get("llama3", "prompt").is_none());
+
+        assert!(clear_response_cache(&c.path).unwrap());
+        assert!(!clear_response_cache(&c.path).unwrap());
+        assert!(c.get("llama3", "prompt").is_none());
+    }
+
+    #[test]
+    fn test_find_response_by_hash_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let c = ResponseCache {
+            path: dir.path().join(DEFAULT_RESPONSE_CACHE_FILE),
+            ttl: Duration::from_secs(60),
+        };
+        c.put("llama3", "prompt one", "first question", "one")
+            .unwrap();
+        c.put("llama3", "prompt two", "second question", "two")
+            .unwrap();
+        let entries = c.entries();
+        assert_eq!(entries.len(), 2);
+        let (key, entry) = entries
+            .iter()
+            .find(|(_, e)| e.query == "first question")
+            .unwrap();
+        assert_eq!(key, &response_cache_key("llama3", "prompt one"));
+        assert_eq!(c.find(key).unwrap(), *entry);
+        assert_eq!(c.find(&key[..12].to_uppercase()).unwrap().response, "one");
+        assert!(c.find("").is_err());
+        assert!(c.find("not-a-hash").is_err());
+
+        // Older caches have no query recorded.
+        std::fs::write(
+            &c.path,
+            r#"{"abc": {"response": "old", "created_secs": 1}}"#,
+        )
+        .unwrap();
+        assert_eq!(c.find("abc").unwrap().query, "");
+    }
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(0), "1970-01-01 00:00:00 UTC");
+        assert_eq!(format_utc(951_782_400), "2000-02-29 00:00:00 UTC");
+        assert_eq!(format_utc(1_700_000_000), "2023-11-14 22:13:20 UTC");
+    }
+}
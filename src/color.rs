@@ -0,0 +1,84 @@
+// ANSI colouring for terminal output (`--color` / `--no-color`): paths in blue, scores in
+// yellow, keywords in green; LLM responses keep the terminal's default colour.
+use std::io::{IsTerminal, Write};
+
+const BLUE: &str = "\x1b[34m";
+const YELLOW: &str = "\x1b[33m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// Whether to colour output: forced by `--color` or `--no-color`, otherwise only when
+/// stdout is a terminal and `NO_COLOR` is unset.
+pub fn enabled(force_color: bool, no_color: bool) -> bool {
+    if force_color || no_color {
+        return force_color;
+    }
+    std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// Writes output tokens, wrapped in ANSI colour codes when `color` is set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ColorPrinter {
+    pub color: bool,
+}
+
+impl ColorPrinter {
+    fn paint(&self, out: &mut dyn Write, code: &str, text: &str) -> std::io::Result<()> {
+        if self.color {
+            write!(out, "{}{}{}", code, text, RESET)
+        } else {
+            write!(out, "{}", text)
+        }
+    }
+
+    pub fn print_path(&self, out: &mut dyn Write, path: &str) -> std::io::Result<()> {
+        self.paint(out, BLUE, path)
+    }
+
+    /// Scores are printed with four decimals, as in uncoloured output.
+    pub fn print_score(&self, out: &mut dyn Write, score: f32) -> std::io::Result<()> {
+        self.paint(out, YELLOW, &format!("{:.4}", score))
+    }
+
+    pub fn print_keyword(&self, out: &mut dyn Write, keyword: &str) -> std::io::Result<()> {
+        self.paint(out, GREEN, keyword)
+    }
+
+    pub fn print_response(&self, out: &mut dyn Write, response: &str) -> std::io::Result<()> {
+        write!(out, "{}", response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(color: bool) -> String {
+        let p = ColorPrinter { color };
+        let mut out = Vec::new();
+        p.print_path(&mut out, "notes.txt").unwrap();
+        p.print_score(&mut out, 0.5).unwrap();
+        p.print_keyword(&mut out, "rust").unwrap();
+        p.print_response(&mut out, "answer").unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_plain_printer_emits_no_escapes() {
+        assert_eq!(render(false), "notes.txt0.5000rustanswer");
+    }
+
+    #[test]
+    fn test_color_printer_wraps_tokens() {
+        assert_eq!(
+            render(true),
+            "\x1b[34mnotes.txt\x1b[0m\x1b[33m0.5000\x1b[0m\x1b[32mrust\x1b[0manswer"
+        );
+    }
+
+    #[test]
+    fn test_flags_override_detection() {
+        assert!(enabled(true, false));
+        assert!(!enabled(false, true));
+    }
+}
@@ -0,0 +1,141 @@
+// Sparse term co-occurrence counts over indexed documents, exported as JSONL for
+// downstream embedding or topic-model tooling.
+use std::collections::HashMap;
+use std::io::Write;
+
+use anyhow::Result;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::{tokenize, Doc};
+
+/// One row of the exported matrix. `term_a < term_b` lexicographically; the matrix is
+/// symmetric, so each unordered pair appears once.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Cooccurrence {
+    pub term_a: String,
+    pub term_b: String,
+    pub count: usize,
+}
+
+/// Counts pairs of distinct terms occurring at most `window` tokens apart (so `window = 1`
+/// counts adjacent tokens) across `docs`, after stop-word filtering. Pairs seen fewer than
+/// `min_count` times are dropped. Rows are ordered by count, then by terms.
+pub fn cooccurrence_counts(docs: &[Doc], window: usize, min_count: usize) -> Vec<Cooccurrence> {
+    let counts: HashMap<(String, String), usize> = docs
+        .par_iter()
+        .map(|doc| {
+            let toks = tokenize(&doc.text);
+            let mut local: HashMap<(String, String), usize> = HashMap::new();
+            for (i, a) in toks.iter().enumerate() {
+                for b in toks.iter().skip(i + 1).take(window) {
+                    if a == b {
+                        continue;
+                    }
+                    let key = if a < b {
+                        (a.clone(), b.clone())
+                    } else {
+                        (b.clone(), a.clone())
+                    };
+                    *local.entry(key).or_insert(0) += 1;
+                }
+            }
+            local
+        })
+        .reduce(HashMap::new, |mut acc, local| {
+            for (k, v) in local {
+                *acc.entry(k).or_insert(0) += v;
+            }
+            acc
+        });
+
+    let mut rows: Vec<Cooccurrence> = counts
+        .into_iter()
+        .filter(|&(_, count)| count >= min_count)
+        .map(|((term_a, term_b), count)| Cooccurrence {
+            term_a,
+            term_b,
+            count,
+        })
+        .collect();
+    rows.sort_by(|x, y| {
+        y.count
+            .cmp(&x.count)
+            .then_with(|| x.term_a.cmp(&y.term_a))
+            .then_with(|| x.term_b.cmp(&y.term_b))
+    });
+    rows
+}
+
+/// Writes one JSON object per line.
+pub fn write_jsonl(rows: &[Cooccurrence], out: &mut dyn Write) -> Result<()> {
+    for row in rows {
+        serde_json::to_writer(&mut *out, row)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(text: &str) -> Doc {
+        Doc {
+            id: "doc".to_string(),
+            path: "doc.txt".to_string(),
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn count(rows: &[Cooccurrence], a: &str, b: &str) -> Option<usize> {
+        rows.iter()
+            .find(|r| r.term_a == a && r.term_b == b)
+            .map(|r| r.count)
+    }
+
+    #[test]
+    fn test_counts_match_manual_calculation() {
+        let docs = vec![doc("alpha beta gamma"), doc("alpha beta beta")];
+
+        // window 1: doc0 alpha-beta, beta-gamma; doc1 alpha-beta (beta-beta ignored).
+        let adjacent = cooccurrence_counts(&docs, 1, 1);
+        assert_eq!(count(&adjacent, "alpha", "beta"), Some(2));
+        assert_eq!(count(&adjacent, "beta", "gamma"), Some(1));
+        assert_eq!(count(&adjacent, "alpha", "gamma"), None);
+        assert_eq!(adjacent.len(), 2);
+
+        // window 2 adds doc0 alpha-gamma and doc1's second alpha-beta.
+        let wider = cooccurrence_counts(&docs, 2, 1);
+        assert_eq!(count(&wider, "alpha", "beta"), Some(3));
+        assert_eq!(count(&wider, "alpha", "gamma"), Some(1));
+        assert_eq!(count(&wider, "beta", "gamma"), Some(1));
+    }
+
+    #[test]
+    fn test_stop_words_are_skipped_and_min_count_prunes() {
+        // "the" is a stop word, so "solar" and "panel" are adjacent after filtering.
+        let docs = vec![doc("solar the panel"), doc("solar panel wind")];
+        let rows = cooccurrence_counts(&docs, 1, 2);
+        assert_eq!(
+            rows,
+            vec![Cooccurrence {
+                term_a: "panel".to_string(),
+                term_b: "solar".to_string(),
+                count: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_write_jsonl_emits_one_object_per_line() {
+        let rows = cooccurrence_counts(&[doc("alpha beta gamma")], 1, 1);
+        let mut out: Vec<u8> = Vec::new();
+        write_jsonl(&rows, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], r#"{"term_a":"alpha","term_b":"beta","count":1}"#);
+    }
+}
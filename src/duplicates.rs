@@ -0,0 +1,114 @@
+// Duplicate detection for `voltai find-duplicates`: exact copies share a content hash,
+// near-duplicates have almost the same term vector.
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::similarity::cosine_similarity;
+use crate::Index;
+
+/// Cosine similarity above which two documents are reported as near-duplicates.
+pub const DEFAULT_NEAR_DUPLICATE_SIMILARITY: f32 = 0.95;
+
+/// Hex SHA-256 of a document's extracted text, stored as `Doc::content_hash`.
+pub fn content_hash(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DuplicateReport {
+    /// Paths of documents with identical text, one group per content hash.
+    pub exact_duplicates: Vec<Vec<String>>,
+    /// `[path_a, path_b, similarity]` for similar documents whose text differs.
+    pub near_duplicates: Vec<(String, String, f32)>,
+}
+
+/// Groups the documents of `idx` by content hash, and pairs documents with different text
+/// whose term vectors' cosine similarity exceeds `min_similarity` (IDF is left out, so a
+/// single changed rare word does not dominate). Documents indexed before content hashes
+/// were recorded are hashed from their stored text.
+pub fn find_duplicates(idx: &Index, min_similarity: f32) -> DuplicateReport {
+    let hashes: Vec<String> = idx
+        .docs
+        .iter()
+        .map(|d| {
+            if d.content_hash.is_empty() {
+                content_hash(&d.text)
+            } else {
+                d.content_hash.clone()
+            }
+        })
+        .collect();
+
+    let mut groups: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    for (doc, hash) in idx.docs.iter().zip(&hashes) {
+        groups.entry(hash).or_default().push(doc.path.clone());
+    }
+    let mut exact_duplicates: Vec<Vec<String>> =
+        groups.into_values().filter(|g| g.len() > 1).collect();
+    exact_duplicates.sort();
+
+    let mut near_duplicates = Vec::new();
+    for a in 0..idx.docs.len() {
+        for b in a + 1..idx.docs.len() {
+            if hashes[a] == hashes[b] {
+                continue;
+            }
+            let sim = cosine_similarity(&idx.vectors[a], &idx.vectors[b]);
+            if sim > min_similarity {
+                near_duplicates.push((idx.docs[a].path.clone(), idx.docs[b].path.clone(), sim));
+            }
+        }
+    }
+    DuplicateReport {
+        exact_duplicates,
+        near_duplicates,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{index_dir, load_index, IndexFormat};
+
+    #[test]
+    fn test_reports_exact_and_near_duplicates_separately() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        let memo = "budget review covering travel, hardware, licences, training, \
+                    contractors, office rent, catering, insurance and marketing spend";
+        std::fs::write(docs.join("memo.txt"), memo)?;
+        std::fs::write(docs.join("memo-copy.txt"), memo)?;
+        let minutes = "minutes of the steering meeting: roadmap, staffing, release dates, \
+                       risks, vendor contracts, security audit, hiring and onboarding plans";
+        std::fs::write(docs.join("minutes.txt"), minutes)?;
+        std::fs::write(
+            docs.join("minutes-v2.txt"),
+            minutes.replace("hiring", "recruiting"),
+        )?;
+        std::fs::write(docs.join("recipe.txt"), "knead the dough and let it rise")?;
+        let out = dir.path().join("idx.json");
+        index_dir(&docs, &out, IndexFormat::Json)?;
+        let idx = load_index(&out)?;
+        assert!(idx.docs.iter().all(|d| d.content_hash.len() == 64));
+
+        let report = find_duplicates(&idx, 0.9);
+        assert_eq!(
+            report.exact_duplicates,
+            vec![vec![
+                docs.join("memo-copy.txt").to_string_lossy().into_owned(),
+                docs.join("memo.txt").to_string_lossy().into_owned(),
+            ]]
+        );
+        assert_eq!(report.near_duplicates.len(), 1, "{:?}", report);
+        let (a, b, sim) = &report.near_duplicates[0];
+        assert!(a.ends_with("minutes-v2.txt") && b.ends_with("minutes.txt"));
+        assert!(*sim > 0.9 && *sim < 1.0);
+
+        let json = serde_json::to_value(&report)?;
+        assert!(json["near_duplicates"][0][2].is_number());
+        Ok(())
+    }
+}
@@ -0,0 +1,454 @@
+// Dense document embeddings from a local sentence-transformer model, used instead of BM25
+// term weights by `voltai index --embeddings onnx|candle`. Model inference needs the `onnx`
+// or `candle` Cargo feature; storing and ranking by embeddings does not.
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+
+use crate::Index;
+
+/// Document representation written by `voltai index --embeddings`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EmbeddingKind {
+    /// BM25/TF-IDF term weights.
+    #[default]
+    Tfidf,
+    /// Sentence embeddings from an ONNX model (`--embedding-model model.onnx`).
+    Onnx,
+    /// [CLS] embeddings from a BERT-style model run with candle
+    /// (`--embedding-model model.safetensors`).
+    Candle,
+}
+
+impl EmbeddingKind {
+    /// Checks that `model` is the kind of file this backend loads; [`load_embedder`] picks
+    /// the backend from the extension alone.
+    pub fn check_model(self, model: &Path) -> Result<()> {
+        let safetensors = is_safetensors(model);
+        match self {
+            EmbeddingKind::Onnx if safetensors => Err(anyhow!(
+                "{} is a safetensors checkpoint; use --embeddings candle",
+                model.display()
+            )),
+            EmbeddingKind::Candle if !safetensors => Err(anyhow!(
+                "--embeddings candle expects a .safetensors model, got {}",
+                model.display()
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn is_safetensors(model: &Path) -> bool {
+    model.extension().is_some_and(|e| e == "safetensors")
+}
+
+/// Turns text into a fixed-size dense vector.
+pub trait Embedder: fmt::Debug + Send + Sync {
+    fn embed(&self, text: &str) -> Result<Vec<f32>>;
+}
+
+/// Replaces the term-weight vectors of `index` with `embedder`'s embedding of each document
+/// and records `model` so queries are embedded the same way.
+pub fn embed_documents(index: &mut Index, embedder: Arc<dyn Embedder>, model: &str) -> Result<()> {
+    let vectors = index
+        .docs
+        .iter()
+        .map(|d| embedder.embed(&d.text))
+        .collect::<Result<Vec<_>>>()?;
+    if let Some(dim) = vectors.first().map(Vec::len) {
+        if let Some(i) = vectors.iter().position(|v| v.len() != dim) {
+            return Err(anyhow!(
+                "embedding of {} has {} dimensions, expected {}",
+                index.docs[i].path,
+                vectors[i].len(),
+                dim
+            ));
+        }
+    }
+    index.vectors = vectors;
+    index.embedding_model = Some(model.to_string());
+    index.embedder = Some(embedder);
+    Ok(())
+}
+
+/// Cosine similarity of every document embedding to the embedding of `q`, parallel to the
+/// index's documents.
+pub fn embedding_scores(index: &Index, embedder: &dyn Embedder, q: &str) -> Result<Vec<f32>> {
+    let query = embedder.embed(q)?;
+    Ok(index
+        .vectors
+        .iter()
+        .map(|v| crate::cosine_similarity(&query, v))
+        .collect())
+}
+
+/// Loads the embedder for `model` (the path recorded in `Index::embedding_model`):
+/// `.safetensors` checkpoints run on candle, anything else on ONNX Runtime.
+pub fn load_embedder(model: &Path) -> Result<Arc<dyn Embedder>> {
+    if is_safetensors(model) {
+        load_candle(model)
+    } else {
+        load_onnx(model)
+    }
+}
+
+#[cfg(feature = "onnx")]
+fn load_onnx(model: &Path) -> Result<Arc<dyn Embedder>> {
+    Ok(Arc::new(onnx::OnnxEmbedder::load(model)?))
+}
+
+#[cfg(not(feature = "onnx"))]
+fn load_onnx(model: &Path) -> Result<Arc<dyn Embedder>> {
+    Err(anyhow!(
+        "{} needs ONNX Runtime; rebuild voltai with `--features onnx`",
+        model.display()
+    ))
+}
+
+#[cfg(feature = "candle")]
+fn load_candle(model: &Path) -> Result<Arc<dyn Embedder>> {
+    Ok(Arc::new(candle::CandleEmbedder::load(model)?))
+}
+
+#[cfg(not(feature = "candle"))]
+fn load_candle(model: &Path) -> Result<Arc<dyn Embedder>> {
+    Err(anyhow!(
+        "{} needs candle; rebuild voltai with `--features candle`",
+        model.display()
+    ))
+}
+
+/// Longest input, in tokens, of BERT-style sentence transformers.
+#[cfg(any(feature = "onnx", feature = "candle"))]
+const MAX_TOKENS: usize = 512;
+
+/// Loads the `tokenizer.json` stored next to `model`, truncating inputs to [`MAX_TOKENS`].
+#[cfg(any(feature = "onnx", feature = "candle"))]
+fn load_tokenizer(model: &Path) -> Result<tokenizers::Tokenizer> {
+    let path = model.with_file_name("tokenizer.json");
+    let mut tokenizer = tokenizers::Tokenizer::from_file(&path)
+        .map_err(|e| anyhow!("cannot load tokenizer {}: {}", path.display(), e))?;
+    tokenizer
+        .with_truncation(Some(tokenizers::TruncationParams {
+            max_length: MAX_TOKENS,
+            ..Default::default()
+        }))
+        .map_err(|e| anyhow!("invalid tokenizer truncation: {}", e))?;
+    Ok(tokenizer)
+}
+
+#[cfg(feature = "onnx")]
+mod onnx {
+    use std::borrow::Cow;
+    use std::path::Path;
+    use std::sync::Mutex;
+
+    use anyhow::{anyhow, Result};
+    use ort::session::{Session, SessionInputValue};
+    use ort::value::Tensor;
+    use tokenizers::Tokenizer;
+
+    use super::{load_tokenizer, Embedder};
+
+    /// A sentence-transformer ONNX model (e.g. `all-MiniLM-L6-v2`) plus the `tokenizer.json`
+    /// stored next to it. ONNX Runtime itself is loaded at run time from `ORT_DYLIB_PATH`.
+    pub struct OnnxEmbedder {
+        session: Mutex<Session>,
+        tokenizer: Tokenizer,
+    }
+
+    impl std::fmt::Debug for OnnxEmbedder {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("OnnxEmbedder").finish_non_exhaustive()
+        }
+    }
+
+    impl OnnxEmbedder {
+        pub fn load(model_path: &Path) -> Result<Self> {
+            let tokenizer = load_tokenizer(model_path)?;
+            let session = Session::builder()?.commit_from_file(model_path)?;
+            Ok(OnnxEmbedder {
+                session: Mutex::new(session),
+                tokenizer,
+            })
+        }
+    }
+
+    impl Embedder for OnnxEmbedder {
+        fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            let mut session = self
+                .session
+                .lock()
+                .map_err(|_| anyhow!("ONNX session poisoned"))?;
+            embed_text(text, &mut session, &self.tokenizer)
+        }
+    }
+
+    /// Tokenises `text`, runs the model and pools the last hidden state over the attention
+    /// mask, returning an L2-normalised sentence embedding (mean pooling, as in
+    /// sentence-transformers; the division by the token count cancels out in the norm).
+    pub fn embed_text(
+        text: &str,
+        session: &mut Session,
+        tokenizer: &Tokenizer,
+    ) -> Result<Vec<f32>> {
+        let encoding = tokenizer
+            .encode(text, true)
+            .map_err(|e| anyhow!("tokenization failed: {}", e))?;
+        let to_i64 = |ids: &[u32]| ids.iter().map(|&x| i64::from(x)).collect::<Vec<i64>>();
+        let mask = to_i64(encoding.get_attention_mask());
+        let n = mask.len();
+        let mut inputs: Vec<(Cow<str>, SessionInputValue)> = Vec::new();
+        // Models differ in whether they take token type ids; feed only what they declare.
+        for input in &session.inputs {
+            let values = match input.name.as_str() {
+                "input_ids" => to_i64(encoding.get_ids()),
+                "attention_mask" => mask.clone(),
+                "token_type_ids" => to_i64(encoding.get_type_ids()),
+                other => return Err(anyhow!("unexpected model input '{}'", other)),
+            };
+            inputs.push((
+                Cow::Owned(input.name.clone()),
+                Tensor::from_array(([1, n], values))?.into(),
+            ));
+        }
+        let outputs = session.run(inputs)?;
+        let (shape, hidden) = outputs[0].try_extract_tensor::<f32>()?;
+        let dim = *shape
+            .last()
+            .ok_or_else(|| anyhow!("model output has no dimensions"))? as usize;
+
+        let mut pooled = vec![0.0_f32; dim];
+        for (token, &m) in hidden.chunks(dim).zip(&mask) {
+            if m == 1 {
+                pooled.iter_mut().zip(token).for_each(|(p, h)| *p += h);
+            }
+        }
+        let norm = pooled.iter().map(|x| x * x).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            pooled.iter_mut().for_each(|x| *x /= norm);
+        }
+        Ok(pooled)
+    }
+}
+
+#[cfg(feature = "candle")]
+pub mod candle {
+    use std::path::Path;
+
+    use anyhow::{anyhow, Result};
+    use candle_core::{Device, IndexOp, Tensor};
+    use candle_nn::VarBuilder;
+    use candle_transformers::models::bert::{BertModel, Config, DTYPE};
+    use tokenizers::Tokenizer;
+
+    use super::{load_tokenizer, Embedder};
+
+    /// A BERT-like model loaded from `model.safetensors`, with `config.json` and
+    /// `tokenizer.json` in the same directory, run on the CPU.
+    pub struct CandleEmbedder {
+        model: BertModel,
+        tokenizer: Tokenizer,
+        device: Device,
+    }
+
+    impl std::fmt::Debug for CandleEmbedder {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("CandleEmbedder").finish_non_exhaustive()
+        }
+    }
+
+    impl CandleEmbedder {
+        pub fn load(model_path: &Path) -> Result<Self> {
+            let config_path = model_path.with_file_name("config.json");
+            let config: Config = serde_json::from_str(
+                &std::fs::read_to_string(&config_path)
+                    .map_err(|e| anyhow!("cannot read {}: {}", config_path.display(), e))?,
+            )?;
+            let tokenizer = load_tokenizer(model_path)?;
+            let device = Device::Cpu;
+            let vb =
+                VarBuilder::from_buffered_safetensors(std::fs::read(model_path)?, DTYPE, &device)?;
+            let model = BertModel::load(vb, &config)?;
+            Ok(CandleEmbedder {
+                model,
+                tokenizer,
+                device,
+            })
+        }
+    }
+
+    impl Embedder for CandleEmbedder {
+        /// Hidden state of the leading [CLS] token.
+        fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            let encoding = self
+                .tokenizer
+                .encode(text, true)
+                .map_err(|e| anyhow!("tokenization failed: {}", e))?;
+            let row = |ids: &[u32]| Tensor::new(ids, &self.device)?.unsqueeze(0);
+            let hidden = self.model.forward(
+                &row(encoding.get_ids())?,
+                &row(encoding.get_type_ids())?,
+                Some(&row(encoding.get_attention_mask())?),
+            )?;
+            Ok(hidden.i((0, 0))?.to_vec1::<f32>()?)
+        }
+    }
+
+    /// Embeds `text` with the model at `model_path`, loading it for this call only; indexing
+    /// goes through [`CandleEmbedder`] so the model loads once.
+    #[cfg(test)]
+    pub fn embed_with_candle(text: &str, model_path: &Path) -> Result<Vec<f32>> {
+        CandleEmbedder::load(model_path)?.embed(text)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use candle_nn::VarMap;
+
+        const HIDDEN: usize = 8;
+
+        /// Writes a randomly initialised one-layer BERT with a six-word vocabulary.
+        fn write_tiny_model(dir: &Path) -> std::path::PathBuf {
+            let config = serde_json::json!({
+                "vocab_size": 6, "hidden_size": HIDDEN, "num_hidden_layers": 1,
+                "num_attention_heads": 2, "intermediate_size": 16, "hidden_act": "gelu",
+                "hidden_dropout_prob": 0.0, "max_position_embeddings": 16,
+                "type_vocab_size": 2, "initializer_range": 0.02, "layer_norm_eps": 1e-12,
+                "pad_token_id": 0, "classifier_dropout": null, "model_type": "bert"
+            });
+            std::fs::write(dir.join("config.json"), config.to_string()).unwrap();
+            let tokenizer = serde_json::json!({
+                "version": "1.0", "truncation": null, "padding": null, "added_tokens": [],
+                "normalizer": {"type": "BertNormalizer", "clean_text": true,
+                    "handle_chinese_chars": true, "strip_accents": null, "lowercase": true},
+                "pre_tokenizer": {"type": "BertPreTokenizer"},
+                "post_processor": {"type": "BertProcessing",
+                    "sep": ["[SEP]", 3], "cls": ["[CLS]", 2]},
+                "decoder": null,
+                "model": {"type": "WordPiece", "unk_token": "[UNK]",
+                    "continuing_subword_prefix": "##", "max_input_chars_per_word": 100,
+                    "vocab": {"[PAD]": 0, "[UNK]": 1, "[CLS]": 2, "[SEP]": 3,
+                        "hello": 4, "world": 5}}
+            });
+            std::fs::write(dir.join("tokenizer.json"), tokenizer.to_string()).unwrap();
+
+            let varmap = VarMap::new();
+            let vb = VarBuilder::from_varmap(&varmap, DTYPE, &Device::Cpu);
+            let config: Config = serde_json::from_value(config).unwrap();
+            BertModel::load(vb, &config).unwrap();
+            let path = dir.join("model.safetensors");
+            varmap.save(&path).unwrap();
+            path
+        }
+
+        #[test]
+        fn test_embed_with_candle_returns_hidden_size_vector() {
+            let dir = tempfile::tempdir().unwrap();
+            let model = write_tiny_model(dir.path());
+            let v = embed_with_candle("hello world", &model).unwrap();
+            assert_eq!(v.len(), HIDDEN);
+            assert!(v.iter().all(|x| x.is_finite()));
+            assert_eq!(v, embed_with_candle("hello world", &model).unwrap());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_index, tokenize, Doc, RankingMethod};
+
+    /// Stand-in for an ONNX session: a fixed 3-dimensional vector per topic keyword.
+    #[derive(Debug)]
+    struct FixedEmbedder;
+
+    impl Embedder for FixedEmbedder {
+        fn embed(&self, text: &str) -> Result<Vec<f32>> {
+            let has = |w: &str| if text.contains(w) { 1.0 } else { 0.0 };
+            Ok(vec![has("cat"), has("car"), has("cake")])
+        }
+    }
+
+    fn index_of(texts: &[&str]) -> Index {
+        let docs: Vec<Doc> = texts
+            .iter()
+            .enumerate()
+            .map(|(i, t)| Doc {
+                id: format!("doc-{i}"),
+                path: format!("{i}.txt"),
+                text: t.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let tokens: Vec<Vec<String>> = docs.iter().map(|d| tokenize(&d.text)).collect();
+        build_index(docs, &tokens, RankingMethod::Bm25, true)
+    }
+
+    #[test]
+    fn test_embed_documents_stores_model_output() {
+        let mut idx = index_of(&["a cat naps", "a fast car", "cat and cake"]);
+        embed_documents(&mut idx, Arc::new(FixedEmbedder), "models/fixed.onnx").unwrap();
+        assert_eq!(
+            idx.vectors,
+            vec![
+                vec![1.0, 0.0, 0.0],
+                vec![0.0, 1.0, 0.0],
+                vec![1.0, 0.0, 1.0]
+            ]
+        );
+        assert_eq!(idx.embedding_model.as_deref(), Some("models/fixed.onnx"));
+        idx.validate().unwrap();
+    }
+
+    #[test]
+    fn test_embedding_scores_rank_by_cosine() {
+        let mut idx = index_of(&["a cat naps", "a fast car", "cat and cake"]);
+        embed_documents(&mut idx, Arc::new(FixedEmbedder), "fixed").unwrap();
+        let scores = embedding_scores(&idx, &FixedEmbedder, "birthday cake").unwrap();
+        assert_eq!(scores[1], 0.0);
+        assert!(scores[2] > scores[0]);
+    }
+
+    #[test]
+    fn test_query_ranks_embedding_index() {
+        let mut idx = index_of(&["a cat naps", "a fast car", "cat and cake"]);
+        embed_documents(&mut idx, Arc::new(FixedEmbedder), "fixed").unwrap();
+        let opts = crate::QueryOptions::default();
+        // The query embeds to [1, 0, 1]: the cat-and-cake document first, then the cat one.
+        let results = crate::retrieve(&idx, "the cat wants cake", &opts);
+        assert!(results[0].path.ends_with("2.txt"));
+        assert!(results[1].path.ends_with("0.txt"));
+    }
+
+    #[test]
+    fn test_term_commands_reject_embedding_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("idx.json");
+        let mut idx = index_of(&["a cat naps", "a fast car"]);
+        embed_documents(&mut idx, Arc::new(FixedEmbedder), "fixed").unwrap();
+        crate::write_index(&mut idx, &path, crate::IndexFormat::Json).unwrap();
+
+        let loaded = crate::load_index(&path).unwrap();
+        assert_eq!(loaded.embedding_model.as_deref(), Some("fixed"));
+        assert_eq!(loaded.vectors, idx.vectors);
+        let err = crate::load_term_index(&path).unwrap_err().to_string();
+        assert!(
+            err.contains("embeddings rather than term weights"),
+            "{}",
+            err
+        );
+    }
+
+    #[cfg(not(feature = "onnx"))]
+    #[test]
+    fn test_load_embedder_without_feature_explains() {
+        let err = load_embedder(Path::new("m.onnx")).unwrap_err().to_string();
+        assert!(err.contains("--features onnx"), "{}", err);
+    }
+}
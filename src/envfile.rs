@@ -0,0 +1,87 @@
+// Dotenv configuration files for `voltai --env-file`, loaded into the process environment
+// before the command line is parsed.
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+
+/// The `--env-file` value among the raw arguments, if any. It is looked up before clap
+/// parses the command line so the file is loaded before anything reads the environment.
+pub fn env_file_arg(args: &[OsString]) -> Option<PathBuf> {
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        // Arguments that are not UTF-8 cannot be `--env-file`; they may come before it.
+        let Some(arg) = arg.to_str() else { continue };
+        if arg == "--" {
+            break;
+        }
+        if arg == "--env-file" {
+            return iter.next().map(PathBuf::from);
+        }
+        if let Some(path) = arg.strip_prefix("--env-file=") {
+            return Some(PathBuf::from(path));
+        }
+    }
+    None
+}
+
+/// Sets every variable in the file at `path` that is not already in the environment, so
+/// real environment variables take precedence.
+pub fn load(path: &Path) -> Result<()> {
+    dotenvy::from_path(path).map_err(|e| anyhow!("cannot load env file {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_sets_variables_without_overriding() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(".env");
+        std::fs::write(
+            &path,
+            "# model settings\n\
+             VOLTAI_MODEL=gemma3:4b # inline comment\n\
+             export VOLTAI_TEST_ENVFILE_QUOTED=\"hello world\"\n\
+             VOLTAI_TEST_ENVFILE_PRESET=from-file\n",
+        )?;
+        std::env::set_var("VOLTAI_TEST_ENVFILE_PRESET", "from-env");
+
+        load(&path)?;
+        assert_eq!(std::env::var("VOLTAI_MODEL")?, "gemma3:4b");
+        assert_eq!(std::env::var("VOLTAI_TEST_ENVFILE_QUOTED")?, "hello world");
+        assert_eq!(std::env::var("VOLTAI_TEST_ENVFILE_PRESET")?, "from-env");
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_file_arg_forms() {
+        let args = |list: &[&str]| list.iter().map(OsString::from).collect::<Vec<_>>();
+        assert_eq!(
+            env_file_arg(&args(&["voltai", "--env-file", "prod.env", "query"])),
+            Some(PathBuf::from("prod.env"))
+        );
+        assert_eq!(
+            env_file_arg(&args(&["voltai", "query", "--env-file=.env"])),
+            Some(PathBuf::from(".env"))
+        );
+        assert_eq!(
+            env_file_arg(&args(&["voltai", "query", "-q", "rust"])),
+            None
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_env_file_arg_after_non_utf8_argument() {
+        use std::os::unix::ffi::OsStringExt;
+        let args = vec![
+            OsString::from("voltai"),
+            OsString::from_vec(vec![b'-', b'-', 0xff]),
+            OsString::from("--env-file"),
+            OsString::from("prod.env"),
+        ];
+        assert_eq!(env_file_arg(&args), Some(PathBuf::from("prod.env")));
+    }
+}
@@ -0,0 +1,198 @@
+// Rocchio relevance feedback: reshape the query vector towards documents the user marked
+// relevant and away from those marked irrelevant, then re-run retrieval with it.
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::similarity::{find_doc, weighted_vector};
+use crate::{
+    dot_product, excerpt_for_query, top_k, Index, QueryResult, EXCERPT_CHARS, RESULT_KEYWORDS,
+};
+
+/// Rocchio coefficients: `q_new = alpha * q + beta * mean(relevant) - gamma * mean(irrelevant)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RocchioWeights {
+    pub alpha: f32,
+    pub beta: f32,
+    pub gamma: f32,
+}
+
+impl Default for RocchioWeights {
+    fn default() -> Self {
+        RocchioWeights {
+            alpha: 1.0,
+            beta: 0.75,
+            gamma: 0.15,
+        }
+    }
+}
+
+/// Mean of the IDF-weighted vectors of `docs`, or `None` when `docs` is empty.
+fn centroid(idx: &Index, docs: &[usize]) -> Option<Vec<f32>> {
+    if docs.is_empty() {
+        return None;
+    }
+    let mut sum = vec![0.0_f32; idx.terms.len()];
+    for &di in docs {
+        for (s, w) in sum.iter_mut().zip(weighted_vector(idx, di)) {
+            *s += w;
+        }
+    }
+    let n = docs.len() as f32;
+    Some(sum.into_iter().map(|s| s / n).collect())
+}
+
+/// Builds the expanded query vector over the index vocabulary. The original query has
+/// `idf[t]` for each query term, as in BM25 scoring; negative weights are clipped to 0,
+/// as is usual for Rocchio.
+pub fn rocchio_query(
+    idx: &Index,
+    q: &str,
+    relevant: &[usize],
+    irrelevant: &[usize],
+    weights: RocchioWeights,
+) -> Vec<f32> {
+    let term_map: HashMap<&str, usize> = idx
+        .terms
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.as_str(), i))
+        .collect();
+    let mut q_vec = vec![0.0_f32; idx.terms.len()];
+    for t in idx.tokenize_query(q) {
+        if let Some(&ti) = term_map.get(t.as_str()) {
+            q_vec[ti] = idx.idf[ti];
+        }
+    }
+    let mut q_new: Vec<f32> = q_vec.iter().map(|w| weights.alpha * w).collect();
+    if let Some(rel) = centroid(idx, relevant) {
+        for (q, r) in q_new.iter_mut().zip(rel) {
+            *q += weights.beta * r;
+        }
+    }
+    if let Some(irr) = centroid(idx, irrelevant) {
+        for (q, r) in q_new.iter_mut().zip(irr) {
+            *q -= weights.gamma * r;
+        }
+    }
+    q_new.iter_mut().for_each(|w| *w = w.max(0.0));
+    q_new
+}
+
+/// Re-runs retrieval for `q` with Rocchio feedback from documents given by id or path.
+pub fn feedback_search(
+    idx: &Index,
+    q: &str,
+    relevant: &[String],
+    irrelevant: &[String],
+    weights: RocchioWeights,
+    k: usize,
+) -> Result<Vec<QueryResult>> {
+    let resolve = |keys: &[String]| -> Result<Vec<usize>> {
+        keys.iter().map(|key| find_doc(idx, key)).collect()
+    };
+    let q_vec = rocchio_query(idx, q, &resolve(relevant)?, &resolve(irrelevant)?, weights);
+    let scores: Vec<f32> = idx.vectors.iter().map(|v| dot_product(&q_vec, v)).collect();
+
+    let q_toks = idx.tokenize_query(q);
+    Ok(top_k(&scores, k)
+        .into_iter()
+        .map(|(di, score)| {
+            let doc = &idx.docs[di];
+            QueryResult {
+                path: doc.path.clone(),
+                score,
+                keywords: idx.keywords(&doc.text, RESULT_KEYWORDS),
+                excerpt: excerpt_for_query(&doc.text, &q_toks, EXCERPT_CHARS),
+                sentence_excerpt: None,
+                last_modified_secs: doc.last_modified_secs,
+                context_used: false,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{index_dir, load_index, rank_documents, IndexFormat, QueryOptions};
+    use std::path::Path;
+
+    fn name(path: &str) -> String {
+        Path::new(path)
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_feedback_promotes_relevant_and_demotes_irrelevant() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("a.txt"), "solar solar panel")?;
+        std::fs::write(dir.path().join("b.txt"), "solar energy grid storage")?;
+        std::fs::write(
+            dir.path().join("c.txt"),
+            "solar battery storage grid inverter",
+        )?;
+        std::fs::write(dir.path().join("d.txt"), "wind turbine blade")?;
+        let index_path = dir.path().join("idx.json");
+        index_dir(dir.path(), &index_path, IndexFormat::Json)?;
+        let idx = load_index(&index_path)?;
+
+        let original: Vec<String> = rank_documents(
+            &idx,
+            "solar",
+            &QueryOptions {
+                k: 4,
+                ..Default::default()
+            },
+        )
+        .into_iter()
+        .map(|(i, _)| name(&idx.docs[i].path))
+        .collect();
+        assert_eq!(original, vec!["a.txt", "b.txt", "c.txt"]);
+
+        let results = feedback_search(
+            &idx,
+            "solar",
+            &["c.txt".to_string()],
+            &["a.txt".to_string()],
+            RocchioWeights::default(),
+            4,
+        )?;
+        let reranked: Vec<String> = results.iter().map(|r| name(&r.path)).collect();
+        let pos = |list: &[String], n: &str| list.iter().position(|x| x == n).unwrap();
+        assert!(
+            pos(&reranked, "c.txt") < pos(&original, "c.txt"),
+            "{:?}",
+            reranked
+        );
+        assert!(
+            pos(&reranked, "a.txt") > pos(&original, "a.txt"),
+            "{:?}",
+            reranked
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rocchio_without_feedback_is_scaled_query() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("a.txt"), "solar panel")?;
+        std::fs::write(dir.path().join("b.txt"), "wind turbine")?;
+        let index_path = dir.path().join("idx.json");
+        index_dir(dir.path(), &index_path, IndexFormat::Json)?;
+        let idx = load_index(&index_path)?;
+
+        let weights = RocchioWeights {
+            alpha: 2.0,
+            ..Default::default()
+        };
+        let q_vec = rocchio_query(&idx, "solar", &[], &[], weights);
+        let solar = idx.terms.iter().position(|t| t == "solar").unwrap();
+        assert_eq!(q_vec[solar], 2.0 * idx.idf[solar]);
+        assert_eq!(q_vec.iter().filter(|&&w| w > 0.0).count(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,101 @@
+// Markdown frontmatter and per-field term boosts (`voltai index --field-boost`).
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error, Result};
+
+/// One `--field-boost` entry: terms of metadata field `field` count `boost` times.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldBoost {
+    pub field: String,
+    pub boost: f32,
+}
+
+impl FromStr for FieldBoost {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (field, boost) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("expected FIELD:BOOST, e.g. title:3.0, got '{}'", s))?;
+        let boost: f32 = boost
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("invalid boost '{}' for field '{}'", boost, field))?;
+        if !(boost.is_finite() && boost > 0.0) {
+            return Err(anyhow!("boost for field '{}' must be positive", field));
+        }
+        Ok(FieldBoost {
+            field: field.trim().to_string(),
+            boost,
+        })
+    }
+}
+
+/// Splits a Markdown document into its `---` frontmatter fields and the body after it.
+/// Each top-level key maps to its string values joined by spaces, so `tags: [a, b]` gives
+/// `"a b"`. Text without frontmatter is returned whole with no fields.
+pub fn parse_markdown_frontmatter(text: &str) -> (HashMap<String, String>, String) {
+    let mut fields = HashMap::new();
+    let Some(rest) = text
+        .strip_prefix("---\n")
+        .or_else(|| text.strip_prefix("---\r\n"))
+    else {
+        return (fields, text.to_string());
+    };
+    let Some((yaml, body)) = rest
+        .split_once("\n---\n")
+        .or_else(|| rest.split_once("\r\n---\r\n"))
+        .or_else(|| rest.strip_suffix("\n---").map(|yaml| (yaml, "")))
+    else {
+        return (fields, text.to_string());
+    };
+    // Frontmatter that is not a YAML mapping has no fields, but is still not body text.
+    if let Ok(serde_yaml::Value::Mapping(mapping)) = serde_yaml::from_str(yaml) {
+        for (key, value) in &mapping {
+            let Some(key) = key.as_str() else { continue };
+            let mut strings = Vec::new();
+            crate::yaml::value_strings(value, &mut strings);
+            if !strings.is_empty() {
+                fields.insert(key.to_string(), strings.join(" "));
+            }
+        }
+    }
+    (fields, body.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_frontmatter_fields_and_body() {
+        let (fields, body) = parse_markdown_frontmatter(
+            "---\ntitle: \"Tide Tables\"\ntags:\n  - harbour\n  - charts\ndraft: false\n---\n# Notes\nBody text.\n",
+        );
+        assert_eq!(fields["title"], "Tide Tables");
+        assert_eq!(fields["tags"], "harbour charts");
+        assert!(!fields.contains_key("draft"));
+        assert_eq!(body, "# Notes\nBody text.\n");
+
+        let plain = "# No frontmatter\n---\ntext";
+        assert_eq!(
+            parse_markdown_frontmatter(plain),
+            (HashMap::new(), plain.to_string())
+        );
+    }
+
+    #[test]
+    fn test_field_boost_parsing() {
+        assert_eq!(
+            "title:3.0".parse::<FieldBoost>().unwrap(),
+            FieldBoost {
+                field: "title".to_string(),
+                boost: 3.0
+            }
+        );
+        assert!("title".parse::<FieldBoost>().is_err());
+        assert!("title:fast".parse::<FieldBoost>().is_err());
+        assert!("title:0".parse::<FieldBoost>().is_err());
+    }
+}
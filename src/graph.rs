@@ -0,0 +1,177 @@
+// Document similarity graphs for `voltai graph`: one node per document and an undirected
+// edge, weighted by cosine similarity, between each sufficiently similar pair. Written as
+// Graphviz DOT or GraphML for Gephi and similar tools.
+use std::io::Write;
+
+use anyhow::Result;
+use clap::ValueEnum;
+
+use crate::similarity::{cosine_similarity, weighted_vector};
+use crate::Index;
+
+/// Output format of `voltai graph`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT.
+    #[default]
+    Dot,
+    /// GraphML XML.
+    #[value(name = "graphml")]
+    GraphMl,
+}
+
+/// A document similarity graph. Nodes are document paths, in index order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocGraph {
+    pub nodes: Vec<String>,
+    /// `(a, b, similarity)` with `a < b`, indexing `nodes`.
+    pub edges: Vec<(usize, usize, f32)>,
+}
+
+/// Compares every pair of documents in `idx` and keeps an edge for each pair whose cosine
+/// similarity (of IDF-weighted term vectors) exceeds `min_similarity`. Quadratic in the
+/// number of documents.
+pub fn build_graph(idx: &Index, min_similarity: f32) -> DocGraph {
+    let vectors: Vec<Vec<f32>> = (0..idx.docs.len())
+        .map(|di| weighted_vector(idx, di))
+        .collect();
+    let mut edges = Vec::new();
+    for a in 0..vectors.len() {
+        for b in a + 1..vectors.len() {
+            let sim = cosine_similarity(&vectors[a], &vectors[b]);
+            if sim > min_similarity {
+                edges.push((a, b, sim));
+            }
+        }
+    }
+    DocGraph {
+        nodes: idx.docs.iter().map(|d| d.path.clone()).collect(),
+        edges,
+    }
+}
+
+/// Writes `graph` in `format`.
+pub fn write_graph(graph: &DocGraph, format: GraphFormat, out: impl Write) -> Result<()> {
+    match format {
+        GraphFormat::Dot => write_dot(graph, out),
+        GraphFormat::GraphMl => write_graphml(graph, out),
+    }
+}
+
+/// Writes `graph` as an undirected Graphviz graph; nodes are `d<N>`, labelled with the path.
+pub fn write_dot(graph: &DocGraph, mut out: impl Write) -> Result<()> {
+    let quote = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    writeln!(out, "graph voltai {{")?;
+    for (i, path) in graph.nodes.iter().enumerate() {
+        writeln!(out, "  d{} [label=\"{}\"];", i, quote(path))?;
+    }
+    for &(a, b, sim) in &graph.edges {
+        writeln!(out, "  d{} -- d{} [weight={:.4}];", a, b, sim)?;
+    }
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+/// Writes `graph` as GraphML with a `label` node attribute and a `weight` edge attribute.
+pub fn write_graphml(graph: &DocGraph, mut out: impl Write) -> Result<()> {
+    let escape = |s: &str| {
+        s.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        out,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="label" for="node" attr.name="label" attr.type="string"/>"#
+    )?;
+    writeln!(
+        out,
+        r#"  <key id="weight" for="edge" attr.name="weight" attr.type="double"/>"#
+    )?;
+    writeln!(out, r#"  <graph id="voltai" edgedefault="undirected">"#)?;
+    for (i, path) in graph.nodes.iter().enumerate() {
+        writeln!(
+            out,
+            r#"    <node id="d{}"><data key="label">{}</data></node>"#,
+            i,
+            escape(path)
+        )?;
+    }
+    for &(a, b, sim) in &graph.edges {
+        writeln!(
+            out,
+            r#"    <edge source="d{}" target="d{}"><data key="weight">{:.4}</data></edge>"#,
+            a, b, sim
+        )?;
+    }
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</graphml>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_index, tokenize, Doc, RankingMethod};
+
+    fn index() -> Index {
+        let docs: Vec<Doc> = [
+            ("tea.txt", "green tea leaves steeped in hot water"),
+            ("coffee.txt", "coffee beans ground and steeped in hot water"),
+            ("bikes.txt", "bicycle gears chains and brakes"),
+        ]
+        .iter()
+        .map(|(path, text)| Doc {
+            id: path.to_string(),
+            path: path.to_string(),
+            text: text.to_string(),
+            ..Default::default()
+        })
+        .collect();
+        let tokens: Vec<Vec<String>> = docs.iter().map(|d| tokenize(&d.text)).collect();
+        build_index(docs, &tokens, RankingMethod::Bm25, true)
+    }
+
+    #[test]
+    fn test_dot_declares_nodes_and_similar_edges() -> Result<()> {
+        let graph = build_graph(&index(), 0.05);
+        assert_eq!(graph.edges.len(), 1, "{:?}", graph.edges);
+        let mut out = Vec::new();
+        write_dot(&graph, &mut out)?;
+        let dot = String::from_utf8(out)?;
+
+        assert!(dot.starts_with("graph voltai {\n") && dot.ends_with("}\n"));
+        for (i, path) in ["tea.txt", "coffee.txt", "bikes.txt"].iter().enumerate() {
+            assert!(
+                dot.contains(&format!("  d{} [label=\"{}\"];\n", i, path)),
+                "{}",
+                dot
+            );
+        }
+        assert!(dot.contains("  d0 -- d1 [weight="), "{}", dot);
+        // The unrelated document is a node without edges.
+        assert!(!dot.contains("-- d2"), "{}", dot);
+        Ok(())
+    }
+
+    #[test]
+    fn test_graphml_escapes_labels() -> Result<()> {
+        let graph = DocGraph {
+            nodes: vec!["a&b.txt".into(), "<c>.txt".into()],
+            edges: vec![(0, 1, 0.5)],
+        };
+        let mut out = Vec::new();
+        write_graphml(&graph, &mut out)?;
+        let xml = String::from_utf8(out)?;
+        assert!(xml.contains(r#"<node id="d0"><data key="label">a&amp;b.txt</data></node>"#));
+        assert!(xml.contains("&lt;c&gt;.txt"));
+        assert!(xml
+            .contains(r#"<edge source="d0" target="d1"><data key="weight">0.5000</data></edge>"#));
+        Ok(())
+    }
+}
@@ -0,0 +1,10035 @@
+//! VoltAI: local document indexing and retrieval-augmented querying.
+//!
+//! This file holds the core: the `voltai` CLI (`Cli`, `Commands` and [`run`]), the
+//! [`Index`] and its documents with the on-disk JSON/bincode format and versioning
+//! (`version`, `schema_version`, `voltai migrate`), tokenization, indexing and term
+//! weighting (BM25 or TF-IDF), and retrieval: ranking, prompt and context building for
+//! Ollama, and query output. Everything else lives in the submodules below, e.g.
+//! entity extraction in `ner`, the Ollama client in `ollama`, caches in `cache` and
+//! approximate nearest neighbours in `ann`.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use indicatif::{ProgressBar, ProgressStyle};
+use once_cell::sync::Lazy;
+use rayon::prelude::*;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+mod ann;
+mod assertions;
+mod benchmark;
+mod cache;
+mod color;
+mod cooccurrence;
+mod duplicates;
+mod embeddings;
+mod envfile;
+mod export;
+mod feedback;
+mod frontmatter;
+mod graph;
+mod ner;
+mod ollama;
+mod pdf;
+mod pipeline;
+mod preprocess;
+mod reader;
+mod report;
+mod similarity;
+mod stats;
+mod stopwords;
+mod template;
+mod termfilter;
+mod terms;
+mod topics;
+mod yaml;
+
+pub use ner::{
+    extract_entities_from_text, extract_entities_from_text_with_config, load_ner_patterns,
+    Language, NerPatterns,
+};
+pub use similarity::cosine_similarity;
+
+use ann::{AnnIndex, DEFAULT_ANN_EF};
+use color::ColorPrinter;
+use ner::{cross_reference_entities, Entity};
+use ollama::{LlmBackend, OllamaCli, OllamaHttp};
+use reader::{DefaultFileReader, FileReader};
+use template::{render_prompt, PromptDocument, PromptExample, PromptVars};
+
+static WORD_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"[a-zA-Z0-9']+").unwrap());
+
+/// A sentence: a run of text up to and including its terminator(s). A trailing fragment
+/// without a full stop still matches.
+static SENTENCE_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"[^.!?]+[.!?]*").unwrap());
+
+/// Common English function words excluded from index and query vectors.
+/// These carry no discriminating signal and inflate the term vocabulary.
+static STOP_WORDS: Lazy<&'static HashSet<&'static str>> =
+    Lazy::new(|| stopwords::load_stop_words("en").expect("English stop words are embedded"));
+
+#[derive(Parser)]
+#[command(
+    name = "VoltAI",
+    about = "Fast local document search and summarization — TF-IDF indexing with Ollama LLM generation"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+    /// Cache `query` retrieval results (not LLM answers) in this directory, and read the
+    /// query history for `search-history` from it.
+    #[arg(long, global = true)]
+    cache_dir: Option<PathBuf>,
+    /// Seconds a cached retrieval result stays valid.
+    #[arg(long, global = true, default_value_t = cache::DEFAULT_CACHE_TTL_SECS)]
+    cache_ttl: u64,
+    /// Colour paths, scores and keywords even when stdout is not a terminal.
+    #[arg(long, global = true, overrides_with = "no_color")]
+    color: bool,
+    /// Never colour output (also disabled by the `NO_COLOR` environment variable).
+    #[arg(long, global = true, overrides_with = "color")]
+    no_color: bool,
+    /// Load `KEY=VALUE` lines from this dotenv-style file into the environment (e.g.
+    /// `OLLAMA_MODEL`, `OLLAMA_TIMEOUT_SECS`). Variables already set take precedence.
+    #[arg(long, global = true, value_name = "PATH")]
+    env_file: Option<PathBuf>,
+    /// Hide progress bars and informational messages such as "Wrote index to ...".
+    /// Results, warnings and errors are still printed.
+    #[arg(long, global = true)]
+    quiet: bool,
+}
+
+// Parsed once per run, so `Query`'s many flags are not worth boxing.
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+enum Commands {
+    Index {
+        #[arg(short, long)]
+        dir: PathBuf,
+        #[arg(short, long, default_value = "voltai_index.json")]
+        out: PathBuf,
+        /// Output format: `json` (human-readable, default) or `binary` (compact bincode, ~3× smaller and faster to load).
+        #[arg(long, default_value = "json")]
+        format: IndexFormat,
+        /// How term weights are stored: `dense` (one weight per document and term),
+        /// `inverted` (only each document's non-zero weights, much smaller for large
+        /// vocabularies) or `auto` (`inverted` above 10 million document-term cells).
+        #[arg(long, default_value = "dense")]
+        index_format: IndexLayout,
+        /// Also build an HNSW approximate nearest-neighbour graph over the document vectors,
+        /// written next to the index as `<out>.hnsw.graph` / `<out>.hnsw.data`.
+        #[arg(long)]
+        ann: bool,
+        /// Store term weights without BM25 document-length normalisation (b = 0), so longer
+        /// documents with more occurrences score higher. Pair with
+        /// `voltai query --pivoted-normalization` for a softer length correction.
+        #[arg(long)]
+        no_normalize: bool,
+        /// Term weighting scheme: `bm25` (default) or `tfidf`.
+        #[arg(long, default_value = "bm25")]
+        ranking: RankingMethod,
+        /// Store each document's token list in the index so `rebuild-vectors` can recompute
+        /// weights without re-reading the files.
+        #[arg(long)]
+        cache_tokens: bool,
+        /// Follow symbolic links while walking `--dir`. Symlink cycles are detected and
+        /// skipped with a warning, and a file reachable through several links is indexed once.
+        #[arg(long, visible_alias = "follow-symlinks")]
+        allow_symlinks: bool,
+        /// Document vectors: `tfidf` term weights (default), or embeddings from
+        /// `--embedding-model` with `onnx` or `candle` (each needs its build feature).
+        #[arg(long, default_value = "tfidf")]
+        embeddings: embeddings::EmbeddingKind,
+        /// Embedding model: a sentence-transformer `model.onnx` (e.g. all-MiniLM-L6-v2) for
+        /// `onnx`, or a BERT `model.safetensors` plus `config.json` for `candle`, with
+        /// `tokenizer.json` in the same directory. Queries reuse the model from this path.
+        #[arg(long, required_if_eq_any([("embeddings", "onnx"), ("embeddings", "candle")]))]
+        embedding_model: Option<PathBuf>,
+        /// Print each file's size and source, token count and number of non-zero terms, and
+        /// any read error (such files are indexed as empty). Printed even with `--quiet`.
+        #[arg(long)]
+        verbose: bool,
+        /// Index only a random sample of this many files, e.g. to explore a large corpus.
+        #[arg(long)]
+        sample: Option<usize>,
+        /// Seed for `--sample`; the same seed picks the same files from the same directory.
+        #[arg(long, default_value_t = 0, requires = "sample")]
+        seed: u64,
+        /// Leave tokens longer than this many characters (hashes, base64 blobs) out of the
+        /// index. Unlimited by default.
+        #[arg(long)]
+        max_keyword_length: Option<usize>,
+        /// Regex matching one token, replacing the built-in `[a-zA-Z0-9']+`; tokens are
+        /// still lowercased and stop words dropped. Stored in the index so queries are
+        /// tokenized the same way.
+        #[arg(long, value_name = "REGEX")]
+        token_pattern: Option<String>,
+        /// TOML file of `term = "replacement"` aliases, e.g. `ml = "machine_learning"`,
+        /// applied to every token. Stored in the index so queries use the same aliases.
+        #[arg(long, value_name = "PATH")]
+        alias_file: Option<PathBuf>,
+        /// Clean each document's text before tokenization; repeat (or comma-separate) to
+        /// chain steps, which run in the given order. The cleaned text is what the index
+        /// stores.
+        #[arg(long = "pre-process", value_name = "STEP", value_delimiter = ',')]
+        pre_process: Vec<preprocess::PreprocessStep>,
+        /// Leave out tokens occurring fewer than this many times across the whole corpus.
+        #[arg(long, default_value_t = 1)]
+        min_keyword_freq: usize,
+        /// Language of the documents (`en`, `fr`, `de`, `es`, `it` or `pt`), selecting which
+        /// stop words are left out of the index. English by default; queries always drop
+        /// English stop words.
+        #[arg(long, value_parser = stopwords::LANGUAGES)]
+        lang: Option<String>,
+        /// Leave purely numeric tokens (years, counts, version parts) out of the index.
+        /// Mixed tokens such as `10am` are kept.
+        #[arg(long)]
+        exclude_numeric: bool,
+        /// Use a Markdown file's leading `# Heading` as its document id instead of the
+        /// file name.
+        #[arg(long)]
+        title_from_first_line: bool,
+        /// Weight terms of a metadata field more than body terms, e.g.
+        /// `title:3.0,tags:2.0`. Markdown `---` frontmatter fields are read into the
+        /// document's metadata and left out of its text. `rebuild-vectors` drops the boosts.
+        #[arg(long, value_name = "FIELD:BOOST", value_delimiter = ',')]
+        field_boost: Vec<frontmatter::FieldBoost>,
+        /// Skip documents whose term vector has at least this cosine similarity (e.g.
+        /// `0.98`) to an earlier document, logging which document each duplicates.
+        #[arg(long, value_name = "THRESHOLD")]
+        deduplicate_near: Option<f32>,
+        /// Fail on the first file that cannot be read, writing no index, instead of
+        /// indexing it as an empty document.
+        #[arg(long)]
+        stop_on_error: bool,
+        /// Cluster the documents into K groups (default 10) with k-means and store the
+        /// top terms of each document's cluster as its topics.
+        #[arg(long, value_name = "K", num_args = 0..=1, default_missing_value = "10")]
+        assign_topics: Option<usize>,
+    },
+    /// Recompute term weights from the token lists cached by `index --cache-tokens`.
+    RebuildVectors {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        /// Term weighting scheme for the rebuilt index.
+        #[arg(long, default_value = "bm25")]
+        ranking: RankingMethod,
+        /// Destination; `.json` is written as JSON, anything else as bincode.
+        #[arg(short, long)]
+        out: PathBuf,
+    },
+    /// Add files to an existing index without re-reading the documents already in it.
+    AppendIndex {
+        #[arg(long, default_value = "voltai_index.json")]
+        existing: PathBuf,
+        /// Files to add; each must not already be indexed.
+        #[arg(long, num_args = 1.., required = true)]
+        new_files: Vec<PathBuf>,
+        /// Destination; `.json` is written as JSON, anything else as bincode.
+        #[arg(short, long)]
+        out: PathBuf,
+    },
+    /// Print the JSON Schema (draft-07) of the index file, with `QueryResult` and `Entity`
+    /// under `definitions`. Needs no index.
+    Schema,
+    /// Upgrade a JSON index written by an older release to the current layout.
+    Migrate {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        /// Layout version of the source index; files without a `version` field are 0.
+        #[arg(long, default_value_t = 0)]
+        from_version: u32,
+        /// Destination; `.json` is written as JSON, anything else as bincode.
+        #[arg(short, long)]
+        out: PathBuf,
+    },
+    Query {
+        /// Index to search; repeat to query several indexes as one corpus.
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: Vec<PathBuf>,
+        #[arg(short, long)]
+        q: Option<String>,
+        /// Read the query from this file instead of `--q`; `-` reads standard input.
+        #[arg(long, value_name = "PATH")]
+        query_file: Option<PathBuf>,
+        #[arg(short, long, default_value_t = 3)]
+        k: usize,
+        /// Optional Ollama model override (e.g. gemma3:4b). If omitted the app will probe for a fast model.
+        #[arg(short = 'm', long = "model")]
+        model: Option<String>,
+        /// Boost documents that share a named entity with the query. Bare `--entity-boost`
+        /// adds 0.2 to their score; pass a value to override (e.g. `--entity-boost 0.5`).
+        /// Document entities are found when the index is built.
+        #[arg(long)]
+        entity_boost: Option<Option<f32>>,
+        /// Print only the ranked documents (scores, keywords, excerpts) without calling Ollama.
+        #[arg(long)]
+        no_ollama: bool,
+        /// HNSW search breadth used when the index has an ANN graph (`voltai index --ann`).
+        /// Higher values improve recall at the cost of latency.
+        #[arg(long, default_value_t = DEFAULT_ANN_EF)]
+        ann_ef: usize,
+        /// Rescale scores by pivoted document-length normalisation:
+        /// `score / (0.2 * doc_norm + 0.8 * avg_norm)`. Only applies to indexes built with
+        /// `--no-normalize`.
+        #[arg(long)]
+        pivoted_normalization: bool,
+        /// Minimum cosine similarity to a known "summarise the corpus" phrasing for the query
+        /// to be answered from every document rather than the top-ranked ones.
+        #[arg(long, default_value_t = DEFAULT_GENERAL_QUERY_THRESHOLD)]
+        general_query_threshold: f32,
+        /// Maximum number of documents included in the LLM prompt context.
+        #[arg(long, default_value_t = DEFAULT_CONTEXT_DOCS)]
+        context_docs: usize,
+        /// Character budget for the prompt's context section; the last document is
+        /// truncated to fit.
+        #[arg(long)]
+        context_chars: Option<usize>,
+        /// What each context document contributes to the prompt: `keywords` (default),
+        /// `excerpt` (leading text, see `--excerpt-chars`) or `full` (whole text).
+        #[arg(long, default_value = "keywords")]
+        context_mode: ContextMode,
+        /// Characters of text per document in `--context-mode excerpt`.
+        #[arg(long, default_value_t = DEFAULT_EXCERPT_CHARS)]
+        excerpt_chars: usize,
+        /// `sentence` also reports the best-matching sentence of each retrieved document.
+        #[arg(long, default_value = "document")]
+        granularity: Granularity,
+        /// Only consider documents whose path or URL matches this glob
+        /// (e.g. `https://docs.example.com/**`); prefix with `re:` for a regex.
+        #[arg(long)]
+        source_filter: Option<String>,
+        /// Talk to an Ollama server over HTTP (e.g. `http://localhost:11434`) instead of
+        /// running the `ollama` command-line tool.
+        #[arg(long)]
+        ollama_url: Option<String>,
+        /// Seconds to wait for Ollama before giving up (default 60, or
+        /// `OLLAMA_TIMEOUT_SECS`). Retrieval results are shown instead.
+        #[arg(long)]
+        ollama_timeout: Option<u64>,
+        /// Format of retrieval results (`--no-ollama`, or when Ollama is unavailable):
+        /// `text` (default), `json`, `pretty-json` (with a keyword summary per result),
+        /// `jsonl`, `csv` or `markdown`. LLM answers are plain
+        /// text, except in `markdown`, where they follow the results as a blockquote.
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+        /// Order of the retrieved documents: `score` (default), `path`, or `date` (most
+        /// recently modified first). Which documents are retrieved is always decided by score.
+        #[arg(long, default_value = "score")]
+        sort_by: SortBy,
+        /// Write the answer or retrieval results to this file instead of stdout.
+        #[arg(long)]
+        output_file: Option<PathBuf>,
+        /// Append to `--output-file` rather than overwriting it.
+        #[arg(long, requires = "output_file")]
+        append: bool,
+        /// Prompt template (Tera/Jinja2 syntax) replacing the built-in prompts: a file, or
+        /// the built-in `default` or `few-shot`. Receives `{{ query }}`, `{{ context }}`,
+        /// `{{ model }}`, `{{ documents }}` (each with `id`, `path`, `filename` and
+        /// `keywords`) and `{{ examples }}` (each with `query` and `expected_answer`).
+        #[arg(long, visible_alias = "context-template")]
+        template: Option<String>,
+        /// JSON array of `{"query", "expected_answer"}` examples for `{{ examples }}`.
+        #[arg(long, requires = "template")]
+        examples_file: Option<PathBuf>,
+        /// Warn on stderr when the prompt is estimated to exceed this many tokens.
+        #[arg(long, default_value_t = DEFAULT_TOKEN_WARN_THRESHOLD)]
+        token_warn_threshold: usize,
+        /// Skip Ollama and print retrieval results when the prompt is estimated to exceed
+        /// this many tokens.
+        #[arg(long)]
+        token_limit: Option<usize>,
+        /// Ask the Ollama model to score each retrieved document's relevance from 0 to 10
+        /// and reorder results and prompt context by that score (one call per document).
+        #[arg(long, conflicts_with = "no_ollama")]
+        rerank: bool,
+        /// Retrieve at most this many chunks of the same source file, so one long document
+        /// cannot fill the top-k. Chunks are grouped by their `source` metadata, or by path.
+        #[arg(long)]
+        max_context_docs_per_file: Option<usize>,
+        /// Write each retrieval result as a JSONL line as soon as it is ready, in score
+        /// order, for piping into another process. Implies `--format jsonl`.
+        #[arg(long, requires = "no_ollama")]
+        stream_results: bool,
+        /// Print the exact prompt sent to Ollama to stderr, between `=== PROMPT START ===`
+        /// and `=== PROMPT END ===` lines.
+        #[arg(long, conflicts_with = "no_ollama")]
+        show_context: bool,
+        /// Retrieve and build the prompt as for an Ollama call, then print the prompt to
+        /// stdout instead of sending it. Needs no Ollama installation.
+        #[arg(long, conflicts_with_all = ["no_ollama", "rerank"])]
+        dry_query: bool,
+        /// Print to stderr which query terms are in the index vocabulary before the results.
+        #[arg(long)]
+        show_term_coverage: bool,
+        /// Cut Ollama's answer after the last sentence that fits in this many characters,
+        /// marking it `[truncated]`.
+        #[arg(long, value_name = "N", conflicts_with = "no_ollama")]
+        output_limit_chars: Option<usize>,
+        /// Print to stderr, for each retrieved document, the raw dot product with the query
+        /// vector, both vectors' L2 norms and their cosine similarity.
+        #[arg(long)]
+        score_debug: bool,
+        /// Drop documents scoring below this. With `--randomize`, `--min-score 0` also keeps
+        /// documents sharing no term with the query, sampling the whole corpus.
+        #[arg(long)]
+        min_score: Option<f32>,
+        /// Return `k` documents picked at random from those passing `--min-score`, in
+        /// random order, instead of the best-scoring ones. `--sort-by` is ignored.
+        #[arg(long, conflicts_with = "max_context_docs_per_file")]
+        randomize: bool,
+        /// Seed for `--randomize`; the same seed gives the same picks and order.
+        #[arg(long, default_value_t = 0, requires = "randomize")]
+        seed: u64,
+        /// List retrieval results under their parent directory, directories ordered by
+        /// their best result (not with `--format csv`).
+        #[arg(long)]
+        group_by_source: bool,
+        /// Reuse Ollama's answer when the same model is sent the same prompt again,
+        /// caching answers in `--response-cache-file`.
+        #[arg(long, conflicts_with = "no_ollama")]
+        cache_ollama_responses: bool,
+        #[arg(long, default_value = cache::DEFAULT_RESPONSE_CACHE_FILE)]
+        response_cache_file: PathBuf,
+        /// Seconds a cached Ollama answer stays valid.
+        #[arg(long, default_value_t = cache::DEFAULT_RESPONSE_CACHE_TTL_SECS)]
+        response_cache_ttl: u64,
+        /// Print the answer cached in `--response-cache-file` under this hash (or a unique
+        /// prefix of it, as shown by `--list-cache`), without retrieval or generation.
+        #[arg(long, value_name = "HASH", conflicts_with_all = ["q", "query_file", "list_cache"])]
+        from_cache: Option<String>,
+        /// List the answers in `--response-cache-file`, newest first: hash, time and the
+        /// query they answered.
+        #[arg(long, conflicts_with_all = ["q", "query_file"])]
+        list_cache: bool,
+    },
+    /// Search every `*.json` index in a directory separately and merge the results by score.
+    /// Unlike repeating `query --index`, each index keeps its own IDF, so scores from
+    /// different indexes are only roughly comparable.
+    CrossIndexQuery {
+        #[arg(long)]
+        indexes_dir: PathBuf,
+        #[arg(short, long)]
+        q: String,
+        /// Number of results overall.
+        #[arg(short, long, default_value_t = 3)]
+        k: usize,
+        /// Number of results taken from each index before merging.
+        #[arg(long, default_value_t = 3)]
+        per_index_k: usize,
+        /// Output format: `text` (default), `json`, `pretty-json`, `jsonl`, `csv` or
+        /// `markdown`.
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Check that queries return expected documents within a rank, printing PASS or FAIL
+    /// for each and failing when any assertion fails. For use in CI.
+    Assert {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        /// JSON array, or TOML `[[assertions]]` tables, of `query`, `expected_doc_path`
+        /// and `min_rank` (default 1).
+        #[arg(long)]
+        assertions_file: PathBuf,
+    },
+    /// Re-rank the documents returned by an earlier query with a new query, ignoring the
+    /// rest of the index.
+    SearchWithin {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        /// Results of the earlier query, as written by `query --format jsonl` (or `json`).
+        #[arg(long)]
+        prev_results: PathBuf,
+        #[arg(short, long)]
+        q: String,
+        #[arg(short, long, default_value_t = 3)]
+        k: usize,
+        /// Output format: `text` (default), `json`, `pretty-json`, `jsonl`, `csv` or
+        /// `markdown`.
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// List the models installed on an Ollama server, smallest first, marking the one
+    /// `query` uses by default (`OLLAMA_MODEL` if installed, else the smallest).
+    ModelList {
+        #[arg(long, default_value = "http://localhost:11434")]
+        ollama_url: String,
+        /// Output format: `text` (default), `json`, `jsonl` or `csv`.
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Download a model onto an Ollama server, showing download progress.
+    PullModel {
+        /// Model to pull, e.g. `gemma3:1b`.
+        model: String,
+        #[arg(long, default_value = "http://localhost:11434")]
+        ollama_url: String,
+    },
+    /// Measure real indexing throughput and query latency on this machine.
+    Bench {
+        /// Number of synthetic documents to index (default: 1000).
+        #[arg(short, long, default_value_t = 1000)]
+        docs: usize,
+        /// Number of query iterations to average (default: 20).
+        #[arg(short, long, default_value_t = 20)]
+        queries: usize,
+    },
+    /// Time retrieval for each query in a file against an existing index, reporting mean,
+    /// p50/p95/p99 and max latency per query plus overall queries per second.
+    Benchmark {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        /// Queries, one per line; blank lines are skipped.
+        queries_file: PathBuf,
+        /// Timed runs per query, after one untimed warm-up run.
+        #[arg(long, default_value_t = 20)]
+        iterations: usize,
+        #[arg(short, long, default_value_t = 3)]
+        k: usize,
+        /// Output format: `markdown` (a table, default) or `json`.
+        #[arg(long, default_value = "markdown")]
+        format: benchmark::BenchmarkFormat,
+    },
+    /// Compare two indexed documents: cosine similarity plus shared and distinct top terms.
+    Compare {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        /// First document, by id, path or file name.
+        doc_a: String,
+        /// Second document, by id, path or file name.
+        doc_b: String,
+    },
+    /// Compare two files without an index: TF-IDF cosine similarity plus the terms unique
+    /// to each and the terms they share.
+    TextDiff { file_a: PathBuf, file_b: PathBuf },
+    /// Cosine similarity of two sentences' TF-IDF vectors, with the tokens they share.
+    SentenceSimilarity {
+        text_a: String,
+        text_b: String,
+        /// Take IDF from this index instead of estimating it from the two sentences.
+        #[arg(short, long)]
+        index: Option<PathBuf>,
+    },
+    /// List the documents most similar to an indexed document.
+    MostSimilar {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        /// Source document, by id, path or file name.
+        #[arg(short, long)]
+        doc: String,
+        #[arg(short, long, default_value_t = 3)]
+        k: usize,
+    },
+    /// Export term co-occurrence counts as JSONL (`{"term_a", "term_b", "count"}` per line).
+    Cooccurrence {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        /// Maximum distance, in tokens, between two co-occurring terms.
+        #[arg(short, long, default_value_t = 5)]
+        window: usize,
+        #[arg(short, long, default_value = "voltai_cooccurrence.jsonl")]
+        out: PathBuf,
+        /// Drop pairs that co-occur fewer than this many times.
+        #[arg(long, default_value_t = 1)]
+        min_count: usize,
+    },
+    /// Report documents with identical text (same content hash) and, separately, pairs of
+    /// near-duplicates, as JSON.
+    FindDuplicates {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        /// Cosine similarity above which differing documents count as near-duplicates.
+        #[arg(long, default_value_t = duplicates::DEFAULT_NEAR_DUPLICATE_SIMILARITY)]
+        min_similarity: f32,
+    },
+    /// Export a document similarity graph (an edge per pair of documents more similar than
+    /// `--min-similarity`) as Graphviz DOT or GraphML, for Gephi or Graphviz.
+    Graph {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        /// Cosine similarity an edge must exceed.
+        #[arg(long, default_value_t = 0.3)]
+        min_similarity: f32,
+        /// `dot` (default) or `graphml`.
+        #[arg(long, default_value = "dot")]
+        format: graph::GraphFormat,
+        #[arg(short, long, default_value = "voltai_graph.dot")]
+        out: PathBuf,
+    },
+    /// List the terms that dominate the index, with document frequency and average and
+    /// maximum TF-IDF weight.
+    TopTerms {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        /// Number of terms to list.
+        #[arg(short, default_value_t = 20)]
+        n: usize,
+        /// Rank by `doc-freq` (default) or average `tf-idf` weight.
+        #[arg(long, default_value = "doc-freq")]
+        sort_by: terms::TermSortBy,
+        /// Leave out terms on the built-in stop-word list.
+        #[arg(long)]
+        exclude_stop_words: bool,
+        /// Add a histogram of the document frequencies of the whole vocabulary and their
+        /// Zipf coefficient.
+        #[arg(long)]
+        histogram: bool,
+    },
+    /// Show the heaviest terms of one indexed document, with its vector norm and number of
+    /// non-zero entries.
+    DocTerms {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        /// Document, by id, path or file name.
+        #[arg(long)]
+        doc_path: String,
+        /// Number of terms to list.
+        #[arg(long, default_value_t = 20)]
+        top_n: usize,
+        /// Output format: `text` (default), `json` (array of `{term, weight}`), `jsonl` or `csv`.
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Re-run a query with relevance feedback (Rocchio): documents marked relevant pull the
+    /// query towards their vocabulary, irrelevant ones push it away.
+    Feedback {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        #[arg(short, long)]
+        query: String,
+        /// Document (id, path or file name) judged relevant; repeatable.
+        #[arg(long)]
+        relevant: Vec<String>,
+        /// Document (id, path or file name) judged irrelevant; repeatable.
+        #[arg(long)]
+        irrelevant: Vec<String>,
+        #[arg(short, long, default_value_t = 3)]
+        k: usize,
+        /// Weight of the original query.
+        #[arg(long, default_value_t = 1.0)]
+        alpha: f32,
+        /// Weight of the relevant-document centroid.
+        #[arg(long, default_value_t = 0.75)]
+        beta: f32,
+        /// Weight of the irrelevant-document centroid.
+        #[arg(long, default_value_t = 0.15)]
+        gamma: f32,
+    },
+    /// Report per-file and corpus-wide text statistics (word and sentence counts,
+    /// vocabulary, lexical diversity, keywords) for a directory before indexing it.
+    TextStats {
+        #[arg(short, long)]
+        dir: PathBuf,
+        /// Output format: `text` (default), `json`, or `jsonl` / `csv` (one row per file).
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+        /// Add a histogram of word document frequencies and the corpus Zipf coefficient
+        /// (text and JSON output).
+        #[arg(long)]
+        histogram: bool,
+    },
+    /// Count words, unique words, sentences and paragraphs per file under a directory, plus
+    /// corpus totals, without indexing it.
+    WordCount {
+        #[arg(short, long)]
+        dir: PathBuf,
+        /// Output format: `text` (default), `json`, `jsonl` or `csv`. The last row holds
+        /// the totals.
+        #[arg(long, default_value = "text")]
+        format: OutputFormat,
+    },
+    /// Extract named entities from every indexed document and write an entity → documents map.
+    IndexEntities {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        #[arg(short, long, default_value = "voltai_entity_index.json")]
+        out: PathBuf,
+        /// Key entities by canonical form, merging aliases ("NYC", "New York City"),
+        /// titled names ("Dr. Jane Doe") and legal forms ("Acme Inc.").
+        #[arg(long)]
+        normalize_entities: bool,
+        /// Language of the documents (`en`, `fr`, `de`), selecting organization suffixes,
+        /// place names and name patterns; defaults to the index's language. Other codes
+        /// use the English patterns.
+        #[arg(long)]
+        lang: Option<String>,
+    },
+    /// Export the index's documents as CSV: id, path, file size, modification time,
+    /// language, top keywords and vector norm.
+    ExportCsv {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        #[arg(short, long, default_value = "voltai_documents.csv")]
+        out: PathBuf,
+        /// Add one column per index term with the document's TF-IDF weight. Output can
+        /// be very wide.
+        #[arg(long)]
+        include_vectors: bool,
+    },
+    /// Run every query in a file and write a self-contained HTML report: a summary table,
+    /// each query's results with snippets, and a bar chart of their scores.
+    Report {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        /// Queries, one per line; blank lines are skipped.
+        queries_file: PathBuf,
+        #[arg(short, long, default_value = "voltai_report.html")]
+        out_html: PathBuf,
+        /// Documents reported per query.
+        #[arg(short, long, default_value_t = 5)]
+        k: usize,
+    },
+    /// Index a directory and run analysis steps over it, writing one JSON file per step
+    /// (`index.json`, `entities.json`, `text_stats.json`) to `--out-dir`.
+    Pipeline {
+        #[arg(short, long)]
+        dir: PathBuf,
+        #[arg(short, long, default_value = "voltai_pipeline")]
+        out_dir: PathBuf,
+        /// Comma-separated steps, run in the given order.
+        #[arg(long, value_delimiter = ',', default_value = "index,ner,stats")]
+        steps: Vec<pipeline::PipelineStep>,
+    },
+    /// List the queries cached under `--cache-dir`, most recent first.
+    SearchHistory,
+    /// Print a shell completion script, e.g.
+    /// `voltai completions bash > ~/.local/share/bash-completion/completions/voltai`.
+    Completions { shell: clap_complete::Shell },
+    /// Delete the Ollama answers cached by `query --cache-ollama-responses`.
+    ClearResponseCache {
+        #[arg(long, default_value = cache::DEFAULT_RESPONSE_CACHE_FILE)]
+        response_cache_file: PathBuf,
+    },
+    /// List entities mentioned by several indexed documents, with the documents mentioning them.
+    CrossRef {
+        #[arg(short, long, default_value = "voltai_index.json")]
+        index: PathBuf,
+        /// Only report entities found in at least this many documents.
+        #[arg(long, default_value_t = 2)]
+        min_docs: usize,
+    },
+    /// List the documents that mention an entity, using an index built by `index-entities`.
+    QueryEntity {
+        #[arg(long, default_value = "voltai_entity_index.json")]
+        entity_index: PathBuf,
+        /// Entity surface form, e.g. "New York". Matching is case-insensitive.
+        #[arg(short, long)]
+        entity: String,
+    },
+}
+
+/// Serialisation format for the index file produced by `voltai index`.
+///
+/// | Format   | Readable | Typical size     | Load speed |
+/// |----------|----------|------------------|------------|
+/// | `json`   | yes      | 1× (baseline)    | baseline   |
+/// | `binary` | no       | ~3× smaller      | ~3–4× faster |
+///
+/// `voltai query` detects the format automatically from the file extension
+/// (`.json` → JSON; any other extension → bincode).
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum IndexFormat {
+    #[default]
+    Json,
+    Binary,
+}
+
+impl IndexFormat {
+    /// The format `load_index` expects for `path`: JSON for `.json`, binary otherwise.
+    fn for_path(path: &Path) -> Self {
+        match path.extension().and_then(|s| s.to_str()) {
+            Some("json") => IndexFormat::Json,
+            _ => IndexFormat::Binary,
+        }
+    }
+}
+
+/// How an index file stores its term weights (`voltai index --index-format`). Loading
+/// always yields dense `Index::vectors`, so both are queried the same way.
+#[derive(
+    ValueEnum, Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexType {
+    /// Every document's full weight row in `vectors`.
+    #[default]
+    Dense,
+    /// Only the non-zero weights of each document, in `sparse_vectors`.
+    Inverted,
+}
+
+/// `--index-format` choice; `auto` picks by corpus size.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IndexLayout {
+    #[default]
+    Dense,
+    Inverted,
+    Auto,
+}
+
+/// Document-term cells above which `--index-format auto` stores an inverted index.
+const AUTO_INVERTED_CELLS: usize = 10_000_000;
+
+impl IndexLayout {
+    fn resolve(self, docs: usize, terms: usize) -> IndexType {
+        match self {
+            IndexLayout::Dense => IndexType::Dense,
+            IndexLayout::Inverted => IndexType::Inverted,
+            IndexLayout::Auto if docs.saturating_mul(terms) > AUTO_INVERTED_CELLS => {
+                IndexType::Inverted
+            }
+            IndexLayout::Auto => IndexType::Dense,
+        }
+    }
+}
+
+/// Term weighting used to build `Index::idf` and `Index::vectors`.
+#[derive(
+    ValueEnum, Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, Default, PartialEq, Eq,
+)]
+#[serde(rename_all = "lowercase")]
+enum RankingMethod {
+    /// BM25: saturating term frequency with document-length normalisation.
+    #[default]
+    Bm25,
+    /// Classic TF-IDF: term frequency relative to document length, smoothed IDF.
+    #[value(name = "tfidf")]
+    TfIdf,
+}
+
+/// Unit reported by `voltai query`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Granularity {
+    #[default]
+    Document,
+    /// Re-rank each retrieved document's sentences and report the best one.
+    Sentence,
+}
+
+/// Output format for reporting subcommands.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    /// Indented JSON; `voltai query` adds a keyword `summary` to each result.
+    PrettyJson,
+    /// One JSON object per line.
+    Jsonl,
+    Csv,
+    /// Markdown document (`voltai query` only).
+    Markdown,
+}
+
+/// Error for `--format markdown` on commands whose reports have no Markdown rendering.
+fn markdown_unsupported() -> anyhow::Error {
+    anyhow!("--format markdown is only supported by `voltai query`")
+}
+
+/// Order of retrieval results (`--sort-by`).
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum SortBy {
+    /// Highest score first.
+    #[default]
+    Score,
+    /// Alphabetically by document path.
+    Path,
+    /// Most recently modified first; documents without a timestamp last.
+    Date,
+}
+
+/// How each retrieved document is represented in the LLM prompt context.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum ContextMode {
+    /// Filename plus top keywords: smallest prompt, and the model cannot quote the source.
+    #[default]
+    Keywords,
+    /// Filename plus the leading `--excerpt-chars` characters of the document.
+    Excerpt,
+    /// Filename plus the whole document text, bounded by the context budget.
+    Full,
+}
+
+/// BM25 free parameters (Robertson-Sparck Jones variant).
+/// k1 controls term frequency saturation; b controls document length normalisation.
+const BM25_K1: f32 = 1.2;
+const BM25_B: f32 = 0.75;
+
+/// Slope of pivoted length normalisation (`--pivoted-normalization`): how far a
+/// document's own vector norm, rather than the corpus average, sets its divisor.
+const PIVOT_ALPHA: f32 = 0.2;
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default)]
+struct Doc {
+    id: String,
+    path: String,
+    text: String,
+    /// Token list, cached by `index --cache-tokens` for `rebuild-vectors`.
+    #[serde(default)]
+    tokens: Option<Vec<String>>,
+    /// Source metadata such as a PDF's `title`, `author`, `subject` and `page_count`.
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+    /// File modification time (seconds since the Unix epoch) when indexed.
+    #[serde(default)]
+    last_modified_secs: Option<u64>,
+    /// Opening sentence (see [`extract_first_sentence`]), the result excerpt when no query
+    /// term occurs verbatim. Empty for indexes built before it was recorded.
+    #[serde(default)]
+    first_sentence: String,
+    /// Hex SHA-256 of `text` (see `voltai find-duplicates`); empty for older indexes.
+    #[serde(default)]
+    content_hash: String,
+    /// Top terms of the document's cluster, filled by `index --assign-topics`.
+    #[serde(default)]
+    topics: Vec<String>,
+}
+
+/// On-disk layout version written by this build. Files without a `version` field predate
+/// versioning and are treated as version 0.
+const INDEX_VERSION: u32 = 1;
+
+/// Schema version of the index file written by this build. Files without a
+/// `schema_version` field predate it and share this schema.
+const SCHEMA_VERSION: &str = "1.0";
+
+fn current_schema_version() -> String {
+    SCHEMA_VERSION.to_string()
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct Index {
+    /// Layout version; see [`INDEX_VERSION`]. Bumped when the shape of a field changes, so
+    /// older files need `voltai migrate` to rewrite them; version 0 is upgraded on load.
+    #[serde(default)]
+    version: u32,
+    /// Schema version; see [`SCHEMA_VERSION`]. Versions the set of fields within a layout:
+    /// a file at the current `version` but an older `schema_version` loads only after
+    /// `voltai migrate --from-version <version>` stamps it with the current schema.
+    #[serde(default = "current_schema_version")]
+    schema_version: String,
+    docs: Vec<Doc>,
+    pub terms: Vec<String>,
+    /// BM25-IDF (Robertson-Sparck Jones) per term, parallel to `terms`.
+    /// Stored so `query_with_ollama` can score without re-computing IDF from DF counts.
+    pub idf: Vec<f32>,
+    /// Pre-normalised BM25 term weights (IDF excluded): `(tf*(k1+1))/(tf+k1*(1-b+b*|d|/avgdl))`,
+    /// with `b = 0` when `normalized` is false.
+    /// Score = dot_product(q_vec, vectors[doc]) where q_vec[t] = idf[t] for query terms.
+    /// Empty on disk for inverted indexes, which `load_index` expands from `sparse_vectors`.
+    pub vectors: Vec<Vec<f32>>,
+    /// On-disk layout of the weights; see [`IndexType`].
+    #[serde(default)]
+    index_type: IndexType,
+    /// `(term, weight)` pairs of each document's non-zero weights, written instead of
+    /// `vectors` by inverted indexes and empty in memory.
+    #[serde(default)]
+    sparse_vectors: Vec<Vec<(u32, f32)>>,
+    /// Inverted index: term → doc indices where that term has a nonzero BM25 weight.
+    /// Enables O(T) candidate accumulation at query time instead of O(n·V) linear scan.
+    inverted: HashMap<String, Vec<usize>>,
+    /// Whether `vectors` include BM25 document-length normalisation. `false` for indexes
+    /// built with `--no-normalize` (b = 0); indexes predating the flag are normalised.
+    #[serde(default = "default_normalized")]
+    normalized: bool,
+    /// Weighting scheme `idf`/`vectors` were built with.
+    #[serde(default)]
+    ranking: RankingMethod,
+    /// ONNX model whose sentence embeddings replace the term weights in `vectors`
+    /// (`voltai index --embeddings onnx`); `None` for term-weight indexes.
+    #[serde(default)]
+    embedding_model: Option<String>,
+    /// Regex that split documents into tokens (`voltai index --token-pattern`), reused for
+    /// queries; `None` for the built-in word pattern.
+    #[serde(default)]
+    token_pattern: Option<String>,
+    /// Term replacements applied after tokenization (`voltai index --alias-file`), reused
+    /// for queries.
+    #[serde(default)]
+    term_aliases: BTreeMap<String, String>,
+    /// Longest token kept (`voltai index --max-keyword-length`), reused for queries.
+    #[serde(default)]
+    max_token_len: Option<usize>,
+    /// Language whose stop words were dropped (`voltai index --lang`), reused for queries.
+    #[serde(default)]
+    lang: Option<String>,
+    /// Whether all-digit tokens were dropped (`voltai index --exclude-numeric`), reused for
+    /// queries.
+    #[serde(default)]
+    exclude_numeric: bool,
+    /// Transformations applied to each document's text before tokenization
+    /// (`voltai index --pre-process`).
+    #[serde(default)]
+    preprocess: Vec<preprocess::PreprocessStep>,
+    /// Corpus-wide occurrences a token needed to be indexed (`voltai index
+    /// --min-keyword-freq`); 0 or 1 when every token was kept.
+    #[serde(default)]
+    min_keyword_freq: usize,
+    /// Named entities of each document by path, found at index time for
+    /// `query --entity-boost`; documents without entities are left out. `None` for indexes
+    /// built before entities were recorded.
+    #[serde(default)]
+    doc_entities: Option<HashMap<String, Vec<Entity>>>,
+    /// HNSW graph over `vectors`, attached by `load_index` when the index was built with
+    /// `--ann`. Stored in sidecar files rather than in the index itself.
+    #[serde(skip)]
+    ann: Option<Arc<AnnIndex>>,
+    /// Model that embeds queries for an embedding index, attached when it is loaded for
+    /// querying.
+    #[serde(skip)]
+    embedder: Option<Arc<dyn embeddings::Embedder>>,
+}
+
+fn default_normalized() -> bool {
+    true
+}
+
+impl Default for Index {
+    fn default() -> Self {
+        Index {
+            version: INDEX_VERSION,
+            schema_version: current_schema_version(),
+            docs: Vec::new(),
+            terms: Vec::new(),
+            idf: Vec::new(),
+            vectors: Vec::new(),
+            index_type: IndexType::Dense,
+            sparse_vectors: Vec::new(),
+            inverted: HashMap::new(),
+            normalized: true,
+            ranking: RankingMethod::Bm25,
+            embedding_model: None,
+            token_pattern: None,
+            term_aliases: BTreeMap::new(),
+            max_token_len: None,
+            lang: None,
+            exclude_numeric: false,
+            preprocess: Vec::new(),
+            min_keyword_freq: 0,
+            doc_entities: None,
+            ann: None,
+            embedder: None,
+        }
+    }
+}
+
+pub fn tokenize(s: &str) -> Vec<String> {
+    tokenize_with_config(s, &TokenizerConfig::default())
+}
+
+/// Index-time token filters (`voltai index --token-pattern`, `--max-keyword-length`,
+/// `--lang`, `--exclude-numeric`, `--alias-file`).
+#[derive(Debug, Clone, Default)]
+struct TokenizerConfig {
+    /// Matches a token, replacing the built-in word pattern.
+    pattern: Option<Regex>,
+    /// Drop tokens longer than this many characters, such as hashes or base64 blobs.
+    max_token_len: Option<usize>,
+    /// Language whose stop words are dropped instead of the English ones.
+    lang: Option<String>,
+    /// Drop tokens made only of digits, such as years and counts.
+    exclude_numeric: bool,
+    /// Rewrites the finished token list, e.g. expanding aliases.
+    filter: Option<Arc<dyn termfilter::TermFilter>>,
+}
+
+/// The filter applying `aliases`, or `None` when there are none.
+fn alias_filter(aliases: &BTreeMap<String, String>) -> Option<Arc<dyn termfilter::TermFilter>> {
+    if aliases.is_empty() {
+        return None;
+    }
+    Some(Arc::new(termfilter::FilterChain(vec![Box::new(
+        termfilter::AliasFilter {
+            aliases: aliases.clone(),
+        },
+    )])))
+}
+
+/// [`tokenize`], additionally applying `cfg`'s filters.
+fn tokenize_with_config(s: &str, cfg: &TokenizerConfig) -> Vec<String> {
+    let stop_words = cfg
+        .lang
+        .as_deref()
+        .and_then(stopwords::load_stop_words)
+        .unwrap_or(&STOP_WORDS);
+    let tokens: Vec<String> = cfg
+        .pattern
+        .as_ref()
+        .unwrap_or(&WORD_RE)
+        .find_iter(s)
+        .filter(|m| !m.as_str().is_empty())
+        .filter(|m| cfg.max_token_len.is_none_or(|max| m.as_str().len() <= max))
+        .filter(|m| !(cfg.exclude_numeric && m.as_str().bytes().all(|b| b.is_ascii_digit())))
+        .map(|m| m.as_str().to_lowercase())
+        .filter(|w| !stop_words.contains(w.as_str()))
+        .collect();
+    cfg.filter
+        .as_deref()
+        .unwrap_or(&termfilter::IdentityFilter)
+        .filter(&tokens)
+}
+
+/// Compiles a `--token-pattern`, explaining what went wrong when it is not a valid regex.
+fn compile_token_pattern(pattern: &str) -> Result<Regex> {
+    Regex::new(pattern).map_err(|e| {
+        anyhow!(
+            "invalid --token-pattern '{}': {}\nThe pattern must be a regular expression matching one token, e.g. '[a-z]+'.",
+            pattern,
+            e
+        )
+    })
+}
+
+/// Removes tokens occurring fewer than `min_freq` times across all of `docs_tokens`.
+fn drop_rare_tokens(docs_tokens: &mut [Vec<String>], min_freq: usize) {
+    if min_freq <= 1 {
+        return;
+    }
+    let mut freq: HashMap<String, usize> = HashMap::new();
+    for t in docs_tokens.iter().flatten() {
+        *freq.entry(t.clone()).or_insert(0) += 1;
+    }
+    for toks in docs_tokens.iter_mut() {
+        toks.retain(|t| freq[t] >= min_freq);
+    }
+}
+
+/// Computes the inner product of two vectors.
+/// Used for document-to-document cosine similarity and, in tests, as a reference
+/// implementation for BM25 score verification. Query scoring uses the inverted-index
+/// O(T) path instead.
+pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+}
+
+/// Loads an `Index` from disk, auto-detecting the serialisation format by file extension.
+/// `.json` files are decoded with `serde_json`; all other extensions are decoded with `bincode`.
+/// An HNSW graph dumped alongside the index is loaded into `Index::ann`.
+pub fn load_index(path: &Path) -> Result<Index> {
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("json");
+    let mut idx: Index = if ext == "json" {
+        let f = std::io::BufReader::new(File::open(path)?);
+        serde_json::from_reader(f).map_err(|e| json_decode_error(path, e))?
+    } else {
+        let data = std::fs::read(path)?;
+        bincode::deserialize(&data)?
+    };
+    check_schema_version(&idx).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+    // Every field added since v0 carries a serde default equal to the value
+    // `migrate_v0_to_v1` fills in, so decoding already performed the upgrade.
+    if idx.version == 0 {
+        idx.version = INDEX_VERSION;
+    }
+    if idx.index_type == IndexType::Inverted {
+        densify(&mut idx).map_err(|e| anyhow!("invalid index {}: {}", path.display(), e))?;
+    }
+    idx.validate()
+        .map_err(|e| anyhow!("invalid index {}: {}", path.display(), e))?;
+    idx.ann = AnnIndex::load(path)?.map(Arc::new);
+    Ok(idx)
+}
+
+/// Fails unless `index` has the schema this build reads, pointing other schemas at
+/// `voltai migrate`. Layout version 0 is accepted: it decodes as the current layout.
+fn check_schema_version(index: &Index) -> Result<()> {
+    if index.version > INDEX_VERSION {
+        return Err(anyhow!(
+            "index version {} is newer than this build supports ({})",
+            index.version,
+            INDEX_VERSION
+        ));
+    }
+    if index.schema_version != SCHEMA_VERSION {
+        return Err(anyhow!(
+            "index schema version {} does not match this build ({}); run `voltai migrate --from-version {}` to upgrade it",
+            index.schema_version,
+            SCHEMA_VERSION,
+            index.version
+        ));
+    }
+    Ok(())
+}
+
+/// The error for a JSON index that failed to decode. A field whose type changed between
+/// layouts fails to decode, so when the file declares an older layout the error points at
+/// `voltai migrate`, which reads it as loose JSON; anything else is reported as corrupt.
+fn json_decode_error(path: &Path, err: serde_json::Error) -> anyhow::Error {
+    let raw: Option<serde_json::Value> = File::open(path)
+        .ok()
+        .and_then(|f| serde_json::from_reader(std::io::BufReader::new(f)).ok());
+    let version = raw
+        .as_ref()
+        .and_then(|r| r.get("version").map_or(Some(0), serde_json::Value::as_u64));
+    match version {
+        Some(v) if v < u64::from(INDEX_VERSION) => anyhow!(
+            "cannot decode {} ({}); it is index version {}, run `voltai migrate --from-version {}`",
+            path.display(),
+            err,
+            v,
+            v
+        ),
+        _ => anyhow!("cannot decode {}: {}", path.display(), err),
+    }
+}
+
+/// Loads an index for a command that reads term weights, rejecting embedding indexes.
+fn load_term_index(path: &Path) -> Result<Index> {
+    let idx = load_index(path)?;
+    if let Some(model) = &idx.embedding_model {
+        return Err(anyhow!(
+            "{} stores {} embeddings rather than term weights; rebuild it without --embeddings",
+            path.display(),
+            model
+        ));
+    }
+    Ok(idx)
+}
+
+impl Index {
+    /// The stored tokenizer settings documents were tokenized with: the token pattern,
+    /// token filters and term aliases.
+    fn tokenizer_config(&self) -> TokenizerConfig {
+        TokenizerConfig {
+            pattern: self
+                .token_pattern
+                .as_deref()
+                .and_then(|p| Regex::new(p).ok()),
+            max_token_len: self.max_token_len,
+            lang: self.lang.clone(),
+            exclude_numeric: self.exclude_numeric,
+            filter: alias_filter(&self.term_aliases),
+        }
+    }
+
+    /// Copies the index-time text settings of `other`, for an index rebuilt from its
+    /// documents.
+    fn copy_text_settings(&mut self, other: &Index) {
+        self.token_pattern.clone_from(&other.token_pattern);
+        self.term_aliases.clone_from(&other.term_aliases);
+        self.max_token_len = other.max_token_len;
+        self.lang.clone_from(&other.lang);
+        self.exclude_numeric = other.exclude_numeric;
+        self.preprocess.clone_from(&other.preprocess);
+        self.min_keyword_freq = other.min_keyword_freq;
+    }
+
+    /// Language of the documents' stop words and entity patterns (`voltai index --lang`).
+    fn language(&self) -> Language {
+        Language::from_code(self.lang.as_deref().unwrap_or("en"))
+    }
+
+    /// Top `n` keywords of `text` without the stop words of the index's language.
+    fn keywords(&self, text: &str, n: usize) -> Vec<String> {
+        language_keywords(text, n, self.lang.as_deref())
+    }
+
+    /// Tokenizes query text the way the documents were tokenized.
+    fn tokenize_query(&self, q: &str) -> Vec<String> {
+        tokenize_with_config(q, &self.tokenizer_config())
+    }
+
+    /// Checks that the parallel arrays agree in length, that every posting points at an
+    /// existing document and that the stored token pattern compiles, so a corrupted or
+    /// hand-edited index file is rejected on load instead of panicking with an
+    /// out-of-bounds index during scoring or tokenizing queries differently.
+    pub fn validate(&self) -> Result<()> {
+        if self.idf.len() != self.terms.len() {
+            return Err(anyhow!(
+                "{} idf weights for {} terms",
+                self.idf.len(),
+                self.terms.len()
+            ));
+        }
+        if self.vectors.len() != self.docs.len() {
+            return Err(anyhow!(
+                "{} vectors for {} documents",
+                self.vectors.len(),
+                self.docs.len()
+            ));
+        }
+        // Term-weight vectors have one entry per term; embeddings share the model's width.
+        let (width, unit) = match self.embedding_model {
+            Some(_) => (self.vectors.first().map_or(0, Vec::len), "dimensions"),
+            None => (self.terms.len(), "terms"),
+        };
+        if let Some(di) = self.vectors.iter().position(|v| v.len() != width) {
+            return Err(anyhow!(
+                "vector {} has {} weights for {} {}",
+                di,
+                self.vectors[di].len(),
+                width,
+                unit
+            ));
+        }
+        for (term, postings) in &self.inverted {
+            if let Some(&di) = postings.iter().find(|&&di| di >= self.docs.len()) {
+                return Err(anyhow!(
+                    "posting list for '{}' references document {} of {}",
+                    term,
+                    di,
+                    self.docs.len()
+                ));
+            }
+        }
+        if let Some(pattern) = &self.token_pattern {
+            compile_token_pattern(pattern)?;
+        }
+        Ok(())
+    }
+}
+
+/// Upgrades a version-0 JSON index (written before `version` existed) to version 1 by
+/// filling in the defaults for fields added since: `normalized` (true), `ranking` (bm25)
+/// and, per document, `tokens` (none) and `metadata` (empty).
+fn migrate_v0_to_v1(mut raw: serde_json::Value) -> Result<Index> {
+    let obj = raw
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("index is not a JSON object"))?;
+    if let Some(v) = obj.get("version") {
+        return Err(anyhow!("expected a version 0 index, found version {}", v));
+    }
+    obj.insert("version".into(), 1.into());
+    obj.entry("normalized").or_insert(true.into());
+    obj.entry("ranking").or_insert("bm25".into());
+    if let Some(docs) = obj.get_mut("docs").and_then(|d| d.as_array_mut()) {
+        for doc in docs.iter_mut().filter_map(|d| d.as_object_mut()) {
+            doc.entry("tokens").or_insert(serde_json::Value::Null);
+            doc.entry("metadata").or_insert(serde_json::json!({}));
+            doc.entry("last_modified_secs")
+                .or_insert(serde_json::Value::Null);
+        }
+    }
+    Ok(serde_json::from_value(raw)?)
+}
+
+/// `major.minor` of a schema version string, for ordering schemas.
+fn parse_schema_version(schema: &str) -> Option<(u32, u32)> {
+    let (major, minor) = schema.split_once('.')?;
+    Some((major.parse().ok()?, minor.parse().ok()?))
+}
+
+/// Stamps a current-layout index whose `schema_version` is older than [`SCHEMA_VERSION`]
+/// with the current schema. Fields added since default on decode, so only the stamp
+/// changes; newer or unparseable schemas are rejected.
+fn migrate_schema(mut raw: serde_json::Value) -> Result<Index> {
+    let obj = raw
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("index is not a JSON object"))?;
+    let schema = obj
+        .get("schema_version")
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or(SCHEMA_VERSION)
+        .to_string();
+    match (
+        parse_schema_version(&schema),
+        parse_schema_version(SCHEMA_VERSION),
+    ) {
+        (Some(found), Some(current)) if found < current => {}
+        (Some(found), Some(current)) if found == current => {
+            return Err(anyhow!(
+                "index is already at the current version ({}) and schema ({})",
+                INDEX_VERSION,
+                SCHEMA_VERSION
+            ))
+        }
+        _ => return Err(anyhow!("no migration from index schema {}", schema)),
+    }
+    obj.insert("schema_version".into(), SCHEMA_VERSION.into());
+    Ok(serde_json::from_value(raw)?)
+}
+
+/// Rewrites the JSON index at `index_file`, which must be at `from_version`, in the
+/// current layout and schema to `out` (JSON or binary by extension).
+fn migrate_index(index_file: &Path, from_version: u32, out: &Path, quiet: bool) -> Result<()> {
+    let raw: serde_json::Value =
+        serde_json::from_reader(std::io::BufReader::new(File::open(index_file)?)).map_err(|e| {
+            anyhow!(
+                "{} is not a JSON index ({}); only JSON indexes can be migrated",
+                index_file.display(),
+                e
+            )
+        })?;
+    let found = raw
+        .get("version")
+        .map_or(Some(0), serde_json::Value::as_u64)
+        .ok_or_else(|| anyhow!("{} has a non-numeric version", index_file.display()))?;
+    if found != u64::from(from_version) {
+        return Err(anyhow!(
+            "{} is index version {}, not {}",
+            index_file.display(),
+            found,
+            from_version
+        ));
+    }
+    let mut index = match from_version {
+        0 => migrate_v0_to_v1(raw)?,
+        INDEX_VERSION => {
+            migrate_schema(raw).map_err(|e| anyhow!("{}: {}", index_file.display(), e))?
+        }
+        v => return Err(anyhow!("no migration from index version {}", v)),
+    };
+    index.validate()?;
+    write_index(&mut index, out, IndexFormat::for_path(out))?;
+    writeln!(
+        status_writer(quiet),
+        "Migrated {} from version {} to {}: {}",
+        index_file.display(),
+        from_version,
+        INDEX_VERSION,
+        out.display()
+    )?;
+    Ok(())
+}
+
+/// JSON Schema (draft-07) of the index file. The root describes `Index`; `QueryResult`
+/// (the `query --format json` element) and `Entity` (`index-entities` output) are added to
+/// `definitions` so tools can validate every JSON shape VoltAI writes from one document.
+fn index_schema() -> schemars::schema::RootSchema {
+    let mut gen = schemars::gen::SchemaSettings::draft07().into_generator();
+    gen.subschema_for::<QueryResult>();
+    gen.subschema_for::<Entity>();
+    gen.into_root_schema_for::<Index>()
+}
+
+/// Settings for `voltai index`.
+#[derive(Debug, Clone, Default)]
+struct IndexOptions {
+    format: IndexFormat,
+    /// Weight layout of term-weight indexes (`--index-format`); embedding indexes are
+    /// always dense.
+    layout: IndexLayout,
+    /// Build and dump an HNSW graph next to the index (`--ann`).
+    ann: bool,
+    /// Skip BM25 document-length normalisation (`--no-normalize`).
+    no_normalize: bool,
+    ranking: RankingMethod,
+    /// Keep each document's tokens in the index (`--cache-tokens`).
+    cache_tokens: bool,
+    /// Follow symbolic links while walking the directory (`--allow-symlinks`).
+    follow_symlinks: bool,
+    /// Replace term weights with embeddings from this ONNX model (`--embeddings onnx`).
+    embedding_model: Option<PathBuf>,
+    /// Hide the progress bar and status messages (`--quiet`).
+    quiet: bool,
+    /// Report per-file details (`--verbose`).
+    verbose: bool,
+    /// Index a seeded random sample of this many files (`--sample`, `--seed`).
+    sample: Option<usize>,
+    seed: u64,
+    /// Text transformations applied before tokenization (`--pre-process`).
+    preprocess: Vec<preprocess::PreprocessStep>,
+    /// Token filters applied before vectorisation (`--max-keyword-length`).
+    tokenizer: TokenizerConfig,
+    /// Term replacements from `--alias-file`, applied after `tokenizer` and stored in the
+    /// index.
+    term_aliases: BTreeMap<String, String>,
+    /// Minimum corpus-wide occurrences for a token to be indexed (`--min-keyword-freq`).
+    min_keyword_freq: usize,
+    /// Take Markdown document ids from their first heading (`--title-from-first-line`).
+    title_from_first_line: bool,
+    /// Extra weight for terms of these metadata fields (`--field-boost`).
+    field_boosts: Vec<frontmatter::FieldBoost>,
+    /// Drop documents at least this cosine-similar to an earlier one (`--deduplicate-near`).
+    deduplicate_near: Option<f32>,
+    /// Abort on the first unreadable file instead of indexing it as empty (`--stop-on-error`).
+    stop_on_error: bool,
+    /// Cluster count for topic labels (`--assign-topics`); clustering is seeded by `seed`.
+    assign_topics: Option<usize>,
+}
+
+/// Per-file indexing details for `voltai index --verbose`; a no-op when disabled.
+struct VerboseLogger<'a> {
+    out: Option<&'a mut dyn Write>,
+}
+
+impl<'a> VerboseLogger<'a> {
+    fn new(verbose: bool, out: &'a mut dyn Write) -> Self {
+        VerboseLogger {
+            out: verbose.then_some(out),
+        }
+    }
+
+    /// One line for `path`: `<path>: <bytes> bytes from disk, <n> tokens, <n> non-zero
+    /// terms`, plus the read error when extraction failed. Indexing keeps no content cache,
+    /// so the source is always `disk`.
+    fn file(
+        &mut self,
+        path: &str,
+        tokens: usize,
+        non_zero: usize,
+        read_error: Option<&str>,
+    ) -> Result<()> {
+        let Some(out) = self.out.as_mut() else {
+            return Ok(());
+        };
+        let bytes = std::fs::metadata(path).map_or(0, |m| m.len());
+        write!(
+            out,
+            "{}: {} bytes from disk, {} tokens, {} non-zero terms",
+            path, bytes, tokens, non_zero
+        )?;
+        match read_error {
+            Some(e) => writeln!(out, ", read failed: {}", e)?,
+            None => writeln!(out)?,
+        }
+        Ok(())
+    }
+}
+
+pub fn index_dir(dir: &Path, out: &Path, format: IndexFormat) -> Result<()> {
+    index_dir_with_options(
+        dir,
+        out,
+        &IndexOptions {
+            format,
+            ..Default::default()
+        },
+    )
+}
+
+/// Modification time of `p` in seconds since the Unix epoch, if the platform reports one.
+fn last_modified_secs(p: &Path) -> Option<u64> {
+    let modified = std::fs::metadata(p).and_then(|m| m.modified()).ok()?;
+    modified
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs())
+}
+
+/// Appends the tokens of each boosted metadata field to its document's tokens and returns
+/// the extra term frequency that makes each of them count `boost` times. Empty when there
+/// are no boosts.
+fn field_boost_tf(
+    docs: &[Doc],
+    docs_tokens: &mut [Vec<String>],
+    boosts: &[frontmatter::FieldBoost],
+    tokenizer: &TokenizerConfig,
+) -> Vec<HashMap<String, f32>> {
+    if boosts.is_empty() {
+        return Vec::new();
+    }
+    docs.iter()
+        .zip(docs_tokens.iter_mut())
+        .map(|(doc, toks)| {
+            let mut extra: HashMap<String, f32> = HashMap::new();
+            for b in boosts {
+                let Some(value) = doc.metadata.get(&b.field) else {
+                    continue;
+                };
+                for t in tokenize_with_config(value, tokenizer) {
+                    *extra.entry(t.clone()).or_insert(0.0) += b.boost - 1.0;
+                    toks.push(t);
+                }
+            }
+            extra
+        })
+        .collect()
+}
+
+/// For each of `vectors`, the earlier vector it nearly duplicates: the first kept one whose
+/// cosine similarity to it is at least `threshold`. `None` marks a document to keep, so
+/// of a group of near-duplicates only the first survives.
+fn deduplicate_near(vectors: &[Vec<f32>], threshold: f32) -> Vec<Option<usize>> {
+    let mut kept: Vec<usize> = Vec::new();
+    vectors
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let original = kept
+                .iter()
+                .copied()
+                .find(|&j| similarity::cosine_similarity(&vectors[j], v) >= threshold);
+            if original.is_none() {
+                kept.push(i);
+            }
+            original
+        })
+        .collect()
+}
+
+/// The heading on the first non-empty line of Markdown `text`, without its `#` markers.
+/// `None` when that line is not a heading.
+fn markdown_title(text: &str) -> Option<String> {
+    let first = text.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let title = first.strip_prefix('#')?.trim_start_matches('#').trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+/// `n` files chosen at random from `files` (all of them when there are no more than `n`),
+/// kept in their original order. The choice depends only on `files` and `seed`.
+fn sample_files(files: Vec<PathBuf>, n: usize, seed: u64) -> Vec<PathBuf> {
+    use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+    if n >= files.len() {
+        return files;
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut picked: Vec<usize> = (0..files.len()).collect();
+    picked.shuffle(&mut rng);
+    picked.truncate(n);
+    picked.sort_unstable();
+    picked.into_iter().map(|i| files[i].clone()).collect()
+}
+
+/// Files under `dir` eligible for indexing, sorted by path.
+///
+/// Symbolic links are skipped unless `follow_symlinks` is set. When following them, each
+/// directory and file is visited once by canonical path: a link back to an ancestor is a
+/// cycle and is reported on stderr, while a second link to an already walked directory or
+/// file is skipped silently.
+fn indexable_files(dir: &Path, follow_symlinks: bool) -> Vec<PathBuf> {
+    let mut visited: HashSet<PathBuf> = HashSet::new();
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut walker = WalkDir::new(dir).follow_links(follow_symlinks).into_iter();
+    while let Some(entry) = walker.next() {
+        let entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                if let Some(ancestor) = e.loop_ancestor() {
+                    eprintln!(
+                        "Warning: symlink cycle at {} (points back to {}); skipping",
+                        e.path().map_or_else(|| dir.display(), Path::display),
+                        ancestor.display()
+                    );
+                }
+                continue;
+            }
+        };
+        if follow_symlinks {
+            let canonical = entry
+                .path()
+                .canonicalize()
+                .unwrap_or_else(|_| entry.path().to_path_buf());
+            if !visited.insert(canonical) {
+                if entry.file_type().is_dir() {
+                    walker.skip_current_dir();
+                }
+                continue;
+            }
+        }
+        // Only formats the reader understands, which keeps binaries out of the index.
+        if entry.file_type().is_file() && reader::is_supported(entry.path()) {
+            files.push(entry.into_path());
+        }
+    }
+
+    files.sort();
+    files
+}
+
+/// Builds BM25 term weight vectors (IDF excluded), one per token list, parallel to `terms`.
+///
+/// `vectors[i][j]` = (tf * (k1+1)) / (tf + k1 * (1 - b + b * |d_i| / avgdl)), with
+/// `avgdl = avg_doc_length`; `b = 0` disables document-length normalisation. At query time:
+/// score = dot_product(q_vec, vectors[doc]) where q_vec[t] = idf[t].
+fn bm25_weight_vectors(
+    docs_tokens: &[Vec<String>],
+    extra_tf: &[HashMap<String, f32>],
+    terms: &[String],
+    b: f32,
+    avg_doc_length: f32,
+) -> Vec<Vec<f32>> {
+    let term_index: HashMap<&String, usize> =
+        terms.iter().enumerate().map(|(i, t)| (t, i)).collect();
+
+    docs_tokens
+        .par_iter()
+        .enumerate()
+        .map(|(doc_idx, toks)| {
+            let dl = toks.len();
+            let dl_norm = 1.0 - b + b * dl as f32 / avg_doc_length;
+            let mut tf: HashMap<usize, f32> = HashMap::new();
+            for t in toks.iter() {
+                if let Some(&i) = term_index.get(t) {
+                    *tf.entry(i).or_insert(0.0) += 1.0;
+                }
+            }
+            for (t, extra) in extra_tf.get(doc_idx).into_iter().flatten() {
+                if let Some(count) = term_index.get(t).and_then(|i| tf.get_mut(i)) {
+                    *count += extra;
+                }
+            }
+            let mut vec: Vec<f32> = vec![0.0; terms.len()];
+            for (i, &count) in tf.iter() {
+                vec[*i] = (count * (BM25_K1 + 1.0)) / (count + BM25_K1 * dl_norm);
+            }
+            vec
+        })
+        .collect()
+}
+
+/// Mean token count of documents with `lengths`, the BM25 `avgdl`; 1 for no documents.
+fn average_doc_length(lengths: &[usize]) -> f32 {
+    if lengths.is_empty() {
+        1.0
+    } else {
+        lengths.iter().sum::<usize>() as f32 / lengths.len() as f32
+    }
+}
+
+/// IDF of each term from its document frequency in a corpus of `n_docs` documents.
+fn idf_weights(ranking: RankingMethod, n_docs: usize, df_counts: &[usize]) -> Vec<f32> {
+    let n_docs = n_docs as f32;
+    df_counts
+        .iter()
+        .map(|&df_count| {
+            let df_count = df_count as f32;
+            match ranking {
+                // BM25-IDF (Robertson-Sparck Jones): rare terms get high weight; ubiquitous
+                // terms approach 0.
+                RankingMethod::Bm25 => ((n_docs - df_count + 0.5) / (df_count + 0.5) + 1.0).ln(),
+                // Smoothed IDF: every term keeps a positive weight, rare terms weigh more.
+                RankingMethod::TfIdf => (n_docs / df_count).ln() + 1.0,
+            }
+        })
+        .collect()
+}
+
+/// TF-IDF term weights (IDF excluded): term frequency divided by document length, or the
+/// raw count when `normalized` is false.
+fn tf_weight_vectors(
+    docs_tokens: &[Vec<String>],
+    extra_tf: &[HashMap<String, f32>],
+    terms: &[String],
+    normalized: bool,
+) -> Vec<Vec<f32>> {
+    let term_index: HashMap<&String, usize> =
+        terms.iter().enumerate().map(|(i, t)| (t, i)).collect();
+    docs_tokens
+        .par_iter()
+        .enumerate()
+        .map(|(doc_idx, toks)| {
+            let mut vec: Vec<f32> = vec![0.0; terms.len()];
+            for t in toks.iter() {
+                if let Some(&i) = term_index.get(t) {
+                    vec[i] += 1.0;
+                }
+            }
+            for (t, extra) in extra_tf.get(doc_idx).into_iter().flatten() {
+                if let Some(&i) = term_index.get(t).filter(|&&i| vec[i] > 0.0) {
+                    vec[i] += extra;
+                }
+            }
+            if normalized && !toks.is_empty() {
+                let len = toks.len() as f32;
+                vec.iter_mut().for_each(|w| *w /= len);
+            }
+            vec
+        })
+        .collect()
+}
+
+/// Rebuilds `index_file` under `ranking` from its cached token lists and writes it to `out`.
+/// Length normalisation and the ANN sidecar follow the source index.
+fn rebuild_vectors(
+    index_file: &Path,
+    ranking: RankingMethod,
+    out: &Path,
+    quiet: bool,
+) -> Result<()> {
+    let mut idx = load_index(index_file)?;
+    let docs_tokens: Vec<Vec<String>> = idx
+        .docs
+        .iter()
+        .map(|d| {
+            d.tokens.clone().ok_or_else(|| {
+                anyhow!(
+                    "{} has no cached tokens for {}; re-run `voltai index --cache-tokens`",
+                    index_file.display(),
+                    d.path
+                )
+            })
+        })
+        .collect::<Result<_>>()?;
+    let had_ann = idx.ann.is_some();
+    let docs = std::mem::take(&mut idx.docs);
+    let mut index = build_index(docs, &docs_tokens, ranking, idx.normalized);
+    index.copy_text_settings(&idx);
+    index.doc_entities = idx.doc_entities.take();
+    index.index_type = idx.index_type;
+
+    let format = IndexFormat::for_path(out);
+    write_index(&mut index, out, format)?;
+    let mut status = status_writer(quiet);
+    writeln!(status, "Wrote rebuilt index to {}", out.display())?;
+    write_ann_sidecar(&index, out, had_ann, &mut status)
+}
+
+/// Adds `new_files` to the index at `existing` without re-reading its documents, writing the
+/// result to `out`. New terms are appended to the vocabulary (existing rows get zero weight
+/// for them) and IDF is recomputed over all documents; only the new rows are weighted.
+fn append_index(existing: &Path, new_files: &[PathBuf], out: &Path, quiet: bool) -> Result<()> {
+    let mut idx = load_index(existing)?;
+    if idx.embedding_model.is_some() {
+        return Err(anyhow!(
+            "{} is an embedding index; re-run `voltai index` to add documents",
+            existing.display()
+        ));
+    }
+    // Dropping rare tokens depends on counts over the whole corpus, which would change
+    // the existing rows.
+    if idx.min_keyword_freq > 1 {
+        return Err(anyhow!(
+            "{} was built with --min-keyword-freq {}; re-run `voltai index` to add documents",
+            existing.display(),
+            idx.min_keyword_freq
+        ));
+    }
+    let cfg = idx.tokenizer_config();
+    let mut new_docs = Vec::with_capacity(new_files.len());
+    for p in new_files {
+        let path = p.to_string_lossy();
+        if idx.docs.iter().any(|d| d.path == path) {
+            return Err(anyhow!(
+                "{} is already in {}",
+                p.display(),
+                existing.display()
+            ));
+        }
+        let text = DefaultFileReader::read(p)
+            .map_err(|e| e.context(format!("cannot read {}", p.display())))?;
+        let text = preprocess::apply_preprocessing(text, &idx.preprocess);
+        let pdf_meta = if p.extension().and_then(|s| s.to_str()) == Some("pdf") {
+            pdf::read_pdf_metadata(p).unwrap_or_default()
+        } else {
+            pdf::PdfMetadata::default()
+        };
+        new_docs.push(file_doc(p, text, pdf_meta.title.clone(), pdf_meta.to_map()));
+    }
+    let new_tokens: Vec<Vec<String>> = new_docs
+        .iter()
+        .map(|d| tokenize_with_config(&d.text, &cfg))
+        .collect();
+
+    // Existing document frequencies are the non-zero entries of each column.
+    let mut df: Vec<usize> = vec![0; idx.terms.len()];
+    for row in &idx.vectors {
+        for (count, &w) in df.iter_mut().zip(row) {
+            if w > 0.0 {
+                *count += 1;
+            }
+        }
+    }
+    let mut new_df: HashMap<&String, usize> = HashMap::new();
+    for toks in &new_tokens {
+        let unique: HashSet<&String> = toks.iter().collect();
+        for t in unique {
+            *new_df.entry(t).or_insert(0) += 1;
+        }
+    }
+    let known: HashMap<String, usize> = idx
+        .terms
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.clone(), i))
+        .collect();
+    let mut added: Vec<(&String, usize)> = Vec::new();
+    for (&t, &n) in &new_df {
+        match known.get(t) {
+            Some(&i) => df[i] += n,
+            None => added.push((t, n)),
+        }
+    }
+    added.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    for (t, n) in added {
+        idx.terms.push(t.clone());
+        df.push(n);
+    }
+    for row in &mut idx.vectors {
+        row.resize(idx.terms.len(), 0.0);
+    }
+
+    let mut lengths: Vec<usize> = idx
+        .docs
+        .iter()
+        .map(|d| match &d.tokens {
+            Some(toks) => toks.len(),
+            None => tokenize_with_config(&d.text, &cfg).len(),
+        })
+        .collect();
+    lengths.extend(new_tokens.iter().map(Vec::len));
+    let rows = term_weight_vectors(
+        &new_tokens,
+        &[],
+        &idx.terms,
+        idx.ranking,
+        idx.normalized,
+        average_doc_length(&lengths),
+    );
+    idx.idf = idf_weights(idx.ranking, lengths.len(), &df);
+
+    let lang = idx.language();
+    if let Some(doc_entities) = idx.doc_entities.as_mut() {
+        doc_entities.extend(document_entities(&new_docs, lang));
+    }
+    let cache_tokens = !idx.docs.is_empty() && idx.docs.iter().all(|d| d.tokens.is_some());
+    for ((mut doc, toks), row) in new_docs.into_iter().zip(new_tokens).zip(rows) {
+        let doc_idx = idx.docs.len();
+        for (term, &w) in idx.terms.iter().zip(&row) {
+            if w > 0.0 {
+                idx.inverted.entry(term.clone()).or_default().push(doc_idx);
+            }
+        }
+        if cache_tokens {
+            doc.tokens = Some(toks);
+        }
+        idx.docs.push(doc);
+        idx.vectors.push(row);
+    }
+
+    let had_ann = idx.ann.is_some();
+    write_index(&mut idx, out, IndexFormat::for_path(out))?;
+    let mut status = status_writer(quiet);
+    writeln!(
+        status,
+        "Wrote index with {} documents to {}",
+        idx.docs.len(),
+        out.display()
+    )?;
+    write_ann_sidecar(&idx, out, had_ann, &mut status)
+}
+
+/// Destination of informational messages: stdout, or nowhere under `--quiet`.
+fn status_writer(quiet: bool) -> Box<dyn Write> {
+    if quiet {
+        Box::new(std::io::sink())
+    } else {
+        Box::new(std::io::stdout())
+    }
+}
+
+/// Progress line for `voltai pull-model`, counted in bytes.
+const PULL_PROGRESS_TEMPLATE: &str =
+    "{spinner:.green} [{elapsed_precise}] {wide_bar} {bytes}/{total_bytes} {msg}";
+
+/// Progress bar over `len` items, drawn on stderr unless `quiet`.
+fn progress_bar(len: u64, quiet: bool) -> Result<ProgressBar> {
+    if quiet {
+        return Ok(ProgressBar::hidden());
+    }
+    let pb = ProgressBar::new(len);
+    pb.set_style(
+        ProgressStyle::with_template(
+            "{spinner:.green} [{elapsed_precise}] {wide_bar} {pos}/{len} {msg}",
+        )?
+        .progress_chars("=>-"),
+    );
+    Ok(pb)
+}
+
+fn index_dir_with_options(dir: &Path, out: &Path, opts: &IndexOptions) -> Result<()> {
+    index_dir_with_reader::<DefaultFileReader>(dir, out, opts, &mut std::io::stdout())
+}
+
+/// Progress of [`index_dir_with_progress`], reported once per file read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexProgress {
+    /// Files read so far, counting this one; runs from 1 to `total`.
+    pub current: usize,
+    pub total: usize,
+    pub current_file: PathBuf,
+}
+
+/// [`index_dir`] writing a JSON index without status output, calling `on_progress` after
+/// each file is read. Calls never overlap, even though files are read in parallel.
+pub fn index_dir_with_progress<F>(dir: &Path, out: &Path, on_progress: F) -> Result<()>
+where
+    F: Fn(IndexProgress) + Send + Sync,
+{
+    let opts = IndexOptions {
+        quiet: true,
+        ..Default::default()
+    };
+    index_dir_reporting::<DefaultFileReader>(dir, out, &opts, &mut std::io::sink(), &on_progress)
+}
+
+/// Indexes `dir` with `R` extracting each file's text, showing a progress bar unless
+/// `opts.quiet` is set. Status messages go to `status` unless `opts.quiet` is set;
+/// `--verbose` details go there regardless.
+fn index_dir_with_reader<R: FileReader>(
+    dir: &Path,
+    out: &Path,
+    opts: &IndexOptions,
+    status: &mut dyn Write,
+) -> Result<()> {
+    let pb = progress_bar(0, opts.quiet)?;
+    let result = index_dir_reporting::<R>(dir, out, opts, status, &|p: IndexProgress| {
+        pb.set_length(p.total as u64);
+        pb.set_position(p.current as u64);
+        // Finished before the status messages that follow reading.
+        if p.current == p.total {
+            pb.finish_with_message("indexing files");
+        }
+    });
+    if !pb.is_finished() {
+        match &result {
+            Ok(()) => pb.finish_with_message("indexing files"),
+            Err(_) => pb.abandon(),
+        }
+    }
+    result
+}
+
+/// [`index_dir_with_reader`], reporting each file read to `on_progress` instead of a
+/// progress bar.
+fn index_dir_reporting<R: FileReader>(
+    dir: &Path,
+    out: &Path,
+    opts: &IndexOptions,
+    status: &mut dyn Write,
+    on_progress: &(dyn Fn(IndexProgress) + Send + Sync),
+) -> Result<()> {
+    if opts.ann && opts.embedding_model.is_some() {
+        return Err(anyhow!(
+            "--ann re-scores candidates with BM25 and cannot be combined with --embeddings"
+        ));
+    }
+    if opts.layout == IndexLayout::Inverted && opts.embedding_model.is_some() {
+        return Err(anyhow!(
+            "embedding indexes are always dense and cannot be combined with --index-format inverted"
+        ));
+    }
+    let embedder = opts
+        .embedding_model
+        .as_deref()
+        .map(embeddings::load_embedder)
+        .transpose()?;
+    let mut files = indexable_files(dir, opts.follow_symlinks);
+    if let Some(n) = opts.sample {
+        let total = files.len();
+        files = sample_files(files, n, opts.seed);
+        if !opts.quiet {
+            writeln!(status, "Sampled {} of {} files", files.len(), total)?;
+        }
+    }
+
+    // Counts files read; held while `on_progress` runs so reports arrive in order.
+    let read_count: Mutex<usize> = Mutex::new(0);
+
+    // With `--stop-on-error`, the first failing worker records its error here and the
+    // remaining workers skip their files.
+    let first_error: Mutex<Option<anyhow::Error>> = Mutex::new(None);
+    let (docs, mut read_errors): (Vec<Doc>, Vec<Option<String>>) = files
+        .par_iter()
+        .map(|p| {
+            if opts.stop_on_error && first_error.lock().unwrap().is_some() {
+                return (Doc::default(), None);
+            }
+            let (text, read_error) = match R::read(p) {
+                Ok(text) => (
+                    preprocess::apply_preprocessing(text, &opts.preprocess),
+                    None,
+                ),
+                Err(e) if opts.stop_on_error => {
+                    first_error
+                        .lock()
+                        .unwrap()
+                        .get_or_insert(e.context(format!("cannot read {}", p.display())));
+                    return (Doc::default(), None);
+                }
+                Err(e) => (String::new(), Some(e.to_string())),
+            };
+            let is_pdf = p.extension().and_then(|s| s.to_str()) == Some("pdf");
+            let pdf_meta = if is_pdf {
+                pdf::read_pdf_metadata(p).unwrap_or_default()
+            } else {
+                pdf::PdfMetadata::default()
+            };
+            let is_md = p.extension().and_then(|s| s.to_str()) == Some("md");
+            let (frontmatter, text) = if is_md && !opts.field_boosts.is_empty() {
+                frontmatter::parse_markdown_frontmatter(&text)
+            } else {
+                (HashMap::new(), text)
+            };
+            // A PDF's own title (or a Markdown heading, when asked for) is a better
+            // identifier than its file name.
+            let md_title = (opts.title_from_first_line && is_md)
+                .then(|| markdown_title(&text))
+                .flatten();
+            {
+                let mut read = read_count.lock().unwrap();
+                *read += 1;
+                on_progress(IndexProgress {
+                    current: *read,
+                    total: files.len(),
+                    current_file: p.clone(),
+                });
+            }
+            let doc = file_doc(
+                p,
+                text,
+                pdf_meta.title.clone().or(md_title),
+                pdf_meta.to_map().into_iter().chain(frontmatter).collect(),
+            );
+            (doc, read_error)
+        })
+        .unzip();
+
+    if let Some(e) = first_error.into_inner().unwrap() {
+        return Err(e);
+    }
+
+    let tokenizer = TokenizerConfig {
+        filter: alias_filter(&opts.term_aliases),
+        ..opts.tokenizer.clone()
+    };
+    let mut docs_tokens: Vec<Vec<String>> = docs
+        .iter()
+        .map(|d| tokenize_with_config(&d.text, &tokenizer))
+        .collect();
+    let mut extra_tf = field_boost_tf(&docs, &mut docs_tokens, &opts.field_boosts, &tokenizer);
+    drop_rare_tokens(&mut docs_tokens, opts.min_keyword_freq);
+    let mut index = build_weighted_index(
+        docs,
+        &docs_tokens,
+        &extra_tf,
+        opts.ranking,
+        !opts.no_normalize,
+    );
+    if let Some(threshold) = opts.deduplicate_near {
+        let duplicate_of = deduplicate_near(&index.vectors, threshold);
+        if duplicate_of.iter().any(Option::is_some) {
+            for (di, original) in duplicate_of.iter().enumerate() {
+                match original {
+                    Some(original) if !opts.quiet => writeln!(
+                        status,
+                        "Skipping {}: near-duplicate of {}",
+                        index.docs[di].path, index.docs[*original].path
+                    )?,
+                    _ => {}
+                }
+            }
+            fn retain_kept<T>(items: &mut Vec<T>, duplicate_of: &[Option<usize>]) {
+                let mut mask = duplicate_of.iter().map(Option::is_none);
+                items.retain(|_| mask.next().unwrap_or(true));
+            }
+            let mut docs = std::mem::take(&mut index.docs);
+            retain_kept(&mut docs, &duplicate_of);
+            retain_kept(&mut docs_tokens, &duplicate_of);
+            retain_kept(&mut read_errors, &duplicate_of);
+            if !extra_tf.is_empty() {
+                retain_kept(&mut extra_tf, &duplicate_of);
+            }
+            // Document frequencies changed, so the surviving documents are re-weighted.
+            index = build_weighted_index(
+                docs,
+                &docs_tokens,
+                &extra_tf,
+                opts.ranking,
+                !opts.no_normalize,
+            );
+        }
+    }
+    if let Some(k) = opts.assign_topics {
+        let (assignments, centroids) = topics::cluster_documents(&index, k, opts.seed);
+        let labels = topics::assign_topics(&index, &assignments, &centroids);
+        for (doc, topics) in index.docs.iter_mut().zip(labels) {
+            doc.topics = topics;
+        }
+    }
+    index.token_pattern = opts
+        .tokenizer
+        .pattern
+        .as_ref()
+        .map(|re| re.as_str().to_string());
+    index.term_aliases = opts.term_aliases.clone();
+    index.max_token_len = opts.tokenizer.max_token_len;
+    index.lang.clone_from(&opts.tokenizer.lang);
+    index.exclude_numeric = opts.tokenizer.exclude_numeric;
+    index.preprocess.clone_from(&opts.preprocess);
+    index.min_keyword_freq = opts.min_keyword_freq;
+    index.doc_entities = Some(document_entities(&index.docs, index.language()));
+    let mut verbose = VerboseLogger::new(opts.verbose, status);
+    for ((doc, toks), (vector, read_error)) in index
+        .docs
+        .iter()
+        .zip(&docs_tokens)
+        .zip(index.vectors.iter().zip(&read_errors))
+    {
+        let non_zero = vector.iter().filter(|&&w| w != 0.0).count();
+        verbose.file(&doc.path, toks.len(), non_zero, read_error.as_deref())?;
+    }
+    let mut sink = std::io::sink();
+    let status: &mut dyn Write = if opts.quiet { &mut sink } else { status };
+    if opts.cache_tokens {
+        for (doc, toks) in index.docs.iter_mut().zip(docs_tokens) {
+            doc.tokens = Some(toks);
+        }
+    }
+    if let (Some(embedder), Some(model)) = (embedder, &opts.embedding_model) {
+        embeddings::embed_documents(&mut index, embedder, &model.to_string_lossy())?;
+    } else {
+        index.index_type = opts.layout.resolve(index.docs.len(), index.terms.len());
+    }
+
+    write_index(&mut index, out, opts.format)?;
+    writeln!(status, "Wrote index to {}", out.display())?;
+    write_ann_sidecar(&index, out, opts.ann, status)
+}
+
+/// The named entities of each of `docs` that has any, keyed by path.
+fn document_entities(docs: &[Doc], lang: Language) -> HashMap<String, Vec<Entity>> {
+    docs.par_iter()
+        .filter_map(|d| {
+            let entities = extract_entities_from_text_with_config(&d.text, lang);
+            (!entities.is_empty()).then(|| (d.path.clone(), entities))
+        })
+        .collect()
+}
+
+/// Term weight rows (IDF excluded) of `docs_tokens` under `ranking`; `avg_doc_length` is
+/// only used by BM25 length normalisation.
+fn term_weight_vectors(
+    docs_tokens: &[Vec<String>],
+    extra_tf: &[HashMap<String, f32>],
+    terms: &[String],
+    ranking: RankingMethod,
+    normalized: bool,
+    avg_doc_length: f32,
+) -> Vec<Vec<f32>> {
+    match ranking {
+        RankingMethod::Bm25 => {
+            let b = if normalized { BM25_B } else { 0.0 };
+            bm25_weight_vectors(docs_tokens, extra_tf, terms, b, avg_doc_length)
+        }
+        RankingMethod::TfIdf => tf_weight_vectors(docs_tokens, extra_tf, terms, normalized),
+    }
+}
+
+/// The document for file `p` with extracted `text`. Its id is `title` (a PDF or Markdown
+/// title) when there is one, else `doc-<file name>`.
+fn file_doc(
+    p: &Path,
+    text: String,
+    title: Option<String>,
+    metadata: HashMap<String, String>,
+) -> Doc {
+    let id = title.unwrap_or_else(|| {
+        format!(
+            "doc-{}",
+            p.file_name()
+                .map(|s| s.to_string_lossy())
+                .unwrap_or_default()
+        )
+    });
+    Doc {
+        id,
+        path: p.to_string_lossy().to_string(),
+        metadata,
+        last_modified_secs: last_modified_secs(p),
+        first_sentence: extract_first_sentence(&text),
+        content_hash: duplicates::content_hash(&text),
+        text,
+        ..Default::default()
+    }
+}
+
+/// Computes the vocabulary, IDF, term weights and inverted index for `docs`, given each
+/// document's token list (parallel to `docs`).
+fn build_index(
+    docs: Vec<Doc>,
+    docs_tokens: &[Vec<String>],
+    ranking: RankingMethod,
+    normalized: bool,
+) -> Index {
+    build_weighted_index(docs, docs_tokens, &[], ranking, normalized)
+}
+
+/// [`build_index`] with `extra_tf[doc][term]` added to the document's term frequencies
+/// (see [`field_boost_tf`]); `extra_tf` is empty or parallel to `docs`.
+fn build_weighted_index(
+    docs: Vec<Doc>,
+    docs_tokens: &[Vec<String>],
+    extra_tf: &[HashMap<String, f32>],
+    ranking: RankingMethod,
+    normalized: bool,
+) -> Index {
+    let mut df: HashMap<String, usize> = HashMap::new();
+    for toks in docs_tokens {
+        let mut seen: HashSet<&String> = HashSet::new();
+        for t in toks.iter() {
+            if seen.insert(t) {
+                *df.entry(t.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let (terms, df_counts): (Vec<String>, Vec<usize>) = {
+        let mut v: Vec<(String, usize)> = df.into_iter().collect();
+        v.sort_by_key(|e| std::cmp::Reverse(e.1));
+        v.into_iter().unzip()
+    };
+
+    let idf = idf_weights(ranking, docs.len(), &df_counts);
+    let lengths: Vec<usize> = docs_tokens.iter().map(Vec::len).collect();
+    let vectors = term_weight_vectors(
+        docs_tokens,
+        extra_tf,
+        &terms,
+        ranking,
+        normalized,
+        average_doc_length(&lengths),
+    );
+
+    // Build inverted index: term → doc indices with a nonzero weight.
+    // Enables O(T) query-time candidate accumulation that skips zero-overlap documents.
+    let mut inverted: HashMap<String, Vec<usize>> = HashMap::new();
+    for (doc_idx, vec) in vectors.iter().enumerate() {
+        for (term_idx, &w) in vec.iter().enumerate() {
+            if w > 0.0 {
+                inverted
+                    .entry(terms[term_idx].clone())
+                    .or_default()
+                    .push(doc_idx);
+            }
+        }
+    }
+
+    Index {
+        version: INDEX_VERSION,
+        schema_version: current_schema_version(),
+        docs,
+        terms,
+        idf,
+        vectors,
+        index_type: IndexType::Dense,
+        sparse_vectors: Vec::new(),
+        inverted,
+        normalized,
+        ranking,
+        embedding_model: None,
+        token_pattern: None,
+        term_aliases: BTreeMap::new(),
+        max_token_len: None,
+        lang: None,
+        exclude_numeric: false,
+        preprocess: Vec::new(),
+        min_keyword_freq: 0,
+        doc_entities: None,
+        ann: None,
+        embedder: None,
+    }
+}
+
+fn write_index(index: &mut Index, out: &Path, format: IndexFormat) -> Result<()> {
+    // Inverted indexes trade the dense rows for their non-zero entries while writing.
+    let dense = match index.index_type {
+        IndexType::Dense => None,
+        IndexType::Inverted => {
+            index.sparse_vectors = index
+                .vectors
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .enumerate()
+                        .filter(|&(_, &w)| w != 0.0)
+                        .map(|(t, &w)| (t as u32, w))
+                        .collect()
+                })
+                .collect();
+            Some(std::mem::take(&mut index.vectors))
+        }
+    };
+    let written = File::create(out)
+        .map_err(anyhow::Error::from)
+        .and_then(|fout| {
+            match format {
+                IndexFormat::Json => serde_json::to_writer_pretty(fout, &*index)?,
+                IndexFormat::Binary => bincode::serialize_into(fout, &*index)?,
+            }
+            Ok(())
+        });
+    if let Some(vectors) = dense {
+        index.vectors = vectors;
+        index.sparse_vectors = Vec::new();
+    }
+    written
+}
+
+/// Expands the `sparse_vectors` of an inverted index into dense `vectors`.
+fn densify(index: &mut Index) -> Result<()> {
+    let n_terms = index.terms.len();
+    index.vectors = std::mem::take(&mut index.sparse_vectors)
+        .into_iter()
+        .map(|entries| {
+            let mut row = vec![0.0_f32; n_terms];
+            for (t, w) in entries {
+                *row.get_mut(t as usize)
+                    .ok_or_else(|| anyhow!("sparse weight for term {} of {}", t, n_terms))? = w;
+            }
+            Ok(row)
+        })
+        .collect::<Result<_>>()?;
+    Ok(())
+}
+
+/// Dumps an HNSW graph for `index` next to `out` when `build` is set; otherwise removes any
+/// graph left over from an earlier `--ann` run, which would no longer match the vectors.
+fn write_ann_sidecar(index: &Index, out: &Path, build: bool, status: &mut dyn Write) -> Result<()> {
+    if build {
+        AnnIndex::build(&index.vectors).dump(out)?;
+        writeln!(
+            status,
+            "Wrote ANN graph to {}",
+            ann::graph_path(out).display()
+        )?;
+    } else {
+        ann::remove_dump(out)?;
+    }
+    Ok(())
+}
+
+/// Default score added by `--entity-boost` to documents sharing an entity with the query.
+const DEFAULT_ENTITY_BOOST: f32 = 0.2;
+
+/// Retrieval and prompt-building settings for `voltai query`.
+#[derive(Debug, Clone)]
+pub struct QueryOptions {
+    /// Number of top-ranked documents to retrieve.
+    k: usize,
+    /// Ollama model override; probed from `ollama list` when `None`.
+    model: Option<String>,
+    /// Score added to documents sharing a named entity with the query (`--entity-boost`).
+    entity_boost: Option<f32>,
+    /// Print ranked retrieval results without invoking the LLM (`--no-ollama`).
+    no_ollama: bool,
+    /// HNSW search breadth when the index carries an ANN graph (`--ann-ef`).
+    ann_ef: usize,
+    /// Similarity above which a query is treated as corpus-wide (`--general-query-threshold`).
+    general_query_threshold: f32,
+    /// Apply pivoted document-length normalisation to unnormalised indexes.
+    pivoted_normalization: bool,
+    /// Maximum number of documents placed in the prompt context (`--context-docs`).
+    context_docs: usize,
+    /// Character budget for the prompt context section (`--context-chars`).
+    context_chars: Option<usize>,
+    /// Per-document context representation (`--context-mode`).
+    context_mode: ContextMode,
+    /// Characters per document in excerpt mode (`--excerpt-chars`).
+    excerpt_chars: usize,
+    /// Report the best sentence per document as well (`--granularity sentence`).
+    granularity: Granularity,
+    /// Restricts retrieval to documents whose path matches (`--source-filter`).
+    source_filter: Option<SourceFilter>,
+    /// Restricts retrieval to documents with these paths (`voltai search-within`).
+    within_paths: Option<HashSet<String>>,
+    /// How retrieval results are written (`--format`).
+    format: OutputFormat,
+    /// Order of retrieval results (`--sort-by`).
+    sort_by: SortBy,
+    /// Tera prompt template replacing the built-in prompts (`--template`).
+    template: Option<String>,
+    /// Few-shot examples exposed to the template (`--examples-file`).
+    examples: Vec<PromptExample>,
+    /// Retrieval result cache (`--cache-dir`, `--cache-ttl`).
+    cache: Option<cache::QueryCache>,
+    /// Estimated prompt size above which a warning is printed (`--token-warn-threshold`).
+    token_warn_threshold: usize,
+    /// Estimated prompt size above which Ollama is skipped (`--token-limit`).
+    token_limit: Option<usize>,
+    /// Reorder retrieved documents by model-judged relevance (`--rerank`).
+    rerank: bool,
+    /// Cap on retrieved documents per source file (`--max-context-docs-per-file`).
+    max_docs_per_file: Option<usize>,
+    /// Write results as they are produced (`--stream-results`).
+    stream_results: bool,
+    /// Print the prompt to stderr before generation (`--show-context`).
+    show_context: bool,
+    /// Print the prompt instead of generating (`--dry-query`).
+    dry_query: bool,
+    /// Print query/vocabulary overlap to stderr before the results (`--show-term-coverage`).
+    show_term_coverage: bool,
+    /// Maximum length of the printed LLM answer (`--output-limit-chars`).
+    output_limit_chars: Option<usize>,
+    /// Print each result's score breakdown to stderr (`--score-debug`).
+    score_debug: bool,
+    /// Lowest score a retrieved document may have (`--min-score`); `None` requires a
+    /// positive score.
+    min_score: Option<f32>,
+    /// Pick results at random instead of by score (`--randomize`, `--seed`).
+    randomize: bool,
+    seed: u64,
+    /// Group retrieval results by parent directory (`--group-by-source`).
+    group_by_source: bool,
+    /// LLM answer cache (`--cache-ollama-responses`).
+    response_cache: Option<cache::ResponseCache>,
+    /// Colours text output (`--color`); always plain when writing to `--output-file`.
+    printer: ColorPrinter,
+    /// Document paths in reranked order; set by `query_with_ollama` after `--rerank` so the
+    /// prompt context follows the new order.
+    context_order: Option<Vec<String>>,
+}
+
+/// Document path pattern for `--source-filter`: a glob, or a regex when prefixed with `re:`.
+#[derive(Debug, Clone)]
+enum SourceFilter {
+    Glob(glob::Pattern),
+    Regex(Regex),
+}
+
+impl SourceFilter {
+    fn parse(pattern: &str) -> Result<Self> {
+        match pattern.strip_prefix("re:") {
+            Some(re) => Ok(SourceFilter::Regex(Regex::new(re).map_err(|e| {
+                anyhow!("invalid --source-filter regex '{}': {}", re, e)
+            })?)),
+            None => Ok(SourceFilter::Glob(glob::Pattern::new(pattern).map_err(
+                |e| anyhow!("invalid --source-filter glob '{}': {}", pattern, e),
+            )?)),
+        }
+    }
+
+    /// Regexes match anywhere in the path unless anchored; globs must match the whole path.
+    fn matches(&self, path: &str) -> bool {
+        match self {
+            SourceFilter::Glob(g) => g.matches(path),
+            SourceFilter::Regex(re) => re.is_match(path),
+        }
+    }
+}
+
+/// Whether `path` matches a `--source-filter` pattern. Invalid patterns match nothing.
+/// Retrieval parses the pattern once into a [`SourceFilter`] instead.
+#[cfg(test)]
+fn match_source(pattern: &str, path: &str) -> bool {
+    SourceFilter::parse(pattern).is_ok_and(|f| f.matches(path))
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        QueryOptions {
+            k: 3,
+            model: None,
+            entity_boost: None,
+            no_ollama: false,
+            ann_ef: DEFAULT_ANN_EF,
+            general_query_threshold: DEFAULT_GENERAL_QUERY_THRESHOLD,
+            pivoted_normalization: false,
+            context_docs: DEFAULT_CONTEXT_DOCS,
+            context_chars: None,
+            context_mode: ContextMode::Keywords,
+            excerpt_chars: DEFAULT_EXCERPT_CHARS,
+            granularity: Granularity::Document,
+            source_filter: None,
+            within_paths: None,
+            format: OutputFormat::Text,
+            sort_by: SortBy::Score,
+            template: None,
+            examples: Vec::new(),
+            cache: None,
+            token_warn_threshold: DEFAULT_TOKEN_WARN_THRESHOLD,
+            token_limit: None,
+            rerank: false,
+            max_docs_per_file: None,
+            stream_results: false,
+            show_context: false,
+            dry_query: false,
+            show_term_coverage: false,
+            output_limit_chars: None,
+            score_debug: false,
+            min_score: None,
+            randomize: false,
+            seed: 0,
+            group_by_source: false,
+            response_cache: None,
+            printer: ColorPrinter::default(),
+            context_order: None,
+        }
+    }
+}
+
+impl QueryOptions {
+    /// The settings that change what `retrieve` returns, rendered for cache entries.
+    fn retrieval_fingerprint(&self) -> String {
+        format!(
+            "{:?}",
+            (
+                self.k,
+                // Decides each result's `context_used`.
+                self.context_docs,
+                self.entity_boost,
+                self.ann_ef,
+                self.general_query_threshold,
+                self.pivoted_normalization,
+                self.granularity,
+                &self.source_filter,
+                self.sort_by,
+                self.max_docs_per_file,
+                self.min_score,
+                self.randomize.then_some(self.seed),
+            )
+        )
+    }
+}
+
+/// Computes the BM25 score of every document for `q_toks`, parallel to `idx.docs`.
+/// Only documents sharing a query term are touched (O(T) inverted-index accumulation):
+/// each adds `idf[t] * bm25_tf(t, doc)` per matching query token.
+pub fn bm25_scores(idx: &Index, q_toks: &[String]) -> Vec<f32> {
+    let term_map: HashMap<&String, usize> =
+        idx.terms.iter().enumerate().map(|(i, t)| (t, i)).collect();
+    let mut scores = vec![0.0_f32; idx.docs.len()];
+    for t in q_toks.iter() {
+        if let Some(&ti) = term_map.get(t) {
+            if let Some(candidates) = idx.inverted.get(t.as_str()) {
+                for &di in candidates {
+                    scores[di] += idx.idf[ti] * idx.vectors[di][ti];
+                }
+            }
+        }
+    }
+    scores
+}
+
+/// Divides each score by its document's pivoted norm,
+/// `alpha * doc_norm + (1 - alpha) * avg_norm`, where `doc_norm` is the L2 norm of the
+/// document's term weights. Long documents are damped relative to the corpus average
+/// without being penalised as hard as full normalisation would.
+fn apply_pivoted_normalization(idx: &Index, scores: &mut [f32], alpha: f32) {
+    let norms: Vec<f32> = idx
+        .vectors
+        .iter()
+        .map(|v| dot_product(v, v).sqrt())
+        .collect();
+    if norms.is_empty() {
+        return;
+    }
+    let avg_norm = norms.iter().sum::<f32>() / norms.len() as f32;
+    for (score, norm) in scores.iter_mut().zip(norms) {
+        let pivot = alpha * norm + (1.0 - alpha) * avg_norm;
+        if pivot > 0.0 {
+            *score /= pivot;
+        }
+    }
+}
+
+/// Intermediate values behind a document's score, for `query --score-debug`. The BM25
+/// score is `dot_product`; `cosine_sim` shows how it relates to plain cosine similarity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ScoreDebugInfo {
+    dot_product: f32,
+    query_norm: f32,
+    doc_norm: f32,
+    cosine_sim: f32,
+}
+
+fn score_debug_info(q_vec: &[f32], doc_vec: &[f32]) -> ScoreDebugInfo {
+    let query_norm = dot_product(q_vec, q_vec).sqrt();
+    let doc_norm = dot_product(doc_vec, doc_vec).sqrt();
+    ScoreDebugInfo {
+        dot_product: dot_product(q_vec, doc_vec),
+        query_norm,
+        doc_norm,
+        cosine_sim: similarity::cosine_similarity(q_vec, doc_vec),
+    }
+}
+
+/// Writes a [`ScoreDebugInfo`] line for each result of `q` against `idx`: the query vector
+/// holds the IDF of each query term, the document vector its stored BM25 term weights.
+fn write_score_debug(
+    idx: &Index,
+    q: &str,
+    results: &[QueryResult],
+    err: &mut dyn Write,
+) -> Result<()> {
+    if idx.embedding_model.is_some() {
+        writeln!(
+            err,
+            "Score debug is only available for term-weight indexes."
+        )?;
+        return Ok(());
+    }
+    let term_map: HashMap<&str, usize> = idx
+        .terms
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (t.as_str(), i))
+        .collect();
+    let mut q_vec = vec![0.0_f32; idx.terms.len()];
+    for t in idx.tokenize_query(q) {
+        if let Some(&ti) = term_map.get(t.as_str()) {
+            q_vec[ti] = idx.idf[ti];
+        }
+    }
+    for r in results {
+        let Some(di) = idx.docs.iter().position(|d| d.path == r.path) else {
+            continue;
+        };
+        let info = score_debug_info(&q_vec, &idx.vectors[di]);
+        writeln!(
+            err,
+            "{}: dot product {:.4}, query norm {:.4}, doc norm {:.4}, cosine {:.4}",
+            r.path, info.dot_product, info.query_norm, info.doc_norm, info.cosine_sim
+        )?;
+    }
+    Ok(())
+}
+
+/// BM25 scores restricted to the documents proposed by the ANN graph; all others score 0.
+///
+/// The graph ranks by cosine between the IDF-weighted query vector and the document
+/// vectors, which only approximates BM25, so `ef` candidates are fetched and re-scored
+/// exactly. Returns `None` when no query term is in the vocabulary.
+fn ann_bm25_scores(idx: &Index, ann: &AnnIndex, q_toks: &[String], ef: usize) -> Option<Vec<f32>> {
+    let term_map: HashMap<&String, usize> =
+        idx.terms.iter().enumerate().map(|(i, t)| (t, i)).collect();
+    let q_terms: Vec<usize> = q_toks
+        .iter()
+        .filter_map(|t| term_map.get(t).copied())
+        .collect();
+    if q_terms.is_empty() {
+        return None;
+    }
+    let mut q_vec = vec![0.0_f32; idx.terms.len()];
+    for &ti in &q_terms {
+        q_vec[ti] = idx.idf[ti];
+    }
+    let mut scores = vec![0.0_f32; idx.docs.len()];
+    for di in ann.search(&q_vec, ef, ef) {
+        if let Some(vec) = idx.vectors.get(di) {
+            scores[di] = q_terms.iter().map(|&ti| idx.idf[ti] * vec[ti]).sum();
+        }
+    }
+    Some(scores)
+}
+
+/// Adds `boost` to the score of every document that mentions at least one of
+/// `query_entities`. `doc_entities` is keyed by document path; surface forms are
+/// compared case-insensitively.
+fn entity_boost_scores(
+    query_entities: &[Entity],
+    doc_entities: &HashMap<String, Vec<Entity>>,
+    docs: &[Doc],
+    base_scores: &mut [f32],
+    boost: f32,
+) {
+    let wanted: HashSet<String> = query_entities
+        .iter()
+        .map(|e| e.text.to_lowercase())
+        .collect();
+    if wanted.is_empty() {
+        return;
+    }
+    for (doc, score) in docs.iter().zip(base_scores.iter_mut()) {
+        let shares_entity = doc_entities
+            .get(&doc.path)
+            .map(|ents| ents.iter().any(|e| wanted.contains(&e.text.to_lowercase())))
+            .unwrap_or(false);
+        if shares_entity {
+            *score += boost;
+        }
+    }
+}
+
+/// Returns the indices and scores of documents with a positive score, best first,
+/// truncated to `k`. Ties keep index order so rankings are deterministic.
+fn top_k(scores: &[f32], k: usize) -> Vec<(usize, f32)> {
+    let mut sims: Vec<(usize, f32)> = scores
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|&(_, s)| s > 0.0)
+        .collect();
+    sims.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    sims.truncate(k);
+    sims
+}
+
+/// Source file a document was chunked from: its `source` metadata entry, else its path.
+fn source_file(doc: &Doc) -> &str {
+    doc.metadata.get("source").unwrap_or(&doc.path)
+}
+
+/// Like [`top_k`], but skips documents whose source file already has `cap` documents
+/// among the higher-ranked ones.
+fn top_k_per_source(idx: &Index, scores: &[f32], k: usize, cap: usize) -> Vec<(usize, f32)> {
+    let mut taken: HashMap<&str, usize> = HashMap::new();
+    let mut ranked = top_k(scores, usize::MAX);
+    ranked.retain(|&(i, _)| {
+        let count = taken.entry(source_file(&idx.docs[i])).or_insert(0);
+        *count += 1;
+        *count <= cap
+    });
+    ranked.truncate(k);
+    ranked
+}
+
+/// Ranks `idx.docs` against `q`: BM25 scoring (over ANN candidates when the index has an
+/// HNSW graph), optional entity boosting, then top-k (capped per source file with
+/// `--max-context-docs-per-file`).
+fn rank_documents(idx: &Index, q: &str, opts: &QueryOptions) -> Vec<(usize, f32)> {
+    let q_toks = idx.tokenize_query(q);
+    let mut scores = match (&idx.embedding_model, &idx.embedder) {
+        (None, _) => idx
+            .ann
+            .as_ref()
+            .and_then(|ann| ann_bm25_scores(idx, ann, &q_toks, opts.ann_ef.max(opts.k)))
+            .unwrap_or_else(|| bm25_scores(idx, &q_toks)),
+        (Some(_), Some(embedder)) => embeddings::embedding_scores(idx, embedder.as_ref(), q)
+            .unwrap_or_else(|e| {
+                eprintln!("Cannot embed the query: {}", e);
+                vec![0.0; idx.docs.len()]
+            }),
+        (Some(model), None) => {
+            eprintln!("No embedder loaded for {}; cannot rank this index.", model);
+            vec![0.0; idx.docs.len()]
+        }
+    };
+    // Pivoting an already length-normalised index would normalise twice.
+    if opts.pivoted_normalization && !idx.normalized {
+        apply_pivoted_normalization(idx, &mut scores, PIVOT_ALPHA);
+    }
+    if let Some(boost) = opts.entity_boost {
+        match &idx.doc_entities {
+            Some(doc_entities) => {
+                let query_entities = extract_entities_from_text_with_config(q, idx.language());
+                entity_boost_scores(&query_entities, doc_entities, &idx.docs, &mut scores, boost);
+            }
+            None => eprintln!(
+                "The index has no stored entities; re-run `voltai index` to use --entity-boost."
+            ),
+        }
+    }
+    if let Some(filter) = &opts.source_filter {
+        for (score, doc) in scores.iter_mut().zip(&idx.docs) {
+            if !filter.matches(&doc.path) {
+                *score = 0.0;
+            }
+        }
+    }
+    if let Some(paths) = &opts.within_paths {
+        for (score, doc) in scores.iter_mut().zip(&idx.docs) {
+            if !paths.contains(&doc.path) {
+                *score = 0.0;
+            }
+        }
+    }
+    if opts.randomize {
+        use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+        let mut picked: Vec<(usize, f32)> = scores
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|&(_, s)| opts.min_score.map_or(s > 0.0, |min| s >= min))
+            .collect();
+        picked.shuffle(&mut StdRng::seed_from_u64(opts.seed));
+        picked.truncate(opts.k);
+        return picked;
+    }
+    if let Some(min) = opts.min_score {
+        for score in scores.iter_mut().filter(|s| **s < min) {
+            *score = 0.0;
+        }
+    }
+    match opts.max_docs_per_file {
+        Some(cap) => top_k_per_source(idx, &scores, opts.k, cap),
+        None => top_k(&scores, opts.k),
+    }
+}
+
+/// Number of keywords reported per document in retrieval results.
+const RESULT_KEYWORDS: usize = 6;
+/// Maximum length, in characters, of the query-centred excerpt in retrieval results.
+const EXCERPT_CHARS: usize = 200;
+
+/// One ranked document returned by retrieval.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+pub struct QueryResult {
+    path: String,
+    score: f32,
+    keywords: Vec<String>,
+    /// Text window around the first query-term occurrence; empty when no term occurs verbatim.
+    excerpt: String,
+    /// Most relevant sentence, with `--granularity sentence`.
+    #[serde(default)]
+    sentence_excerpt: Option<String>,
+    /// Modification time of the document when it was indexed, if recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_modified_secs: Option<u64>,
+    /// Whether the document is among the top `--context-docs` matches that `query` passes
+    /// to Ollama as context.
+    #[serde(default)]
+    context_used: bool,
+}
+
+/// Most frequent tokens of `text` longer than two characters, most frequent first; ties
+/// are broken alphabetically so output is stable across runs.
+fn top_keywords(text: &str, n: usize) -> Vec<String> {
+    language_keywords(text, n, None)
+}
+
+/// [`top_keywords`] leaving out the stop words of `lang` (`voltai index --lang`) instead of
+/// the English ones.
+fn language_keywords(text: &str, n: usize, lang: Option<&str>) -> Vec<String> {
+    let cfg = TokenizerConfig {
+        lang: lang.map(str::to_string),
+        ..Default::default()
+    };
+    let mut tf: HashMap<String, usize> = HashMap::new();
+    for tk in tokenize_with_config(text, &cfg) {
+        if tk.len() > 2 {
+            *tf.entry(tk).or_insert(0) += 1;
+        }
+    }
+    let mut kv: Vec<(String, usize)> = tf.into_iter().collect();
+    kv.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    kv.into_iter().take(n).map(|(t, _)| t).collect()
+}
+
+/// Returns up to `max_chars` characters of `text` around the first case-insensitive
+/// occurrence of any query token, with whitespace collapsed. Empty if none occurs.
+fn excerpt_for_query(text: &str, q_toks: &[String], max_chars: usize) -> String {
+    let lower = text.to_lowercase();
+    // Lowercasing can change byte lengths for non-ASCII text; only trust offsets when it didn't.
+    if lower.len() != text.len() {
+        return String::new();
+    }
+    let first_hit = q_toks.iter().filter_map(|t| lower.find(t.as_str())).min();
+    let Some(hit) = first_hit else {
+        return String::new();
+    };
+    let mut start = hit.saturating_sub(max_chars / 4);
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    let window: String = text[start..].chars().take(max_chars).collect();
+    window.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Longest [`extract_first_sentence`] result, before the `...` marking a cut.
+const FIRST_SENTENCE_CHARS: usize = 200;
+
+/// The first sentence of `text` with whitespace collapsed, cut to
+/// [`FIRST_SENTENCE_CHARS`] characters plus `...` when longer. Empty for blank text.
+fn extract_first_sentence(text: &str) -> String {
+    let Some(sentence) = SENTENCE_PATTERN
+        .find_iter(text)
+        .map(|m| m.as_str().split_whitespace().collect::<Vec<_>>().join(" "))
+        .find(|s| !s.is_empty())
+    else {
+        return String::new();
+    };
+    if sentence.chars().count() <= FIRST_SENTENCE_CHARS {
+        return sentence;
+    }
+    let cut: String = sentence.chars().take(FIRST_SENTENCE_CHARS).collect();
+    format!("{}...", cut.trim_end())
+}
+
+/// Marker appended by [`truncate_at_sentence_boundary`].
+const TRUNCATION_MARKER: &str = " [truncated]";
+
+/// `text` unchanged if it has at most `max_chars` characters. Otherwise it is cut after
+/// the last sentence (per [`SENTENCE_PATTERN`]) that fits, together with
+/// [`TRUNCATION_MARKER`], in `max_chars`; when not even the first sentence fits, it is cut
+/// mid-sentence instead.
+fn truncate_at_sentence_boundary(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let budget = max_chars.saturating_sub(TRUNCATION_MARKER.chars().count());
+    let end = SENTENCE_PATTERN
+        .find_iter(text)
+        .filter(|m| m.as_str().ends_with(['.', '!', '?']))
+        .map(|m| m.end())
+        .take_while(|&end| text[..end].chars().count() <= budget)
+        .last()
+        .unwrap_or_else(|| {
+            text.char_indices()
+                .nth(budget)
+                .map_or(text.len(), |(i, _)| i)
+        });
+    format!("{}{}", text[..end].trim_end(), TRUNCATION_MARKER)
+}
+
+/// The sentence of `text` whose TF-IDF vector (term counts × `idf` over the index
+/// vocabulary) has the highest cosine similarity to the IDF-weighted query. `None` when
+/// no sentence shares a term with the query. Ties go to the earlier sentence.
+fn best_sentence(text: &str, q_toks: &[String], idf: &HashMap<&str, f32>) -> Option<String> {
+    let weigh = |counts: HashMap<String, f32>| -> HashMap<String, f32> {
+        counts
+            .into_iter()
+            .filter_map(|(t, c)| idf.get(t.as_str()).map(|w| (t, c * w)))
+            .collect()
+    };
+    let q_vec: HashMap<String, f32> = weigh(q_toks.iter().map(|t| (t.clone(), 1.0)).collect());
+
+    let mut best: Option<(f32, &str)> = None;
+    for m in SENTENCE_PATTERN.find_iter(text) {
+        let s_vec = weigh(term_counts(&tokenize(m.as_str())));
+        let sim = sparse_cosine(&q_vec, &s_vec);
+        if sim > 0.0 && best.map(|(b, _)| sim > b).unwrap_or(true) {
+            best = Some((sim, m.as_str()));
+        }
+    }
+    best.map(|(_, s)| s.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// Runs retrieval for `q` and packages the top-ranked documents as `QueryResult`s.
+pub fn retrieve(idx: &Index, q: &str, opts: &QueryOptions) -> Vec<QueryResult> {
+    let mut results: Vec<QueryResult> = Vec::new();
+    retrieve_each(idx, q, opts, |r| results.push(r));
+    if !opts.randomize {
+        sort_results(&mut results, opts.sort_by);
+    }
+    results
+}
+
+/// Packages the top-ranked documents for `q` in score order, handing each result to
+/// `emit` as soon as it is built. `--sort-by` is not applied.
+fn retrieve_each(idx: &Index, q: &str, opts: &QueryOptions, mut emit: impl FnMut(QueryResult)) {
+    let q_toks = idx.tokenize_query(q);
+    let idf: HashMap<&str, f32> = match opts.granularity {
+        Granularity::Sentence => idx
+            .terms
+            .iter()
+            .map(String::as_str)
+            .zip(idx.idf.iter().copied())
+            .collect(),
+        Granularity::Document => HashMap::new(),
+    };
+    for (rank, (i, score)) in rank_documents(idx, q, opts).into_iter().enumerate() {
+        let doc = &idx.docs[i];
+        emit(QueryResult {
+            path: doc.path.clone(),
+            score,
+            keywords: idx.keywords(&doc.text, RESULT_KEYWORDS),
+            excerpt: match excerpt_for_query(&doc.text, &q_toks, EXCERPT_CHARS) {
+                e if e.is_empty() => doc.first_sentence.clone(),
+                e => e,
+            },
+            sentence_excerpt: match opts.granularity {
+                Granularity::Sentence => best_sentence(&doc.text, &q_toks, &idf),
+                Granularity::Document => None,
+            },
+            last_modified_secs: doc.last_modified_secs,
+            context_used: rank < opts.context_docs && score > 0.0,
+        });
+    }
+}
+
+/// Writes each result received on `results_rx` as a JSONL line, flushing it straight
+/// away so a downstream process can start before retrieval finishes. Returns once every
+/// sender is dropped.
+fn write_results_jsonl_streaming(
+    results_rx: Receiver<QueryResult>,
+    mut out: impl Write,
+) -> Result<()> {
+    for r in results_rx {
+        serde_json::to_writer(&mut out, &r)?;
+        writeln!(out)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+/// `--stream-results`: retrieval runs on a worker thread and every result is written as
+/// soon as it is packaged, in score order.
+fn stream_retrieval(idx: &Index, q: &str, opts: &QueryOptions, out: &mut dyn Write) -> Result<()> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::scope(|s| {
+        s.spawn(move || {
+            // A send only fails once the writer has stopped on an error, reported below.
+            retrieve_each(idx, q, opts, |r| {
+                let _ = tx.send(r);
+            })
+        });
+        write_results_jsonl_streaming(rx, out)
+    })
+}
+
+/// Reorders ranked `results` for display. Sorting is stable, so results that tie under
+/// `path` or `date` keep their score order.
+fn sort_results(results: &mut [QueryResult], sort_by: SortBy) {
+    match sort_by {
+        SortBy::Score => results.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        }),
+        SortBy::Path => results.sort_by(|a, b| a.path.cmp(&b.path)),
+        // `Reverse` puts larger timestamps first; `None` orders below every `Some`, so
+        // reversed it would come first, hence the explicit `is_none` key.
+        SortBy::Date => results.sort_by_key(|r| {
+            (
+                r.last_modified_secs.is_none(),
+                std::cmp::Reverse(r.last_modified_secs),
+            )
+        }),
+    }
+}
+
+/// Opening of the keyword summary given for each result without an Ollama answer.
+const SUMMARY_PREFIX: &str = "This document discusses: ";
+
+/// Keyword summary of a result, as printed by [`print_retrieval_results`].
+fn keyword_summary(keywords: &[String]) -> String {
+    if keywords.is_empty() {
+        format!("{}(no keywords).", SUMMARY_PREFIX)
+    } else {
+        format!("{}{}.", SUMMARY_PREFIX, keywords.join(", "))
+    }
+}
+
+/// A [`QueryResult`] with its keyword summary, for `--format pretty-json`.
+#[derive(Serialize)]
+struct SummarizedResult<'a> {
+    #[serde(flatten)]
+    result: &'a QueryResult,
+    summary: String,
+}
+
+/// Writes ranked retrieval results with keyword-derived summaries and excerpts.
+/// Used for `--no-ollama` and as the deterministic fallback when Ollama is unavailable or fails.
+fn print_retrieval_results(
+    results: &[QueryResult],
+    printer: &ColorPrinter,
+    out: &mut dyn Write,
+) -> Result<()> {
+    for r in results {
+        write!(out, "Document: ")?;
+        printer.print_path(out, &r.path)?;
+        write!(out, "\nScore: ")?;
+        printer.print_score(out, r.score)?;
+        write!(out, "\nSummary: {}", SUMMARY_PREFIX)?;
+        if r.keywords.is_empty() {
+            write!(out, "(no keywords)")?;
+        }
+        for (i, kw) in r.keywords.iter().enumerate() {
+            if i > 0 {
+                write!(out, ", ")?;
+            }
+            printer.print_keyword(out, kw)?;
+        }
+        writeln!(out, ".")?;
+        if !r.excerpt.is_empty() {
+            writeln!(out, "Excerpt: {}", r.excerpt)?;
+        }
+        if let Some(sentence) = &r.sentence_excerpt {
+            writeln!(out, "Best sentence: {}", sentence)?;
+        }
+        writeln!(out, "---")?;
+    }
+    Ok(())
+}
+
+/// Writes retrieval results for `query` in `format`. JSON is an array of `QueryResult`, with
+/// pretty JSON adding each result's keyword summary; JSONL and CSV carry one result per line.
+fn write_results(
+    results: &[QueryResult],
+    query: &str,
+    format: OutputFormat,
+    printer: &ColorPrinter,
+    out: &mut dyn Write,
+) -> Result<()> {
+    match format {
+        OutputFormat::Text => print_retrieval_results(results, printer, out)?,
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, results)?;
+            writeln!(out)?;
+        }
+        OutputFormat::PrettyJson => {
+            let summarized: Vec<SummarizedResult> = results
+                .iter()
+                .map(|result| SummarizedResult {
+                    result,
+                    summary: keyword_summary(&result.keywords),
+                })
+                .collect();
+            serde_json::to_writer_pretty(&mut *out, &summarized)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Jsonl => {
+            for r in results {
+                serde_json::to_writer(&mut *out, r)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv => {
+            writeln!(out, "path,score,keywords,excerpt,sentence_excerpt")?;
+            for r in results {
+                writeln!(
+                    out,
+                    "{},{:.4},{},{},{}",
+                    stats::csv_field(&r.path),
+                    r.score,
+                    stats::csv_field(&r.keywords.join(";")),
+                    stats::csv_field(&r.excerpt),
+                    stats::csv_field(r.sentence_excerpt.as_deref().unwrap_or(""))
+                )?;
+            }
+        }
+        OutputFormat::Markdown => write!(out, "{}", render_markdown(results, query, None))?,
+    }
+    Ok(())
+}
+
+/// Results sharing a parent directory, for `query --group-by-source`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+struct ResultGroup {
+    /// Parent directory of the results, with a trailing `/`.
+    source: String,
+    best_score: f32,
+    results: Vec<QueryResult>,
+}
+
+/// Groups `results` by parent directory. Groups are ordered by their best score; results
+/// keep their order within a group.
+fn group_by_source(results: &[QueryResult]) -> Vec<ResultGroup> {
+    let mut groups: Vec<ResultGroup> = Vec::new();
+    for r in results {
+        let parent = Path::new(&r.path)
+            .parent()
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let source = if parent.is_empty() {
+            "./".to_string()
+        } else {
+            format!("{}/", parent.trim_end_matches('/'))
+        };
+        match groups.iter_mut().find(|g| g.source == source) {
+            Some(g) => {
+                g.best_score = g.best_score.max(r.score);
+                g.results.push(r.clone());
+            }
+            None => groups.push(ResultGroup {
+                source,
+                best_score: r.score,
+                results: vec![r.clone()],
+            }),
+        }
+    }
+    groups.sort_by(|a, b| b.best_score.total_cmp(&a.best_score));
+    groups
+}
+
+/// Writes retrieval results as `--format` asks, grouped by directory with
+/// `--group-by-source`: a `## Group: <dir>/` line per group and a `- <file> (<score>)` line
+/// per result in text and Markdown, an array of groups in JSON, and a group per line in
+/// JSONL.
+fn write_query_results(
+    results: &[QueryResult],
+    query: &str,
+    opts: &QueryOptions,
+    out: &mut dyn Write,
+) -> Result<()> {
+    if !opts.group_by_source {
+        return write_results(results, query, opts.format, &opts.printer, out);
+    }
+    let groups = group_by_source(results);
+    match opts.format {
+        OutputFormat::Text | OutputFormat::Markdown => {
+            for g in &groups {
+                writeln!(out, "## Group: {}", g.source)?;
+                for r in &g.results {
+                    let name = Path::new(&r.path)
+                        .file_name()
+                        .map_or_else(|| r.path.clone(), |n| n.to_string_lossy().into_owned());
+                    writeln!(out, "  - {} ({:.2})", name, r.score)?;
+                }
+            }
+        }
+        OutputFormat::Json | OutputFormat::PrettyJson => {
+            serde_json::to_writer_pretty(&mut *out, &groups)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Jsonl => {
+            for g in &groups {
+                serde_json::to_writer(&mut *out, g)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv => {
+            return Err(anyhow!("--group-by-source does not support --format csv"))
+        }
+    }
+    Ok(())
+}
+
+/// Renders `results` as a Markdown document: a `# Query:` header, a `##` section per
+/// result, and `ollama_response`, if any, as a closing blockquote.
+fn render_markdown(results: &[QueryResult], query: &str, ollama_response: Option<&str>) -> String {
+    let mut md = format!("# Query: {}\n", query);
+    for r in results {
+        md.push_str(&format!(
+            "\n## {}\n**Score:** {:.4}\n**Keywords:** {}\n",
+            r.path,
+            r.score,
+            r.keywords.join(", ")
+        ));
+        if !r.excerpt.is_empty() {
+            md.push_str(&format!("{}\n", r.excerpt));
+        }
+    }
+    if let Some(response) = ollama_response {
+        md.push('\n');
+        for line in response.trim_end().lines() {
+            if line.is_empty() {
+                md.push_str(">\n");
+            } else {
+                md.push_str(&format!("> {}\n", line));
+            }
+        }
+    }
+    md
+}
+
+/// Default for `--general-query-threshold`.
+const DEFAULT_GENERAL_QUERY_THRESHOLD: f32 = 0.6;
+
+/// Phrasings of corpus-wide requests ("summarise everything") as opposed to questions
+/// about a topic. A query close enough to one of these gets every document as context.
+const GENERAL_QUERY_EXAMPLES: &[&str] = &[
+    "summarize all documents",
+    "summarize everything",
+    "summarize the corpus",
+    "summary of all files",
+    "give me an overview of everything",
+    "overview of the documents",
+    "list all files",
+    "list all documents",
+    "show me the documents",
+    "what are these documents about",
+    "what is in these files",
+    "describe each document",
+];
+
+/// Term-count vectors of `GENERAL_QUERY_EXAMPLES`, tokenised like queries.
+static GENERAL_QUERY_VECTORS: Lazy<Vec<HashMap<String, f32>>> = Lazy::new(|| {
+    GENERAL_QUERY_EXAMPLES
+        .iter()
+        .map(|e| term_counts(&tokenize(e)))
+        .collect()
+});
+
+fn term_counts(toks: &[String]) -> HashMap<String, f32> {
+    let mut counts: HashMap<String, f32> = HashMap::new();
+    for t in toks {
+        *counts.entry(t.clone()).or_insert(0.0) += 1.0;
+    }
+    counts
+}
+
+fn sparse_cosine(a: &HashMap<String, f32>, b: &HashMap<String, f32>) -> f32 {
+    let dot: f32 = a.iter().filter_map(|(t, w)| b.get(t).map(|v| w * v)).sum();
+    let na: f32 = a.values().map(|w| w * w).sum::<f32>().sqrt();
+    let nb: f32 = b.values().map(|w| w * w).sum::<f32>().sqrt();
+    if na == 0.0 || nb == 0.0 {
+        0.0
+    } else {
+        dot / (na * nb)
+    }
+}
+
+/// Highest cosine similarity between `q` and any of `GENERAL_QUERY_EXAMPLES`.
+fn general_query_similarity(q: &str) -> f32 {
+    let q_vec = term_counts(&tokenize(q));
+    GENERAL_QUERY_VECTORS
+        .iter()
+        .map(|e| sparse_cosine(&q_vec, e))
+        .fold(0.0, f32::max)
+}
+
+/// Whether `q` asks about the corpus as a whole rather than a specific topic.
+fn is_general_query_with_threshold(q: &str, threshold: f32) -> bool {
+    general_query_similarity(q) > threshold
+}
+
+/// [`is_general_query_with_threshold`] at the default `--general-query-threshold`; the
+/// labeled cases in `tests::general_query_classification` pin its behaviour.
+#[cfg(test)]
+fn is_general_query(q: &str) -> bool {
+    is_general_query_with_threshold(q, DEFAULT_GENERAL_QUERY_THRESHOLD)
+}
+
+/// Default for `--context-docs`.
+const DEFAULT_CONTEXT_DOCS: usize = 5;
+
+/// Default for `--excerpt-chars`.
+const DEFAULT_EXCERPT_CHARS: usize = 500;
+
+/// Default for `--token-warn-threshold`.
+const DEFAULT_TOKEN_WARN_THRESHOLD: usize = 3000;
+
+/// Rough LLM token count of `text`: whitespace-separated words times 1.3, the usual
+/// tokens-per-word ratio for English, rounded up. Only used for prompt-size warnings, so
+/// it need not match any particular tokenizer.
+fn estimate_tokens(text: &str) -> usize {
+    let words = text.split_whitespace().count();
+    (words * 13).div_ceil(10)
+}
+
+/// Per-document text budget in `--context-mode full` when `--context-chars` is not set,
+/// so a single large file cannot blow past the model's context window.
+const DEFAULT_FULL_CONTEXT_CHARS: usize = 8000;
+
+/// Number of keywords listed per document in the LLM prompt context.
+const PROMPT_KEYWORDS: usize = 8;
+
+/// Shortens `s` to at most `max_chars` characters, cutting on a char boundary.
+fn truncate_to_chars(s: &mut String, max_chars: usize) {
+    if let Some((byte_idx, _)) = s.char_indices().nth(max_chars) {
+        s.truncate(byte_idx);
+    }
+}
+
+/// Formats `docs` as the prompt's context section, one `Filename: ...` block per document.
+///
+/// `budget_chars` bounds the per-document text: the excerpt length in `Excerpt` mode and
+/// the text cap in `Full` mode. `Keywords` mode ignores it.
+fn build_context(
+    docs: &[&Doc],
+    mode: ContextMode,
+    budget_chars: usize,
+    lang: Option<&str>,
+) -> String {
+    let mut context = String::new();
+    for doc in docs {
+        let fname = std::path::Path::new(&doc.path)
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| doc.path.clone());
+        let entry = match mode {
+            ContextMode::Keywords => {
+                let keywords = language_keywords(&doc.text, PROMPT_KEYWORDS, lang);
+                let kw = if keywords.is_empty() {
+                    String::from("(no keywords)")
+                } else {
+                    keywords.join(", ")
+                };
+                format!("Filename: {}\nKeywords: {}\n---\n", fname, kw)
+            }
+            ContextMode::Excerpt => {
+                let text = doc.text.split_whitespace().collect::<Vec<_>>().join(" ");
+                let excerpt: String = text.chars().take(budget_chars).collect();
+                format!("Filename: {}\nExcerpt: {}\n---\n", fname, excerpt)
+            }
+            ContextMode::Full => {
+                let text: String = doc.text.trim().chars().take(budget_chars).collect();
+                format!("Filename: {}\nContent:\n{}\n---\n", fname, text)
+            }
+        };
+        context.push_str(&entry);
+    }
+    context
+}
+
+/// Picks the context documents for `q` (every document for a corpus-wide question, the
+/// top-ranked ones otherwise, at most `--context-docs`) and renders their context block.
+/// Returns whether `q` was classified as general, the documents, and the block.
+fn select_context<'a>(
+    idx: &'a Index,
+    q: &str,
+    opts: &QueryOptions,
+) -> (bool, Vec<&'a Doc>, String) {
+    let is_general_query = is_general_query_with_threshold(q, opts.general_query_threshold);
+    let selected_docs: Vec<usize> = if is_general_query {
+        (0..idx.docs.len())
+            .filter(|&i| {
+                opts.source_filter
+                    .as_ref()
+                    .is_none_or(|f| f.matches(&idx.docs[i].path))
+            })
+            .collect()
+    } else if let Some(order) = &opts.context_order {
+        order
+            .iter()
+            .filter_map(|path| idx.docs.iter().position(|d| &d.path == path))
+            .collect()
+    } else {
+        // `--randomize` and `--min-score` can rank documents that share no term with `q`.
+        rank_documents(idx, q, opts)
+            .into_iter()
+            .filter(|&(_, score)| score > 0.0)
+            .map(|(i, _)| i)
+            .collect()
+    };
+
+    let docs: Vec<&Doc> = selected_docs
+        .iter()
+        .take(opts.context_docs)
+        .map(|&i| &idx.docs[i])
+        .collect();
+    let per_doc_budget = match opts.context_mode {
+        ContextMode::Excerpt => opts.excerpt_chars,
+        ContextMode::Full => opts.context_chars.unwrap_or(DEFAULT_FULL_CONTEXT_CHARS),
+        ContextMode::Keywords => usize::MAX,
+    };
+    let mut context = build_context(
+        &docs,
+        opts.context_mode,
+        per_doc_budget,
+        idx.lang.as_deref(),
+    );
+    if let Some(limit) = opts.context_chars {
+        truncate_to_chars(&mut context, limit);
+    }
+    (is_general_query, docs, context)
+}
+
+/// Prepended to the query when retrieval finds no document to use as context, so the model
+/// does not invent sources.
+const NO_CONTEXT_PREFIX: &str = "Answer from general knowledge (no matching documents found): ";
+
+/// Builds the full Ollama prompt for query `q` using BM25 retrieval against `idx`.
+///
+/// Performs O(T) inverted-index candidate accumulation, selects up to `k` documents,
+/// formats per-document keyword excerpts, and wraps the result in the appropriate
+/// prompt template (summarisation vs. specific-question). When no document matches `q`,
+/// returns the query behind [`NO_CONTEXT_PREFIX`] so the caller can still invoke Ollama.
+fn build_prompt(idx: &Index, q: &str, opts: &QueryOptions) -> String {
+    let no_context = || format!("{}{}", NO_CONTEXT_PREFIX, q);
+    if idx.terms.is_empty() || idx.vectors.is_empty() {
+        return no_context();
+    }
+
+    let (is_general_query, _, context) = select_context(idx, q, opts);
+    let mut prompt = no_context();
+    if !context.is_empty() {
+        if is_general_query {
+            let example = "Example:\nFilename: example.txt\nKeywords: contract, delivery, schedule\n---\nOutput:\n- example.txt — The document outlines the delivery schedule and contractual obligations for shipments.\n";
+            prompt = format!(
+                "You are a concise summarizer. DO NOT QUOTE OR OUTPUT RAW DOCUMENT TEXT. Use the provided keywords to produce paraphrased summaries; do not reuse whole sentences from the source. For each document below, output: (1) a one-line label (filename — short descriptive title), (2) one-sentence paraphrased summary. After that, provide a brief combined summary of all documents (max 200 words). Keep summaries original and concise.\n\n{example}\nDocuments:\n{}\nEnd of documents.\n\nProvide the summaries now.",
+                context
+            );
+        } else {
+            prompt = format!(
+                "Use the following documents as context:\n{}\nQuestion: {}",
+                context, q
+            );
+        }
+    }
+
+    prompt
+}
+
+/// Variables for a `--template` prompt: the same context block and documents the built-in
+/// prompts would use.
+fn prompt_vars(idx: Option<&Index>, q: &str, opts: &QueryOptions, model: &str) -> PromptVars {
+    let lang = idx.and_then(|idx| idx.lang.as_deref());
+    let (docs, context) = match idx {
+        Some(idx) if !idx.terms.is_empty() => {
+            let (_, docs, context) = select_context(idx, q, opts);
+            (docs, context)
+        }
+        _ => (Vec::new(), String::new()),
+    };
+    PromptVars {
+        context,
+        query: q.to_string(),
+        documents: docs
+            .iter()
+            .map(|d| PromptDocument {
+                id: d.id.clone(),
+                path: d.path.clone(),
+                filename: Path::new(&d.path)
+                    .file_name()
+                    .map(|f| f.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| d.path.clone()),
+                keywords: language_keywords(&d.text, PROMPT_KEYWORDS, lang),
+            })
+            .collect(),
+        examples: opts.examples.clone(),
+        model: model.to_string(),
+    }
+}
+
+/// Runs `q` against every `*.json` index in `indexes_dir` in parallel, taking the top
+/// `per_index_k` results of each, and returns the best `k` overall. Files that are not term
+/// indexes are skipped with a warning.
+fn cross_index_query(
+    indexes_dir: &Path,
+    q: &str,
+    k: usize,
+    per_index_k: usize,
+) -> Result<Vec<QueryResult>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(indexes_dir)
+        .map_err(|e| anyhow!("cannot read {}: {}", indexes_dir.display(), e))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    let opts = QueryOptions {
+        k: per_index_k,
+        ..Default::default()
+    };
+    let per_index: Vec<Vec<QueryResult>> = files
+        .par_iter()
+        .filter_map(|path| match load_term_index(path) {
+            Ok(idx) => Some(retrieve(&idx, q, &opts)),
+            Err(e) => {
+                eprintln!("Warning: skipping {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect();
+    let mut results: Vec<QueryResult> = per_index.into_iter().flatten().collect();
+    sort_results(&mut results, SortBy::Score);
+    results.truncate(k);
+    Ok(results)
+}
+
+/// Reads the results of an earlier query: JSONL lines or a JSON array of [`QueryResult`].
+fn read_prev_results(path: &Path) -> Result<Vec<QueryResult>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("cannot read {}: {}", path.display(), e))?;
+    let parsed = if text.trim_start().starts_with('[') {
+        serde_json::from_str(&text)
+    } else {
+        text.lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect()
+    };
+    parsed.map_err(|e| {
+        anyhow!(
+            "{} is not `query --format jsonl` output: {}",
+            path.display(),
+            e
+        )
+    })
+}
+
+/// Ranks only the documents listed in `prev_results` against `q`, keeping the best `k`.
+fn search_within(
+    index_file: &Path,
+    prev_results: &Path,
+    q: &str,
+    k: usize,
+) -> Result<Vec<QueryResult>> {
+    let idx = load_indexes(std::slice::from_ref(&index_file.to_path_buf()))?
+        .ok_or_else(|| anyhow!("index {} not found", index_file.display()))?;
+    let opts = QueryOptions {
+        k,
+        within_paths: Some(
+            read_prev_results(prev_results)?
+                .into_iter()
+                .map(|r| r.path)
+                .collect(),
+        ),
+        ..Default::default()
+    };
+    Ok(retrieve(&idx, q, &opts))
+}
+
+/// Loads the indexes a query runs against as one corpus, or `None` when none of the files
+/// exist. A single index is returned as is. Several are concatenated in argument order and
+/// re-weighted with `build_index`, so IDF and length normalisation reflect the combined
+/// collection; ranking method and normalisation follow the first index.
+fn load_indexes(index_files: &[PathBuf]) -> Result<Option<Index>> {
+    let mut loaded = Vec::with_capacity(index_files.len());
+    for path in index_files {
+        if path.exists() {
+            loaded.push(load_index(path)?);
+        } else if index_files.len() > 1 {
+            eprintln!("Warning: index {} not found; skipping", path.display());
+        }
+    }
+    if loaded.len() <= 1 {
+        let mut idx = loaded.pop();
+        if let Some(idx) = idx.as_mut() {
+            if let Some(model) = &idx.embedding_model {
+                idx.embedder = Some(embeddings::load_embedder(Path::new(model))?);
+            }
+        }
+        return Ok(idx);
+    }
+    if loaded.iter().any(|idx| idx.embedding_model.is_some()) {
+        return Err(anyhow!(
+            "indexes built with --embeddings cannot be queried together"
+        ));
+    }
+    let (ranking, normalized) = (loaded[0].ranking, loaded[0].normalized);
+    let cfg = loaded[0].tokenizer_config();
+    let docs: Vec<Doc> = loaded
+        .iter_mut()
+        .flat_map(|idx| std::mem::take(&mut idx.docs))
+        .collect();
+    let docs_tokens: Vec<Vec<String>> = docs
+        .iter()
+        .map(|d| {
+            d.tokens
+                .clone()
+                .unwrap_or_else(|| tokenize_with_config(&d.text, &cfg))
+        })
+        .collect();
+    let mut merged = build_index(docs, &docs_tokens, ranking, normalized);
+    merged.copy_text_settings(&loaded[0]);
+    merged.doc_entities = loaded
+        .iter_mut()
+        .map(|idx| idx.doc_entities.take())
+        .collect::<Option<Vec<_>>>()
+        .map(|maps| maps.into_iter().flatten().collect());
+    Ok(Some(merged))
+}
+
+/// Answers `q` from the indexes in `index_files` (see [`load_indexes`]), writing
+/// everything to `out`.
+///
+/// Retrieval always runs first. Generation is skipped — and the ranked retrieval results
+/// printed instead — when `--no-ollama` is set, when the backend fails its health check,
+/// or when generation itself fails.
+fn query_with_ollama(
+    index_files: &[PathBuf],
+    q: &str,
+    opts: &QueryOptions,
+    backend: &dyn LlmBackend,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let fingerprint = opts.retrieval_fingerprint();
+    // Cache entries are keyed by a single index file; merged queries are not cached.
+    let cache = match index_files {
+        [index_file] => opts.cache.as_ref().map(|c| (c, index_file)),
+        _ => None,
+    };
+    let cached = cache.and_then(|(c, index_file)| c.get(q, index_file, &fingerprint));
+    // Retrieval-only output needs nothing but the results, so a cache hit skips the index.
+    if opts.no_ollama && !opts.show_term_coverage && !opts.score_debug {
+        if let Some(results) = &cached {
+            return write_query_results(results, q, opts, out);
+        }
+    }
+
+    // Load the index exactly once. An absent or empty index is not an error —
+    // we simply skip context-building and report no retrieval results.
+    // Format is auto-detected by load_index based on file extension.
+    let maybe_idx = load_indexes(index_files)?;
+    if opts.pivoted_normalization && maybe_idx.as_ref().is_some_and(|idx| idx.normalized) {
+        eprintln!(
+            "Index is already length-normalised; ignoring --pivoted-normalization (rebuild with --no-normalize)."
+        );
+    }
+    if opts.show_term_coverage {
+        if let Some(idx) = maybe_idx.as_ref() {
+            terms::write_terms_overlap(
+                &terms::terms_overlap(q, idx),
+                &mut std::io::stderr().lock(),
+            )?;
+        }
+    }
+    // `--stream-results` implies `--no-ollama`; streamed results are never all in memory,
+    // so they are not cached.
+    if opts.stream_results {
+        return match maybe_idx.as_ref() {
+            Some(idx) => stream_retrieval(idx, q, opts, out),
+            None => Ok(()),
+        };
+    }
+    let results: Vec<QueryResult> = match (cached, maybe_idx.as_ref()) {
+        (Some(results), _) => results,
+        (None, Some(idx)) => {
+            let results = retrieve(idx, q, opts);
+            if let Some((c, index_file)) = cache {
+                if let Err(e) = c.put(q, index_file, &fingerprint, &results) {
+                    eprintln!(
+                        "Warning: could not cache results in {}: {}",
+                        c.dir.display(),
+                        e
+                    );
+                }
+            }
+            results
+        }
+        (None, None) => Vec::new(),
+    };
+    if opts.score_debug {
+        if let Some(idx) = maybe_idx.as_ref() {
+            write_score_debug(idx, q, &results, &mut std::io::stderr().lock())?;
+        }
+    }
+
+    if opts.no_ollama {
+        return write_query_results(&results, q, opts, out);
+    }
+    if !opts.dry_query && !backend.is_available() {
+        eprintln!("Ollama is unavailable; showing retrieval results only.");
+        return write_query_results(&results, q, opts, out);
+    }
+
+    // Model precedence: --model, then OLLAMA_MODEL, then the smallest installed model
+    // (fastest), then a well-known default. A dry query never contacts Ollama.
+    let model = opts
+        .model
+        .clone()
+        .or_else(|| std::env::var("OLLAMA_MODEL").ok())
+        .or_else(|| (!opts.dry_query).then(|| backend.probe_model()).flatten())
+        .unwrap_or_else(|| String::from("mistral"));
+
+    let reranked_opts;
+    let (results, opts) = if opts.rerank {
+        match cross_encode_rerank(results.clone(), q, &model, backend) {
+            Ok(reranked) => {
+                reranked_opts = QueryOptions {
+                    context_order: Some(reranked.iter().map(|r| r.path.clone()).collect()),
+                    ..opts.clone()
+                };
+                (reranked, &reranked_opts)
+            }
+            Err(e) => {
+                eprintln!(
+                    "Warning: reranking failed ({}); keeping retrieval order.",
+                    e
+                );
+                (results, opts)
+            }
+        }
+    } else {
+        (results, opts)
+    };
+
+    let prompt = match &opts.template {
+        Some(t) => render_prompt(t, &prompt_vars(maybe_idx.as_ref(), q, opts, &model))?,
+        None => maybe_idx
+            .as_ref()
+            .map(|idx| build_prompt(idx, q, opts))
+            .unwrap_or_else(|| q.to_string()),
+    };
+    if opts.show_context {
+        write_prompt_context(&mut std::io::stderr().lock(), &prompt)?;
+    }
+    if opts.dry_query {
+        writeln!(out, "{}", prompt.trim_end_matches('\n'))?;
+        return Ok(());
+    }
+
+    let tokens = estimate_tokens(&prompt);
+    if let Some(limit) = opts.token_limit.filter(|&limit| tokens > limit) {
+        eprintln!(
+            "Prompt is ~{} tokens, over --token-limit {}; showing retrieval results only.",
+            tokens, limit
+        );
+        return write_query_results(&results, q, opts, out);
+    }
+    if tokens > opts.token_warn_threshold {
+        eprintln!(
+            "Warning: prompt is ~{} tokens (threshold {}); Ollama may be slow or truncate it.",
+            tokens, opts.token_warn_threshold
+        );
+    }
+
+    let generated = match opts
+        .response_cache
+        .as_ref()
+        .and_then(|c| c.get(&model, &prompt))
+    {
+        Some(response) => Ok(response),
+        None => backend.generate(&model, &prompt).inspect(|response| {
+            if let Some(c) = &opts.response_cache {
+                if let Err(e) = c.put(&model, &prompt, q, response) {
+                    eprintln!(
+                        "Warning: could not cache the response in {}: {}",
+                        c.path.display(),
+                        e
+                    );
+                }
+            }
+        }),
+    };
+    // The full answer is cached; only what is printed is truncated.
+    let generated = generated.map(|response| match opts.output_limit_chars {
+        Some(max_chars) => truncate_at_sentence_boundary(&response, max_chars),
+        None => response,
+    });
+    match generated {
+        Ok(response) if opts.format == OutputFormat::Markdown => {
+            write!(out, "{}", render_markdown(&results, q, Some(&response)))?;
+            Ok(())
+        }
+        Ok(response) => {
+            opts.printer.print_response(out, &response)?;
+            Ok(())
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            // Fallback: lightweight, non-verbatim summaries derived from keywords
+            write_query_results(&results, q, opts, out)
+        }
+    }
+}
+
+/// Prints the cached answer `hash` refers to (`query --from-cache`) as `query` printed it,
+/// without loading an index or calling Ollama.
+fn replay_cached_response(
+    cache: &cache::ResponseCache,
+    hash: &str,
+    printer: &ColorPrinter,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let entry = cache.find(hash)?;
+    printer.print_response(out, &entry.response)?;
+    Ok(())
+}
+
+/// The query text for `voltai query`: `--q`, or the contents of `--query-file` (`-` for
+/// stdin). Exactly one of them must be given.
+fn read_query(q: Option<String>, query_file: Option<PathBuf>) -> Result<String> {
+    read_query_from(q, query_file, &mut std::io::stdin().lock())
+}
+
+fn read_query_from(
+    q: Option<String>,
+    query_file: Option<PathBuf>,
+    stdin: &mut dyn std::io::Read,
+) -> Result<String> {
+    let text = match (q, query_file) {
+        (Some(_), Some(_)) => return Err(anyhow!("give either --q or --query-file, not both")),
+        (None, None) => return Err(anyhow!("a query is required: pass --q or --query-file")),
+        (Some(q), None) => return Ok(q),
+        (None, Some(path)) if path.as_os_str() == "-" => {
+            let mut text = String::new();
+            stdin.read_to_string(&mut text)?;
+            text
+        }
+        (None, Some(path)) => std::fs::read_to_string(&path)
+            .map_err(|e| anyhow!("cannot read query file {}: {}", path.display(), e))?,
+    };
+    let text = text.trim();
+    if text.is_empty() {
+        return Err(anyhow!("the query file is empty"));
+    }
+    Ok(text.to_string())
+}
+
+/// Writes `prompt` between `=== PROMPT START ===` / `=== PROMPT END ===` marker lines
+/// (`--show-context`).
+fn write_prompt_context(err: &mut dyn Write, prompt: &str) -> Result<()> {
+    writeln!(err, "=== PROMPT START ===")?;
+    writeln!(err, "{}", prompt.trim_end_matches('\n'))?;
+    writeln!(err, "=== PROMPT END ===")?;
+    Ok(())
+}
+
+/// Reorders `candidates` by how relevant `model` judges each one to `query`, asking for a
+/// 0-10 score per document. Ties, and documents whose answer has no number (ranked last),
+/// keep their retrieval order. The original retrieval scores are left untouched.
+fn cross_encode_rerank(
+    candidates: Vec<QueryResult>,
+    query: &str,
+    model: &str,
+    backend: &dyn LlmBackend,
+) -> Result<Vec<QueryResult>> {
+    let mut scored = Vec::with_capacity(candidates.len());
+    for candidate in candidates {
+        let excerpt = if candidate.excerpt.is_empty() {
+            candidate.keywords.join(", ")
+        } else {
+            candidate.excerpt.clone()
+        };
+        let prompt = format!(
+            "On a scale of 0-10, how relevant is this document to the query? Query: {}. Document: {}. Answer with only a number.",
+            query, excerpt
+        );
+        let relevance = parse_relevance(&backend.generate(model, &prompt)?);
+        if relevance.is_none() {
+            eprintln!(
+                "Warning: no relevance score for {}; ranking it last.",
+                candidate.path
+            );
+        }
+        scored.push((relevance, candidate));
+    }
+    scored.sort_by_key(|(relevance, _)| std::cmp::Reverse(*relevance));
+    Ok(scored.into_iter().map(|(_, r)| r).collect())
+}
+
+/// First integer in a model's relevance answer, capped at 10 (`"7/10"` is 7).
+fn parse_relevance(answer: &str) -> Option<u8> {
+    let digits: String = answer
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(char::is_ascii_digit)
+        .collect();
+    digits.parse::<u32>().ok().map(|n| n.min(10) as u8)
+}
+
+/// Runs [`query_with_ollama`] with its output going to `path`, created if absent and
+/// truncated unless `append` is set.
+fn query_to_file(
+    index_files: &[PathBuf],
+    q: &str,
+    opts: &QueryOptions,
+    backend: &dyn LlmBackend,
+    path: &Path,
+    append: bool,
+) -> Result<()> {
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .map_err(|e| anyhow!("cannot open {}: {}", path.display(), e))?;
+    let mut out = std::io::BufWriter::new(file);
+    query_with_ollama(index_files, q, opts, backend, &mut out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Vocabulary used by the bench subcommand to generate realistic synthetic documents.
+/// Each entry must be unique — verified by `test_bench_vocab_no_duplicates`.
+const BENCH_VOCAB: &[&str] = &[
+    "database",
+    "index",
+    "query",
+    "search",
+    "vector",
+    "document",
+    "retrieval",
+    "embedding",
+    "neural",
+    "network",
+    "training",
+    "inference",
+    "model",
+    "weight",
+    "gradient",
+    "loss",
+    "accuracy",
+    "precision",
+    "recall",
+    "latency",
+    "throughput",
+    "memory",
+    "cache",
+    "buffer",
+    "pipeline",
+    "parallel",
+    "thread",
+    "process",
+    "kernel",
+    "tensor",
+    "matrix",
+    "algorithm",
+    "cluster",
+    "shard",
+    "replica",
+    "checkpoint",
+    "snapshot",
+    "partition",
+    "segment",
+    "bucket",
+    "block",
+    "page",
+    "pointer",
+    "reference",
+    "dependency",
+    "abstraction",
+    "interface",
+    "protocol",
+    "schema",
+    "migration",
+    "transaction",
+    "commit",
+    "rollback",
+    "replication",
+    "consensus",
+    "leader",
+    "follower",
+    "quorum",
+    "heartbeat",
+    "timeout",
+    "retry",
+    "circuit",
+    "breaker",
+    "threshold",
+    "metric",
+    "monitor",
+    "alert",
+    "trace",
+    "span",
+    "log",
+    "event",
+    "stream",
+    "batch",
+    "queue",
+    "producer",
+    "consumer",
+    "offset",
+    "topic",
+    "subscriber",
+    "webhook",
+    "endpoint",
+    "payload",
+    "header",
+    "token",
+    "certificate",
+    "cipher",
+    "entropy",
+    "hash",
+    "digest",
+    "signature",
+    "key",
+    "value",
+    "store",
+    "eviction",
+    "ttl",
+    "expiry",
+    "bloom",
+    "filter",
+    "probabilistic",
+    "sketch",
+    "hyperloglog",
+    "reservoir",
+    "sampling",
+    "histogram",
+    "percentile",
+    "quantile",
+    "aggregation",
+    "projection",
+    "selection",
+    "join",
+    "merge",
+    "sort",
+    "scan",
+    "photosynthesis",
+    "chloroplast",
+    "mitochondria",
+    "ribosome",
+    "protein",
+    "metabolism",
+    "catalyst",
+    "enzyme",
+    "substrate",
+    "reaction",
+    "compound",
+    "molecule",
+    "polymer",
+    "crystal",
+    "lattice",
+    "diffraction",
+    "resonance",
+];
+
+fn run_bench(doc_count: usize, query_iterations: usize) -> Result<()> {
+    let words_per_doc = 60usize;
+    let temp_dir = std::env::temp_dir().join(format!("voltai_bench_{}", std::process::id()));
+    std::fs::create_dir_all(&temp_dir)?;
+
+    // --- Phase 1: generate synthetic documents ---
+    println!(
+        "Generating {} synthetic documents ({} words each)...",
+        doc_count, words_per_doc
+    );
+    for i in 0..doc_count {
+        let path = temp_dir.join(format!("doc_{:06}.txt", i));
+        let mut f = File::create(&path)?;
+        // Each document draws words from a sliding window of the vocabulary so different
+        // documents have different dominant terms, simulating a realistic corpus.
+        let mut line = String::with_capacity(words_per_doc * 10);
+        let offset = (i * 7) % BENCH_VOCAB.len();
+        for j in 0..words_per_doc {
+            let w = BENCH_VOCAB[(offset + j * 3) % BENCH_VOCAB.len()];
+            if j > 0 {
+                line.push(' ');
+            }
+            line.push_str(w);
+        }
+        writeln!(f, "{}", line)?;
+    }
+
+    // --- Phase 2: indexing ---
+    let index_path = temp_dir.join("bench_index.json");
+    println!("Indexing...");
+    let t0 = Instant::now();
+    index_dir(&temp_dir, &index_path, IndexFormat::Json)?;
+    let index_elapsed = t0.elapsed();
+    let docs_per_sec = doc_count as f64 / index_elapsed.as_secs_f64();
+
+    let index_bytes = std::fs::metadata(&index_path).map(|m| m.len()).unwrap_or(0);
+
+    // --- Phase 3: load index and run query timing ---
+    let f = File::open(&index_path)?;
+    let idx: Index = serde_json::from_reader(f)?;
+
+    // Build a BM25 query vector: weight each query term by its IDF.
+    let query_terms = ["database", "neural", "photosynthesis"];
+    let term_map: HashMap<&String, usize> =
+        idx.terms.iter().enumerate().map(|(i, t)| (t, i)).collect();
+
+    println!("Running {} query iterations...", query_iterations);
+    let mut query_times_us: Vec<u128> = Vec::with_capacity(query_iterations);
+    for _ in 0..query_iterations {
+        let qt = Instant::now();
+        // O(T) candidate accumulation via inverted index.
+        let _best: Option<usize> = {
+            let mut scores: HashMap<usize, f32> = HashMap::new();
+            for t in &query_terms {
+                if let Some(&ti) = term_map.get(&t.to_string()) {
+                    if let Some(candidates) = idx.inverted.get(*t) {
+                        for &di in candidates {
+                            *scores.entry(di).or_insert(0.0) += idx.idf[ti] * idx.vectors[di][ti];
+                        }
+                    }
+                }
+            }
+            scores
+                .into_iter()
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+                .map(|(i, _)| i)
+        };
+        query_times_us.push(qt.elapsed().as_micros());
+    }
+
+    query_times_us.sort_unstable();
+    let mean_us: f64 = query_times_us.iter().sum::<u128>() as f64 / query_iterations as f64;
+    let p50_us = query_times_us[query_iterations / 2];
+    let p95_us = query_times_us[(query_iterations as f64 * 0.95) as usize];
+    let min_us = query_times_us[0];
+    let max_us = query_times_us[query_iterations - 1];
+
+    // --- Phase 4: HNSW approximate search over the same queries ---
+    let t_ann = Instant::now();
+    let ann_index = AnnIndex::build(&idx.vectors);
+    let ann_build_elapsed = t_ann.elapsed();
+    let q_toks: Vec<String> = query_terms.iter().map(|t| t.to_string()).collect();
+    let mut ann_times_us: Vec<u128> = Vec::with_capacity(query_iterations);
+    for _ in 0..query_iterations {
+        let qt = Instant::now();
+        let _top = ann_bm25_scores(&idx, &ann_index, &q_toks, DEFAULT_ANN_EF)
+            .map(|scores| top_k(&scores, 3));
+        ann_times_us.push(qt.elapsed().as_micros());
+    }
+    ann_times_us.sort_unstable();
+    let ann_mean_us: f64 = ann_times_us.iter().sum::<u128>() as f64 / query_iterations as f64;
+    let ann_p95_us = ann_times_us[(query_iterations as f64 * 0.95) as usize];
+
+    // --- Report ---
+    println!();
+    println!("=== VoltAI Benchmark Results ===");
+    println!();
+    println!("Indexing:");
+    println!("  documents:  {}", doc_count);
+    println!("  words/doc:  {}", words_per_doc);
+    println!("  elapsed:    {:.2}s", index_elapsed.as_secs_f64());
+    println!("  throughput: {:.0} docs/sec", docs_per_sec);
+    println!(
+        "  index size: {:.2} MB  ({} bytes)",
+        index_bytes as f64 / 1_048_576.0,
+        index_bytes
+    );
+    println!(
+        "  vocabulary: {} terms (after stop-word filtering)",
+        idx.terms.len()
+    );
+    println!();
+    println!(
+        "Query (BM25 inverted-index, {} iterations):",
+        query_iterations
+    );
+    println!("  mean:  {:.1}µs  ({:.2}ms)", mean_us, mean_us / 1000.0);
+    println!("  p50:   {}µs", p50_us);
+    println!("  p95:   {}µs", p95_us);
+    println!("  min:   {}µs", min_us);
+    println!("  max:   {}µs", max_us);
+    println!();
+    println!(
+        "Query (HNSW ANN, ef={}, {} iterations):",
+        DEFAULT_ANN_EF, query_iterations
+    );
+    println!("  build: {:.2}s", ann_build_elapsed.as_secs_f64());
+    println!(
+        "  mean:  {:.1}µs  ({:.2}ms)",
+        ann_mean_us,
+        ann_mean_us / 1000.0
+    );
+    println!("  p95:   {}µs", ann_p95_us);
+
+    std::fs::remove_dir_all(&temp_dir).ok();
+    Ok(())
+}
+
+fn index_entities(
+    index_file: &Path,
+    out: &Path,
+    normalize: bool,
+    lang: Option<Language>,
+    quiet: bool,
+) -> Result<()> {
+    let idx = load_index(index_file)?;
+    let lang = lang.unwrap_or_else(|| idx.language());
+    let entity_index = cross_reference_entities(&idx.docs, normalize, lang);
+    let fout = File::create(out)?;
+    serde_json::to_writer_pretty(fout, &entity_index)?;
+    writeln!(
+        status_writer(quiet),
+        "Wrote {} entities to {}",
+        entity_index.len(),
+        out.display()
+    )?;
+    Ok(())
+}
+
+/// Returns the documents mentioning `entity`, matching surface forms case-insensitively.
+/// Paths are de-duplicated and sorted so output is stable across runs.
+fn lookup_entity(entity_index: &HashMap<String, Vec<String>>, entity: &str) -> Vec<String> {
+    let needle = entity.trim().to_lowercase();
+    let mut paths: Vec<String> = entity_index
+        .iter()
+        .filter(|(name, _)| name.to_lowercase() == needle)
+        .flat_map(|(_, paths)| paths.iter().cloned())
+        .collect();
+    paths.sort();
+    paths.dedup();
+    paths
+}
+
+/// Entities from `entity_index` mentioned by at least `min_docs` documents, most widely
+/// shared first (ties by name) so output is stable across runs.
+fn shared_entities(
+    entity_index: &HashMap<String, Vec<String>>,
+    min_docs: usize,
+) -> Vec<(&String, &Vec<String>)> {
+    let mut shared: Vec<(&String, &Vec<String>)> = entity_index
+        .iter()
+        .filter(|(_, paths)| paths.len() >= min_docs)
+        .collect();
+    shared.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(b.0)));
+    shared
+}
+
+fn cross_ref(index_file: &Path, min_docs: usize, out: &mut dyn Write) -> Result<()> {
+    let idx = load_index(index_file)?;
+    let entity_index = cross_reference_entities(&idx.docs, false, idx.language());
+    let shared = shared_entities(&entity_index, min_docs);
+    if shared.is_empty() {
+        eprintln!("No entities appear in {} or more documents", min_docs);
+    }
+    for (entity, paths) in shared {
+        let noun = if paths.len() == 1 {
+            "document"
+        } else {
+            "documents"
+        };
+        writeln!(out, "{} ({} {})", entity, paths.len(), noun)?;
+        for path in paths {
+            writeln!(out, "  {}", path)?;
+        }
+    }
+    Ok(())
+}
+
+fn compare(index_file: &Path, doc_a: &str, doc_b: &str, out: &mut dyn Write) -> Result<()> {
+    let idx = load_term_index(index_file)?;
+    let cmp = similarity::compare_docs(&idx, doc_a, doc_b)?;
+    let list = |terms: &[String]| {
+        if terms.is_empty() {
+            String::from("(none)")
+        } else {
+            terms.join(", ")
+        }
+    };
+    writeln!(out, "Similarity: {:.4}", cmp.similarity)?;
+    writeln!(out, "Shared terms: {}", list(&cmp.shared_terms))?;
+    writeln!(out, "Only in {}: {}", doc_a, list(&cmp.unique_to_a))?;
+    writeln!(out, "Only in {}: {}", doc_b, list(&cmp.unique_to_b))?;
+    Ok(())
+}
+
+/// `Unique to A: [..]. Unique to B: [..]. Shared: [..]. Similarity: 0.72`
+fn write_text_diff(diff: &similarity::DocComparison, out: &mut dyn Write) -> Result<()> {
+    writeln!(
+        out,
+        "Unique to A: [{}]. Unique to B: [{}]. Shared: [{}]. Similarity: {:.2}",
+        diff.unique_to_a.join(", "),
+        diff.unique_to_b.join(", "),
+        diff.shared_terms.join(", "),
+        diff.similarity
+    )?;
+    Ok(())
+}
+
+fn export_cooccurrence(
+    index_file: &Path,
+    window: usize,
+    out: &Path,
+    min_count: usize,
+    quiet: bool,
+) -> Result<()> {
+    let idx = load_index(index_file)?;
+    let rows = cooccurrence::cooccurrence_counts(&idx.docs, window, min_count);
+    let mut fout = std::io::BufWriter::new(File::create(out)?);
+    cooccurrence::write_jsonl(&rows, &mut fout)?;
+    fout.flush()?;
+    writeln!(
+        status_writer(quiet),
+        "Wrote {} term pairs to {}",
+        rows.len(),
+        out.display()
+    )?;
+    Ok(())
+}
+
+fn query_entity(entity_index_file: &Path, entity: &str) -> Result<()> {
+    let f = File::open(entity_index_file)?;
+    let entity_index: HashMap<String, Vec<String>> = serde_json::from_reader(f)?;
+    let paths = lookup_entity(&entity_index, entity);
+    if paths.is_empty() {
+        eprintln!("No documents mention '{}'", entity);
+    }
+    for path in paths {
+        println!("{}", path);
+    }
+    Ok(())
+}
+
+/// Parses the command line and runs the selected subcommand.
+pub fn run() -> Result<()> {
+    let args: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    if let Some(path) = envfile::env_file_arg(&args) {
+        envfile::load(&path)?;
+    }
+    let cli = Cli::parse_from(args);
+    let printer = ColorPrinter {
+        color: color::enabled(cli.color, cli.no_color),
+    };
+    match cli.command {
+        Commands::Index {
+            dir,
+            out,
+            format,
+            index_format,
+            ann,
+            no_normalize,
+            ranking,
+            cache_tokens,
+            allow_symlinks,
+            embeddings,
+            embedding_model,
+            verbose,
+            sample,
+            seed,
+            max_keyword_length,
+            token_pattern,
+            alias_file,
+            pre_process,
+            min_keyword_freq,
+            lang,
+            exclude_numeric,
+            title_from_first_line,
+            field_boost,
+            deduplicate_near,
+            stop_on_error,
+            assign_topics,
+        } => index_dir_with_options(
+            &dir,
+            &out,
+            &IndexOptions {
+                format,
+                layout: index_format,
+                ann,
+                no_normalize,
+                ranking,
+                cache_tokens,
+                follow_symlinks: allow_symlinks,
+                embedding_model: match embeddings {
+                    embeddings::EmbeddingKind::Tfidf => None,
+                    kind => {
+                        if let Some(model) = &embedding_model {
+                            kind.check_model(model)?;
+                        }
+                        embedding_model
+                    }
+                },
+                quiet: cli.quiet,
+                verbose,
+                sample,
+                seed,
+                preprocess: pre_process,
+                tokenizer: TokenizerConfig {
+                    pattern: token_pattern
+                        .as_deref()
+                        .map(compile_token_pattern)
+                        .transpose()?,
+                    max_token_len: max_keyword_length,
+                    lang,
+                    exclude_numeric,
+                    filter: None,
+                },
+                term_aliases: match &alias_file {
+                    Some(path) => termfilter::AliasFilter::load(path)?.aliases,
+                    None => BTreeMap::new(),
+                },
+                min_keyword_freq,
+                title_from_first_line,
+                field_boosts: field_boost,
+                deduplicate_near,
+                stop_on_error,
+                assign_topics,
+            },
+        )?,
+        Commands::RebuildVectors {
+            index,
+            ranking,
+            out,
+        } => rebuild_vectors(&index, ranking, &out, cli.quiet)?,
+        Commands::AppendIndex {
+            existing,
+            new_files,
+            out,
+        } => append_index(&existing, &new_files, &out, cli.quiet)?,
+        Commands::Migrate {
+            index,
+            from_version,
+            out,
+        } => migrate_index(&index, from_version, &out, cli.quiet)?,
+        Commands::Schema => {
+            let mut stdout = std::io::stdout().lock();
+            serde_json::to_writer_pretty(&mut stdout, &index_schema())?;
+            writeln!(stdout)?;
+        }
+        Commands::Query {
+            index,
+            q,
+            query_file,
+            k,
+            model,
+            entity_boost,
+            no_ollama,
+            ann_ef,
+            pivoted_normalization,
+            general_query_threshold,
+            context_docs,
+            context_chars,
+            context_mode,
+            excerpt_chars,
+            granularity,
+            source_filter,
+            ollama_url,
+            ollama_timeout,
+            format,
+            sort_by,
+            output_file,
+            append,
+            template,
+            examples_file,
+            token_warn_threshold,
+            token_limit,
+            rerank,
+            max_context_docs_per_file,
+            stream_results,
+            show_context,
+            dry_query,
+            show_term_coverage,
+            output_limit_chars,
+            score_debug,
+            min_score,
+            randomize,
+            seed,
+            group_by_source,
+            cache_ollama_responses,
+            response_cache_file,
+            response_cache_ttl,
+            from_cache,
+            list_cache,
+        } => {
+            let response_cache = cache::ResponseCache {
+                path: response_cache_file,
+                ttl: std::time::Duration::from_secs(response_cache_ttl),
+            };
+            if list_cache {
+                for (hash, entry) in response_cache.entries() {
+                    println!(
+                        "{}  {}  {}",
+                        hash,
+                        cache::format_utc(entry.created_secs),
+                        entry.query
+                    );
+                }
+                return Ok(());
+            }
+            if let Some(hash) = from_cache {
+                return replay_cached_response(
+                    &response_cache,
+                    &hash,
+                    &printer,
+                    &mut std::io::stdout().lock(),
+                );
+            }
+            let q = read_query(q, query_file)?;
+            let opts = QueryOptions {
+                k,
+                model,
+                entity_boost: entity_boost.map(|b| b.unwrap_or(DEFAULT_ENTITY_BOOST)),
+                no_ollama,
+                ann_ef,
+                pivoted_normalization,
+                general_query_threshold,
+                context_docs,
+                context_chars,
+                context_mode,
+                excerpt_chars,
+                granularity,
+                source_filter: source_filter
+                    .as_deref()
+                    .map(SourceFilter::parse)
+                    .transpose()?,
+                within_paths: None,
+                format: if stream_results {
+                    OutputFormat::Jsonl
+                } else {
+                    format
+                },
+                sort_by,
+                template: template
+                    .as_deref()
+                    .map(template::load_template)
+                    .transpose()?,
+                examples: examples_file
+                    .as_deref()
+                    .map(template::load_examples)
+                    .transpose()?
+                    .unwrap_or_default(),
+                cache: cli.cache_dir.clone().map(|dir| cache::QueryCache {
+                    dir,
+                    ttl: std::time::Duration::from_secs(cli.cache_ttl),
+                }),
+                token_warn_threshold,
+                token_limit,
+                rerank,
+                max_docs_per_file: max_context_docs_per_file,
+                stream_results,
+                show_context,
+                dry_query,
+                show_term_coverage,
+                output_limit_chars,
+                score_debug,
+                min_score,
+                randomize,
+                seed,
+                group_by_source,
+                response_cache: cache_ollama_responses.then_some(response_cache),
+                printer: ColorPrinter {
+                    color: printer.color && output_file.is_none(),
+                },
+                context_order: None,
+            };
+            let timeout = ollama::resolve_timeout(ollama_timeout);
+            let backend: Box<dyn LlmBackend> = match ollama_url {
+                Some(url) => Box::new(OllamaHttp::new(&url, timeout)),
+                None => Box::new(OllamaCli { timeout }),
+            };
+            match output_file {
+                Some(path) => {
+                    query_to_file(&index, &q, &opts, backend.as_ref(), &path, append)?;
+                    if opts.format == OutputFormat::Text && !cli.quiet {
+                        println!("Results written to {}", path.display());
+                    }
+                }
+                None => query_with_ollama(
+                    &index,
+                    &q,
+                    &opts,
+                    backend.as_ref(),
+                    &mut std::io::stdout().lock(),
+                )?,
+            }
+        }
+        Commands::CrossIndexQuery {
+            indexes_dir,
+            q,
+            k,
+            per_index_k,
+            format,
+        } => {
+            let results = cross_index_query(&indexes_dir, &q, k, per_index_k)?;
+            write_results(
+                &results,
+                &q,
+                format,
+                &printer,
+                &mut std::io::stdout().lock(),
+            )?
+        }
+        Commands::Assert {
+            index,
+            assertions_file,
+        } => {
+            let idx = load_indexes(std::slice::from_ref(&index))?
+                .ok_or_else(|| anyhow!("index {} not found", index.display()))?;
+            let results: Vec<assertions::AssertionResult> =
+                assertions::load_assertions(&assertions_file)?
+                    .iter()
+                    .map(|a| assertions::run_assertion(&idx, a))
+                    .collect();
+            assertions::write_assertion_results(&results, &mut std::io::stdout().lock())?;
+            let failed = results.iter().filter(|r| !r.passed()).count();
+            if failed > 0 {
+                return Err(anyhow!("{} of {} assertions failed", failed, results.len()));
+            }
+        }
+        Commands::SearchWithin {
+            index,
+            prev_results,
+            q,
+            k,
+            format,
+        } => {
+            let results = search_within(&index, &prev_results, &q, k)?;
+            write_results(
+                &results,
+                &q,
+                format,
+                &printer,
+                &mut std::io::stdout().lock(),
+            )?
+        }
+        Commands::ModelList { ollama_url, format } => {
+            let http = OllamaHttp::new(&ollama_url, ollama::resolve_timeout(None));
+            let preferred = std::env::var("OLLAMA_MODEL").ok();
+            ollama::write_model_list(
+                &http.list_models(preferred.as_deref())?,
+                format,
+                &printer,
+                &mut std::io::stdout().lock(),
+            )?
+        }
+        Commands::PullModel { model, ollama_url } => {
+            let http = OllamaHttp::new(&ollama_url, ollama::resolve_timeout(None));
+            let mut status = status_writer(cli.quiet);
+            if http.has_model(&model)? {
+                writeln!(status, "{} is already installed", model)?;
+            } else {
+                let bar = if cli.quiet {
+                    ProgressBar::hidden()
+                } else {
+                    let pb = ProgressBar::new(0);
+                    pb.set_style(
+                        ProgressStyle::with_template(PULL_PROGRESS_TEMPLATE)?.progress_chars("=>-"),
+                    );
+                    pb
+                };
+                http.pull_model(&model, &bar)?;
+                writeln!(status, "Pulled {}", model)?;
+            }
+        }
+        Commands::Bench { docs, queries } => run_bench(docs, queries)?,
+        Commands::IndexEntities {
+            index,
+            out,
+            normalize_entities,
+            lang,
+        } => index_entities(
+            &index,
+            &out,
+            normalize_entities,
+            lang.as_deref().map(Language::from_code),
+            cli.quiet,
+        )?,
+        Commands::Compare {
+            index,
+            doc_a,
+            doc_b,
+        } => compare(&index, &doc_a, &doc_b, &mut std::io::stdout().lock())?,
+        Commands::TextDiff { file_a, file_b } => {
+            let diff = similarity::diff_texts(
+                &DefaultFileReader::read(&file_a)?,
+                &DefaultFileReader::read(&file_b)?,
+            );
+            write_text_diff(&diff, &mut std::io::stdout().lock())?
+        }
+        Commands::SentenceSimilarity {
+            text_a,
+            text_b,
+            index,
+        } => {
+            let idx = index.as_deref().map(load_term_index).transpose()?;
+            let result = similarity::sentence_similarity(&text_a, &text_b, idx.as_ref());
+            println!(
+                "Similarity: {:.4}. Overlap: [{}]",
+                result.similarity,
+                result.overlap.join(", ")
+            );
+        }
+        Commands::MostSimilar { index, doc, k } => {
+            let idx = load_term_index(&index)?;
+            let results = similarity::find_similar_docs(&idx, &doc, k)?;
+            print_retrieval_results(&results, &printer, &mut std::io::stdout().lock())?
+        }
+        Commands::Cooccurrence {
+            index,
+            window,
+            out,
+            min_count,
+        } => export_cooccurrence(&index, window, &out, min_count, cli.quiet)?,
+        Commands::FindDuplicates {
+            index,
+            min_similarity,
+        } => {
+            let report = duplicates::find_duplicates(&load_term_index(&index)?, min_similarity);
+            let mut stdout = std::io::stdout().lock();
+            serde_json::to_writer_pretty(&mut stdout, &report)?;
+            writeln!(stdout)?;
+        }
+        Commands::Graph {
+            index,
+            min_similarity,
+            format,
+            out,
+        } => {
+            let g = graph::build_graph(&load_term_index(&index)?, min_similarity);
+            let mut fout = std::io::BufWriter::new(File::create(&out)?);
+            graph::write_graph(&g, format, &mut fout)?;
+            fout.flush()?;
+            writeln!(
+                status_writer(cli.quiet),
+                "Wrote {} documents and {} edges to {}",
+                g.nodes.len(),
+                g.edges.len(),
+                out.display()
+            )?;
+        }
+        Commands::TopTerms {
+            index,
+            n,
+            sort_by,
+            exclude_stop_words,
+            histogram,
+        } => {
+            let idx = load_term_index(&index)?;
+            let top = terms::top_terms(&idx, n, sort_by, exclude_stop_words);
+            let mut stdout = std::io::stdout().lock();
+            terms::write_term_stats(&top, &mut stdout)?;
+            if histogram {
+                writeln!(stdout)?;
+                stats::write_term_distribution(&terms::index_term_distribution(&idx), &mut stdout)?;
+            }
+        }
+        Commands::DocTerms {
+            index,
+            doc_path,
+            top_n,
+            format,
+        } => {
+            let idx = load_term_index(&index)?;
+            let di = similarity::find_doc(&idx, &doc_path)?;
+            let report = terms::doc_terms(&idx, di, top_n);
+            terms::write_doc_terms(&report, format, &mut std::io::stdout().lock())?
+        }
+        Commands::Feedback {
+            index,
+            query,
+            relevant,
+            irrelevant,
+            k,
+            alpha,
+            beta,
+            gamma,
+        } => {
+            let idx = load_term_index(&index)?;
+            let weights = feedback::RocchioWeights { alpha, beta, gamma };
+            let results =
+                feedback::feedback_search(&idx, &query, &relevant, &irrelevant, weights, k)?;
+            print_retrieval_results(&results, &printer, &mut std::io::stdout().lock())?
+        }
+        Commands::WordCount { dir, format } => stats::write_word_counts(
+            &stats::word_counts(&dir),
+            format,
+            &mut std::io::stdout().lock(),
+        )?,
+        Commands::TextStats {
+            dir,
+            format,
+            histogram,
+        } => stats::write_report(
+            &stats::analyze_dir(&dir, histogram),
+            format,
+            &mut std::io::stdout().lock(),
+        )?,
+        Commands::Pipeline {
+            dir,
+            out_dir,
+            steps,
+        } => {
+            let written = pipeline::run_pipeline(&dir, &out_dir, &steps, cli.quiet)?;
+            writeln!(
+                status_writer(cli.quiet),
+                "Pipeline wrote {} files to {}",
+                written.len(),
+                out_dir.display()
+            )?;
+        }
+        Commands::ExportCsv {
+            index,
+            out,
+            include_vectors,
+        } => {
+            let idx = load_term_index(&index)?;
+            let file = File::create(&out)
+                .map_err(|e| anyhow!("cannot create {}: {}", out.display(), e))?;
+            export::write_documents_csv(&idx, include_vectors, std::io::BufWriter::new(file))?;
+            writeln!(
+                status_writer(cli.quiet),
+                "Wrote {} documents to {}",
+                idx.docs.len(),
+                out.display()
+            )?;
+        }
+        Commands::Benchmark {
+            index,
+            queries_file,
+            iterations,
+            k,
+            format,
+        } => {
+            let idx = load_indexes(std::slice::from_ref(&index))?
+                .ok_or_else(|| anyhow!("index {} not found", index.display()))?;
+            let queries = report::parse_queries(
+                &std::fs::read_to_string(&queries_file)
+                    .map_err(|e| anyhow!("cannot read {}: {}", queries_file.display(), e))?,
+            );
+            let opts = QueryOptions {
+                k,
+                ..Default::default()
+            };
+            let report = benchmark::run_benchmark(&idx, &queries, iterations, &opts);
+            benchmark::write_benchmark(&report, format, &mut std::io::stdout().lock())?;
+        }
+        Commands::Report {
+            index,
+            queries_file,
+            out_html,
+            k,
+        } => {
+            let idx = load_indexes(std::slice::from_ref(&index))?
+                .ok_or_else(|| anyhow!("index {} not found", index.display()))?;
+            let queries = report::parse_queries(
+                &std::fs::read_to_string(&queries_file)
+                    .map_err(|e| anyhow!("cannot read {}: {}", queries_file.display(), e))?,
+            );
+            let opts = QueryOptions {
+                k,
+                ..Default::default()
+            };
+            let reports = report::build_report(&idx, &queries, &opts);
+            std::fs::write(
+                &out_html,
+                report::render_html(&index.to_string_lossy(), &reports)?,
+            )?;
+            writeln!(
+                status_writer(cli.quiet),
+                "Wrote report for {} queries to {}",
+                queries.len(),
+                out_html.display()
+            )?;
+        }
+        Commands::SearchHistory => {
+            let dir = cli
+                .cache_dir
+                .as_deref()
+                .ok_or_else(|| anyhow!("search-history needs --cache-dir"))?;
+            for entry in cache::history(dir)? {
+                println!(
+                    "{}  {}  ({})",
+                    cache::format_utc(entry.created_secs),
+                    entry.query,
+                    entry.index
+                );
+            }
+        }
+        Commands::Completions { shell } => clap_complete::generate(
+            shell,
+            &mut Cli::command(),
+            "voltai",
+            &mut std::io::stdout().lock(),
+        ),
+        Commands::ClearResponseCache {
+            response_cache_file,
+        } => {
+            if cache::clear_response_cache(&response_cache_file)? && !cli.quiet {
+                println!("Removed {}", response_cache_file.display());
+            }
+        }
+        Commands::CrossRef { index, min_docs } => {
+            cross_ref(&index, min_docs, &mut std::io::stdout().lock())?
+        }
+        Commands::QueryEntity {
+            entity_index,
+            entity,
+        } => query_entity(&entity_index, &entity)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_tokenize_basic() {
+        let text = "Hello World! This is a test.";
+        let tokens = tokenize(text);
+        // "this", "is", "a" are stop words and should be filtered out
+        assert_eq!(tokens, vec!["hello", "world", "test"]);
+    }
+
+    #[test]
+    fn test_stop_words_filtered() {
+        // Common function words must not appear in output
+        let stop_inputs = [
+            "the", "is", "a", "and", "or", "in", "of", "for", "how", "does",
+        ];
+        for word in &stop_inputs {
+            let tokens = tokenize(word);
+            assert!(tokens.is_empty(), "stop word '{}' should be filtered", word);
+        }
+
+        // Content words must be preserved
+        let content_inputs = ["kubernetes", "database", "photosynthesis", "latency"];
+        for word in &content_inputs {
+            let tokens = tokenize(word);
+            assert_eq!(
+                tokens,
+                vec![*word],
+                "content word '{}' should pass through",
+                word
+            );
+        }
+    }
+
+    #[test]
+    fn test_tokenize_empty() {
+        let tokens = tokenize("");
+        assert_eq!(tokens, Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_tokenize_with_numbers() {
+        let text = "test123 hello456";
+        let tokens = tokenize(text);
+        assert_eq!(tokens, vec!["test123", "hello456"]);
+    }
+
+    #[test]
+    fn test_tokenize_with_apostrophes() {
+        let text = "don't can't won't";
+        let tokens = tokenize(text);
+        assert_eq!(tokens, vec!["don't", "can't", "won't"]);
+    }
+
+    #[test]
+    fn test_dot_product_identical() {
+        // When both inputs are L2-normalized unit vectors, dot product equals cosine similarity.
+        // Identical unit vectors should produce similarity = 1.0.
+        let a = vec![0.6_f32, 0.8_f32]; // ||a|| = sqrt(0.36 + 0.64) = 1.0
+        let b = vec![0.6_f32, 0.8_f32];
+        let sim = dot_product(&a, &b);
+        assert!((sim - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_dot_product_orthogonal() {
+        let a = vec![1.0, 0.0];
+        let b = vec![0.0, 1.0];
+        let sim = dot_product(&a, &b);
+        assert_eq!(sim, 0.0);
+    }
+
+    #[test]
+    fn test_dot_product_opposite() {
+        let a = vec![1.0, 0.0];
+        let b = vec![-1.0, 0.0];
+        let sim = dot_product(&a, &b);
+        assert_eq!(sim, -1.0);
+    }
+
+    #[test]
+    fn test_read_text_file() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file_path = temp_dir.path().join("test.txt");
+        let mut file = File::create(&file_path)?;
+        writeln!(file, "Hello, World!")?;
+
+        let content = DefaultFileReader::read(&file_path)?;
+        assert_eq!(content, "Hello, World!\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_file_content_txt() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let file_path = temp_dir.path().join("test.txt");
+        let mut file = File::create(&file_path)?;
+        writeln!(file, "Test content")?;
+
+        let content = DefaultFileReader::read(&file_path)?;
+        assert_eq!(content, "Test content\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_creation() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+
+        // Create test files
+        let file1 = temp_dir.path().join("doc1.txt");
+        let file2 = temp_dir.path().join("doc2.txt");
+
+        let mut f1 = File::create(&file1)?;
+        writeln!(f1, "machine learning artificial intelligence")?;
+
+        let mut f2 = File::create(&file2)?;
+        writeln!(f2, "deep learning neural networks")?;
+
+        let index_path = temp_dir.path().join("test_index.json");
+        index_dir(temp_dir.path(), &index_path, IndexFormat::Json)?;
+
+        // Verify index file was created
+        assert!(index_path.exists());
+
+        // Read and verify index structure
+        let f = File::open(&index_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+
+        assert_eq!(idx.docs.len(), 2);
+        assert!(!idx.terms.is_empty());
+        assert_eq!(idx.vectors.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_with_empty_dir() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let index_path = temp_dir.path().join("empty_index.json");
+
+        index_dir(temp_dir.path(), &index_path, IndexFormat::Json)?;
+
+        assert!(index_path.exists());
+        let f = File::open(&index_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+
+        assert_eq!(idx.docs.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_doc_serialization() {
+        let doc = Doc {
+            id: "test-id".to_string(),
+            path: "/path/to/file.txt".to_string(),
+            text: "Test content".to_string(),
+            ..Default::default()
+        };
+
+        let json = serde_json::to_string(&doc).unwrap();
+        let deserialized: Doc = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(doc.id, deserialized.id);
+        assert_eq!(doc.path, deserialized.path);
+        assert_eq!(doc.text, deserialized.text);
+    }
+
+    #[test]
+    fn test_index_serialization() {
+        let index = Index {
+            docs: vec![Doc {
+                id: "doc1".to_string(),
+                path: "path1.txt".to_string(),
+                text: "content 1".to_string(),
+                ..Default::default()
+            }],
+            terms: vec!["content".to_string(), "test".to_string()],
+            idf: vec![1.0, 1.0],
+            vectors: vec![vec![0.5, 0.5]],
+            inverted: HashMap::new(),
+            ..Default::default()
+        };
+
+        let json = serde_json::to_string(&index).unwrap();
+        let deserialized: Index = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(index.docs.len(), deserialized.docs.len());
+        assert_eq!(index.terms.len(), deserialized.terms.len());
+        assert_eq!(index.vectors.len(), deserialized.vectors.len());
+    }
+
+    #[test]
+    fn test_tokenize_special_chars() {
+        let text = "hello@world #test $money";
+        let tokens = tokenize(text);
+        assert_eq!(tokens, vec!["hello", "world", "test", "money"]);
+    }
+
+    #[test]
+    fn test_tokenize_mixed_case() {
+        let text = "Hello WORLD HeLLo";
+        let tokens = tokenize(text);
+        assert_eq!(tokens, vec!["hello", "world", "hello"]);
+    }
+
+    #[test]
+    fn test_vector_normalization() {
+        let vec = [3.0, 4.0];
+        let norm = vec.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert_eq!(norm, 5.0);
+    }
+
+    #[test]
+    fn test_dot_product_normalized() {
+        // Normalized vectors
+        let a = vec![0.6, 0.8];
+        let b = vec![0.6, 0.8];
+        let sim = dot_product(&a, &b);
+        assert!((sim - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_index_filters_binary_files() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+
+        // Create text file (should be indexed)
+        let text_file = temp_dir.path().join("doc.txt");
+        let mut f = File::create(&text_file)?;
+        writeln!(f, "This is a text file")?;
+
+        // Create binary file (should be skipped)
+        let bin_file = temp_dir.path().join("image.png");
+        File::create(&bin_file)?;
+
+        let index_path = temp_dir.path().join("index.json");
+        index_dir(temp_dir.path(), &index_path, IndexFormat::Json)?;
+
+        let f = File::open(&index_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+
+        // Should only index the text file
+        assert_eq!(idx.docs.len(), 1);
+        assert!(idx.docs[0].path.contains("doc.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_query_vector() {
+        let q_toks = tokenize("");
+        assert_eq!(q_toks.len(), 0);
+    }
+
+    #[test]
+    fn test_word_regex() {
+        let text = "test-case hello_world foo.bar";
+        let tokens = tokenize(text);
+        // Regex only captures alphanumeric and apostrophes
+        assert_eq!(tokens, vec!["test", "case", "hello", "world", "foo", "bar"]);
+    }
+
+    #[test]
+    fn test_index_dir_real_workflow() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let out_path = dir.path().join("index.json");
+
+        // Create test files
+        let doc1 = dir.path().join("doc1.txt");
+        let doc2 = dir.path().join("doc2.md");
+        std::fs::write(&doc1, "machine learning algorithms")?;
+        std::fs::write(&doc2, "data science and statistics")?;
+
+        // Run indexing
+        index_dir(dir.path(), &out_path, IndexFormat::Json)?;
+        assert!(out_path.exists());
+
+        // Verify index contents
+        let f = File::open(&out_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+        assert_eq!(idx.docs.len(), 2);
+        assert!(!idx.terms.is_empty());
+        assert_eq!(idx.vectors.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_with_ollama_no_index() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = dir.path().join("nonexistent.json");
+
+        // Query without index - should attempt to run ollama
+        // May fail if ollama not installed, but shouldn't panic
+        let _ = query_with_ollama(
+            std::slice::from_ref(&index_path),
+            "test query",
+            &QueryOptions {
+                k: 5,
+                model: Some("mistral".to_string()),
+                ..Default::default()
+            },
+            &OllamaCli::default(),
+            &mut std::io::sink(),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_with_ollama_with_index() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = dir.path().join("test_index.json");
+
+        // Create a minimal index
+        let idx = Index {
+            terms: vec!["test".to_string(), "document".to_string()],
+            idf: vec![1.0, 1.0],
+            docs: vec![Doc {
+                id: "1".to_string(),
+                path: "test.txt".to_string(),
+                text: "test document content".to_string(),
+                ..Default::default()
+            }],
+            vectors: vec![vec![
+                std::f32::consts::FRAC_1_SQRT_2,
+                std::f32::consts::FRAC_1_SQRT_2,
+            ]],
+            inverted: HashMap::new(),
+            ..Default::default()
+        };
+
+        let f = File::create(&index_path)?;
+        serde_json::to_writer(f, &idx)?;
+
+        // Query with index - may fail if ollama not installed
+        let _ = query_with_ollama(
+            std::slice::from_ref(&index_path),
+            "test",
+            &QueryOptions {
+                k: 1,
+                model: Some("mistral".to_string()),
+                ..Default::default()
+            },
+            &OllamaCli::default(),
+            &mut std::io::sink(),
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_text_file_binary() {
+        let dir = tempfile::tempdir().unwrap();
+        // Use a .txt extension so the file is read as text
+        let bin_path = dir.path().join("test.txt");
+        // Write raw non-UTF-8 bytes; read_to_string must return Err
+        std::fs::write(&bin_path, vec![0xFF, 0xFE, 0x00, 0x80]).unwrap();
+        let result = DefaultFileReader::read(&bin_path);
+        assert!(result.is_err(), "expected Err for non-UTF-8 binary content");
+    }
+
+    #[test]
+    fn test_quiet_indexing_writes_no_status() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("a.txt"), "quiet indexing test")?;
+        let run = |quiet: bool| -> Result<String> {
+            let mut status: Vec<u8> = Vec::new();
+            let opts = IndexOptions {
+                ann: true,
+                quiet,
+                ..Default::default()
+            };
+            index_dir_with_reader::<DefaultFileReader>(
+                &docs,
+                &dir.path().join("idx.json"),
+                &opts,
+                &mut status,
+            )?;
+            Ok(String::from_utf8(status)?)
+        };
+
+        let loud = run(false)?;
+        assert!(loud.contains("Wrote index to"), "{}", loud);
+        assert!(loud.contains("Wrote ANN graph to"), "{}", loud);
+        assert_eq!(run(true)?, "");
+        assert!(progress_bar(1, true)?.is_hidden());
+        Ok(())
+    }
+
+    #[test]
+    fn test_text_diff_output_line() -> Result<()> {
+        let diff = similarity::diff_texts("apple banana", "banana cherry");
+        let mut out: Vec<u8> = Vec::new();
+        write_text_diff(&diff, &mut out)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "Unique to A: [apple]. Unique to B: [cherry]. Shared: [banana]. Similarity: 0.34\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verbose_indexing_reports_each_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        // Stop words are dropped: "kettle kettle boiled" is 3 tokens over 2 distinct terms.
+        std::fs::write(docs.join("a.txt"), "the kettle and the kettle boiled")?;
+        std::fs::write(docs.join("b.pdf"), "not really a pdf")?;
+        let mut status: Vec<u8> = Vec::new();
+        let opts = IndexOptions {
+            verbose: true,
+            quiet: true,
+            ..Default::default()
+        };
+        index_dir_with_reader::<DefaultFileReader>(
+            &docs,
+            &dir.path().join("idx.json"),
+            &opts,
+            &mut status,
+        )?;
+        let log = String::from_utf8(status)?;
+        let a = docs.join("a.txt");
+        assert!(
+            log.contains(&format!(
+                "{}: 32 bytes from disk, 3 tokens, 2 non-zero terms\n",
+                a.display()
+            )),
+            "{}",
+            log
+        );
+        let b = docs.join("b.pdf");
+        assert!(
+            log.contains(&format!(
+                "{}: 16 bytes from disk, 0 tokens, 0 non-zero terms, read failed:",
+                b.display()
+            )),
+            "{}",
+            log
+        );
+        assert!(!log.contains("Wrote index"), "{}", log);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sample_files_is_seeded_subset() {
+        let files: Vec<PathBuf> = (0..50)
+            .map(|i| PathBuf::from(format!("doc{:02}.txt", i)))
+            .collect();
+        let sample = sample_files(files.clone(), 7, 42);
+        assert_eq!(sample.len(), 7);
+        assert!(sample.iter().all(|p| files.contains(p)));
+        assert!(sample.windows(2).all(|w| w[0] < w[1]), "{:?}", sample);
+        assert_eq!(sample_files(files.clone(), 7, 42), sample);
+        assert_ne!(sample_files(files.clone(), 7, 43), sample);
+        assert_eq!(sample_files(files.clone(), 80, 42), files);
+    }
+
+    #[test]
+    fn test_index_sample_indexes_n_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        for i in 0..10 {
+            std::fs::write(
+                docs.join(format!("{}.txt", i)),
+                format!("note number {}", i),
+            )?;
+        }
+        let out = dir.path().join("idx.json");
+        let mut status: Vec<u8> = Vec::new();
+        let opts = IndexOptions {
+            sample: Some(3),
+            seed: 7,
+            ..Default::default()
+        };
+        index_dir_with_reader::<DefaultFileReader>(&docs, &out, &opts, &mut status)?;
+        assert_eq!(load_index(&out)?.docs.len(), 3);
+        assert!(String::from_utf8(status)?.starts_with("Sampled 3 of 10 files\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_keyword_length_drops_long_tokens() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        let blob: String = "QmFzZTY0IGVuY29kZWQgcGF5bG9hZA"
+            .chars()
+            .cycle()
+            .take(200)
+            .collect();
+        std::fs::write(
+            docs.join("data.json"),
+            format!("{{\"avatar\": \"{}\", \"name\": \"teapot\"}}", blob),
+        )?;
+        let out = dir.path().join("idx.json");
+        let opts = IndexOptions {
+            tokenizer: TokenizerConfig {
+                max_token_len: Some(30),
+                ..Default::default()
+            },
+            quiet: true,
+            ..Default::default()
+        };
+        index_dir_with_reader::<DefaultFileReader>(&docs, &out, &opts, &mut std::io::sink())?;
+        let idx = load_index(&out)?;
+        assert!(idx.terms.iter().all(|t| t.len() <= 30), "{:?}", idx.terms);
+        assert!(idx.terms.contains(&"teapot".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_pre_process_cleans_text_before_indexing() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(
+            docs.join("page.txt"),
+            "<div class=\"banner\">Harbour ferries</div> https://ferries.example/timetable",
+        )?;
+        let out = dir.path().join("idx.json");
+        let opts = IndexOptions {
+            preprocess: vec![
+                preprocess::PreprocessStep::StripHtml,
+                preprocess::PreprocessStep::RemoveUrls,
+                preprocess::PreprocessStep::Trim,
+            ],
+            quiet: true,
+            ..Default::default()
+        };
+        index_dir_with_reader::<DefaultFileReader>(&docs, &out, &opts, &mut std::io::sink())?;
+        let idx = load_index(&out)?;
+        assert_eq!(idx.docs[0].text, "Harbour ferries");
+        assert!(!idx.terms.iter().any(|t| t == "banner" || t == "https"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_pattern_is_stored_and_used_for_queries() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("launch.txt"), "NASA rocket launch window")?;
+        std::fs::write(docs.join("tide.txt"), "tide tables for the harbour")?;
+        let out = dir.path().join("idx.json");
+        let opts = IndexOptions {
+            tokenizer: TokenizerConfig {
+                pattern: Some(compile_token_pattern("[a-z]+")?),
+                ..Default::default()
+            },
+            quiet: true,
+            ..Default::default()
+        };
+        index_dir_with_reader::<DefaultFileReader>(&docs, &out, &opts, &mut std::io::sink())?;
+        let idx = load_index(&out)?;
+        assert_eq!(idx.token_pattern.as_deref(), Some("[a-z]+"));
+        // The uppercase-only token never matches the pattern.
+        assert!(!idx.terms.contains(&"nasa".to_string()), "{:?}", idx.terms);
+        assert!(idx.terms.contains(&"rocket".to_string()));
+
+        assert_eq!(
+            idx.tokenize_query("NASA rocket-launch"),
+            ["rocket", "launch"]
+        );
+        let results = retrieve(&idx, "rocket", &QueryOptions::default());
+        assert!(results[0].path.ends_with("launch.txt"));
+
+        let mut raw: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&out)?)?;
+        raw["token_pattern"] = "[a-z".into();
+        std::fs::write(&out, raw.to_string())?;
+        let err = load_index(&out).unwrap_err().to_string();
+        assert!(err.contains("invalid --token-pattern '[a-z'"), "{}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn test_inverted_index_format_matches_dense_results() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("rust.txt"), "rust ownership keeps memory safe")?;
+        std::fs::write(docs.join("go.txt"), "go has a garbage collector for memory")?;
+        std::fs::write(
+            docs.join("python.txt"),
+            "python has a garbage collector too",
+        )?;
+        std::fs::write(docs.join("sauce.txt"), "slow cooked tomato sauce recipe")?;
+        let build = |name: &str, layout: IndexLayout, format: IndexFormat| -> Result<Index> {
+            let out = dir.path().join(name);
+            let opts = IndexOptions {
+                layout,
+                format,
+                quiet: true,
+                ..Default::default()
+            };
+            index_dir_with_reader::<DefaultFileReader>(&docs, &out, &opts, &mut std::io::sink())?;
+            load_index(&out)
+        };
+        let dense = build("dense.json", IndexLayout::Dense, IndexFormat::Json)?;
+        let inverted = build("inverted.json", IndexLayout::Inverted, IndexFormat::Json)?;
+        let binary = build("inverted.bin", IndexLayout::Inverted, IndexFormat::Binary)?;
+
+        let raw: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.path().join("inverted.json"))?)?;
+        assert_eq!(raw["index_type"], "inverted");
+        assert_eq!(raw["vectors"], serde_json::json!([]));
+        assert_eq!(inverted.vectors.len(), 4);
+        assert!(inverted.sparse_vectors.is_empty());
+
+        let opts = QueryOptions {
+            k: 3,
+            ..Default::default()
+        };
+        for q in ["rust memory safety", "python", "garbage collector"] {
+            let expected = retrieve(&dense, q, &opts);
+            assert!(!expected.is_empty(), "{}", q);
+            for idx in [&inverted, &binary] {
+                let got = retrieve(idx, q, &opts);
+                // Separate builds may order the vocabulary differently, so scores can
+                // differ in the last bits.
+                assert_eq!(
+                    got.iter().map(|r| &r.path).collect::<Vec<_>>(),
+                    expected.iter().map(|r| &r.path).collect::<Vec<_>>()
+                );
+                for (g, e) in got.iter().zip(&expected) {
+                    assert!(
+                        (g.score - e.score).abs() < 1e-5,
+                        "{} vs {}",
+                        g.score,
+                        e.score
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_index_format_switches_on_size() {
+        assert_eq!(IndexLayout::Auto.resolve(1_000, 10_000), IndexType::Dense);
+        assert_eq!(
+            IndexLayout::Auto.resolve(1_001, 10_000),
+            IndexType::Inverted
+        );
+        assert_eq!(
+            IndexLayout::Dense.resolve(1_000_000, 1_000_000),
+            IndexType::Dense
+        );
+    }
+
+    #[test]
+    fn test_alias_file_rewrites_vocabulary_and_queries() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("ml.txt"), "ml models need training data")?;
+        std::fs::write(docs.join("tide.txt"), "tide tables for the harbour")?;
+        let aliases = dir.path().join("aliases.toml");
+        std::fs::write(&aliases, "ml = \"machine_learning\"\n")?;
+        let out = dir.path().join("idx.json");
+        let opts = IndexOptions {
+            term_aliases: termfilter::AliasFilter::load(&aliases)?.aliases,
+            quiet: true,
+            ..Default::default()
+        };
+        index_dir_with_reader::<DefaultFileReader>(&docs, &out, &opts, &mut std::io::sink())?;
+
+        let idx = load_index(&out)?;
+        assert!(idx.terms.contains(&"machine_learning".to_string()));
+        assert!(!idx.terms.contains(&"ml".to_string()), "{:?}", idx.terms);
+        assert_eq!(
+            idx.tokenize_query("ML training"),
+            ["machine_learning", "training"]
+        );
+        let results = retrieve(&idx, "ml", &QueryOptions::default());
+        assert!(results[0].path.ends_with("ml.txt"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_token_pattern_is_explained() {
+        let err = compile_token_pattern("[a-z").unwrap_err().to_string();
+        assert!(err.starts_with("invalid --token-pattern '[a-z'"), "{}", err);
+        assert!(err.contains("e.g. '[a-z]+'"), "{}", err);
+    }
+
+    #[test]
+    fn test_exclude_numeric_drops_digit_tokens() -> Result<()> {
+        let cfg = TokenizerConfig {
+            exclude_numeric: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            tokenize_with_config("meeting on 2024-01-15 at 10am", &cfg),
+            ["meeting", "10am"]
+        );
+
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("a.txt"), "meeting on 2024-01-15 at 10am")?;
+        std::fs::write(docs.join("b.txt"), "budget of 100 for 2024")?;
+        let out = dir.path().join("idx.json");
+        let opts = IndexOptions {
+            tokenizer: cfg,
+            quiet: true,
+            ..Default::default()
+        };
+        index_dir_with_reader::<DefaultFileReader>(&docs, &out, &opts, &mut std::io::sink())?;
+        let idx = load_index(&out)?;
+        assert!(
+            idx.terms
+                .iter()
+                .all(|t| !t.bytes().all(|b| b.is_ascii_digit())),
+            "{:?}",
+            idx.terms
+        );
+        // A purely numeric query shares no terms with the index, so nothing scores.
+        assert!(retrieve(&idx, "2024 100", &QueryOptions::default()).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_title_from_first_line_names_markdown_docs() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(
+            docs.join("guide.md"),
+            "\n# My Document Title\n\nSome body text.\n",
+        )?;
+        std::fs::write(docs.join("plain.md"), "No heading here.\n# Later heading\n")?;
+        std::fs::write(docs.join("notes.txt"), "# Not markdown\n")?;
+        let out = dir.path().join("idx.json");
+        let opts = IndexOptions {
+            title_from_first_line: true,
+            quiet: true,
+            ..Default::default()
+        };
+        index_dir_with_reader::<DefaultFileReader>(&docs, &out, &opts, &mut std::io::sink())?;
+        let mut ids: Vec<String> = load_index(&out)?.docs.into_iter().map(|d| d.id).collect();
+        ids.sort();
+        assert_eq!(ids, ["My Document Title", "doc-notes.txt", "doc-plain.md"]);
+        assert_eq!(markdown_title("  ### Deep  "), Some("Deep".to_string()));
+        assert_eq!(markdown_title("#"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deduplicate_near_keeps_first_of_near_duplicates() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        let text = "quarterly report covering revenue growth, hiring plans, office moves, \
+                    supplier contracts, travel budgets and the product roadmap";
+        std::fs::write(docs.join("a.txt"), text)?;
+        std::fs::write(docs.join("b.txt"), text.replace("hiring", "recruiting"))?;
+        std::fs::write(docs.join("c.txt"), "sourdough starter feeding schedule")?;
+        let out = dir.path().join("idx.json");
+        let mut status: Vec<u8> = Vec::new();
+        let opts = IndexOptions {
+            deduplicate_near: Some(0.9),
+            ..Default::default()
+        };
+        index_dir_with_reader::<DefaultFileReader>(&docs, &out, &opts, &mut status)?;
+
+        let idx = load_index(&out)?;
+        let mut paths: Vec<&str> = idx.docs.iter().map(|d| d.path.as_str()).collect();
+        paths.sort();
+        assert_eq!(paths.len(), 2);
+        assert!(paths[0].ends_with("a.txt") && paths[1].ends_with("c.txt"));
+        assert!(!idx.terms.contains(&"recruiting".to_string()));
+        let log = String::from_utf8(status)?;
+        assert!(
+            log.contains(&format!(
+                "Skipping {}: near-duplicate of {}",
+                docs.join("b.txt").display(),
+                docs.join("a.txt").display()
+            )),
+            "{}",
+            log
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_assign_topics_labels_every_document() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("a.txt"), "rust borrow checker and lifetimes")?;
+        std::fs::write(docs.join("b.txt"), "sourdough starter and rye flour")?;
+        std::fs::write(docs.join("c.txt"), "tide tables for the harbour")?;
+        let out = dir.path().join("idx.json");
+        let opts = IndexOptions {
+            assign_topics: Some(10),
+            quiet: true,
+            ..Default::default()
+        };
+        index_dir_with_reader::<DefaultFileReader>(&docs, &out, &opts, &mut std::io::sink())?;
+
+        let idx = load_index(&out)?;
+        assert_eq!(idx.docs.len(), 3);
+        assert!(
+            idx.docs.iter().all(|d| !d.topics.is_empty()),
+            "{:?}",
+            idx.docs
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_field_boost_weights_frontmatter_title_above_body() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(
+            docs.join("title.md"),
+            "---\ntitle: zephyr\n---\nwind speed charts logged daily\n",
+        )?;
+        std::fs::write(
+            docs.join("body.md"),
+            "---\ntitle: daily log\n---\nzephyr wind speed charts\n",
+        )?;
+        let out = dir.path().join("idx.json");
+        let opts = IndexOptions {
+            field_boosts: vec!["title:3.0".parse()?],
+            quiet: true,
+            ..Default::default()
+        };
+        index_dir_with_reader::<DefaultFileReader>(&docs, &out, &opts, &mut std::io::sink())?;
+
+        let idx = load_index(&out)?;
+        let t = idx.terms.iter().position(|t| t == "zephyr").unwrap();
+        let weight = |name: &str| {
+            let di = idx
+                .docs
+                .iter()
+                .position(|d| d.path.ends_with(name))
+                .unwrap();
+            idx.vectors[di][t]
+        };
+        assert!(weight("title.md") > weight("body.md"));
+        let titled = idx
+            .docs
+            .iter()
+            .find(|d| d.path.ends_with("title.md"))
+            .unwrap();
+        assert_eq!(titled.metadata["title"], "zephyr");
+        assert!(!titled.text.contains("title:"), "{}", titled.text);
+        let results = retrieve(&idx, "zephyr", &QueryOptions::default());
+        assert!(results[0].path.ends_with("title.md"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_dir_with_progress_reports_every_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        for name in ["a.txt", "b.md", "c.txt"] {
+            std::fs::write(docs.join(name), format!("notes about {}", name))?;
+        }
+        let out = dir.path().join("idx.json");
+        let events = Mutex::new(Vec::new());
+        index_dir_with_progress(&docs, &out, |p| events.lock().unwrap().push(p))?;
+
+        let events = events.into_inner().unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].current, 1);
+        assert_eq!(events[2].current, events[2].total);
+        assert!(events
+            .iter()
+            .all(|e| e.total == 3 && e.current_file.is_file()));
+        assert_eq!(load_index(&out)?.docs.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deduplicate_near_mask() {
+        let vectors = vec![
+            vec![1.0, 0.0],
+            vec![0.0, 1.0],
+            vec![0.99, 0.01],
+            vec![0.0, 0.0],
+        ];
+        assert_eq!(
+            deduplicate_near(&vectors, 0.98),
+            [None, None, Some(0), None]
+        );
+    }
+
+    #[test]
+    fn test_min_keyword_freq_counts_across_corpus() {
+        let mut toks = vec![
+            tokenize("kettle teapot kettle"),
+            tokenize("teapot saucer"),
+            tokenize("kettle"),
+        ];
+        drop_rare_tokens(&mut toks, 2);
+        assert_eq!(
+            toks,
+            vec![
+                vec!["kettle", "teapot", "kettle"],
+                vec!["teapot"],
+                vec!["kettle"]
+            ]
+        );
+    }
+
+    #[test]
+    fn test_index_dir_with_custom_reader() -> Result<()> {
+        struct StubReader;
+        impl FileReader for StubReader {
+            fn read(path: &Path) -> Result<String> {
+                Ok(format!(
+                    "stub text for {}",
+                    path.file_stem().unwrap().to_string_lossy()
+                ))
+            }
+        }
+
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("alpha.txt"), "ignored")?;
+        let out = dir.path().join("idx.json");
+        index_dir_with_reader::<StubReader>(
+            dir.path(),
+            &out,
+            &IndexOptions::default(),
+            &mut std::io::sink(),
+        )?;
+
+        let idx = load_index(&out)?;
+        assert_eq!(idx.docs.len(), 1);
+        assert_eq!(idx.docs[0].text, "stub text for alpha");
+        assert!(idx.terms.contains(&"stub".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stop_on_error_writes_no_index() -> Result<()> {
+        struct FailingReader;
+        impl FileReader for FailingReader {
+            fn read(path: &Path) -> Result<String> {
+                if path.ends_with("broken.txt") {
+                    return Err(anyhow!("corrupt file"));
+                }
+                Ok(std::fs::read_to_string(path)?)
+            }
+        }
+
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("fine.txt"), "readable notes")?;
+        std::fs::write(docs.join("broken.txt"), "unreadable notes")?;
+        let out = dir.path().join("idx.json");
+
+        let opts = IndexOptions {
+            stop_on_error: true,
+            ..Default::default()
+        };
+        let err = index_dir_with_reader::<FailingReader>(&docs, &out, &opts, &mut std::io::sink())
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("broken.txt"), "{:#}", err);
+        assert!(!out.exists());
+
+        // Without the flag the broken file is indexed as an empty document.
+        index_dir_with_reader::<FailingReader>(
+            &docs,
+            &out,
+            &IndexOptions::default(),
+            &mut std::io::sink(),
+        )?;
+        assert_eq!(load_index(&out)?.docs.len(), 2);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stop_on_error_with_unreadable_file() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("fine.txt"), "readable notes")?;
+        let locked = docs.join("locked.txt");
+        std::fs::write(&locked, "secret notes")?;
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000))?;
+        if std::fs::read(&locked).is_ok() {
+            // Running as root: permissions are not enforced.
+            return Ok(());
+        }
+        let out = dir.path().join("idx.json");
+        let opts = IndexOptions {
+            stop_on_error: true,
+            ..Default::default()
+        };
+        assert!(index_dir_with_options(&docs, &out, &opts).is_err());
+        assert!(!out.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_dir_nested_structure() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let subdir = dir.path().join("nested");
+        std::fs::create_dir(&subdir)?;
+
+        let doc1 = dir.path().join("root.txt");
+        let doc2 = subdir.join("nested.txt");
+        std::fs::write(&doc1, "root level document")?;
+        std::fs::write(&doc2, "nested directory file")?;
+
+        let out_path = dir.path().join("nested_index.json");
+        index_dir(dir.path(), &out_path, IndexFormat::Json)?;
+
+        let f = File::open(&out_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+        assert_eq!(idx.docs.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tokenize_punctuation() {
+        let text = "Hello, world! How are you? I'm fine.";
+        let tokens = tokenize(text);
+        assert!(!tokens.contains(&",".to_string()));
+        assert!(!tokens.contains(&"!".to_string()));
+        assert!(!tokens.contains(&"?".to_string()));
+        assert!(tokens.contains(&"hello".to_string()));
+        assert!(tokens.contains(&"world".to_string()));
+    }
+
+    #[test]
+    fn test_dot_product_zero_vectors() {
+        let v1 = vec![0.0, 0.0, 0.0];
+        let v2 = vec![1.0, 2.0, 3.0];
+        let sim = dot_product(&v1, &v2);
+        assert_eq!(sim, 0.0);
+    }
+
+    #[test]
+    fn test_index_preserves_path_info() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let out_path = dir.path().join("path_index.json");
+
+        let doc_path = dir.path().join("test_document.txt");
+        std::fs::write(&doc_path, "content")?;
+
+        index_dir(dir.path(), &out_path, IndexFormat::Json)?;
+
+        let f = File::open(&out_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+        assert_eq!(idx.docs.len(), 1);
+        assert!(idx.docs[0].path.contains("test_document.txt"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tokenize_unicode() {
+        let text = "café résumé naïve";
+        let tokens = tokenize(text);
+        assert!(tokens.len() >= 3);
+        assert!(tokens.contains(&"café".to_string()) || tokens.contains(&"caf".to_string()));
+    }
+
+    #[test]
+    fn test_read_file_content_pdf() {
+        let dir = tempfile::tempdir().unwrap();
+        let pdf_path = dir.path().join("test.pdf");
+        // Write bytes that look like a PDF header but are not a complete valid PDF
+        std::fs::write(&pdf_path, b"%PDF-1.4-fake-truncated-content").unwrap();
+        // DefaultFileReader must dispatch to pdf-extract for .pdf extension
+        let result = DefaultFileReader::read(&pdf_path);
+        // pdf-extract must fail on a truncated/invalid PDF without panicking
+        assert!(
+            result.is_err(),
+            "expected Err when pdf-extract receives invalid PDF bytes"
+        );
+        let err_msg = format!("{}", result.unwrap_err());
+        assert!(
+            err_msg.contains("PDF extraction failed"),
+            "error should say 'PDF extraction failed', got: {err_msg}"
+        );
+    }
+
+    #[test]
+    fn test_index_with_various_extensions() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let out_path = dir.path().join("index.json");
+
+        // Create files with different extensions
+        std::fs::write(dir.path().join("doc.txt"), "text file")?;
+        std::fs::write(dir.path().join("data.csv"), "csv,data")?;
+        std::fs::write(dir.path().join("config.json"), r#"{"key": "value"}"#)?;
+        std::fs::write(dir.path().join("readme.md"), "# Markdown")?;
+        std::fs::write(dir.path().join("image.jpg"), [0xFF, 0xD8])?; // Not indexed
+
+        index_dir(dir.path(), &out_path, IndexFormat::Json)?;
+
+        let f = File::open(&out_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+        // Should index txt, csv, json, md but not jpg
+        assert!(idx.docs.len() >= 4 && idx.docs.len() <= 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tokenize_contractions() {
+        let text = "don't can't won't I'll you're";
+        let tokens = tokenize(text);
+        assert!(tokens.contains(&"don".to_string()) || tokens.contains(&"don't".to_string()));
+        assert!(tokens.len() >= 5);
+    }
+
+    #[test]
+    fn test_dot_product_negative_values() {
+        let v1 = vec![-1.0, 2.0, -3.0];
+        let v2 = vec![1.0, -2.0, 3.0];
+        let sim = dot_product(&v1, &v2);
+        assert!(sim < 0.0); // Opposite directions
+    }
+
+    #[test]
+    fn test_index_empty_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let out_path = dir.path().join("index.json");
+
+        let empty_file = dir.path().join("empty.txt");
+        std::fs::write(&empty_file, "")?;
+
+        index_dir(dir.path(), &out_path, IndexFormat::Json)?;
+
+        let f = File::open(&out_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+        assert_eq!(idx.docs.len(), 1);
+        assert!(idx.docs[0].text.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_general_query_detection() {
+        let text = "summarize all documents";
+        assert!(text.to_lowercase().contains("summarize"));
+        assert!(text.to_lowercase().contains("all"));
+
+        let text2 = "list everything";
+        assert!(text2.to_lowercase().contains("list"));
+    }
+
+    #[test]
+    fn test_index_large_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let out_path = dir.path().join("index.json");
+
+        // Create a file with repetitive content
+        let large_content = "word ".repeat(1000);
+        std::fs::write(dir.path().join("large.txt"), large_content)?;
+
+        index_dir(dir.path(), &out_path, IndexFormat::Json)?;
+
+        let f = File::open(&out_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+        assert_eq!(idx.docs.len(), 1);
+        assert!(!idx.terms.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_vector_operations_precision() {
+        let v1 = vec![1.0, 0.0, 0.0];
+        let v2 = vec![0.0, 1.0, 0.0];
+        let sim = dot_product(&v1, &v2);
+        assert!((sim - 0.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_tokenize_numbers_and_words() {
+        let text = "buy 10 apples for $5.99";
+        let tokens = tokenize(text);
+        assert!(tokens.contains(&"buy".to_string()));
+        assert!(tokens.contains(&"10".to_string()));
+        assert!(tokens.contains(&"apples".to_string()));
+        assert!(tokens.contains(&"5".to_string()));
+    }
+
+    #[test]
+    fn test_index_special_filenames() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let out_path = dir.path().join("index.json");
+
+        // Create files with special characters in names
+        std::fs::write(dir.path().join("file-with-dashes.txt"), "content1")?;
+        std::fs::write(dir.path().join("file_with_underscores.txt"), "content2")?;
+        std::fs::write(dir.path().join("file with spaces.txt"), "content3")?;
+
+        index_dir(dir.path(), &out_path, IndexFormat::Json)?;
+
+        let f = File::open(&out_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+        assert_eq!(idx.docs.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_text_file_utf8() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file_path = dir.path().join("utf8.txt");
+
+        let content = "Hello 世界 🌍";
+        std::fs::write(&file_path, content)?;
+
+        let result = DefaultFileReader::read(&file_path)?;
+        assert!(result.contains("Hello"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bm25_retrieval_ranks_rare_term_document_first() -> Result<()> {
+        // Validates that BM25-IDF weighting causes a document containing a rare term to rank
+        // above documents containing only common terms when queried by that rare term.
+        let temp_dir = TempDir::new()?;
+
+        // Three docs: only doc_a contains "photosynthesis" (rare term)
+        let mut f = File::create(temp_dir.path().join("a.txt"))?;
+        writeln!(
+            f,
+            "plants convert sunlight through photosynthesis to produce energy"
+        )?;
+        let mut f = File::create(temp_dir.path().join("b.txt"))?;
+        writeln!(
+            f,
+            "the sun shines bright over the green meadow every morning"
+        )?;
+        let mut f = File::create(temp_dir.path().join("c.txt"))?;
+        writeln!(
+            f,
+            "animals and plants share the same habitat near the river"
+        )?;
+
+        let index_path = temp_dir.path().join("idx.json");
+        index_dir(temp_dir.path(), &index_path, IndexFormat::Json)?;
+
+        let f = File::open(&index_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+
+        // Build a BM25 query vector for "photosynthesis": weight by IDF, no L2 normalisation
+        let term_map: HashMap<&String, usize> =
+            idx.terms.iter().enumerate().map(|(i, t)| (t, i)).collect();
+        let mut q_vec = vec![0.0f32; idx.terms.len()];
+        let query_term = "photosynthesis".to_string();
+        if let Some(&i) = term_map.get(&query_term) {
+            q_vec[i] = idx.idf[i];
+        }
+
+        let scores: Vec<(usize, f32)> = idx
+            .vectors
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (i, dot_product(&q_vec, v)))
+            .collect();
+        let best = scores
+            .iter()
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+            .unwrap();
+
+        // The document containing "photosynthesis" must score highest
+        assert!(
+            best.1 > 0.0,
+            "query score should be positive for a matching document"
+        );
+        let best_path = &idx.docs[best.0].path;
+        assert!(
+            best_path.contains("a.txt"),
+            "doc a.txt (photosynthesis) should rank first, got: {}",
+            best_path
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bm25_idf_formula() -> Result<()> {
+        // Verify that `index_dir` computes Robertson-Sparck Jones BM25-IDF correctly.
+        // With N=3 docs and df=1, expected IDF = ln((3-1+0.5)/(1+0.5) + 1) ≈ 0.9808.
+        let temp_dir = TempDir::new()?;
+
+        // "photon" appears only in doc_a — df = 1, N = 3.
+        std::fs::write(temp_dir.path().join("a.txt"), "photon quantum entanglement")?;
+        std::fs::write(
+            temp_dir.path().join("b.txt"),
+            "classical mechanics velocity",
+        )?;
+        std::fs::write(temp_dir.path().join("c.txt"), "thermodynamics entropy heat")?;
+
+        let index_path = temp_dir.path().join("idx.json");
+        index_dir(temp_dir.path(), &index_path, IndexFormat::Json)?;
+
+        let f = File::open(&index_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+
+        let term_map: HashMap<&String, usize> =
+            idx.terms.iter().enumerate().map(|(i, t)| (t, i)).collect();
+
+        let photon_idx = term_map.get(&"photon".to_string()).copied();
+        assert!(photon_idx.is_some(), "'photon' must be in vocabulary");
+        let i = photon_idx.unwrap();
+
+        // N=3, df=1 → IDF = ln((3-1+0.5)/(1+0.5)+1) = ln(2.5/1.5+1) = ln(2.6̄) ≈ 0.9808
+        let expected = ((3.0_f32 - 1.0 + 0.5) / (1.0 + 0.5) + 1.0).ln();
+        assert!(
+            (idx.idf[i] - expected).abs() < 1e-4,
+            "BM25 IDF for photon: expected {:.6}, got {:.6}",
+            expected,
+            idx.idf[i]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bm25_shorter_doc_scores_higher_with_equal_tf() -> Result<()> {
+        // BM25 document-length normalisation: given equal TF for the query term,
+        // the shorter document must have a higher BM25 term weight.
+        let temp_dir = TempDir::new()?;
+
+        // doc_a: very short — "rust embedded minimal" (3 content tokens)
+        // doc_b: much longer — 10 content tokens, also contains "rust" once
+        std::fs::write(temp_dir.path().join("a.txt"), "rust embedded minimal")?;
+        std::fs::write(
+            temp_dir.path().join("b.txt"),
+            "rust programming systems concurrent memory cryptography \
+             benchmark performance distributed parallel",
+        )?;
+
+        let index_path = temp_dir.path().join("idx.json");
+        index_dir(temp_dir.path(), &index_path, IndexFormat::Json)?;
+
+        let f = File::open(&index_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+
+        // Identify which doc index corresponds to a.txt and b.txt
+        let doc_a_idx = idx
+            .docs
+            .iter()
+            .position(|d| d.path.contains("a.txt"))
+            .expect("a.txt not found in index");
+        let doc_b_idx = idx
+            .docs
+            .iter()
+            .position(|d| d.path.contains("b.txt"))
+            .expect("b.txt not found in index");
+
+        let term_map: HashMap<&String, usize> =
+            idx.terms.iter().enumerate().map(|(i, t)| (t, i)).collect();
+        let rust_idx = term_map
+            .get(&"rust".to_string())
+            .copied()
+            .expect("'rust' must be in vocabulary");
+
+        let weight_a = idx.vectors[doc_a_idx][rust_idx];
+        let weight_b = idx.vectors[doc_b_idx][rust_idx];
+
+        assert!(
+            weight_a > weight_b,
+            "shorter doc (a.txt, bm25_tf={:.4}) should outscore longer doc (b.txt, bm25_tf={:.4})",
+            weight_a,
+            weight_b
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inverted_index_contains_all_nonzero_term_doc_pairs() -> Result<()> {
+        // Verify that every (term, doc) pair with a nonzero BM25 weight appears in `inverted`.
+        let temp_dir = TempDir::new()?;
+        std::fs::write(temp_dir.path().join("a.txt"), "rust ownership memory")?;
+        std::fs::write(
+            temp_dir.path().join("b.txt"),
+            "python garbage collection memory",
+        )?;
+
+        let index_path = temp_dir.path().join("idx.json");
+        index_dir(temp_dir.path(), &index_path, IndexFormat::Json)?;
+
+        let f = File::open(&index_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+
+        // For every (doc, term) pair where the BM25 weight is positive, the inverted index
+        // must list that doc as a candidate for the term.
+        for (doc_idx, vec) in idx.vectors.iter().enumerate() {
+            for (term_idx, &w) in vec.iter().enumerate() {
+                if w > 0.0 {
+                    let term = &idx.terms[term_idx];
+                    let candidates = idx
+                        .inverted
+                        .get(term)
+                        .unwrap_or_else(|| panic!("term '{}' missing from inverted index", term));
+                    assert!(
+                        candidates.contains(&doc_idx),
+                        "doc {} (weight={:.4}) missing from inverted[{}]",
+                        doc_idx,
+                        w,
+                        term
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inverted_query_returns_same_results_as_linear_scan() -> Result<()> {
+        // Confirm that O(T) inverted-index scoring and naïve O(n·V) dot-product scoring
+        // produce the same top-k ranking for a concrete query.
+        let temp_dir = TempDir::new()?;
+        std::fs::write(
+            temp_dir.path().join("a.txt"),
+            "rust systems programming ownership",
+        )?;
+        std::fs::write(
+            temp_dir.path().join("b.txt"),
+            "python machine learning neural",
+        )?;
+        std::fs::write(
+            temp_dir.path().join("c.txt"),
+            "rust concurrency async await",
+        )?;
+
+        let index_path = temp_dir.path().join("idx.json");
+        index_dir(temp_dir.path(), &index_path, IndexFormat::Json)?;
+
+        let f = File::open(&index_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+
+        let query = "rust programming";
+        let q_toks = tokenize(query);
+        let term_map: HashMap<&String, usize> =
+            idx.terms.iter().enumerate().map(|(i, t)| (t, i)).collect();
+
+        // --- O(T) inverted path ---
+        let mut inv_scores: HashMap<usize, f32> = HashMap::new();
+        for t in q_toks.iter() {
+            if let Some(&ti) = term_map.get(t) {
+                if let Some(candidates) = idx.inverted.get(t.as_str()) {
+                    for &di in candidates {
+                        *inv_scores.entry(di).or_insert(0.0) += idx.idf[ti] * idx.vectors[di][ti];
+                    }
+                }
+            }
+        }
+        let mut inv_sims: Vec<(usize, f32)> = inv_scores.into_iter().collect();
+        inv_sims.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        let inv_top: Vec<usize> = inv_sims.iter().take(3).map(|(i, _)| *i).collect();
+
+        // --- Naïve O(n·V) path (reference) ---
+        let mut q_vec = vec![0.0_f32; idx.terms.len()];
+        for t in q_toks.iter() {
+            if let Some(&i) = term_map.get(t) {
+                q_vec[i] = idx.idf[i];
+            }
+        }
+        let mut lin_sims: Vec<(usize, f32)> = idx
+            .vectors
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (i, dot_product(&q_vec, v)))
+            .collect();
+        lin_sims.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        // Filter to only docs with positive score to match inverted behaviour.
+        let lin_top: Vec<usize> = lin_sims
+            .iter()
+            .filter(|(_, s)| *s > 0.0)
+            .take(3)
+            .map(|(i, _)| *i)
+            .collect();
+
+        assert_eq!(
+            inv_top, lin_top,
+            "inverted and linear-scan rankings must agree for top-k"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inverted_skips_zero_overlap_docs() -> Result<()> {
+        // Documents with no query-term overlap must never appear in the candidate set.
+        let temp_dir = TempDir::new()?;
+        // doc_a has "photon" and "quantum"; doc_b has completely different vocabulary.
+        std::fs::write(temp_dir.path().join("a.txt"), "photon quantum entanglement")?;
+        std::fs::write(
+            temp_dir.path().join("b.txt"),
+            "classical mechanics velocity",
+        )?;
+
+        let index_path = temp_dir.path().join("idx.json");
+        index_dir(temp_dir.path(), &index_path, IndexFormat::Json)?;
+
+        let f = File::open(&index_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+
+        let term_map: HashMap<&String, usize> =
+            idx.terms.iter().enumerate().map(|(i, t)| (t, i)).collect();
+
+        // Identify doc_b's index so we can assert it never appears as a candidate.
+        let doc_b_idx = idx
+            .docs
+            .iter()
+            .position(|d| d.path.contains("b.txt"))
+            .expect("b.txt not found in index");
+
+        // Query for "photon quantum" — terms only in doc_a.
+        let mut candidate_set: std::collections::HashSet<usize> = std::collections::HashSet::new();
+        for t in tokenize("photon quantum").iter() {
+            if let Some(&ti) = term_map.get(t) {
+                if let Some(candidates) = idx.inverted.get(t.as_str()) {
+                    for &di in candidates {
+                        let _ = ti; // suppress unused-variable warning
+                        candidate_set.insert(di);
+                    }
+                }
+            }
+        }
+
+        assert!(
+            !candidate_set.contains(&doc_b_idx),
+            "doc_b must never appear in the candidate set for a query it has no overlap with"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dot_product_precise_value() {
+        assert_eq!(dot_product(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]), 32.0);
+    }
+
+    #[test]
+    fn test_keyword_extraction_logic() {
+        // Simulate keyword extraction
+        let text = "kubernetes kubernetes docker docker docker nginx";
+        let tokens = tokenize(text);
+
+        let mut tf: HashMap<String, usize> = HashMap::new();
+        for tk in tokens {
+            if tk.len() > 2 {
+                *tf.entry(tk).or_insert(0) += 1;
+            }
+        }
+
+        let mut kv: Vec<(String, usize)> = tf.into_iter().collect();
+        kv.sort_by_key(|e| std::cmp::Reverse(e.1));
+
+        // docker should be first (3 occurrences)
+        assert_eq!(kv[0].0, "docker");
+        assert_eq!(kv[0].1, 3);
+
+        // kubernetes should be second (2 occurrences)
+        assert_eq!(kv[1].0, "kubernetes");
+        assert_eq!(kv[1].1, 2);
+    }
+
+    #[test]
+    fn test_build_prompt_contains_filename_and_keywords() {
+        let docs = vec![Doc {
+            id: "doc-0".to_string(),
+            path: "/docs/alpha.txt".to_string(),
+            text: "rust programming memory ownership safety".to_string(),
+            ..Default::default()
+        }];
+        let terms = vec!["programming".to_string(), "memory".to_string()];
+        let norm = (2.0f32).sqrt().recip();
+        let vectors = vec![vec![norm, norm]];
+        let idf = vec![1.0f32, 1.0f32];
+        let mut inverted: HashMap<String, Vec<usize>> = HashMap::new();
+        inverted.insert("programming".to_string(), vec![0]);
+        inverted.insert("memory".to_string(), vec![0]);
+        let idx = Index {
+            docs,
+            terms,
+            idf,
+            vectors,
+            inverted,
+            ..Default::default()
+        };
+
+        let result = build_prompt(
+            &idx,
+            "programming memory",
+            &QueryOptions {
+                k: 5,
+                ..Default::default()
+            },
+        );
+        assert!(
+            result.contains("Filename:"),
+            "prompt must contain 'Filename:'"
+        );
+        assert!(
+            result.contains("Keywords:"),
+            "prompt must contain 'Keywords:'"
+        );
+        assert!(
+            result.contains("alpha.txt"),
+            "prompt must reference the indexed document"
+        );
+    }
+
+    #[test]
+    fn test_bench_reports_positive_throughput() {
+        let result = run_bench(10, 2);
+        assert!(
+            result.is_ok(),
+            "run_bench should complete successfully: {:?}",
+            result.err()
+        );
+    }
+
+    #[test]
+    fn test_query_vector_construction() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+
+        // Create index with known terms
+        let file = temp_dir.path().join("test.txt");
+        let mut f = File::create(&file)?;
+        writeln!(f, "kubernetes docker container orchestration")?;
+
+        let index_path = temp_dir.path().join("index.json");
+        index_dir(temp_dir.path(), &index_path, IndexFormat::Json)?;
+
+        // Load index
+        let idx_file = File::open(&index_path)?;
+        let idx: Index = serde_json::from_reader(idx_file)?;
+
+        // Verify terms were extracted
+        assert!(!idx.terms.is_empty());
+        assert!(idx.terms.contains(&"kubernetes".to_string()));
+        assert!(idx.terms.contains(&"docker".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_multiple_docs_ranking() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+
+        // Create docs with different content
+        let file1 = temp_dir.path().join("k8s.txt");
+        let mut f1 = File::create(&file1)?;
+        writeln!(f1, "kubernetes cluster pod deployment")?;
+
+        let file2 = temp_dir.path().join("docker.txt");
+        let mut f2 = File::create(&file2)?;
+        writeln!(f2, "docker container image registry")?;
+
+        let index_path = temp_dir.path().join("index.json");
+        index_dir(temp_dir.path(), &index_path, IndexFormat::Json)?;
+
+        let idx_file = File::open(&index_path)?;
+        let idx: Index = serde_json::from_reader(idx_file)?;
+
+        assert_eq!(idx.docs.len(), 2);
+        assert!(idx.terms.len() >= 6); // At least unique terms from both docs
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_term_frequency_deduplication() {
+        let text = "test test test unique word";
+        let tokens = tokenize(text);
+
+        let unique_terms: HashSet<String> = tokens.iter().cloned().collect();
+        assert_eq!(unique_terms.len(), 3); // test, unique, word
+    }
+
+    #[test]
+    fn test_ollama_model_selection_env() {
+        // Test environment variable override
+        std::env::set_var("OLLAMA_MODEL", "gemma3:4b");
+        let model = std::env::var("OLLAMA_MODEL").unwrap();
+        assert_eq!(model, "gemma3:4b");
+        std::env::remove_var("OLLAMA_MODEL");
+    }
+
+    #[test]
+    fn test_query_tokenization() {
+        let query = "How does kubernetes work?";
+        let tokens = tokenize(query);
+        // "how" and "does" are stop words and are filtered; content terms are preserved
+        assert!(!tokens.contains(&"how".to_string()));
+        assert!(!tokens.contains(&"does".to_string()));
+        assert!(tokens.contains(&"kubernetes".to_string()));
+        assert!(tokens.contains(&"work".to_string()));
+    }
+
+    #[test]
+    fn test_general_query_patterns() {
+        // Test various general query patterns
+        let queries = [
+            "summarize everything",
+            "Summarize all documents",
+            "list all files",
+            "show documents",
+            "give me an overview",
+        ];
+
+        for q in queries {
+            assert!(
+                is_general_query(q),
+                "Query '{}' should be detected as general (similarity {:.2})",
+                q,
+                general_query_similarity(q)
+            );
+        }
+    }
+
+    #[test]
+    fn test_specific_query_patterns() {
+        let queries = vec![
+            "kubernetes deployment best practices",
+            "docker container networking explained",
+            "nginx configuration tutorial",
+        ];
+
+        for q in queries {
+            let lower = q.to_lowercase();
+            let tokens = tokenize(&lower);
+            assert!(tokens.len() >= 3, "Query '{}' should have 3+ tokens", q);
+            assert!(!is_general_query(q));
+        }
+    }
+
+    #[test]
+    fn test_topic_queries_with_general_keywords_are_specific() {
+        // The old keyword heuristic sent these to general mode because they contain
+        // "list", "all" or "documents".
+        let queries = [
+            "list kubernetes pods in a namespace",
+            "all docker networking options",
+            "documents describing the tax audit process",
+        ];
+        for q in queries {
+            assert!(
+                !is_general_query(q),
+                "Query '{}' should be specific (similarity {:.2})",
+                q,
+                general_query_similarity(q)
+            );
+        }
+    }
+
+    #[test]
+    fn test_general_query_threshold_is_respected() {
+        let q = "summarize kubernetes docs";
+        let sim = general_query_similarity(q);
+        assert!(sim > 0.0 && sim < 1.0);
+        assert!(is_general_query_with_threshold(q, sim - 0.01));
+        assert!(!is_general_query_with_threshold(q, sim));
+    }
+
+    mod proptest_tokenizer {
+        use super::*;
+        use proptest::prelude::*;
+
+        /// Arbitrary Unicode text mixed with ASCII words, so inputs contain both tokens and
+        /// non-ASCII letters, digits and punctuation next to them.
+        fn text() -> impl Strategy<Value = String> {
+            prop_oneof![
+                any::<String>(),
+                "[a-zA-Z0-9' .,!?\\-]{0,64}",
+                prop::collection::vec(prop_oneof!["[a-zA-Z']{1,8}", "\\PC{1,4}"], 0..16)
+                    .prop_map(|parts| parts.concat()),
+            ]
+        }
+
+        proptest! {
+            #[test]
+            fn tokens_only_contain_word_characters(s in text()) {
+                for tok in tokenize(&s) {
+                    prop_assert!(!tok.is_empty());
+                    prop_assert!(
+                        tok.chars().all(|c| c.is_ascii_alphanumeric() || c == '\''),
+                        "token {:?} from {:?}", tok, s
+                    );
+                }
+            }
+
+            #[test]
+            fn tokenize_is_idempotent(s in text()) {
+                let toks = tokenize(&s);
+                prop_assert_eq!(tokenize(&toks.join(" ")), toks);
+            }
+
+            #[test]
+            fn retokenizing_joined_tokens_is_a_superset(s in text()) {
+                let toks = tokenize(&s);
+                let again: HashSet<String> = tokenize(&toks.join(" ")).into_iter().collect();
+                prop_assert!(toks.iter().all(|t| again.contains(t)));
+            }
+
+            #[test]
+            fn whitespace_only_input_has_no_tokens(s in "\\s{0,16}") {
+                prop_assert!(tokenize(&s).is_empty());
+            }
+        }
+
+        #[test]
+        fn empty_input_has_no_tokens() {
+            assert!(tokenize("").is_empty());
+        }
+    }
+
+    mod general_query_classification {
+        use super::*;
+
+        /// Labeled queries: `true` when the query asks about the corpus as a whole.
+        const CASES: &[(&str, bool)] = &[
+            // Corpus-wide requests, including paraphrases of the reference phrasings.
+            ("summarize everything", true),
+            ("Summarize all documents", true),
+            ("please summarize all the documents", true),
+            ("summarize the corpus", true),
+            ("give me an overview of everything", true),
+            ("overview of all documents", true),
+            ("list all files", true),
+            ("can you list all the documents?", true),
+            ("show me the documents", true),
+            ("what are these documents about?", true),
+            ("what is in these files", true),
+            ("describe each document", true),
+            ("SUMMARY OF ALL FILES", true),
+            // Short queries naming a specific entity or topic.
+            ("kubernetes", false),
+            ("Acme Corporation", false),
+            ("John Smith", false),
+            ("invoice 2023", false),
+            // Long topical queries that happen to contain "all", "list" or "documents".
+            (
+                "list all kubernetes pods running in the default namespace",
+                false,
+            ),
+            (
+                "all docker networking options for bridge and overlay drivers",
+                false,
+            ),
+            (
+                "documents describing the tax audit process for small businesses",
+                false,
+            ),
+            ("which contract mentions the delivery schedule", false),
+            ("nginx reverse proxy configuration tutorial", false),
+            // No content terms at all.
+            ("", false),
+            ("the of and", false),
+        ];
+
+        #[test]
+        fn labeled_cases() {
+            let failures: Vec<String> = CASES
+                .iter()
+                .filter(|(q, expected)| is_general_query(q) != *expected)
+                .map(|(q, expected)| {
+                    format!(
+                        "{:?}: expected general={} (similarity {:.2})",
+                        q,
+                        expected,
+                        general_query_similarity(q)
+                    )
+                })
+                .collect();
+            assert!(
+                failures.is_empty(),
+                "misclassified:\n{}",
+                failures.join("\n")
+            );
+        }
+    }
+
+    #[test]
+    fn test_query_vector_normalization() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let index_path = temp_dir.path().join("index.json");
+
+        // Create index with known terms
+        let idx = Index {
+            terms: vec![
+                "kubernetes".to_string(),
+                "docker".to_string(),
+                "container".to_string(),
+            ],
+            idf: vec![1.0, 1.0, 1.0],
+            docs: vec![Doc {
+                id: "1".to_string(),
+                path: "test.txt".to_string(),
+                text: "kubernetes and docker".to_string(),
+                ..Default::default()
+            }],
+            vectors: vec![vec![
+                std::f32::consts::FRAC_1_SQRT_2,
+                std::f32::consts::FRAC_1_SQRT_2,
+                0.0,
+            ]],
+            inverted: HashMap::new(),
+            ..Default::default()
+        };
+
+        let f = File::create(&index_path)?;
+        serde_json::to_writer(f, &idx)?;
+
+        // Load and verify
+        let idx_file = File::open(&index_path)?;
+        let loaded: Index = serde_json::from_reader(idx_file)?;
+        assert_eq!(loaded.terms.len(), 3);
+        assert_eq!(loaded.vectors[0].len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_keywords_skip_stop_words_even_when_most_frequent() {
+        let text = "the cat and the dog and the bird and the cat, but the cat";
+        assert_eq!(top_keywords(text, 3), ["cat", "bird", "dog"]);
+
+        let doc = Doc {
+            path: "pets.txt".to_string(),
+            text: text.to_string(),
+            ..Default::default()
+        };
+        let context = build_context(&[&doc], ContextMode::Keywords, usize::MAX, None);
+        assert!(
+            context.contains("Keywords: cat, bird, dog\n"),
+            "{}",
+            context
+        );
+    }
+
+    #[test]
+    fn test_keywords_skip_stop_words_of_the_index_language() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("marche.txt"),
+            "les pommes et les poires dans les paniers des marchands, les pommes",
+        )?;
+        let index_path = dir.path().join("idx.json");
+        let opts = IndexOptions {
+            tokenizer: TokenizerConfig {
+                lang: Some("fr".to_string()),
+                ..Default::default()
+            },
+            quiet: true,
+            ..Default::default()
+        };
+        index_dir_with_options(dir.path(), &index_path, &opts)?;
+        let idx = load_index(&index_path)?;
+
+        let results = retrieve(&idx, "pommes", &QueryOptions::default());
+        assert_eq!(results[0].keywords[0], "pommes");
+        assert!(!results[0]
+            .keywords
+            .iter()
+            .any(|k| k == "les" || k == "des" || k == "dans"));
+        let context = build_context(
+            &[&idx.docs[0]],
+            ContextMode::Keywords,
+            usize::MAX,
+            idx.lang.as_deref(),
+        );
+        assert!(!context.contains("les"), "{}", context);
+        Ok(())
+    }
+
+    #[test]
+    fn test_document_keyword_extraction() {
+        let text = "kubernetes kubernetes docker nginx nginx nginx";
+        let tokens = tokenize(text);
+
+        let mut tf: HashMap<String, usize> = HashMap::new();
+        for token in tokens {
+            if token.len() > 2 {
+                *tf.entry(token).or_insert(0) += 1;
+            }
+        }
+
+        let mut kv: Vec<(String, usize)> = tf.into_iter().collect();
+        kv.sort_by_key(|e| std::cmp::Reverse(e.1));
+
+        // Top keywords by frequency
+        assert_eq!(kv[0].0, "nginx"); // 3 occurrences
+        assert_eq!(kv[1].0, "kubernetes"); // 2 occurrences
+        assert_eq!(kv[2].0, "docker"); // 1 occurrence
+    }
+
+    #[test]
+    fn test_context_string_building() {
+        let keywords = ["kubernetes", "docker", "nginx", "container"];
+        let context = keywords.join(", ");
+
+        assert!(context.contains("kubernetes"));
+        assert!(context.contains("docker"));
+        assert!(context.contains("nginx"));
+        assert_eq!(context, "kubernetes, docker, nginx, container");
+    }
+
+    #[test]
+    fn test_filename_extraction_from_path() {
+        let paths = vec![
+            "/path/to/document.txt",
+            "/home/user/file.md",
+            "relative/path/data.csv",
+        ];
+
+        for path_str in paths {
+            let path = std::path::Path::new(path_str);
+            let fname = path
+                .file_name()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| path_str.to_string());
+
+            assert!(!fname.is_empty());
+            assert!(!fname.contains('/'));
+        }
+    }
+
+    #[test]
+    fn test_prompt_formatting_for_general_queries() {
+        let context = "Filename: test.txt\nKeywords: kubernetes, docker\n---\n";
+        let prompt = format!(
+            "Documents:\n{}\nEnd of documents.\n\nProvide the summaries now.",
+            context
+        );
+
+        assert!(prompt.contains("Documents:"));
+        assert!(prompt.contains("test.txt"));
+        assert!(prompt.contains("kubernetes"));
+        assert!(prompt.contains("End of documents"));
+    }
+
+    #[test]
+    fn test_prompt_formatting_for_specific_queries() {
+        let context = "Document content here";
+        let query = "How does kubernetes work?";
+        let prompt = format!(
+            "Use the following documents as context:\n{}\nQuestion: {}",
+            context, query
+        );
+
+        assert!(prompt.contains("Use the following documents as context"));
+        assert!(prompt.contains(query));
+        assert!(prompt.contains(context));
+    }
+
+    #[test]
+    fn test_ollama_command_construction() {
+        let model = "mistral";
+        let prompt = "test prompt";
+
+        // Simulate command construction
+        let args = ["run", model, prompt];
+        assert_eq!(args[0], "run");
+        assert_eq!(args[1], "mistral");
+        assert_eq!(args[2], "test prompt");
+    }
+
+    #[test]
+    fn test_fallback_summary_generation() {
+        // Test keyword-based summary generation
+        let keywords = ["kubernetes", "deployment", "scaling"];
+        let summary = format!("This document discusses: {}.", keywords.join(", "));
+
+        assert_eq!(
+            summary,
+            "This document discusses: kubernetes, deployment, scaling."
+        );
+    }
+
+    #[test]
+    fn test_empty_keywords_handling() {
+        let keywords: Vec<String> = vec![];
+        let kw_str = if keywords.is_empty() {
+            String::from("(no keywords)")
+        } else {
+            keywords.join(", ")
+        };
+
+        assert_eq!(kw_str, "(no keywords)");
+    }
+
+    #[test]
+    fn test_top_k_selection() {
+        let k = 3;
+        let mut scores = vec![(0, 0.9), (1, 0.7), (2, 0.8), (3, 0.6), (4, 0.95)];
+
+        scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        let top_k: Vec<usize> = scores.into_iter().take(k).map(|(i, _)| i).collect();
+
+        assert_eq!(top_k.len(), 3);
+        assert_eq!(top_k[0], 4); // 0.95
+        assert_eq!(top_k[1], 0); // 0.9
+        assert_eq!(top_k[2], 2); // 0.8
+    }
+
+    #[test]
+    fn test_query_with_empty_index() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let index_path = temp_dir.path().join("empty_index.json");
+
+        let idx = Index {
+            terms: vec![],
+            idf: vec![],
+            docs: vec![],
+            vectors: vec![],
+            inverted: HashMap::new(),
+            ..Default::default()
+        };
+
+        let f = File::create(&index_path)?;
+        serde_json::to_writer(f, &idx)?;
+
+        // Verify empty index can be loaded
+        let idx_file = File::open(&index_path)?;
+        let loaded: Index = serde_json::from_reader(idx_file)?;
+        assert!(loaded.terms.is_empty());
+        assert!(loaded.docs.is_empty());
+        assert!(loaded.vectors.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_similarity_ranking() {
+        let mut sims = [(0, 0.5), (1, 0.9), (2, 0.3), (3, 0.7)];
+
+        sims.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        assert_eq!(sims[0].0, 1); // Highest similarity first
+        assert_eq!(sims[1].0, 3);
+        assert_eq!(sims[2].0, 0);
+        assert_eq!(sims[3].0, 2); // Lowest similarity last
+    }
+
+    #[test]
+    fn test_model_size_comparison() {
+        let size_gb = 3.3 * 1024.0 * 1024.0 * 1024.0;
+        let size_mb = 700.0 * 1024.0 * 1024.0;
+        let size_kb = 500.0 * 1024.0;
+
+        assert!(size_kb < size_mb);
+        assert!(size_mb < size_gb);
+    }
+
+    #[test]
+    fn test_index_with_pdf_extension() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let pdf_path = dir.path().join("test.pdf");
+
+        // Create a fake PDF file
+        std::fs::write(&pdf_path, b"%PDF-1.4 fake content")?;
+
+        // PDF files are in allowed_exts list
+        let allowed_exts = ["txt", "md", "csv", "json", "pdf"];
+        let ext = pdf_path.extension().and_then(|s| s.to_str()).unwrap_or("");
+        assert!(allowed_exts.contains(&ext));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tf_idf_log_calculation() {
+        let count = 5.0_f32;
+        let tfv = 1.0 + count.log2();
+
+        assert!(tfv > 1.0);
+        assert!((tfv - std::f32::consts::LOG2_10).abs() < 0.001); // log2(5) + 1 ≈ 3.32
+    }
+
+    #[test]
+    fn test_vector_dot_product() {
+        let v1 = [0.6, 0.8];
+        let v2 = [0.8, 0.6];
+
+        let dot = v1.iter().zip(v2.iter()).map(|(a, b)| a * b).sum::<f32>();
+        assert!((dot - 0.96).abs() < 0.001); // 0.6*0.8 + 0.8*0.6 = 0.96
+    }
+
+    #[test]
+    fn test_term_document_frequency_map() {
+        let mut df: HashMap<String, usize> = HashMap::new();
+        let docs = vec![
+            vec!["docker", "kubernetes"],
+            vec!["docker", "container"],
+            vec!["kubernetes", "pod"],
+        ];
+
+        for doc in &docs {
+            let mut seen: HashSet<String> = HashSet::new();
+            for &term in doc {
+                if seen.insert(term.to_string()) {
+                    *df.entry(term.to_string()).or_insert(0) += 1;
+                }
+            }
+        }
+
+        assert_eq!(df.get("docker"), Some(&2)); // In 2 docs
+        assert_eq!(df.get("kubernetes"), Some(&2)); // In 2 docs
+        assert_eq!(df.get("container"), Some(&1)); // In 1 doc
+        assert_eq!(df.get("pod"), Some(&1)); // In 1 doc
+    }
+
+    #[test]
+    fn test_sorting_terms_by_frequency() {
+        let df: HashMap<String, usize> = [
+            ("docker".to_string(), 5),
+            ("kubernetes".to_string(), 3),
+            ("nginx".to_string(), 8),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let mut v: Vec<(String, usize)> = df.into_iter().collect();
+        v.sort_by_key(|e| std::cmp::Reverse(e.1));
+
+        assert_eq!(v[0].0, "nginx"); // 8
+        assert_eq!(v[1].0, "docker"); // 5
+        assert_eq!(v[2].0, "kubernetes"); // 3
+    }
+
+    #[test]
+    fn test_term_index_mapping() {
+        let terms = ["alpha".to_string(), "beta".to_string(), "gamma".to_string()];
+        let term_index: HashMap<&String, usize> =
+            terms.iter().enumerate().map(|(i, t)| (t, i)).collect();
+
+        assert_eq!(term_index.get(&"alpha".to_string()), Some(&0));
+        assert_eq!(term_index.get(&"beta".to_string()), Some(&1));
+        assert_eq!(term_index.get(&"gamma".to_string()), Some(&2));
+    }
+
+    #[test]
+    fn test_vector_initialization() {
+        let size = 10;
+        let vec: Vec<f32> = vec![0.0; size];
+
+        assert_eq!(vec.len(), size);
+        assert!(vec.iter().all(|&x| x == 0.0));
+    }
+
+    #[test]
+    fn test_norm_calculation() {
+        let vec = [3.0, 4.0];
+        let norm = vec.iter().map(|x| x * x).sum::<f32>().sqrt();
+        assert_eq!(norm, 5.0);
+
+        let norm_with_floor = norm.max(1e-9);
+        assert_eq!(norm_with_floor, 5.0);
+    }
+
+    #[test]
+    fn test_vector_normalization_division() {
+        let mut vec = [3.0_f32, 4.0_f32];
+        let norm = 5.0_f32;
+
+        for x in vec.iter_mut() {
+            *x /= norm;
+        }
+
+        assert!((vec[0] - 0.6_f32).abs() < 0.001);
+        assert!((vec[1] - 0.8_f32).abs() < 0.001);
+    }
+
+    /// `root/docs/a.txt` plus `root/docs/loop -> root` (a cycle) and two links to an
+    /// outside directory holding `b.txt`.
+    #[cfg(unix)]
+    fn symlink_fixture() -> Result<(TempDir, TempDir)> {
+        use std::os::unix::fs::symlink;
+        let root = TempDir::new()?;
+        let outside = TempDir::new()?;
+        std::fs::create_dir(root.path().join("docs"))?;
+        std::fs::write(root.path().join("docs/a.txt"), "alpha")?;
+        std::fs::write(outside.path().join("b.txt"), "beta")?;
+        symlink(root.path(), root.path().join("docs/loop"))?;
+        symlink(outside.path(), root.path().join("link1"))?;
+        symlink(outside.path(), root.path().join("link2"))?;
+        Ok((root, outside))
+    }
+
+    #[cfg(unix)]
+    fn file_names(files: &[PathBuf]) -> Vec<String> {
+        files
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_are_not_followed_by_default() -> Result<()> {
+        let (root, _outside) = symlink_fixture()?;
+        let files = indexable_files(root.path(), false);
+        assert_eq!(file_names(&files), vec!["a.txt"]);
+
+        // The cycle does not stop indexing.
+        let out = root.path().join("idx.json");
+        index_dir(root.path(), &out, IndexFormat::Json)?;
+        assert_eq!(load_index(&out)?.docs.len(), 1);
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_follow_symlinks_skips_cycles_and_duplicates() -> Result<()> {
+        let (root, _outside) = symlink_fixture()?;
+        let files = indexable_files(root.path(), true);
+        // a.txt once despite the cycle, b.txt once despite two links to its directory.
+        assert_eq!(file_names(&files), vec!["a.txt", "b.txt"]);
+
+        let out = root.path().join("idx.json");
+        index_dir_with_options(
+            root.path(),
+            &out,
+            &IndexOptions {
+                follow_symlinks: true,
+                ..Default::default()
+            },
+        )?;
+        assert_eq!(load_index(&out)?.docs.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_allowed_extensions_filter() {
+        let allowed_exts = ["txt", "md", "csv", "json", "pdf"];
+
+        assert!(allowed_exts.contains(&"txt"));
+        assert!(allowed_exts.contains(&"pdf"));
+        assert!(!allowed_exts.contains(&"exe"));
+        assert!(!allowed_exts.contains(&"jpg"));
+    }
+
+    #[test]
+    fn test_path_extension_extraction() {
+        let path = std::path::Path::new("/path/to/file.txt");
+        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+
+        assert_eq!(ext, "txt");
+    }
+
+    #[test]
+    fn test_file_sorting() {
+        let mut files = [
+            std::path::PathBuf::from("c.txt"),
+            std::path::PathBuf::from("a.txt"),
+            std::path::PathBuf::from("b.txt"),
+        ];
+
+        files.sort();
+
+        assert_eq!(files[0], std::path::PathBuf::from("a.txt"));
+        assert_eq!(files[1], std::path::PathBuf::from("b.txt"));
+        assert_eq!(files[2], std::path::PathBuf::from("c.txt"));
+    }
+
+    #[test]
+    fn test_query_vector_with_unknown_terms() {
+        let terms = ["kubernetes".to_string(), "docker".to_string()];
+        let term_map: HashMap<&String, usize> =
+            terms.iter().enumerate().map(|(i, t)| (t, i)).collect();
+
+        let query_tokens = ["nginx".to_string(), "unknown".to_string()];
+        let mut q_vec: Vec<f32> = vec![0.0; terms.len()];
+
+        for t in query_tokens.iter() {
+            if let Some(&i) = term_map.get(t) {
+                q_vec[i] += 1.0;
+            }
+        }
+
+        // Unknown terms should not affect the vector
+        assert_eq!(q_vec, vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_index_preserves_document_order() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+
+        std::fs::write(dir.path().join("a.txt"), "first")?;
+        std::fs::write(dir.path().join("b.txt"), "second")?;
+        std::fs::write(dir.path().join("c.txt"), "third")?;
+
+        let index_path = dir.path().join("index.json");
+        index_dir(dir.path(), &index_path, IndexFormat::Json)?;
+
+        let f = File::open(&index_path)?;
+        let idx: Index = serde_json::from_reader(f)?;
+
+        // Files should be sorted
+        assert_eq!(idx.docs.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parallel_vector_computation() {
+        use rayon::prelude::*;
+
+        let data = vec![vec![1.0, 2.0], vec![3.0, 4.0], vec![5.0, 6.0]];
+
+        let results: Vec<f32> = data.par_iter().map(|v| v.iter().sum::<f32>()).collect();
+
+        assert_eq!(results, vec![3.0, 7.0, 11.0]);
+    }
+
+    #[test]
+    fn test_print_retrieval_results_does_not_panic() {
+        // Verifies that print_retrieval_results completes without panicking and that
+        // score ordering is correct: doc-0 (programming/memory) must rank first for
+        // "programming memory" verified via build_prompt's inverted-index path.
+        let docs = vec![
+            Doc {
+                id: "doc-0".to_string(),
+                path: "/docs/alpha.txt".to_string(),
+                text: "rust programming systems language memory safety ownership".to_string(),
+                ..Default::default()
+            },
+            Doc {
+                id: "doc-1".to_string(),
+                path: "/docs/beta.txt".to_string(),
+                text: "python scripting web framework requests asyncio".to_string(),
+                ..Default::default()
+            },
+        ];
+        let terms = vec![
+            "programming".to_string(),
+            "memory".to_string(),
+            "python".to_string(),
+            "framework".to_string(),
+        ];
+        let norm = (2.0f32).sqrt().recip();
+        let vectors = vec![vec![norm, norm, 0.0, 0.0], vec![0.0, 0.0, norm, norm]];
+        let mut inverted: HashMap<String, Vec<usize>> = HashMap::new();
+        inverted.insert("programming".to_string(), vec![0]);
+        inverted.insert("memory".to_string(), vec![0]);
+        inverted.insert("python".to_string(), vec![1]);
+        inverted.insert("framework".to_string(), vec![1]);
+        let idx = Index {
+            docs,
+            terms,
+            idf: vec![1.0, 1.0, 1.0, 1.0],
+            vectors,
+            inverted,
+            ..Default::default()
+        };
+        // Verify doc-0 ("alpha.txt") ranks first for "programming memory".
+        let prompt = build_prompt(
+            &idx,
+            "programming memory",
+            &QueryOptions {
+                k: 2,
+                ..Default::default()
+            },
+        );
+        assert!(
+            prompt.contains("alpha.txt"),
+            "doc-0 (alpha.txt) must be the top-ranked result for 'programming memory'"
+        );
+        // Also verify the function itself does not panic.
+        let results = retrieve(
+            &idx,
+            "programming memory",
+            &QueryOptions {
+                k: 2,
+                ..Default::default()
+            },
+        );
+        print_retrieval_results(&results, &ColorPrinter::default(), &mut std::io::sink()).unwrap();
+    }
+
+    fn write_context_fixture(dir: &Path) -> Result<Index> {
+        for (name, extra) in [
+            ("a.txt", "helm charts"),
+            ("b.txt", "rolling updates"),
+            ("c.txt", "service mesh"),
+            ("d.txt", "node pools"),
+        ] {
+            std::fs::write(
+                dir.join(name),
+                format!("kubernetes cluster deployment {}", extra),
+            )?;
+        }
+        let index_path = dir.join("idx.json");
+        index_dir(dir, &index_path, IndexFormat::Json)?;
+        load_index(&index_path)
+    }
+
+    #[test]
+    fn test_context_docs_limits_documents_in_prompt() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let idx = write_context_fixture(dir.path())?;
+        let q = "kubernetes cluster deployment";
+        let opts = QueryOptions {
+            k: 4,
+            context_docs: 2,
+            ..Default::default()
+        };
+        assert_eq!(rank_documents(&idx, q, &opts).len(), 4);
+        let prompt = build_prompt(&idx, q, &opts);
+        assert_eq!(prompt.matches("Filename:").count(), 2, "{}", prompt);
+        Ok(())
+    }
+
+    #[test]
+    fn test_context_chars_truncates_last_document() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let idx = write_context_fixture(dir.path())?;
+        let q = "kubernetes cluster deployment";
+        let full = build_prompt(
+            &idx,
+            q,
+            &QueryOptions {
+                k: 4,
+                ..Default::default()
+            },
+        );
+        let budgeted = build_prompt(
+            &idx,
+            q,
+            &QueryOptions {
+                k: 4,
+                context_chars: Some(100),
+                ..Default::default()
+            },
+        );
+        let context = |p: &str| -> String {
+            p.trim_start_matches("Use the following documents as context:\n")
+                .split("\nQuestion:")
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        };
+        assert!(context(&full).chars().count() > 100);
+        assert_eq!(context(&budgeted).chars().count(), 100);
+        assert_eq!(budgeted.matches("Filename:").count(), 2);
+        Ok(())
+    }
+
+    fn context_doc(path: &str, text: &str) -> Doc {
+        Doc {
+            id: format!("doc-{}", path),
+            path: format!("/corpus/{}", path),
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_retrieval_results_are_coloured_only_when_enabled() -> Result<()> {
+        let results = vec![QueryResult {
+            path: "notes.txt".to_string(),
+            score: 0.25,
+            keywords: vec!["rust".to_string(), "cargo".to_string()],
+            excerpt: String::new(),
+            sentence_excerpt: None,
+            last_modified_secs: None,
+            context_used: false,
+        }];
+        let render = |color: bool| -> Result<String> {
+            let mut out: Vec<u8> = Vec::new();
+            print_retrieval_results(&results, &ColorPrinter { color }, &mut out)?;
+            Ok(String::from_utf8(out)?)
+        };
+
+        let plain = render(false)?;
+        assert!(!plain.contains('\x1b'), "{:?}", plain);
+        assert!(plain.starts_with(
+            "Document: notes.txt\nScore: 0.2500\nSummary: This document discusses: rust, cargo.\n"
+        ));
+
+        let coloured = render(true)?;
+        assert!(coloured.contains("Document: \x1b[34mnotes.txt\x1b[0m\n"));
+        assert!(coloured.contains("Score: \x1b[33m0.2500\x1b[0m\n"));
+        assert!(coloured.contains("\x1b[32mrust\x1b[0m, \x1b[32mcargo\x1b[0m."));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sentence_granularity_returns_relevant_sentence() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("ops.txt"),
+            "The team met on Monday. Lunch was served at noon! \
+             Rotating the database credentials every quarter limits exposure. \
+             Parking will be closed next week.",
+        )?;
+        std::fs::write(dir.path().join("other.txt"), "gardening tips for spring")?;
+        let index_path = dir.path().join("idx.json");
+        index_dir(dir.path(), &index_path, IndexFormat::Json)?;
+        let idx = load_index(&index_path)?;
+
+        let q = "rotate database credentials";
+        let sentence_opts = QueryOptions {
+            k: 1,
+            granularity: Granularity::Sentence,
+            ..Default::default()
+        };
+        let results = retrieve(&idx, q, &sentence_opts);
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].sentence_excerpt.as_deref(),
+            Some("Rotating the database credentials every quarter limits exposure.")
+        );
+
+        let mut out: Vec<u8> = Vec::new();
+        print_retrieval_results(&results, &ColorPrinter::default(), &mut out)?;
+        assert!(String::from_utf8(out)?.contains("Best sentence: Rotating the database"));
+
+        // Document granularity leaves the field unset.
+        let doc_results = retrieve(
+            &idx,
+            q,
+            &QueryOptions {
+                k: 1,
+                ..Default::default()
+            },
+        );
+        assert_eq!(doc_results[0].sentence_excerpt, None);
+        Ok(())
+    }
+
+    fn web_index() -> Index {
+        let pages = [
+            (
+                "https://docs.example.com/guide/install",
+                "install the rust toolchain",
+            ),
+            (
+                "https://docs.example.com/guide/config",
+                "rust configuration options",
+            ),
+            ("https://blog.other.org/posts/rust", "why we love rust"),
+        ];
+        let docs: Vec<Doc> = pages
+            .iter()
+            .map(|(url, text)| Doc {
+                id: url.to_string(),
+                path: url.to_string(),
+                text: text.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let tokens: Vec<Vec<String>> = docs.iter().map(|d| tokenize(&d.text)).collect();
+        build_index(docs, &tokens, RankingMethod::Bm25, true)
+    }
+
+    #[test]
+    fn test_source_filter_restricts_to_domain() -> Result<()> {
+        let idx = web_index();
+        for pattern in [
+            "https://docs.example.com/**",
+            r"re:^https://docs\.example\.com/",
+        ] {
+            let opts = QueryOptions {
+                k: 10,
+                source_filter: Some(SourceFilter::parse(pattern)?),
+                ..Default::default()
+            };
+            let paths: Vec<String> = retrieve(&idx, "rust", &opts)
+                .into_iter()
+                .map(|r| r.path)
+                .collect();
+            assert_eq!(paths.len(), 2, "{}: {:?}", pattern, paths);
+            assert!(paths
+                .iter()
+                .all(|p| p.starts_with("https://docs.example.com/")));
+        }
+        // Without a filter both domains are returned.
+        let all = retrieve(
+            &idx,
+            "rust",
+            &QueryOptions {
+                k: 10,
+                ..Default::default()
+            },
+        );
+        assert_eq!(all.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_docs_per_file_caps_chunks_of_one_source() {
+        let chunk = |source: &str, n: usize, text: &str| Doc {
+            id: format!("{source}#{n}"),
+            path: format!("{source}#chunk-{n}"),
+            text: text.to_string(),
+            metadata: HashMap::from([("source".to_string(), source.to_string())]),
+            ..Default::default()
+        };
+        let mut docs: Vec<Doc> = (0..5)
+            .map(|n| chunk("long.txt", n, "glacier glacier ice melt"))
+            .collect();
+        docs.extend((0..2).map(|n| chunk("short.txt", n, "glacier ice caves")));
+        let tokens: Vec<Vec<String>> = docs.iter().map(|d| tokenize(&d.text)).collect();
+        let idx = build_index(docs, &tokens, RankingMethod::Bm25, true);
+
+        let sources = |max_docs_per_file: Option<usize>| -> Vec<String> {
+            let opts = QueryOptions {
+                k: 5,
+                max_docs_per_file,
+                ..Default::default()
+            };
+            retrieve(&idx, "glacier", &opts)
+                .into_iter()
+                .map(|r| r.path.split('#').next().unwrap().to_string())
+                .collect()
+        };
+        let uncapped = sources(None);
+        assert_eq!(uncapped, vec!["long.txt"; 5]);
+        let capped = sources(Some(2));
+        assert_eq!(
+            capped,
+            vec!["long.txt", "long.txt", "short.txt", "short.txt"]
+        );
+    }
+
+    #[test]
+    fn test_match_source_glob_and_regex() {
+        let url = "https://docs.example.com/guide/install";
+        assert!(match_source("https://docs.example.com/**", url));
+        assert!(!match_source("https://blog.other.org/**", url));
+        assert!(match_source(r"re:https://.*example\.com.*", url));
+        assert!(!match_source(r"re:other\.org", url));
+        // Globs must cover the whole path; regexes may match a substring.
+        assert!(!match_source("docs.example.com", url));
+        assert!(match_source("re:docs", url));
+        assert!(!match_source("re:(", url));
+        assert!(SourceFilter::parse("re:(").is_err());
+    }
+
+    /// A version-0 index as written before `version`, `normalized`, `ranking` and the
+    /// per-document `tokens`/`metadata` fields existed.
+    const V0_INDEX: &str = r#"{
+        "docs": [
+            {"id": "a.txt", "path": "/docs/a.txt", "text": "alpha beta"},
+            {"id": "b.txt", "path": "/docs/b.txt", "text": "beta gamma"}
+        ],
+        "terms": ["beta", "alpha", "gamma"],
+        "idf": [0.18, 0.69, 0.69],
+        "vectors": [[1.0, 1.0, 0.0], [1.0, 0.0, 1.0]],
+        "inverted": {"beta": [0, 1], "alpha": [0], "gamma": [1]}
+    }"#;
+
+    fn assert_v1_defaults(idx: &Index) {
+        assert_eq!(idx.version, INDEX_VERSION);
+        assert!(idx.normalized);
+        assert_eq!(idx.ranking, RankingMethod::Bm25);
+        let paths: Vec<&str> = idx.docs.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(paths, vec!["/docs/a.txt", "/docs/b.txt"]);
+        assert!(idx
+            .docs
+            .iter()
+            .all(|d| d.tokens.is_none() && d.metadata.is_empty()));
+        assert_eq!(idx.docs[1].text, "beta gamma");
+        assert_eq!(idx.inverted["beta"], vec![0, 1]);
+    }
+
+    #[test]
+    fn test_migrate_v0_to_v1_fills_defaults_and_keeps_docs() -> Result<()> {
+        let idx = migrate_v0_to_v1(serde_json::from_str(V0_INDEX)?)?;
+        assert_v1_defaults(&idx);
+        idx.validate()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_command_writes_current_version() -> Result<()> {
+        let dir = TempDir::new()?;
+        let old = dir.path().join("old.json");
+        let new = dir.path().join("new.json");
+        std::fs::write(&old, V0_INDEX)?;
+
+        migrate_index(&old, 0, &new, true)?;
+        let raw: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&new)?)?;
+        assert_eq!(raw["version"], INDEX_VERSION);
+        assert_v1_defaults(&load_index(&new)?);
+
+        // The migrated file is no longer version 0, and a wrong --from-version is rejected.
+        assert!(migrate_index(&new, 0, &dir.path().join("again.json"), true).is_err());
+        assert!(migrate_index(&old, 1, &dir.path().join("again.json"), true).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_index_auto_upgrades_v0() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("old.json");
+        std::fs::write(&path, V0_INDEX)?;
+        assert_v1_defaults(&load_index(&path)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_index_rejects_future_version() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("future.json");
+        let mut raw: serde_json::Value = serde_json::from_str(V0_INDEX)?;
+        raw["version"] = (INDEX_VERSION + 1).into();
+        std::fs::write(&path, raw.to_string())?;
+        let err = load_index(&path).unwrap_err().to_string();
+        assert!(err.contains("newer than this build supports"), "{}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_index_suggests_migrate_on_schema_mismatch() -> Result<()> {
+        let dir = TempDir::new()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("a.txt"), "alpha beta")?;
+        let path = dir.path().join("index.json");
+        index_dir(&docs, &path, IndexFormat::Json)?;
+        let mut raw: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        assert_eq!(raw["schema_version"], "1.0");
+
+        raw["schema_version"] = "0.9".into();
+        std::fs::write(&path, raw.to_string())?;
+        let err = load_index(&path).unwrap_err().to_string();
+        assert!(
+            err.contains("schema version 0.9 does not match this build (1.0)")
+                && err.contains("voltai migrate --from-version 1"),
+            "{}",
+            err
+        );
+
+        let migrated = dir.path().join("migrated.json");
+        migrate_index(&path, 1, &migrated, true)?;
+        let idx = load_index(&migrated)?;
+        assert_eq!(idx.schema_version, SCHEMA_VERSION);
+        assert_eq!(idx.docs.len(), 1);
+        let err = migrate_index(&migrated, 1, &dir.path().join("again.json"), true)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("already at the current version"), "{}", err);
+
+        raw["schema_version"] = "2.0".into();
+        std::fs::write(&path, raw.to_string())?;
+        let err = migrate_index(&path, 1, &migrated, true)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("no migration from index schema 2.0"),
+            "{}",
+            err
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_index_reports_corrupt_json_without_migrate_hint() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("index.json");
+        std::fs::write(&path, &V0_INDEX[..V0_INDEX.len() / 2])?;
+        let err = load_index(&path).unwrap_err().to_string();
+        assert!(err.starts_with("cannot decode"), "{}", err);
+        assert!(!err.contains("migrate"), "{}", err);
+
+        // A v0 file whose field no longer decodes is pointed at `migrate`.
+        let mut raw: serde_json::Value = serde_json::from_str(V0_INDEX)?;
+        raw["idf"] = "not a list".into();
+        std::fs::write(&path, raw.to_string())?;
+        let err = load_index(&path).unwrap_err().to_string();
+        assert!(err.contains("voltai migrate --from-version 0"), "{}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_schema_snapshot() {
+        let schema = serde_json::to_string_pretty(&index_schema()).unwrap();
+        insta::with_settings!({
+            snapshot_path => "../tests/snapshots",
+            prepend_module_to_snapshot => false,
+        }, {
+            insta::assert_snapshot!("index_schema", schema);
+        });
+    }
+
+    #[test]
+    fn test_index_schema_is_draft07_and_matches_written_index() -> Result<()> {
+        let schema = serde_json::to_value(index_schema())?;
+        assert_eq!(schema["$schema"], "http://json-schema.org/draft-07/schema#");
+        for def in [
+            "Doc",
+            "QueryResult",
+            "Entity",
+            "EntityType",
+            "RankingMethod",
+        ] {
+            assert!(schema["definitions"].get(def).is_some(), "missing {}", def);
+        }
+        // Every property of a freshly written index is described by the schema, and the
+        // in-memory ANN graph is not.
+        let dir = TempDir::new()?;
+        std::fs::write(dir.path().join("a.txt"), "alpha beta")?;
+        let path = dir.path().join("idx.json");
+        index_dir(dir.path(), &path, IndexFormat::Json)?;
+        let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        let props = schema["properties"].as_object().unwrap();
+        for key in written.as_object().unwrap().keys() {
+            assert!(props.contains_key(key), "schema lacks {}", key);
+        }
+        assert!(!props.contains_key("ann"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_index_rejects_inconsistent_index() -> Result<()> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("idx.json");
+        // Decodes fine, but the posting list references a document that does not exist.
+        std::fs::write(
+            &path,
+            r#"{"docs":[{"id":"a","path":"a","text":"alpha"}],"terms":["alpha"],
+                "idf":[0.5],"vectors":[[1.0]],"inverted":{"alpha":[0,3]}}"#,
+        )?;
+        let err = load_index(&path).unwrap_err().to_string();
+        assert!(err.contains("references document 3 of 1"), "{}", err);
+
+        std::fs::write(
+            &path,
+            r#"{"docs":[{"id":"a","path":"a","text":"alpha"}],"terms":["alpha"],
+                "idf":[0.5],"vectors":[[1.0, 2.0]],"inverted":{"alpha":[0]}}"#,
+        )?;
+        let err = load_index(&path).unwrap_err().to_string();
+        assert!(
+            err.contains("vector 0 has 2 weights for 1 terms"),
+            "{}",
+            err
+        );
+        Ok(())
+    }
+
+    fn dated_result(path: &str, score: f32, modified: Option<u64>) -> QueryResult {
+        QueryResult {
+            path: path.to_string(),
+            score,
+            keywords: Vec::new(),
+            excerpt: String::new(),
+            sentence_excerpt: None,
+            last_modified_secs: modified,
+            context_used: false,
+        }
+    }
+
+    #[test]
+    fn test_randomize_is_reproducible_per_seed() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        for i in 0..12 {
+            std::fs::write(
+                dir.path().join(format!("note{:02}.txt", i)),
+                format!("note {}", i),
+            )?;
+        }
+        let out = dir.path().join("idx.json");
+        index_dir(dir.path(), &out, IndexFormat::Json)?;
+        let idx = load_index(&out)?;
+        let paths = |seed: u64| -> Vec<String> {
+            let opts = QueryOptions {
+                k: 12,
+                min_score: Some(0.0),
+                randomize: true,
+                seed,
+                ..Default::default()
+            };
+            retrieve(&idx, "unrelated words", &opts)
+                .into_iter()
+                .map(|r| r.path)
+                .collect()
+        };
+
+        // With --min-score 0 every document is a candidate, though none matches the query.
+        assert_eq!(paths(7).len(), 12);
+        assert_eq!(paths(7), paths(7));
+        assert_ne!(paths(7), paths(8));
+        let mut sorted = paths(7);
+        sorted.sort();
+        assert_ne!(
+            paths(7),
+            sorted,
+            "results should not come back in path order"
+        );
+        // Without it, only matching documents can be picked.
+        let opts = QueryOptions {
+            randomize: true,
+            ..Default::default()
+        };
+        assert!(retrieve(&idx, "unrelated words", &opts).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_min_score_drops_weak_matches() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let idx = load_index(&write_retrieval_fixture(dir.path())?)?;
+        let all = retrieve(&idx, "garbage collector memory", &QueryOptions::default());
+        assert!(all.len() >= 2);
+        let opts = QueryOptions {
+            min_score: Some(all[0].score),
+            ..Default::default()
+        };
+        let strong = retrieve(&idx, "garbage collector memory", &opts);
+        assert!(strong.iter().all(|r| r.score >= all[0].score));
+        assert!(strong.len() < all.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cross_index_query_merges_indexes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let indexes = dir.path().join("indexes");
+        std::fs::create_dir(&indexes)?;
+        for (project, texts) in [
+            ("alpha", ["garbage collector pauses", "build scripts"]),
+            ("beta", ["garbage collector memory tuning", "release notes"]),
+            ("gamma", ["bread recipes", "sourdough starter"]),
+        ] {
+            let docs = dir.path().join(project);
+            std::fs::create_dir(&docs)?;
+            for (i, text) in texts.iter().enumerate() {
+                std::fs::write(docs.join(format!("{}.txt", i)), text)?;
+            }
+            index_dir(
+                &docs,
+                &indexes.join(format!("{}.json", project)),
+                IndexFormat::Json,
+            )?;
+        }
+        std::fs::write(indexes.join("notes.json"), "{\"not\": \"an index\"}")?;
+
+        let results = cross_index_query(&indexes, "garbage collector memory", 3, 2)?;
+        assert_eq!(results.len(), 2, "{:?}", results);
+        assert!(results[0].path.contains("beta"), "{:?}", results);
+        assert!(results[1].path.contains("alpha"), "{:?}", results);
+        assert!(results[0].score >= results[1].score);
+
+        let top = cross_index_query(&indexes, "garbage collector memory", 1, 2)?;
+        assert_eq!(top.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bash_completion_script_lists_subcommands() -> Result<()> {
+        let mut out = Vec::new();
+        clap_complete::generate(
+            clap_complete::Shell::Bash,
+            &mut Cli::command(),
+            "voltai",
+            &mut out,
+        );
+        let bash = String::from_utf8(out)?;
+        assert!(!bash.is_empty());
+        assert!(bash.contains("index") && bash.contains("query"), "{}", bash);
+        Ok(())
+    }
+
+    #[test]
+    fn test_group_by_source_separates_directories() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (a, b) = (dir.path().join("project_a"), dir.path().join("project_b"));
+        std::fs::create_dir(&a)?;
+        std::fs::create_dir(&b)?;
+        std::fs::write(a.join("gc.txt"), "garbage collector tuning")?;
+        std::fs::write(a.join("alloc.txt"), "memory allocator design notes")?;
+        std::fs::write(
+            b.join("collector.txt"),
+            "garbage collector memory garbage collector memory",
+        )?;
+        let out = dir.path().join("idx.json");
+        index_dir(dir.path(), &out, IndexFormat::Json)?;
+
+        let opts = QueryOptions {
+            k: 5,
+            no_ollama: true,
+            group_by_source: true,
+            ..Default::default()
+        };
+        let mut buf = Vec::new();
+        query_with_ollama(
+            std::slice::from_ref(&out),
+            "garbage collector memory",
+            &opts,
+            &MockBackend::new(true, "unused"),
+            &mut buf,
+        )?;
+        let text = String::from_utf8(buf)?;
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 5, "{}", text);
+        // The best overall match is in project_b, so its group comes first.
+        assert_eq!(lines[0], format!("## Group: {}/", b.display()));
+        assert!(lines[1].starts_with("  - collector.txt ("), "{}", text);
+        assert_eq!(lines[2], format!("## Group: {}/", a.display()));
+        assert!(lines[3..].iter().all(|l| l.starts_with("  - ")), "{}", text);
+
+        let groups = group_by_source(&retrieve(&load_index(&out)?, "garbage memory", &opts));
+        assert_eq!(groups.len(), 2);
+        assert!(groups[0].best_score >= groups[1].best_score);
+        Ok(())
+    }
+
+    #[test]
+    fn test_sort_results_modes() {
+        let ranked = vec![
+            dated_result("b.txt", 0.9, Some(100)),
+            dated_result("c.txt", 0.7, None),
+            dated_result("a.txt", 0.5, Some(300)),
+            dated_result("d.txt", 0.3, None),
+        ];
+        let order = |sort_by: SortBy| {
+            let mut results = ranked.clone();
+            sort_results(&mut results, sort_by);
+            results.into_iter().map(|r| r.path).collect::<Vec<_>>()
+        };
+        assert_eq!(order(SortBy::Score), ["b.txt", "c.txt", "a.txt", "d.txt"]);
+        assert_eq!(order(SortBy::Path), ["a.txt", "b.txt", "c.txt", "d.txt"]);
+        // Newest first; undated results last, still in score order.
+        assert_eq!(order(SortBy::Date), ["a.txt", "b.txt", "c.txt", "d.txt"]);
+
+        let mut shuffled = vec![ranked[3].clone(), ranked[0].clone(), ranked[2].clone()];
+        sort_results(&mut shuffled, SortBy::Score);
+        let scores: Vec<f32> = shuffled.iter().map(|r| r.score).collect();
+        assert_eq!(scores, [0.9, 0.5, 0.3]);
+    }
+
+    #[test]
+    fn test_index_records_modification_time() -> Result<()> {
+        let dir = TempDir::new()?;
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, "alpha")?;
+        let out = dir.path().join("idx.json");
+        index_dir(dir.path(), &out, IndexFormat::Json)?;
+        let idx = load_index(&out)?;
+        assert_eq!(idx.docs[0].last_modified_secs, last_modified_secs(&file));
+        assert!(idx.docs[0].last_modified_secs.is_some());
+
+        let results = retrieve(
+            &idx,
+            "alpha",
+            &QueryOptions {
+                sort_by: SortBy::Date,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            results[0].last_modified_secs,
+            idx.docs[0].last_modified_secs
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_best_sentence_none_without_overlap() {
+        let idf: HashMap<&str, f32> = [("solar", 1.0)].into_iter().collect();
+        let q = vec!["solar".to_string()];
+        assert_eq!(best_sentence("Wind farms. Tidal power.", &q, &idf), None);
+        assert_eq!(
+            best_sentence("Wind farms. Solar roofs help!", &q, &idf).as_deref(),
+            Some("Solar roofs help!")
+        );
+    }
+
+    #[test]
+    fn test_build_context_keywords_mode() {
+        let doc = context_doc("k8s.txt", "kubernetes kubernetes docker");
+        let context = build_context(&[&doc], ContextMode::Keywords, 10, None);
+        let expected = "Filename: k8s.txt\nKeywords: kubernetes, docker\n---\n";
+        assert_eq!(context, expected);
+        assert_eq!(context.len(), expected.len());
+    }
+
+    #[test]
+    fn test_build_context_excerpt_mode_caps_each_document() {
+        let long = "word ".repeat(300);
+        let a = context_doc("a.txt", &long);
+        let b = context_doc("b.txt", "short   text\nhere");
+        let context = build_context(&[&a, &b], ContextMode::Excerpt, 50, None);
+        let header = |name: &str| format!("Filename: {}\nExcerpt: ", name).len();
+        let footer = "\n---\n".len();
+        assert_eq!(
+            context.len(),
+            header("a.txt") + 50 + footer + header("b.txt") + "short text here".len() + footer
+        );
+        assert!(context.contains("Excerpt: short text here\n"));
+    }
+
+    #[test]
+    fn test_build_context_full_mode_includes_whole_text_within_budget() {
+        let text = "alpha beta gamma delta";
+        let doc = context_doc("greek.txt", text);
+        let overhead = "Filename: greek.txt\nContent:\n\n---\n".len();
+
+        let full = build_context(&[&doc], ContextMode::Full, 1000, None);
+        assert_eq!(full.len(), overhead + text.len());
+        assert!(full.contains(text));
+
+        let capped = build_context(&[&doc], ContextMode::Full, 10, None);
+        assert_eq!(capped.len(), overhead + 10);
+    }
+
+    #[test]
+    fn test_context_mode_excerpt_reaches_prompt() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let idx = write_context_fixture(dir.path())?;
+        let prompt = build_prompt(
+            &idx,
+            "kubernetes cluster deployment",
+            &QueryOptions {
+                k: 4,
+                context_mode: ContextMode::Excerpt,
+                ..Default::default()
+            },
+        );
+        assert!(prompt.contains("Excerpt: kubernetes cluster deployment"));
+        assert!(!prompt.contains("Keywords:"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_print_retrieval_results_empty_index_does_not_panic() {
+        let idx = Index {
+            docs: vec![],
+            terms: vec![],
+            idf: vec![],
+            vectors: vec![],
+            inverted: HashMap::new(),
+            ..Default::default()
+        };
+        let results = retrieve(
+            &idx,
+            "any query",
+            &QueryOptions {
+                k: 5,
+                ..Default::default()
+            },
+        );
+        assert!(results.is_empty());
+        print_retrieval_results(&results, &ColorPrinter::default(), &mut std::io::sink()).unwrap();
+    }
+
+    #[test]
+    fn test_bench_vocab_no_duplicates() {
+        use std::collections::HashSet;
+        let unique: HashSet<&str> = BENCH_VOCAB.iter().copied().collect();
+        assert_eq!(
+            BENCH_VOCAB.len(),
+            unique.len(),
+            "BENCH_VOCAB contains {} duplicate(s); entries occur more than once",
+            BENCH_VOCAB.len() - unique.len(),
+        );
+    }
+
+    // ---- binary index format (bincode) ----------------------------------------
+
+    #[test]
+    fn test_load_index_json_roundtrip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let out = dir.path().join("idx.json");
+
+        let src = dir.path().join("a.txt");
+        std::fs::write(&src, "hello world")?;
+        index_dir(dir.path(), &out, IndexFormat::Json)?;
+
+        let loaded = load_index(&out)?;
+        assert!(!loaded.docs.is_empty(), "expected at least one doc");
+        assert_eq!(loaded.docs.len(), loaded.vectors.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_index_binary_roundtrip() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let out = dir.path().join("idx.bin");
+
+        let src = dir.path().join("doc.txt");
+        std::fs::write(&src, "rust performance systems")?;
+        index_dir(dir.path(), &out, IndexFormat::Binary)?;
+
+        let loaded = load_index(&out)?;
+        assert!(!loaded.docs.is_empty(), "expected at least one doc");
+        assert_eq!(loaded.docs.len(), loaded.vectors.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_records_pdf_metadata_and_title_id() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        pdf::tests::write_pdf(
+            &dir.path().join("q3.pdf"),
+            &[("Title", "Q3 Board Minutes"), ("Author", "Jane Doe")],
+        );
+        std::fs::write(dir.path().join("notes.txt"), "plain notes")?;
+        let out = dir.path().join("idx.json");
+        index_dir(dir.path(), &out, IndexFormat::Json)?;
+
+        let idx = load_index(&out)?;
+        let pdf_doc = idx
+            .docs
+            .iter()
+            .find(|d| d.path.ends_with("q3.pdf"))
+            .unwrap();
+        assert_eq!(pdf_doc.id, "Q3 Board Minutes");
+        assert_eq!(
+            pdf_doc.metadata.get("author").map(String::as_str),
+            Some("Jane Doe")
+        );
+        assert_eq!(
+            pdf_doc.metadata.get("page_count").map(String::as_str),
+            Some("1")
+        );
+        let txt_doc = idx
+            .docs
+            .iter()
+            .find(|d| d.path.ends_with("notes.txt"))
+            .unwrap();
+        assert_eq!(txt_doc.id, "doc-notes.txt");
+        assert!(txt_doc.metadata.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_binary_and_json_indexes_equivalent() -> Result<()> {
+        // Index the same document twice — once as JSON, once as binary — and verify
+        // that load_index surfaces the same term vocabulary and doc count.
+        // Note: term *ordering* is non-deterministic (HashMap ties break differently
+        // each run), so we compare sorted term sets rather than ordered slices.
+        let dir_j = tempfile::tempdir()?;
+        let dir_b = tempfile::tempdir()?;
+        let content = "distributed systems replication consensus latency";
+        std::fs::write(dir_j.path().join("doc.txt"), content)?;
+        std::fs::write(dir_b.path().join("doc.txt"), content)?;
+
+        let json_out = dir_j.path().join("idx.json");
+        let bin_out = dir_b.path().join("idx.bin");
+        index_dir(dir_j.path(), &json_out, IndexFormat::Json)?;
+        index_dir(dir_b.path(), &bin_out, IndexFormat::Binary)?;
+
+        let j = load_index(&json_out)?;
+        let b = load_index(&bin_out)?;
+
+        assert_eq!(j.docs.len(), b.docs.len(), "doc count must match");
+        assert_eq!(j.vectors.len(), b.vectors.len(), "vector count must match");
+
+        let mut j_terms = j.terms.clone();
+        let mut b_terms = b.terms.clone();
+        j_terms.sort();
+        b_terms.sort();
+        assert_eq!(
+            j_terms, b_terms,
+            "term sets must be identical (order-independent)"
+        );
+        Ok(())
+    }
+
+    /// Ranks a short and a long document for "solar" under the given index/query settings.
+    fn length_normalization_top(no_normalize: bool, pivoted: bool) -> Result<String> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("short.txt"), "solar panel")?;
+        let filler: Vec<String> = (0..80).map(|i| format!("filler{}", i)).collect();
+        std::fs::write(
+            dir.path().join("long.txt"),
+            format!("solar solar {}", filler.join(" ")),
+        )?;
+        std::fs::write(dir.path().join("wind.txt"), "wind turbine blade")?;
+        let index_path = dir.path().join("idx.json");
+        index_dir_with_options(
+            dir.path(),
+            &index_path,
+            &IndexOptions {
+                no_normalize,
+                ..Default::default()
+            },
+        )?;
+        let idx = load_index(&index_path)?;
+        assert_eq!(idx.normalized, !no_normalize);
+        let ranked = rank_documents(
+            &idx,
+            "solar",
+            &QueryOptions {
+                k: 2,
+                pivoted_normalization: pivoted,
+                ..Default::default()
+            },
+        );
+        assert_eq!(ranked.len(), 2);
+        let top = Path::new(&idx.docs[ranked[0].0].path).file_name().unwrap();
+        Ok(top.to_string_lossy().into_owned())
+    }
+
+    #[test]
+    fn test_length_normalization_modes_change_ranking() -> Result<()> {
+        // BM25 length normalisation favours the short document...
+        assert_eq!(length_normalization_top(false, false)?, "short.txt");
+        // ...raw term weights favour the long one with more occurrences...
+        assert_eq!(length_normalization_top(true, false)?, "long.txt");
+        // ...and pivoting damps the long document's vector norm back below it.
+        assert_eq!(length_normalization_top(true, true)?, "short.txt");
+        Ok(())
+    }
+
+    #[test]
+    fn test_pivoted_normalization_ignored_on_normalized_index() -> Result<()> {
+        assert_eq!(
+            length_normalization_top(false, true)?,
+            length_normalization_top(false, false)?
+        );
+        Ok(())
+    }
+
+    fn top_paths(idx: &Index, q: &str) -> Vec<String> {
+        rank_documents(
+            idx,
+            q,
+            &QueryOptions {
+                k: 3,
+                ..Default::default()
+            },
+        )
+        .into_iter()
+        .map(|(i, _)| {
+            Path::new(&idx.docs[i].path)
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_rebuild_vectors_switches_ranking_from_cached_tokens() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        // TF-IDF rewards the highest share of "solar" (terse.txt: 100%); BM25 saturates
+        // term frequency, so eight occurrences in verbose.txt win instead.
+        std::fs::write(dir.path().join("terse.txt"), "solar")?;
+        std::fs::write(
+            dir.path().join("verbose.txt"),
+            "solar solar solar solar solar solar solar solar array mount",
+        )?;
+        std::fs::write(dir.path().join("wind.txt"), "wind turbine blade")?;
+        let tfidf_path = dir.path().join("tfidf.json");
+        index_dir_with_options(
+            dir.path(),
+            &tfidf_path,
+            &IndexOptions {
+                ranking: RankingMethod::TfIdf,
+                cache_tokens: true,
+                ..Default::default()
+            },
+        )?;
+        let tfidf = load_index(&tfidf_path)?;
+        assert_eq!(tfidf.ranking, RankingMethod::TfIdf);
+        assert!(tfidf.docs.iter().all(|d| d.tokens.is_some()));
+
+        // Remove the sources: rebuilding must not need them.
+        for name in ["terse.txt", "verbose.txt", "wind.txt"] {
+            std::fs::remove_file(dir.path().join(name))?;
+        }
+        let bm25_path = dir.path().join("bm25.bin");
+        rebuild_vectors(&tfidf_path, RankingMethod::Bm25, &bm25_path, true)?;
+        let bm25 = load_index(&bm25_path)?;
+        assert_eq!(bm25.ranking, RankingMethod::Bm25);
+
+        assert_eq!(top_paths(&tfidf, "solar"), vec!["terse.txt", "verbose.txt"]);
+        assert_eq!(top_paths(&bm25, "solar"), vec!["verbose.txt", "terse.txt"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_index_adds_documents_and_terms() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("rust.txt"), "rust ownership keeps memory safe")?;
+        std::fs::write(docs.join("go.txt"), "go has a garbage collector")?;
+        let index_path = dir.path().join("idx.json");
+        index_dir(&docs, &index_path, IndexFormat::Json)?;
+        let before = load_index(&index_path)?;
+
+        let sauce = dir.path().join("sauce.txt");
+        std::fs::write(&sauce, "slow cooked tomato sauce recipe with garbage")?;
+        let out = dir.path().join("appended.bin");
+        append_index(&index_path, std::slice::from_ref(&sauce), &out, true)?;
+        let idx = load_index(&out)?;
+        idx.validate()?;
+
+        assert_eq!(idx.docs.len(), 3);
+        assert_eq!(idx.terms[..before.terms.len()], before.terms[..]);
+        let vocabulary: HashSet<&str> = idx.terms.iter().map(String::as_str).collect();
+        let mut expected: HashSet<&str> = before.terms.iter().map(String::as_str).collect();
+        expected.extend(["slow", "cooked", "tomato", "sauce", "recipe"]);
+        assert_eq!(vocabulary, expected);
+        assert_eq!(top_paths(&idx, "tomato"), vec!["sauce.txt"]);
+        assert_eq!(top_paths(&idx, "rust memory")[0], "rust.txt");
+
+        // Appending the same file again is refused.
+        assert!(append_index(&out, &[sauce], &out, true).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_index_reuses_index_time_tokenizer_settings() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("pain.txt"), "le pain avec une croûte dorée")?;
+        let index_path = dir.path().join("idx.json");
+        let opts = IndexOptions {
+            preprocess: vec![preprocess::PreprocessStep::StripHtml],
+            tokenizer: TokenizerConfig {
+                max_token_len: Some(10),
+                lang: Some("fr".to_string()),
+                exclude_numeric: true,
+                ..Default::default()
+            },
+            quiet: true,
+            ..Default::default()
+        };
+        index_dir_with_options(&docs, &index_path, &opts)?;
+        let before = load_index(&index_path)?.terms.len();
+
+        let tarte = dir.path().join("tarte.txt");
+        std::fs::write(
+            &tarte,
+            "<p>une tarte avec les pommes</p> 1995 anticonstitutionnellement",
+        )?;
+        let out = dir.path().join("appended.json");
+        append_index(&index_path, std::slice::from_ref(&tarte), &out, true)?;
+        let idx = load_index(&out)?;
+        let added: Vec<&str> = idx.terms[before..].iter().map(String::as_str).collect();
+        assert_eq!(added, ["pommes", "tarte"]);
+
+        let rare = IndexOptions {
+            min_keyword_freq: 2,
+            ..opts
+        };
+        index_dir_with_options(&docs, &index_path, &rare)?;
+        let err = append_index(&index_path, &[tarte], &out, true).unwrap_err();
+        assert!(err.to_string().contains("--min-keyword-freq 2"), "{}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pretty_json_adds_keyword_summaries() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("cluster.txt"),
+            "kubernetes schedules docker containers behind nginx",
+        )?;
+        std::fs::write(dir.path().join("proxy.txt"), "nginx proxies docker traffic")?;
+        let index_path = dir.path().join("idx.json");
+        index_dir_with_options(
+            dir.path(),
+            &index_path,
+            &IndexOptions {
+                ranking: RankingMethod::TfIdf,
+                ..Default::default()
+            },
+        )?;
+        let idx = load_index(&index_path)?;
+        let results = retrieve(&idx, "docker nginx", &QueryOptions::default());
+        let mut out: Vec<u8> = Vec::new();
+        write_results(
+            &results,
+            "docker nginx",
+            OutputFormat::PrettyJson,
+            &ColorPrinter::default(),
+            &mut out,
+        )?;
+
+        let text = String::from_utf8(out)?;
+        assert!(text.contains("\n  {\n    \"path\": "), "{}", text);
+        let parsed: Vec<serde_json::Value> = serde_json::from_str(&text)?;
+        assert_eq!(parsed.len(), 2);
+        for (value, result) in parsed.iter().zip(&results) {
+            let score = value["score"].as_f64().expect("score is a number");
+            assert!((0.0..=1.0).contains(&score), "{}", score);
+            assert_eq!(
+                value["summary"],
+                format!("This document discusses: {}.", result.keywords.join(", "))
+            );
+        }
+        assert_eq!(
+            keyword_summary(&[]),
+            "This document discusses: (no keywords)."
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebuild_vectors_requires_cached_tokens() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = write_retrieval_fixture(dir.path())?;
+        let err = rebuild_vectors(
+            &index_path,
+            RankingMethod::TfIdf,
+            &dir.path().join("o.json"),
+            true,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("--cache-tokens"), "{}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_without_normalized_field_defaults_to_normalized() -> Result<()> {
+        let json = r#"{"docs":[],"terms":[],"idf":[],"vectors":[],"inverted":{}}"#;
+        let idx: Index = serde_json::from_str(json)?;
+        assert!(idx.normalized);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ann_index_is_loaded_and_ranks_like_exact() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = write_retrieval_fixture(dir.path())?;
+        let exact = load_index(&index_path)?;
+        assert!(exact.ann.is_none(), "plain index must not carry a graph");
+
+        index_dir_with_options(
+            dir.path(),
+            &index_path,
+            &IndexOptions {
+                ann: true,
+                ..Default::default()
+            },
+        )?;
+        let approx = load_index(&index_path)?;
+        assert!(approx.ann.is_some(), "--ann index must reload its graph");
+
+        let opts = QueryOptions {
+            k: 2,
+            ..Default::default()
+        };
+        let q = "garbage collector memory";
+        let paths = |idx: &Index| -> Vec<String> {
+            rank_documents(idx, q, &opts)
+                .into_iter()
+                .map(|(i, _)| idx.docs[i].path.clone())
+                .collect()
+        };
+        assert_eq!(paths(&approx), paths(&exact));
+
+        // Re-indexing without --ann must drop the now-stale graph.
+        index_dir(dir.path(), &index_path, IndexFormat::Json)?;
+        assert!(load_index(&index_path)?.ann.is_none());
+        Ok(())
+    }
+
+    // ---- named entity index ----------------------------------------------------
+
+    #[test]
+    fn test_entity_index_maps_entities_to_documents() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("paris.txt"),
+            "The conference takes place in Paris this spring.",
+        )?;
+        std::fs::write(
+            dir.path().join("london.txt"),
+            "Our new office opens in London next year.",
+        )?;
+        let index_path = dir.path().join("idx.json");
+        index_dir(dir.path(), &index_path, IndexFormat::Json)?;
+
+        let entity_path = dir.path().join("entities.json");
+        index_entities(&index_path, &entity_path, false, None, true)?;
+        let f = File::open(&entity_path)?;
+        let entity_index: HashMap<String, Vec<String>> = serde_json::from_reader(f)?;
+
+        let paris = lookup_entity(&entity_index, "Paris");
+        assert_eq!(paris.len(), 1);
+        assert!(paris[0].ends_with("paris.txt"), "got {:?}", paris);
+
+        let london = lookup_entity(&entity_index, "london");
+        assert_eq!(london.len(), 1);
+        assert!(london[0].ends_with("london.txt"), "got {:?}", london);
+
+        assert!(lookup_entity(&entity_index, "Tokyo").is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_entity_index_lists_each_document_once() {
+        let idx = Index {
+            docs: vec![Doc {
+                id: "doc-0".to_string(),
+                path: "/docs/trip.txt".to_string(),
+                text: "Paris in May, Paris in June.".to_string(),
+                ..Default::default()
+            }],
+            terms: vec![],
+            idf: vec![],
+            vectors: vec![vec![]],
+            inverted: HashMap::new(),
+            ..Default::default()
+        };
+        let entity_index = cross_reference_entities(&idx.docs, false, Language::English);
+        assert_eq!(
+            entity_index.get("Paris"),
+            Some(&vec!["/docs/trip.txt".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_cross_ref_reports_only_shared_entities() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("a.txt"),
+            "The summit in Paris was chaired by Angela Merkel.",
+        )?;
+        std::fs::write(
+            dir.path().join("b.txt"),
+            "Delegates later left Paris for Tokyo.",
+        )?;
+        let index_path = dir.path().join("idx.json");
+        index_dir(dir.path(), &index_path, IndexFormat::Json)?;
+
+        let mut out: Vec<u8> = Vec::new();
+        cross_ref(&index_path, 2, &mut out)?;
+        let out = String::from_utf8(out)?;
+        assert!(out.starts_with("Paris (2 documents)\n"), "{}", out);
+        assert!(out.contains("a.txt") && out.contains("b.txt"));
+        assert!(!out.contains("Tokyo"));
+        assert!(!out.contains("Angela Merkel"));
+
+        let mut all: Vec<u8> = Vec::new();
+        cross_ref(&index_path, 1, &mut all)?;
+        let all = String::from_utf8(all)?;
+        assert!(all.contains("Tokyo (1 document)"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_entity_commands_follow_index_language() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("a.txt"), "Renault SAS a signé le contrat.")?;
+        std::fs::write(
+            docs.join("b.txt"),
+            "Le contrat lie Renault SAS pour trois ans.",
+        )?;
+        let index_path = dir.path().join("idx.json");
+        let opts = IndexOptions {
+            tokenizer: TokenizerConfig {
+                lang: Some("fr".to_string()),
+                ..Default::default()
+            },
+            quiet: true,
+            ..Default::default()
+        };
+        index_dir_with_options(&docs, &index_path, &opts)?;
+
+        let mut out: Vec<u8> = Vec::new();
+        cross_ref(&index_path, 2, &mut out)?;
+        let out = String::from_utf8(out)?;
+        assert!(out.starts_with("Renault SAS (2 documents)\n"), "{}", out);
+
+        let entity_path = dir.path().join("entities.json");
+        index_entities(&index_path, &entity_path, false, None, true)?;
+        let entity_index: HashMap<String, Vec<String>> =
+            serde_json::from_reader(File::open(&entity_path)?)?;
+        assert_eq!(lookup_entity(&entity_index, "Renault SAS").len(), 2);
+        Ok(())
+    }
+
+    // ---- entity boosting -------------------------------------------------------
+
+    fn entity_boost_fixture() -> Index {
+        let docs = vec![
+            Doc {
+                id: "doc-0".to_string(),
+                path: "/docs/berlin.txt".to_string(),
+                text: "quarterly office report from Berlin".to_string(),
+                ..Default::default()
+            },
+            Doc {
+                id: "doc-1".to_string(),
+                path: "/docs/paris.txt".to_string(),
+                text: "quarterly office report from Paris".to_string(),
+                ..Default::default()
+            },
+        ];
+        let mut inverted: HashMap<String, Vec<usize>> = HashMap::new();
+        inverted.insert("office".to_string(), vec![0, 1]);
+        let doc_entities = document_entities(&docs, Language::English);
+        Index {
+            docs,
+            terms: vec!["office".to_string()],
+            idf: vec![1.0],
+            vectors: vec![vec![0.5], vec![0.5]],
+            inverted,
+            doc_entities: Some(doc_entities),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_entity_boost_adds_boost_only_to_matching_docs() {
+        let idx = entity_boost_fixture();
+        let mut scores = vec![1.0_f32, 1.0];
+        let query_entities = extract_entities_from_text("office news from Paris");
+        entity_boost_scores(
+            &query_entities,
+            idx.doc_entities.as_ref().unwrap(),
+            &idx.docs,
+            &mut scores,
+            DEFAULT_ENTITY_BOOST,
+        );
+        assert_eq!(scores[0], 1.0);
+        assert!((scores[1] - (1.0 + DEFAULT_ENTITY_BOOST)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_entity_boost_ranks_entity_document_above_equal_score_document() {
+        let idx = entity_boost_fixture();
+        let q = "office in Paris";
+        let plain = rank_documents(
+            &idx,
+            q,
+            &QueryOptions {
+                k: 2,
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            plain[0].1, plain[1].1,
+            "fixture docs must tie without boost"
+        );
+
+        let boosted = rank_documents(
+            &idx,
+            q,
+            &QueryOptions {
+                k: 2,
+                entity_boost: Some(DEFAULT_ENTITY_BOOST),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            boosted[0].0, 1,
+            "paris.txt must rank first with entity boost"
+        );
+        assert!(boosted[0].1 > boosted[1].1);
+    }
+
+    #[test]
+    fn test_entity_boost_uses_entities_stored_at_index_time() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(
+            docs.join("berlin.txt"),
+            "quarterly office report from Berlin",
+        )?;
+        std::fs::write(docs.join("paris.txt"), "quarterly office report from Paris")?;
+        let index_path = dir.path().join("idx.bin");
+        index_dir(&docs, &index_path, IndexFormat::Binary)?;
+        let mut idx = load_index(&index_path)?;
+        let stored = idx.doc_entities.as_ref().expect("entities are stored");
+        assert!(
+            stored.keys().any(|p| p.ends_with("paris.txt")),
+            "{:?}",
+            stored
+        );
+
+        // Boosting reads the stored entities, not the document text.
+        for doc in &mut idx.docs {
+            doc.text.clear();
+        }
+        let opts = QueryOptions {
+            k: 2,
+            entity_boost: Some(DEFAULT_ENTITY_BOOST),
+            ..Default::default()
+        };
+        let ranked = rank_documents(&idx, "office in Paris", &opts);
+        assert!(idx.docs[ranked[0].0].path.ends_with("paris.txt"));
+        assert!(ranked[0].1 > ranked[1].1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_entity_boost_without_query_entities_is_noop() {
+        let idx = entity_boost_fixture();
+        let mut scores = vec![0.3_f32, 0.7];
+        entity_boost_scores(&[], &HashMap::new(), &idx.docs, &mut scores, 1.0);
+        assert_eq!(scores, vec![0.3, 0.7]);
+    }
+
+    // ---- retrieval-only output (--no-ollama) ----------------------------------
+
+    /// Test double recording every backend interaction.
+    struct MockBackend {
+        available: bool,
+        response: String,
+        health_checks: std::cell::Cell<usize>,
+        generate_calls: std::cell::Cell<usize>,
+        last_prompt: std::cell::RefCell<String>,
+    }
+
+    impl MockBackend {
+        fn new(available: bool, response: &str) -> Self {
+            MockBackend {
+                available,
+                response: response.to_string(),
+                health_checks: std::cell::Cell::new(0),
+                generate_calls: std::cell::Cell::new(0),
+                last_prompt: std::cell::RefCell::new(String::new()),
+            }
+        }
+    }
+
+    impl LlmBackend for MockBackend {
+        fn is_available(&self) -> bool {
+            self.health_checks.set(self.health_checks.get() + 1);
+            self.available
+        }
+
+        fn generate(&self, _model: &str, prompt: &str) -> Result<String> {
+            self.generate_calls.set(self.generate_calls.get() + 1);
+            *self.last_prompt.borrow_mut() = prompt.to_string();
+            Ok(self.response.clone())
+        }
+    }
+
+    fn write_retrieval_fixture(dir: &Path) -> Result<PathBuf> {
+        std::fs::write(
+            dir.join("rust.txt"),
+            "rust ownership keeps memory safe without a garbage collector",
+        )?;
+        std::fs::write(
+            dir.join("go.txt"),
+            "go uses a garbage collector and goroutines for memory management",
+        )?;
+        std::fs::write(dir.join("cooking.txt"), "slow cooked tomato sauce recipe")?;
+        let index_path = dir.join("idx.json");
+        let opts = IndexOptions {
+            quiet: true,
+            ..Default::default()
+        };
+        index_dir_with_options(dir, &index_path, &opts)?;
+        Ok(index_path)
+    }
+
+    #[test]
+    fn test_search_within_ranks_only_previous_results() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = write_retrieval_fixture(dir.path())?;
+        let prev = dir.path().join("prev.jsonl");
+        let opts = QueryOptions {
+            k: 10,
+            no_ollama: true,
+            format: OutputFormat::Jsonl,
+            ..Default::default()
+        };
+        let mut out: Vec<u8> = Vec::new();
+        query_with_ollama(
+            std::slice::from_ref(&index_path),
+            "memory garbage collector",
+            &opts,
+            &MockBackend::new(false, ""),
+            &mut out,
+        )?;
+        std::fs::write(&prev, &out)?;
+        let previous: Vec<String> = read_prev_results(&prev)?
+            .into_iter()
+            .map(|r| r.path)
+            .collect();
+        assert_eq!(previous.len(), 2);
+
+        // "tomato" only occurs in the cooking document, which the broad query left out.
+        let results = search_within(&index_path, &prev, "tomato goroutines", 10)?;
+        assert!(!results.is_empty());
+        assert!(results.iter().all(|r| previous.contains(&r.path)));
+        assert!(results[0].path.ends_with("go.txt"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_output_limit_chars_truncates_answer_at_sentence() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = write_retrieval_fixture(dir.path())?;
+        // 25 sentences of 40 characters: a 1000-character answer.
+        let answer = "Garbage collectors reclaim memory late! ".repeat(25);
+        assert_eq!(answer.chars().count(), 1000);
+        let backend = MockBackend::new(true, &answer);
+        let opts = QueryOptions {
+            model: Some("test-model".to_string()),
+            output_limit_chars: Some(500),
+            ..Default::default()
+        };
+        let mut out: Vec<u8> = Vec::new();
+        query_with_ollama(
+            std::slice::from_ref(&index_path),
+            "garbage collector memory",
+            &opts,
+            &backend,
+            &mut out,
+        )?;
+        let text = String::from_utf8(out)?;
+        assert!(text.chars().count() < 500, "{} chars", text.chars().count());
+        let kept = text
+            .strip_suffix(" [truncated]")
+            .expect("truncation marker");
+        assert!(kept.ends_with(['.', '!', '?']), "{}", kept);
+        assert_eq!(kept.matches('!').count(), 12);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_query_sources() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("query.txt");
+        std::fs::write(
+            &path,
+            "How do garbage collectors work?\n\nCompare Go and Java.\n",
+        )?;
+        let no_stdin = &mut std::io::empty();
+
+        assert_eq!(
+            read_query_from(Some("rust".into()), None, no_stdin)?,
+            "rust"
+        );
+        assert_eq!(
+            read_query_from(None, Some(path.clone()), no_stdin)?,
+            "How do garbage collectors work?\n\nCompare Go and Java."
+        );
+        let mut stdin = std::io::Cursor::new("query from a pipe\n");
+        assert_eq!(
+            read_query_from(None, Some(PathBuf::from("-")), &mut stdin)?,
+            "query from a pipe"
+        );
+
+        let both = read_query_from(Some("rust".into()), Some(path), no_stdin).unwrap_err();
+        assert!(both.to_string().contains("not both"), "{}", both);
+        assert!(read_query_from(None, None, no_stdin).is_err());
+        let empty = dir.path().join("empty.txt");
+        std::fs::write(&empty, " \n")?;
+        assert!(read_query_from(None, Some(empty), no_stdin).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_score_debug_info_components() -> Result<()> {
+        let info = score_debug_info(&[1.0, 2.0, 0.0], &[0.5, 0.5, 3.0]);
+        assert!((info.dot_product - 1.5).abs() < 1e-6);
+        assert!((info.query_norm - 5.0_f32.sqrt()).abs() < 1e-6);
+        assert!((info.doc_norm - 9.5_f32.sqrt()).abs() < 1e-6);
+        assert!(
+            (info.dot_product / (info.query_norm * info.doc_norm) - info.cosine_sim).abs() < 1e-5
+        );
+
+        let dir = tempfile::tempdir()?;
+        let idx = load_index(&write_retrieval_fixture(dir.path())?)?;
+        let results = retrieve(&idx, "garbage collector memory", &QueryOptions::default());
+        let mut err = Vec::new();
+        write_score_debug(&idx, "garbage collector memory", &results, &mut err)?;
+        let text = String::from_utf8(err)?;
+        assert_eq!(text.lines().count(), results.len());
+        // Without pivoting or boosts, the BM25 score is the raw dot product.
+        assert!(
+            text.contains(&format!("dot product {:.4},", results[0].score)),
+            "{}",
+            text
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncate_at_sentence_boundary() {
+        assert_eq!(truncate_at_sentence_boundary("Short.", 10), "Short.");
+        assert_eq!(
+            truncate_at_sentence_boundary("One. Two? Three and four.", 20),
+            "One. [truncated]"
+        );
+        // No complete sentence fits: cut mid-sentence.
+        assert_eq!(
+            truncate_at_sentence_boundary("abcdefghijklmnopqrstuvwxyz.", 16),
+            "abcd [truncated]"
+        );
+    }
+
+    #[test]
+    fn test_no_ollama_prints_results_without_calling_backend() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = write_retrieval_fixture(dir.path())?;
+        let backend = MockBackend::new(true, "LLM ANSWER");
+        let opts = QueryOptions {
+            k: 2,
+            no_ollama: true,
+            ..Default::default()
+        };
+        let mut out: Vec<u8> = Vec::new();
+        query_with_ollama(
+            std::slice::from_ref(&index_path),
+            "garbage collector memory",
+            &opts,
+            &backend,
+            &mut out,
+        )?;
+        let text = String::from_utf8(out)?;
+
+        assert_eq!(backend.health_checks.get(), 0, "no health check expected");
+        assert_eq!(backend.generate_calls.get(), 0, "no generation expected");
+        assert!(!text.contains("LLM ANSWER"));
+        assert!(text.contains("rust.txt"), "output: {}", text);
+        assert!(text.contains("go.txt"), "output: {}", text);
+        assert!(!text.contains("cooking.txt"), "output: {}", text);
+        assert_eq!(text.matches("Score: ").count(), 2);
+        assert!(text.contains("Excerpt: "));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unavailable_backend_falls_back_to_retrieval_results() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = write_retrieval_fixture(dir.path())?;
+        let backend = MockBackend::new(false, "LLM ANSWER");
+        let mut out: Vec<u8> = Vec::new();
+        query_with_ollama(
+            std::slice::from_ref(&index_path),
+            "garbage collector memory",
+            &QueryOptions::default(),
+            &backend,
+            &mut out,
+        )?;
+        let text = String::from_utf8(out)?;
+
+        assert_eq!(backend.health_checks.get(), 1);
+        assert_eq!(backend.generate_calls.get(), 0);
+        assert!(text.contains("Document: "), "output: {}", text);
+        Ok(())
+    }
+
+    #[test]
+    fn test_available_backend_output_is_written() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = write_retrieval_fixture(dir.path())?;
+        let backend = MockBackend::new(true, "LLM ANSWER");
+        let opts = QueryOptions {
+            model: Some("test-model".to_string()),
+            ..Default::default()
+        };
+        let mut out: Vec<u8> = Vec::new();
+        query_with_ollama(
+            std::slice::from_ref(&index_path),
+            "memory",
+            &opts,
+            &backend,
+            &mut out,
+        )?;
+
+        assert_eq!(backend.generate_calls.get(), 1);
+        assert_eq!(String::from_utf8(out)?, "LLM ANSWER");
+        Ok(())
+    }
+
+    /// `query_with_ollama` against a mock Ollama HTTP server, so the HTTP backend is covered
+    /// in CI without a real Ollama install.
+    mod integration {
+        use super::*;
+        use mockito::Matcher;
+        use std::time::Duration;
+
+        const TAGS: &str = r#"{"models":[
+            {"name":"llama3:8b","size":4661224676},
+            {"name":"gemma3:1b","size":815319791}]}"#;
+
+        #[test]
+        fn query_uses_mock_generate_endpoint() -> Result<()> {
+            let dir = tempfile::tempdir()?;
+            let index_path = write_retrieval_fixture(dir.path())?;
+            let mut server = mockito::Server::new();
+            let tags = server
+                .mock("GET", "/api/tags")
+                .with_body(TAGS)
+                .expect_at_least(1)
+                .create();
+            let generate = server
+                .mock("POST", "/api/generate")
+                .match_body(Matcher::AllOf(vec![
+                    Matcher::PartialJsonString(r#"{"model":"test-model","stream":false}"#.into()),
+                    // The prompt carries the retrieved documents' keywords as context.
+                    Matcher::Regex("Keywords: [^\"]*garbage".into()),
+                    Matcher::Regex("Question: garbage collector memory".into()),
+                ]))
+                .with_body(r#"{"model":"test-model","response":"MOCK ANSWER","done":true}"#)
+                .expect(1)
+                .create();
+
+            let opts = QueryOptions {
+                model: Some("test-model".to_string()),
+                ..Default::default()
+            };
+            let mut out: Vec<u8> = Vec::new();
+            query_with_ollama(
+                std::slice::from_ref(&index_path),
+                "garbage collector memory",
+                &opts,
+                &OllamaHttp::new(&server.url(), Duration::from_secs(5)),
+                &mut out,
+            )?;
+
+            tags.assert();
+            generate.assert();
+            assert_eq!(String::from_utf8(out)?, "MOCK ANSWER");
+            Ok(())
+        }
+
+        #[test]
+        fn cached_response_skips_second_generate_call() -> Result<()> {
+            let dir = tempfile::tempdir()?;
+            let index_path = write_retrieval_fixture(dir.path())?;
+            let mut server = mockito::Server::new();
+            server.mock("GET", "/api/tags").with_body(TAGS).create();
+            let generate = server
+                .mock("POST", "/api/generate")
+                .with_body(r#"{"response":"CACHED ANSWER"}"#)
+                .expect(1)
+                .create();
+
+            let opts = QueryOptions {
+                model: Some("test-model".to_string()),
+                response_cache: Some(cache::ResponseCache {
+                    path: dir.path().join(cache::DEFAULT_RESPONSE_CACHE_FILE),
+                    ttl: Duration::from_secs(60),
+                }),
+                ..Default::default()
+            };
+            let backend = OllamaHttp::new(&server.url(), Duration::from_secs(5));
+            for _ in 0..2 {
+                let mut out: Vec<u8> = Vec::new();
+                query_with_ollama(
+                    std::slice::from_ref(&index_path),
+                    "garbage collector memory",
+                    &opts,
+                    &backend,
+                    &mut out,
+                )?;
+                assert_eq!(String::from_utf8(out)?, "CACHED ANSWER");
+            }
+            generate.assert();
+            Ok(())
+        }
+
+        #[test]
+        fn from_cache_replays_the_original_answer() -> Result<()> {
+            let dir = tempfile::tempdir()?;
+            let index_path = write_retrieval_fixture(dir.path())?;
+            let mut server = mockito::Server::new();
+            server.mock("GET", "/api/tags").with_body(TAGS).create();
+            let generate = server
+                .mock("POST", "/api/generate")
+                .with_body(r#"{"response":"Go has a **garbage collector**."}"#)
+                .expect(1)
+                .create();
+            let response_cache = cache::ResponseCache {
+                path: dir.path().join(cache::DEFAULT_RESPONSE_CACHE_FILE),
+                ttl: Duration::from_secs(60),
+            };
+            let opts = QueryOptions {
+                model: Some("test-model".to_string()),
+                response_cache: Some(response_cache.clone()),
+                ..Default::default()
+            };
+            let backend = OllamaHttp::new(&server.url(), Duration::from_secs(5));
+            let mut original: Vec<u8> = Vec::new();
+            query_with_ollama(
+                std::slice::from_ref(&index_path),
+                "garbage collector memory",
+                &opts,
+                &backend,
+                &mut original,
+            )?;
+
+            let entries = response_cache.entries();
+            assert_eq!(entries.len(), 1);
+            let (hash, entry) = &entries[0];
+            assert_eq!(entry.query, "garbage collector memory");
+            // Replaying needs neither the index nor the server.
+            std::fs::remove_file(&index_path)?;
+            let mut replayed: Vec<u8> = Vec::new();
+            replay_cached_response(
+                &response_cache,
+                hash,
+                &ColorPrinter::default(),
+                &mut replayed,
+            )?;
+            assert_eq!(replayed, original);
+            generate.assert();
+            Ok(())
+        }
+
+        #[test]
+        fn probed_model_is_the_smallest_listed() -> Result<()> {
+            // The probe only runs when neither --model nor OLLAMA_MODEL is set.
+            if std::env::var("OLLAMA_MODEL").is_ok() {
+                return Ok(());
+            }
+            let dir = tempfile::tempdir()?;
+            let index_path = write_retrieval_fixture(dir.path())?;
+            let mut server = mockito::Server::new();
+            server.mock("GET", "/api/tags").with_body(TAGS).create();
+            let generate = server
+                .mock("POST", "/api/generate")
+                .match_body(Matcher::PartialJsonString(
+                    r#"{"model":"gemma3:1b"}"#.into(),
+                ))
+                .with_body(r#"{"response":"FROM GEMMA"}"#)
+                .expect(1)
+                .create();
+
+            let mut out: Vec<u8> = Vec::new();
+            query_with_ollama(
+                std::slice::from_ref(&index_path),
+                "memory",
+                &QueryOptions::default(),
+                &OllamaHttp::new(&server.url(), Duration::from_secs(5)),
+                &mut out,
+            )?;
+            generate.assert();
+            assert_eq!(String::from_utf8(out)?, "FROM GEMMA");
+            Ok(())
+        }
+
+        #[test]
+        fn generate_error_falls_back_to_retrieval_results() -> Result<()> {
+            let dir = tempfile::tempdir()?;
+            let index_path = write_retrieval_fixture(dir.path())?;
+            let mut server = mockito::Server::new();
+            server.mock("GET", "/api/tags").with_body(TAGS).create();
+            server
+                .mock("POST", "/api/generate")
+                .with_status(500)
+                .with_body(r#"{"error":"model not found"}"#)
+                .create();
+
+            let opts = QueryOptions {
+                model: Some("missing".to_string()),
+                ..Default::default()
+            };
+            let mut out: Vec<u8> = Vec::new();
+            query_with_ollama(
+                std::slice::from_ref(&index_path),
+                "garbage collector memory",
+                &opts,
+                &OllamaHttp::new(&server.url(), Duration::from_secs(5)),
+                &mut out,
+            )?;
+            let text = String::from_utf8(out)?;
+            assert!(text.contains("Document: "), "output: {}", text);
+            Ok(())
+        }
+
+        #[test]
+        fn unreachable_server_is_unavailable() {
+            // Nothing listens on port 9 (discard) in CI containers.
+            assert!(!OllamaHttp::new("http://127.0.0.1:9", Duration::from_secs(5)).is_available());
+        }
+    }
+
+    /// Pins the retrieval output formats that downstream parsers consume. Snapshots live in
+    /// `tests/snapshots/`; after an intentional format change run `cargo insta review`.
+    mod snapshots {
+        use super::*;
+
+        const QUERY: &str = "garbage collector memory";
+
+        fn snapshot_index() -> Index {
+            let docs: Vec<Doc> = [
+                (
+                    "notes/rust.txt",
+                    "Rust ownership keeps memory safe without a garbage collector. \
+                     Borrowing rules are checked at compile time.",
+                ),
+                (
+                    "notes/go.txt",
+                    "Go uses a concurrent garbage collector, and goroutines share memory \
+                     through channels.",
+                ),
+                (
+                    "recipes/sauce.txt",
+                    "Slow cooked tomato sauce, with basil and garlic.",
+                ),
+            ]
+            .iter()
+            .map(|(path, text)| Doc {
+                id: path.to_string(),
+                path: path.to_string(),
+                text: text.to_string(),
+                ..Default::default()
+            })
+            .collect();
+            let tokens: Vec<Vec<String>> = docs.iter().map(|d| tokenize(&d.text)).collect();
+            build_index(docs, &tokens, RankingMethod::Bm25, true)
+        }
+
+        fn render(format: OutputFormat) -> String {
+            let opts = QueryOptions {
+                granularity: Granularity::Sentence,
+                ..Default::default()
+            };
+            let results = retrieve(&snapshot_index(), QUERY, &opts);
+            let mut out: Vec<u8> = Vec::new();
+            write_results(&results, QUERY, format, &ColorPrinter::default(), &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        }
+
+        macro_rules! assert_output_snapshot {
+            ($name:expr, $output:expr) => {
+                insta::with_settings!({
+                    snapshot_path => "../tests/snapshots",
+                    prepend_module_to_snapshot => false,
+                }, {
+                    insta::assert_snapshot!($name, $output);
+                })
+            };
+        }
+
+        #[test]
+        fn text_output() {
+            assert_output_snapshot!("query_text", render(OutputFormat::Text));
+        }
+
+        #[test]
+        fn json_output() {
+            assert_output_snapshot!("query_json", render(OutputFormat::Json));
+        }
+
+        #[test]
+        fn jsonl_output() {
+            assert_output_snapshot!("query_jsonl", render(OutputFormat::Jsonl));
+        }
+
+        #[test]
+        fn csv_output() {
+            assert_output_snapshot!("query_csv", render(OutputFormat::Csv));
+        }
+
+        #[test]
+        fn markdown_output() {
+            let md = render(OutputFormat::Markdown);
+            assert!(md.starts_with(&format!("# Query: {}\n", QUERY)));
+            assert_eq!(md.matches("\n## ").count(), 2, "{}", md);
+            assert_output_snapshot!("query_markdown", md);
+        }
+
+        #[test]
+        fn markdown_quotes_the_llm_response() {
+            let results = retrieve(&snapshot_index(), QUERY, &QueryOptions::default());
+            let md = render_markdown(&results, QUERY, Some("Both collect garbage.\n\nGo does."));
+            assert!(md.contains("\n## notes/go.txt\n**Score:** "), "{}", md);
+            assert!(
+                md.ends_with("\n> Both collect garbage.\n>\n> Go does.\n"),
+                "{}",
+                md
+            );
+        }
+
+        #[test]
+        fn fallback_keyword_summary_output() -> Result<()> {
+            let dir = tempfile::tempdir()?;
+            let index_path = dir.path().join("idx.json");
+            write_index(&mut snapshot_index(), &index_path, IndexFormat::Json)?;
+            let mut out: Vec<u8> = Vec::new();
+            query_with_ollama(
+                std::slice::from_ref(&index_path),
+                QUERY,
+                &QueryOptions::default(),
+                &MockBackend::new(false, "unused"),
+                &mut out,
+            )?;
+            let text = String::from_utf8(out)?;
+            assert_output_snapshot!("query_fallback", text);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_template_replaces_builtin_prompt() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = write_retrieval_fixture(dir.path())?;
+        let backend = MockBackend::new(true, "LLM ANSWER");
+        let q = "garbage collector memory";
+        let opts = QueryOptions {
+            k: 1,
+            context_docs: 1,
+            model: Some("test-model".to_string()),
+            template: Some(
+                "{{ query }} ({{ model }})\n{{ context }}{% for d in documents %}{{ d.id }}{% endfor %}"
+                    .to_string(),
+            ),
+            ..Default::default()
+        };
+        let mut out: Vec<u8> = Vec::new();
+        query_with_ollama(
+            std::slice::from_ref(&index_path),
+            q,
+            &opts,
+            &backend,
+            &mut out,
+        )?;
+
+        let idx = load_index(&index_path)?;
+        let (_, docs, context) = select_context(&idx, q, &opts);
+        let expected = format!("{} (test-model)\n{}{}", q, context, docs[0].id);
+        assert_eq!(*backend.last_prompt.borrow(), expected);
+        assert!(context.starts_with("Filename: "), "{}", context);
+        assert_eq!(String::from_utf8(out)?, "LLM ANSWER");
+        Ok(())
+    }
+
+    #[test]
+    fn test_show_context_frames_the_prompt_sent_to_ollama() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = write_retrieval_fixture(dir.path())?;
+        let backend = MockBackend::new(true, "LLM ANSWER");
+        let q = "which language has a garbage collector";
+        let opts = QueryOptions {
+            model: Some("test-model".to_string()),
+            show_context: true,
+            ..Default::default()
+        };
+        query_with_ollama(
+            std::slice::from_ref(&index_path),
+            q,
+            &opts,
+            &backend,
+            &mut std::io::sink(),
+        )?;
+
+        let sent = backend.last_prompt.borrow().clone();
+        let mut err: Vec<u8> = Vec::new();
+        write_prompt_context(&mut err, &sent)?;
+        let shown = String::from_utf8(err)?;
+        assert!(shown.starts_with("=== PROMPT START ===\n"), "{}", shown);
+        assert!(shown.ends_with("\n=== PROMPT END ===\n"), "{}", shown);
+        assert!(shown.contains(&format!("Question: {}", q)), "{}", shown);
+        Ok(())
+    }
+
+    #[test]
+    fn test_dry_query_prints_prompt_without_calling_ollama() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = write_retrieval_fixture(dir.path())?;
+        // Unavailable: a dry query must not depend on Ollama at all.
+        let backend = MockBackend::new(false, "LLM ANSWER");
+        let q = "which language has a garbage collector";
+        let opts = QueryOptions {
+            dry_query: true,
+            ..Default::default()
+        };
+        let mut out: Vec<u8> = Vec::new();
+        query_with_ollama(
+            std::slice::from_ref(&index_path),
+            q,
+            &opts,
+            &backend,
+            &mut out,
+        )?;
+
+        let printed = String::from_utf8(out)?;
+        assert!(
+            printed.starts_with("Use the following documents as context:"),
+            "{}",
+            printed
+        );
+        assert!(printed.contains("Keywords:"), "{}", printed);
+        assert!(printed.contains("garbage"), "{}", printed);
+        assert!(printed.contains(&format!("Question: {}", q)), "{}", printed);
+        assert_eq!(backend.generate_calls.get(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unmatched_query_is_sent_without_context() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs)?;
+        std::fs::write(docs.join("foo.txt"), "foo foo bar")?;
+        std::fs::write(docs.join("bar.txt"), "bar bar foo")?;
+        let index_path = dir.path().join("idx.json");
+        index_dir(&docs, &index_path, IndexFormat::Json)?;
+        let backend = MockBackend::new(true, "LLM ANSWER");
+        let opts = QueryOptions {
+            // Even documents ranked with a zero score must not become context.
+            randomize: true,
+            min_score: Some(0.0),
+            ..Default::default()
+        };
+        let mut out: Vec<u8> = Vec::new();
+        query_with_ollama(
+            std::slice::from_ref(&index_path),
+            "unrelated xyz",
+            &opts,
+            &backend,
+            &mut out,
+        )?;
+
+        assert_eq!(
+            *backend.last_prompt.borrow(),
+            format!("{}unrelated xyz", NO_CONTEXT_PREFIX)
+        );
+        let idx = load_index(&index_path)?;
+        let ranked = retrieve(&idx, "unrelated xyz", &opts);
+        assert_eq!(ranked.len(), 2);
+        assert!(ranked.iter().all(|r| !r.context_used), "{:?}", ranked);
+        let matched = retrieve(
+            &idx,
+            "foo",
+            &QueryOptions {
+                context_docs: 1,
+                ..Default::default()
+            },
+        );
+        let used: Vec<bool> = matched.iter().map(|r| r.context_used).collect();
+        assert_eq!(used, [true, false]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_to_file_matches_stdout_and_appends() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = write_retrieval_fixture(dir.path())?;
+        let backend = MockBackend::new(true, "unused");
+        let opts = QueryOptions {
+            k: 2,
+            no_ollama: true,
+            format: OutputFormat::Jsonl,
+            ..Default::default()
+        };
+        let run = |q: &str| -> Result<String> {
+            let mut out: Vec<u8> = Vec::new();
+            query_with_ollama(
+                std::slice::from_ref(&index_path),
+                q,
+                &opts,
+                &backend,
+                &mut out,
+            )?;
+            Ok(String::from_utf8(out)?)
+        };
+        let first = run("garbage collector")?;
+        let second = run("tomato sauce")?;
+
+        let out_file = dir.path().join("results").with_extension("jsonl");
+        query_to_file(
+            std::slice::from_ref(&index_path),
+            "garbage collector",
+            &opts,
+            &backend,
+            &out_file,
+            false,
+        )?;
+        assert_eq!(std::fs::read_to_string(&out_file)?, first);
+
+        query_to_file(
+            std::slice::from_ref(&index_path),
+            "tomato sauce",
+            &opts,
+            &backend,
+            &out_file,
+            true,
+        )?;
+        assert_eq!(
+            std::fs::read_to_string(&out_file)?,
+            format!("{}{}", first, second)
+        );
+
+        // Without --append the file is replaced.
+        query_to_file(
+            std::slice::from_ref(&index_path),
+            "tomato sauce",
+            &opts,
+            &backend,
+            &out_file,
+            false,
+        )?;
+        assert_eq!(std::fs::read_to_string(&out_file)?, second);
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("hello"), 2);
+        assert_eq!(
+            estimate_tokens("one two three four five six seven eight nine ten"),
+            13
+        );
+        assert_eq!(estimate_tokens("  spaced\tout\nwords  "), 4);
+    }
+
+    #[test]
+    fn test_streaming_writer_emits_each_result_on_arrival() -> Result<()> {
+        /// Appends to a buffer the test can inspect while the writer is still running.
+        #[derive(Clone, Default)]
+        struct SharedBuf(Arc<std::sync::Mutex<Vec<u8>>>);
+        impl Write for SharedBuf {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buf = SharedBuf::default();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let writer = {
+            let buf = buf.clone();
+            std::thread::spawn(move || write_results_jsonl_streaming(rx, buf))
+        };
+        for (i, path) in ["a.txt", "b.txt", "c.txt"].iter().enumerate() {
+            tx.send(QueryResult {
+                path: path.to_string(),
+                score: 1.0 / (i + 1) as f32,
+                keywords: Vec::new(),
+                excerpt: String::new(),
+                sentence_excerpt: None,
+                last_modified_secs: None,
+                context_used: false,
+            })?;
+        }
+        // The channel is still open, so every line must already have been written.
+        let deadline = Instant::now() + std::time::Duration::from_secs(5);
+        let lines = loop {
+            let text = String::from_utf8(buf.0.lock().unwrap().clone())?;
+            if text.lines().count() == 3 || Instant::now() > deadline {
+                break text;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        };
+        let paths: Vec<String> = lines
+            .lines()
+            .map(|l| serde_json::from_str::<QueryResult>(l).map(|r| r.path))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(paths, vec!["a.txt", "b.txt", "c.txt"]);
+
+        drop(tx);
+        writer.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream_results_writes_jsonl_in_score_order() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = write_retrieval_fixture(dir.path())?;
+        let opts = QueryOptions {
+            no_ollama: true,
+            stream_results: true,
+            sort_by: SortBy::Path,
+            ..Default::default()
+        };
+        let mut out: Vec<u8> = Vec::new();
+        query_with_ollama(
+            std::slice::from_ref(&index_path),
+            "rust ownership garbage collector",
+            &opts,
+            &MockBackend::new(true, "unused"),
+            &mut out,
+        )?;
+        let results: Vec<QueryResult> = String::from_utf8(out)?
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()?;
+        assert_eq!(results.len(), 2);
+        assert!(results[0].path.ends_with("rust.txt"));
+        assert!(results[0].score >= results[1].score);
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_limit_falls_back_to_retrieval() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = write_retrieval_fixture(dir.path())?;
+        let backend = MockBackend::new(true, "LLM ANSWER");
+        let run = |token_limit: Option<usize>| -> Result<String> {
+            let opts = QueryOptions {
+                model: Some("test-model".to_string()),
+                token_limit,
+                ..Default::default()
+            };
+            let mut out: Vec<u8> = Vec::new();
+            query_with_ollama(
+                std::slice::from_ref(&index_path),
+                "garbage collector",
+                &opts,
+                &backend,
+                &mut out,
+            )?;
+            Ok(String::from_utf8(out)?)
+        };
+
+        let fallback = run(Some(5))?;
+        assert_eq!(backend.generate_calls.get(), 0);
+        assert!(fallback.contains("rust.txt"), "{}", fallback);
+
+        assert_eq!(run(Some(100_000))?, "LLM ANSWER");
+        assert_eq!(backend.generate_calls.get(), 1);
+        Ok(())
+    }
+
+    /// Answers relevance prompts with the score of the first `(needle, score)` whose needle
+    /// occurs in the prompt, and any other prompt with `ANSWER`.
+    struct RelevanceBackend {
+        scores: Vec<(&'static str, &'static str)>,
+        last_prompt: std::cell::RefCell<String>,
+    }
+
+    impl LlmBackend for RelevanceBackend {
+        fn is_available(&self) -> bool {
+            true
+        }
+
+        fn generate(&self, _model: &str, prompt: &str) -> Result<String> {
+            *self.last_prompt.borrow_mut() = prompt.to_string();
+            if !prompt.starts_with("On a scale of 0-10") {
+                return Ok("ANSWER".to_string());
+            }
+            Ok(self
+                .scores
+                .iter()
+                .find(|(needle, _)| prompt.contains(needle))
+                .map_or("no idea", |(_, score)| score)
+                .to_string())
+        }
+    }
+
+    fn result_at(path: &str, score: f32, excerpt: &str) -> QueryResult {
+        QueryResult {
+            path: path.to_string(),
+            score,
+            keywords: Vec::new(),
+            excerpt: excerpt.to_string(),
+            sentence_excerpt: None,
+            last_modified_secs: None,
+            context_used: false,
+        }
+    }
+
+    #[test]
+    fn test_cross_encode_rerank_orders_by_model_score() -> Result<()> {
+        let backend = RelevanceBackend {
+            scores: vec![
+                ("alpha", "3"),
+                ("beta", " 9\n"),
+                ("gamma", "Relevance: 7/10"),
+            ],
+            last_prompt: Default::default(),
+        };
+        let candidates = vec![
+            result_at("a.txt", 0.9, "alpha text"),
+            result_at("b.txt", 0.5, "beta text"),
+            result_at("c.txt", 0.4, "gamma text"),
+            result_at("d.txt", 0.3, "delta text"),
+        ];
+        let reranked = cross_encode_rerank(candidates, "query", "m", &backend)?;
+        let paths: Vec<&str> = reranked.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(paths, vec!["b.txt", "c.txt", "a.txt", "d.txt"]);
+        assert_eq!(reranked[0].score, 0.5);
+        assert!(backend
+            .last_prompt
+            .borrow()
+            .contains("Query: query. Document: delta text."));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_relevance() {
+        assert_eq!(parse_relevance("8"), Some(8));
+        assert_eq!(parse_relevance("Score: 10."), Some(10));
+        assert_eq!(parse_relevance("42"), Some(10));
+        assert_eq!(parse_relevance("none"), None);
+    }
+
+    #[test]
+    fn test_rerank_reorders_prompt_context() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = write_retrieval_fixture(dir.path())?;
+        let backend = RelevanceBackend {
+            scores: vec![("goroutines", "9"), ("ownership", "2")],
+            last_prompt: Default::default(),
+        };
+        let context_order = |rerank: bool| -> Result<bool> {
+            let opts = QueryOptions {
+                model: Some("test-model".to_string()),
+                rerank,
+                ..Default::default()
+            };
+            let mut out: Vec<u8> = Vec::new();
+            query_with_ollama(
+                std::slice::from_ref(&index_path),
+                "rust ownership garbage collector",
+                &opts,
+                &backend,
+                &mut out,
+            )?;
+            assert_eq!(String::from_utf8(out)?, "ANSWER");
+            let prompt = backend.last_prompt.borrow();
+            let pos = |name: &str| prompt.find(name).unwrap();
+            Ok(pos("go.txt") < pos("rust.txt"))
+        };
+        assert!(!context_order(false)?);
+        assert!(context_order(true)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_across_several_indexes() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+        std::fs::create_dir(&a)?;
+        std::fs::create_dir(&b)?;
+        std::fs::write(a.join("cooking.txt"), "tomato sauce simmers with basil")?;
+        std::fs::write(a.join("baking.txt"), "bread dough rises overnight")?;
+        std::fs::write(
+            b.join("astronomy.txt"),
+            "telescopes observe distant galaxies",
+        )?;
+        let (idx_a, idx_b) = (dir.path().join("a.json"), dir.path().join("b.json"));
+        index_dir(&a, &idx_a, IndexFormat::Json)?;
+        index_dir(&b, &idx_b, IndexFormat::Json)?;
+
+        let both = [idx_a.clone(), idx_b.clone()];
+        let merged = load_indexes(&both)?.expect("indexes exist");
+        let paths: Vec<&str> = merged.docs.iter().map(|d| d.path.as_str()).collect();
+        assert!(paths[0].ends_with("baking.txt"));
+        assert!(paths[1].ends_with("cooking.txt"));
+        assert!(paths[2].ends_with("astronomy.txt"));
+        merged.validate()?;
+
+        let opts = QueryOptions {
+            k: 1,
+            ..Default::default()
+        };
+        let top = retrieve(&merged, "distant galaxies", &opts);
+        assert!(top[0].path.ends_with("astronomy.txt"));
+
+        let backend = MockBackend::new(false, "unused");
+        let mut out: Vec<u8> = Vec::new();
+        query_with_ollama(&both, "distant galaxies", &opts, &backend, &mut out)?;
+        assert!(String::from_utf8(out)?.contains("astronomy.txt"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_repeated_query_is_served_from_cache() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let index_path = write_retrieval_fixture(dir.path())?;
+        let backend = MockBackend::new(true, "unused");
+        let opts = QueryOptions {
+            k: 2,
+            no_ollama: true,
+            format: OutputFormat::Jsonl,
+            cache: Some(cache::QueryCache {
+                dir: dir.path().join("cache"),
+                ttl: std::time::Duration::from_secs(300),
+            }),
+            ..Default::default()
+        };
+        let run = |opts: &QueryOptions| -> Result<String> {
+            let mut out: Vec<u8> = Vec::new();
+            query_with_ollama(
+                std::slice::from_ref(&index_path),
+                "garbage collector",
+                opts,
+                &backend,
+                &mut out,
+            )?;
+            Ok(String::from_utf8(out)?)
+        };
+        let first = run(&opts)?;
+
+        // Corrupt the index but keep its modification time, so the cache key is unchanged:
+        // only a cache hit can still answer the query.
+        let mtime = std::fs::metadata(&index_path)?.modified()?;
+        std::fs::write(&index_path, "not an index")?;
+        File::options()
+            .write(true)
+            .open(&index_path)?
+            .set_modified(mtime)?;
+        assert_eq!(run(&opts)?, first);
+
+        // Different retrieval settings miss the cache and read the (now broken) index.
+        assert!(run(&QueryOptions {
+            k: 1,
+            ..opts.clone()
+        })
+        .is_err());
+        assert!(run(&QueryOptions {
+            context_docs: opts.context_docs + 1,
+            ..opts.clone()
+        })
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_extract_first_sentence() {
+        assert_eq!(
+            extract_first_sentence("  Rust is fast.\nIt is also   safe! Really?"),
+            "Rust is fast."
+        );
+        assert_eq!(
+            extract_first_sentence("no terminator at all"),
+            "no terminator at all"
+        );
+        assert_eq!(extract_first_sentence(" \n "), "");
+
+        let exact = "a".repeat(FIRST_SENTENCE_CHARS);
+        assert_eq!(extract_first_sentence(&exact), exact);
+        let long = format!("{}b. Second.", "a".repeat(FIRST_SENTENCE_CHARS));
+        assert_eq!(
+            extract_first_sentence(&long),
+            format!("{}...", "a".repeat(FIRST_SENTENCE_CHARS))
+        );
+    }
+
+    #[test]
+    fn test_excerpt_falls_back_to_first_sentence() {
+        // Lowercasing "İ" changes the text's byte length, so no query window is cut.
+        let text = "Ferries cross the bay at dawn. İstanbul timetables change in winter.";
+        let docs = vec![Doc {
+            id: "ferries".into(),
+            path: "ferries.txt".into(),
+            text: text.into(),
+            first_sentence: extract_first_sentence(text),
+            ..Default::default()
+        }];
+        let tokens: Vec<Vec<String>> = docs.iter().map(|d| tokenize(&d.text)).collect();
+        let idx = build_index(docs, &tokens, RankingMethod::Bm25, true);
+        let results = retrieve(&idx, "winter timetables", &QueryOptions::default());
+        assert_eq!(results[0].excerpt, "Ferries cross the bay at dawn.");
+    }
+
+    #[test]
+    fn test_excerpt_centres_on_first_query_term() {
+        let text = "intro text. The kubernetes scheduler places pods on nodes.";
+        let excerpt = excerpt_for_query(text, &["kubernetes".to_string()], 30);
+        assert!(excerpt.contains("kubernetes"), "excerpt: {}", excerpt);
+        assert!(excerpt.chars().count() <= 30);
+        assert!(excerpt_for_query(text, &["absent".to_string()], 30).is_empty());
+    }
+}
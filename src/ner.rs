@@ -0,0 +1,697 @@
+// Rule-based named entity recognition: regex patterns plus a small location gazetteer.
+// Deliberately dependency-free so entity extraction stays fast and fully offline.
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{Doc, STOP_WORDS};
+
+/// Category assigned to an extracted entity. Serialised in upper case (`"PERSON"`, ...)
+/// to match the conventional NER label set.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum EntityType {
+    Person,
+    Organization,
+    Location,
+    Email,
+}
+
+/// A single entity mention. `start`/`end` are byte offsets into the source text.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq)]
+pub struct Entity {
+    pub text: String,
+    pub entity_type: EntityType,
+    pub start: usize,
+    pub end: usize,
+    /// Shared form of differently written mentions ("NYC" -> "New York"); set by
+    /// [`normalize_entities`].
+    #[serde(default)]
+    pub canonical_form: Option<String>,
+}
+
+/// Well-known places recognised as LOCATION entities. Multi-word names are matched
+/// before their prefixes (e.g. "New York City" before "New York").
+const KNOWN_LOCATIONS: &[&str] = &[
+    "Amsterdam",
+    "Athens",
+    "Australia",
+    "Bangkok",
+    "Barcelona",
+    "Beijing",
+    "Berlin",
+    "Boston",
+    "Brazil",
+    "Brussels",
+    "Buenos Aires",
+    "Cairo",
+    "California",
+    "Canada",
+    "Chicago",
+    "China",
+    "Dubai",
+    "Dublin",
+    "England",
+    "Europe",
+    "France",
+    "Germany",
+    "Hong Kong",
+    "India",
+    "Istanbul",
+    "Italy",
+    "Japan",
+    "Lisbon",
+    "London",
+    "Los Angeles",
+    "Madrid",
+    "Mexico",
+    "Moscow",
+    "Mumbai",
+    "New York",
+    "New York City",
+    "NYC",
+    "Paris",
+    "Rome",
+    "San Francisco",
+    "Seattle",
+    "Seoul",
+    "Singapore",
+    "Spain",
+    "Sydney",
+    "Tokyo",
+    "Toronto",
+    "United Kingdom",
+    "United States",
+    "Vienna",
+    "Washington",
+];
+
+/// Alternative names of gazetteer locations, matched case-insensitively, and the
+/// canonical name each one normalises to.
+const LOCATION_ALIASES: &[(&str, &str)] = &[
+    ("NYC", "New York"),
+    ("New York City", "New York"),
+    ("LA", "Los Angeles"),
+    ("SF", "San Francisco"),
+    ("UK", "United Kingdom"),
+    ("Great Britain", "United Kingdom"),
+    ("US", "United States"),
+    ("USA", "United States"),
+    ("U.S.", "United States"),
+    ("United States of America", "United States"),
+];
+
+/// Honorifics dropped from person names.
+const PERSON_TITLES: &[&str] = &["Mr", "Mrs", "Ms", "Dr", "Prof"];
+
+/// Legal-form suffixes dropped from organization names.
+const ORGANIZATION_SUFFIXES: &[&str] = &[
+    "Inc",
+    "LLC",
+    "Ltd",
+    "Corp",
+    "Corporation",
+    "Co",
+    "SA",
+    "SAS",
+    "SARL",
+    "GmbH",
+    "AG",
+    "KG",
+];
+
+/// French exonyms and cities added to [`KNOWN_LOCATIONS`] for French text.
+const FRENCH_LOCATIONS: &[&str] = &[
+    "Allemagne",
+    "Angleterre",
+    "Belgique",
+    "Bordeaux",
+    "Bruxelles",
+    "Chine",
+    "Espagne",
+    "États-Unis",
+    "Genève",
+    "Italie",
+    "Japon",
+    "Lille",
+    "Londres",
+    "Lyon",
+    "Marseille",
+    "Montréal",
+    "Nantes",
+    "Nice",
+    "Québec",
+    "Royaume-Uni",
+    "Strasbourg",
+    "Suisse",
+    "Toulouse",
+];
+
+/// German exonyms and cities added to [`KNOWN_LOCATIONS`] for German text.
+const GERMAN_LOCATIONS: &[&str] = &[
+    "Bayern",
+    "Deutschland",
+    "Dresden",
+    "Düsseldorf",
+    "England",
+    "Frankfurt",
+    "Frankreich",
+    "Hamburg",
+    "Italien",
+    "Köln",
+    "Leipzig",
+    "München",
+    "Österreich",
+    "Schweiz",
+    "Spanien",
+    "Stuttgart",
+    "Vereinigte Staaten",
+    "Wien",
+    "Zürich",
+];
+
+/// Language of the text given to [`extract_entities_from_text_with_config`], selecting
+/// the organization suffixes, gazetteer and name patterns from [`load_ner_patterns`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    French,
+    German,
+}
+
+impl Language {
+    /// Language for an ISO 639 code or English name (`"fr"`, `"fra"`, `"french"`), falling
+    /// back to English for codes without patterns of their own.
+    pub fn from_code(code: &str) -> Language {
+        match code.trim().to_lowercase().as_str() {
+            "fr" | "fra" | "fre" | "french" => Language::French,
+            "de" | "deu" | "ger" | "german" => Language::German,
+            _ => Language::English,
+        }
+    }
+}
+
+/// Language-specific patterns used by [`extract_entities_from_text_with_config`].
+#[derive(Debug, Clone, Copy)]
+pub struct NerPatterns {
+    pub organization: &'static Lazy<Regex>,
+    pub location: &'static Lazy<Regex>,
+    pub person: &'static Lazy<Regex>,
+}
+
+/// Patterns for language code `lang` (see [`Language::from_code`]); unknown languages get
+/// the English ones.
+pub fn load_ner_patterns(lang: &str) -> NerPatterns {
+    patterns_for(Language::from_code(lang))
+}
+
+fn patterns_for(lang: Language) -> NerPatterns {
+    match lang {
+        Language::English => NerPatterns {
+            organization: &ORGANIZATION_PATTERN,
+            location: &LOCATION_PATTERN,
+            person: &PERSON_PATTERN,
+        },
+        Language::French => NerPatterns {
+            organization: &FRENCH_ORGANIZATION_PATTERN,
+            location: &FRENCH_LOCATION_PATTERN,
+            person: &FRENCH_PERSON_PATTERN,
+        },
+        Language::German => NerPatterns {
+            organization: &GERMAN_ORGANIZATION_PATTERN,
+            location: &GERMAN_LOCATION_PATTERN,
+            person: &GERMAN_PERSON_PATTERN,
+        },
+    }
+}
+
+/// Email addresses.
+static EMAIL_PATTERN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}\b").unwrap());
+
+/// Capitalised name sequences ending in a corporate or institutional suffix.
+static ORGANIZATION_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"\b(?:[A-Z][A-Za-z&]*\s+)+(?:Inc|Corp|Corporation|Ltd|LLC|Company|Co|Group|University|Institute|Foundation|Bank)\b\.?",
+    )
+    .unwrap()
+});
+
+/// Capitalised name sequences (accented letters included) ending in a French legal form,
+/// as in "Société Générale SA", or one of the English suffixes.
+static FRENCH_ORGANIZATION_PATTERN: Lazy<Regex> =
+    Lazy::new(|| organization_regex(&["SARL", "SAS", "SA", "Groupe"]));
+
+/// As [`FRENCH_ORGANIZATION_PATTERN`], with German legal forms ("Siemens AG").
+static GERMAN_ORGANIZATION_PATTERN: Lazy<Regex> =
+    Lazy::new(|| organization_regex(&["GmbH", "AG", "KG", "Gruppe", "Universität", "Bank"]));
+
+fn organization_regex(suffixes: &[&str]) -> Regex {
+    Regex::new(&format!(
+        r"\b(?:\p{{Lu}}[\p{{L}}&'-]*\s+)+(?:{}|Inc|Corp|Corporation|Ltd|LLC|Company|Co|Group)\b\.?",
+        suffixes.join("|")
+    ))
+    .unwrap()
+}
+
+/// Gazetteer alternation built from `KNOWN_LOCATIONS`, longest names first so the
+/// leftmost-first regex semantics prefer the most specific match.
+static LOCATION_PATTERN: Lazy<Regex> = Lazy::new(|| gazetteer_regex(&[], None));
+
+/// The gazetteer plus [`FRENCH_LOCATIONS`] and street names ("rue de Rivoli", "place de
+/// la Concorde").
+static FRENCH_LOCATION_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    gazetteer_regex(
+        FRENCH_LOCATIONS,
+        Some(
+            r"\b(?:rue|avenue|boulevard|place|quai)\s+(?:de\s+la\s+|de\s+l'|du\s+|des\s+|de\s+)?\p{Lu}[\p{L}-]*",
+        ),
+    )
+});
+
+/// The gazetteer plus [`GERMAN_LOCATIONS`] and street names ("Hauptstraße", "Marienplatz").
+static GERMAN_LOCATION_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    gazetteer_regex(
+        GERMAN_LOCATIONS,
+        Some(r"\b\p{Lu}\p{Ll}+(?:straße|strasse|platz|allee|gasse)\b"),
+    )
+});
+
+fn gazetteer_regex(extra: &[&str], street: Option<&str>) -> Regex {
+    let mut names: Vec<&str> = KNOWN_LOCATIONS.iter().chain(extra).copied().collect();
+    names.sort_by_key(|n| std::cmp::Reverse(n.len()));
+    let alternation: Vec<String> = names.iter().map(|n| regex::escape(n)).collect();
+    let gazetteer = format!(r"\b(?:{})\b", alternation.join("|"));
+    Regex::new(&match street {
+        Some(street) => format!("{}|{}", street, gazetteer),
+        None => gazetteer,
+    })
+    .unwrap()
+}
+
+/// Optional honorific followed by two or more capitalised words ("Dr. Jane Doe", "Elon Musk").
+static PERSON_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\b(?:(?:Mr|Mrs|Ms|Dr|Prof)\.?\s+)?[A-Z][a-z]+(?:\s+[A-Z][a-z]+)+\b").unwrap()
+});
+
+/// As [`PERSON_PATTERN`], with French honorifics and accented names ("Mme Élise Dupont").
+static FRENCH_PERSON_PATTERN: Lazy<Regex> = Lazy::new(|| person_regex("M|Mme|Mlle|Dr|Pr"));
+
+/// As [`PERSON_PATTERN`], with German honorifics and umlauts ("Herr Jürgen Müller").
+static GERMAN_PERSON_PATTERN: Lazy<Regex> = Lazy::new(|| person_regex("Herr|Frau|Dr|Prof"));
+
+fn person_regex(titles: &str) -> Regex {
+    Regex::new(&format!(
+        r"\b(?:(?:{})\.?\s+)?\p{{Lu}}\p{{Ll}}+(?:[\s-]+\p{{Lu}}\p{{Ll}}+)+\b",
+        titles
+    ))
+    .unwrap()
+}
+
+/// An entity marker emoji (optionally with variation selector U+FE0F) followed by the
+/// marked text: an email address, or a run of capitalised words ("📍 New York").
+static EMOJI_ENTITY_PATTERN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(📧|📍|👤|🏢)\x{FE0F}?[ \t]*([^\s,;]+@[^\s,;]+|\p{Lu}[\p{L}&'-]*(?:[ \t]+\p{Lu}[\p{L}&'-]*)*)",
+    )
+    .unwrap()
+});
+
+/// Entity type announced by a marker emoji in chat exports and notes.
+fn emoji_entity_type(emoji: &str) -> Option<EntityType> {
+    match emoji {
+        "📧" => Some(EntityType::Email),
+        "📍" => Some(EntityType::Location),
+        "👤" => Some(EntityType::Person),
+        "🏢" => Some(EntityType::Organization),
+        _ => None,
+    }
+}
+
+/// Entities introduced by a marker emoji: 📧 an email address, 📍 a location, 👤 a person
+/// and 🏢 an organization. The entity is the text after the emoji, which need not be in
+/// the gazetteer; a 📧 not followed by an address is ignored.
+pub fn extract_emoji_entities(text: &str) -> Vec<Entity> {
+    EMOJI_ENTITY_PATTERN
+        .captures_iter(text)
+        .filter_map(|caps| {
+            let entity_type = emoji_entity_type(caps.get(1)?.as_str())?;
+            let m = caps.get(2)?;
+            if (entity_type == EntityType::Email) != m.as_str().contains('@') {
+                return None;
+            }
+            Some(Entity {
+                text: m.as_str().to_string(),
+                entity_type,
+                start: m.start(),
+                end: m.end(),
+                canonical_form: None,
+            })
+        })
+        .collect()
+}
+
+/// Extracts named entities from English `text`, ordered by position.
+///
+/// Emoji-marked entities ([`extract_emoji_entities`]) come first, then patterns in
+/// priority order (email, organization, location, person); a lower-priority match
+/// overlapping an accepted span is discarded, so "New York" is a LOCATION rather than a
+/// two-word PERSON.
+pub fn extract_entities_from_text(text: &str) -> Vec<Entity> {
+    extract_entities_from_text_with_config(text, Language::English)
+}
+
+/// [`extract_entities_from_text`] with the organization, location and person patterns of
+/// `lang`.
+pub fn extract_entities_from_text_with_config(text: &str, lang: Language) -> Vec<Entity> {
+    let lang_patterns = patterns_for(lang);
+    let patterns: [(&Lazy<Regex>, EntityType); 4] = [
+        (&EMAIL_PATTERN, EntityType::Email),
+        (lang_patterns.organization, EntityType::Organization),
+        (lang_patterns.location, EntityType::Location),
+        (lang_patterns.person, EntityType::Person),
+    ];
+
+    let mut entities: Vec<Entity> = extract_emoji_entities(text);
+    for (pattern, entity_type) in patterns {
+        for m in pattern.find_iter(text) {
+            let overlaps = entities
+                .iter()
+                .any(|e| m.start() < e.end && e.start < m.end());
+            if overlaps || (entity_type == EntityType::Person && starts_with_stop_word(m.as_str()))
+            {
+                continue;
+            }
+            entities.push(Entity {
+                text: m.as_str().to_string(),
+                entity_type,
+                start: m.start(),
+                end: m.end(),
+                canonical_form: None,
+            });
+        }
+    }
+    entities.sort_by_key(|e| e.start);
+    entities
+}
+
+/// Canonical form of `entity`, so differently written mentions compare equal:
+/// - locations: aliases map to one name ("NYC" and "New York City" become "New York");
+/// - persons: titles are dropped and "Last, First" becomes "First Last";
+/// - organizations: a trailing legal form ("Inc.", ", LLC") is dropped;
+/// - emails: lower-cased.
+///
+/// Whitespace is collapsed in every case; text with nothing to normalise is returned as is.
+pub fn normalize_entity(entity: &Entity) -> String {
+    let text = entity.text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match entity.entity_type {
+        EntityType::Location => LOCATION_ALIASES
+            .iter()
+            .find(|(alias, _)| alias.eq_ignore_ascii_case(&text))
+            .map_or(text, |(_, canonical)| canonical.to_string()),
+        EntityType::Person => {
+            let mut words: Vec<&str> = text.split(' ').collect();
+            if words.len() > 1 && PERSON_TITLES.contains(&words[0].trim_end_matches('.')) {
+                words.remove(0);
+            }
+            let name = words.join(" ");
+            match name.split_once(", ") {
+                Some((last, first)) => format!("{} {}", first, last),
+                None => name,
+            }
+        }
+        EntityType::Organization => {
+            let mut words: Vec<&str> = text.split(' ').collect();
+            let is_suffix = |w: &str| ORGANIZATION_SUFFIXES.contains(&w.trim_end_matches('.'));
+            if words.len() > 1 && words.last().is_some_and(|w| is_suffix(w)) {
+                words.pop();
+            }
+            words.join(" ").trim_end_matches(',').to_string()
+        }
+        EntityType::Email => text.to_lowercase(),
+    }
+}
+
+/// Fills in `canonical_form` for each of `entities`.
+pub fn normalize_entities(entities: &mut [Entity]) {
+    for entity in entities {
+        entity.canonical_form = Some(normalize_entity(entity));
+    }
+}
+
+/// Maps each entity found in `docs` to the paths of the documents mentioning it, so
+/// entities shared across documents can be found. Entities are keyed by surface form, or
+/// by canonical form with `normalize`. Paths appear once per entity, in document order.
+pub fn cross_reference_entities(
+    docs: &[Doc],
+    normalize: bool,
+    lang: Language,
+) -> HashMap<String, Vec<String>> {
+    let mut references: HashMap<String, Vec<String>> = HashMap::new();
+    for doc in docs {
+        let mut entities = extract_entities_from_text_with_config(&doc.text, lang);
+        if normalize {
+            normalize_entities(&mut entities);
+        }
+        for entity in entities {
+            let key = entity.canonical_form.unwrap_or(entity.text);
+            let paths = references.entry(key).or_default();
+            if !paths.contains(&doc.path) {
+                paths.push(doc.path.clone());
+            }
+        }
+    }
+    references
+}
+
+/// Capitalised sentence openers ("The Board", "This Week") are not person names.
+fn starts_with_stop_word(candidate: &str) -> bool {
+    candidate
+        .split_whitespace()
+        .next()
+        .map(|w| STOP_WORDS.contains(w.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find<'a>(entities: &'a [Entity], text: &str) -> Option<&'a Entity> {
+        entities.iter().find(|e| e.text == text)
+    }
+
+    #[test]
+    fn test_extracts_known_locations() {
+        let entities = extract_entities_from_text("We flew from Paris to London last week.");
+        assert_eq!(
+            find(&entities, "Paris").map(|e| e.entity_type),
+            Some(EntityType::Location)
+        );
+        assert_eq!(
+            find(&entities, "London").map(|e| e.entity_type),
+            Some(EntityType::Location)
+        );
+    }
+
+    #[test]
+    fn test_multi_word_location_is_not_a_person() {
+        let entities = extract_entities_from_text("The office moved to New York in May.");
+        let ny = find(&entities, "New York").expect("New York must be extracted");
+        assert_eq!(ny.entity_type, EntityType::Location);
+        assert!(entities.iter().all(|e| e.entity_type != EntityType::Person));
+    }
+
+    #[test]
+    fn test_extracts_person_email_and_organization() {
+        let text = "Elon Musk met Dr. Jane Doe at Acme Corp. Contact jane@example.com for details.";
+        let entities = extract_entities_from_text(text);
+        assert_eq!(
+            find(&entities, "Elon Musk").map(|e| e.entity_type),
+            Some(EntityType::Person)
+        );
+        assert_eq!(
+            find(&entities, "Dr. Jane Doe").map(|e| e.entity_type),
+            Some(EntityType::Person)
+        );
+        assert_eq!(
+            find(&entities, "Acme Corp.").map(|e| e.entity_type),
+            Some(EntityType::Organization)
+        );
+        assert_eq!(
+            find(&entities, "jane@example.com").map(|e| e.entity_type),
+            Some(EntityType::Email)
+        );
+    }
+
+    #[test]
+    fn test_entities_are_ordered_and_offsets_are_valid() {
+        let text = "London calling Paris";
+        let entities = extract_entities_from_text(text);
+        assert_eq!(entities.len(), 2);
+        assert!(entities[0].start < entities[1].start);
+        for e in &entities {
+            assert_eq!(&text[e.start..e.end], e.text);
+        }
+    }
+
+    #[test]
+    fn test_sentence_opener_is_not_a_person() {
+        let entities = extract_entities_from_text("The Board approved the budget.");
+        assert!(entities.is_empty(), "unexpected entities: {:?}", entities);
+    }
+
+    #[test]
+    fn test_cross_reference_entities_shared_and_unique() {
+        let doc = |path: &str, text: &str| Doc {
+            id: format!("doc-{}", path),
+            path: path.to_string(),
+            text: text.to_string(),
+            ..Default::default()
+        };
+        let docs = vec![
+            doc("a.txt", "Meeting in London with the Berlin team."),
+            doc("b.txt", "Follow-up call from London."),
+        ];
+        let refs = cross_reference_entities(&docs, false, Language::English);
+        assert_eq!(
+            refs.get("London"),
+            Some(&vec!["a.txt".to_string(), "b.txt".to_string()])
+        );
+        assert_eq!(refs.get("Berlin"), Some(&vec!["a.txt".to_string()]));
+    }
+
+    #[test]
+    fn test_emoji_markers_type_the_following_text() {
+        let text = "📍 New York\n👤 Alice Smith\n📍 Springfield\n📧 ops@example.com\n🏢 Northwind";
+        let entities = extract_entities_from_text(text);
+        let kind = |t: &str| find(&entities, t).map(|e| e.entity_type);
+        assert_eq!(kind("New York"), Some(EntityType::Location));
+        assert_eq!(kind("Alice Smith"), Some(EntityType::Person));
+        // Not in the gazetteer, but marked as a place.
+        assert_eq!(kind("Springfield"), Some(EntityType::Location));
+        assert_eq!(kind("ops@example.com"), Some(EntityType::Email));
+        assert_eq!(kind("Northwind"), Some(EntityType::Organization));
+        assert_eq!(entities.len(), 5, "{:?}", entities);
+        for e in &entities {
+            assert_eq!(&text[e.start..e.end], e.text);
+        }
+    }
+
+    #[test]
+    fn test_email_marker_without_address_is_ignored() {
+        assert!(extract_emoji_entities("📧 Reply Soon").is_empty());
+    }
+
+    fn normalized(text: &str, entity_type: EntityType) -> String {
+        normalize_entity(&Entity {
+            text: text.to_string(),
+            entity_type,
+            start: 0,
+            end: text.len(),
+            canonical_form: None,
+        })
+    }
+
+    #[test]
+    fn test_location_aliases_share_a_canonical_form() {
+        let nyc = normalized("NYC", EntityType::Location);
+        assert_eq!(nyc, normalized("New York City", EntityType::Location));
+        assert_eq!(nyc, normalized("New York", EntityType::Location));
+        assert_eq!(nyc, "New York");
+        assert_eq!(normalized("Paris", EntityType::Location), "Paris");
+    }
+
+    #[test]
+    fn test_person_organization_and_email_normalization() {
+        assert_eq!(normalized("Dr. Jane Doe", EntityType::Person), "Jane Doe");
+        assert_eq!(normalized("Doe, Jane", EntityType::Person), "Jane Doe");
+        assert_eq!(normalized("Acme Corp.", EntityType::Organization), "Acme");
+        assert_eq!(
+            normalized("Initech, LLC", EntityType::Organization),
+            "Initech"
+        );
+        assert_eq!(
+            normalized("Stanford University", EntityType::Organization),
+            "Stanford University"
+        );
+        assert_eq!(
+            normalized("Jane@Example.com", EntityType::Email),
+            "jane@example.com"
+        );
+    }
+
+    #[test]
+    fn test_normalized_cross_reference_merges_aliases() {
+        let doc = |path: &str, text: &str| Doc {
+            path: path.to_string(),
+            text: text.to_string(),
+            ..Default::default()
+        };
+        let docs = vec![
+            doc("a.txt", "Landed in NYC on Monday."),
+            doc("b.txt", "The New York City office opens soon."),
+        ];
+        let raw = cross_reference_entities(&docs, false, Language::English);
+        assert!(raw.contains_key("NYC") && raw.contains_key("New York City"));
+        let merged = cross_reference_entities(&docs, true, Language::English);
+        assert_eq!(
+            merged.get("New York"),
+            Some(&vec!["a.txt".to_string(), "b.txt".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_empty_text_has_no_entities() {
+        assert!(extract_entities_from_text("").is_empty());
+    }
+
+    #[test]
+    fn test_french_organization_and_locations() {
+        let text = "Société Générale SA a ouvert un bureau rue de Rivoli, près de Londres.";
+        let entities = extract_entities_from_text_with_config(text, Language::French);
+        assert_eq!(
+            find(&entities, "Société Générale SA").map(|e| e.entity_type),
+            Some(EntityType::Organization)
+        );
+        assert_eq!(
+            find(&entities, "rue de Rivoli").map(|e| e.entity_type),
+            Some(EntityType::Location)
+        );
+        assert_eq!(
+            find(&entities, "Londres").map(|e| e.entity_type),
+            Some(EntityType::Location)
+        );
+        // The English patterns miss both the accented name and the legal form.
+        assert!(find(&extract_entities_from_text(text), "Société Générale SA").is_none());
+    }
+
+    #[test]
+    fn test_german_patterns_and_language_fallback() {
+        let text = "Herr Jürgen Müller arbeitet bei Siemens AG in München.";
+        let entities = extract_entities_from_text_with_config(text, Language::from_code("de"));
+        let org = find(&entities, "Siemens AG").expect("Siemens AG must be extracted");
+        assert_eq!(org.entity_type, EntityType::Organization);
+        assert_eq!(normalize_entity(org), "Siemens");
+        assert_eq!(
+            find(&entities, "Herr Jürgen Müller").map(|e| e.entity_type),
+            Some(EntityType::Person)
+        );
+        assert_eq!(
+            find(&entities, "München").map(|e| e.entity_type),
+            Some(EntityType::Location)
+        );
+
+        assert_eq!(Language::from_code("pt"), Language::English);
+        let fallback = load_ner_patterns("pt");
+        assert_eq!(
+            fallback.organization.as_str(),
+            ORGANIZATION_PATTERN.as_str()
+        );
+        assert!(load_ner_patterns("fr").organization.is_match("Renault SAS"));
+    }
+}
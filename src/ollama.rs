@@ -0,0 +1,647 @@
+// Ollama integration behind a small trait so retrieval can be exercised without a model.
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+
+use crate::color::ColorPrinter;
+use crate::stats::csv_field;
+use crate::{markdown_unsupported, OutputFormat};
+
+/// Default for `--ollama-timeout`, in seconds.
+pub const DEFAULT_OLLAMA_TIMEOUT_SECS: u64 = 60;
+
+/// Generation timeout: `--ollama-timeout` if given, else `OLLAMA_TIMEOUT_SECS`, else
+/// [`DEFAULT_OLLAMA_TIMEOUT_SECS`]. An unparsable environment value is reported and ignored.
+pub fn resolve_timeout(flag: Option<u64>) -> Duration {
+    let secs = flag.unwrap_or_else(|| match std::env::var("OLLAMA_TIMEOUT_SECS") {
+        Ok(v) => v.trim().parse().unwrap_or_else(|_| {
+            eprintln!(
+                "Warning: ignoring OLLAMA_TIMEOUT_SECS={:?}; expected whole seconds",
+                v
+            );
+            DEFAULT_OLLAMA_TIMEOUT_SECS
+        }),
+        Err(_) => DEFAULT_OLLAMA_TIMEOUT_SECS,
+    });
+    Duration::from_secs(secs)
+}
+
+/// Text-generation backend used by `voltai query`.
+///
+/// `OllamaCli` is the production implementation; tests substitute a mock to assert
+/// whether (and how) generation was attempted.
+pub trait LlmBackend {
+    /// Cheap health check run before any generation request.
+    fn is_available(&self) -> bool;
+
+    /// Smallest (fastest) installed model, if the backend can enumerate models.
+    fn probe_model(&self) -> Option<String> {
+        None
+    }
+
+    /// Runs `prompt` through `model` and returns the generated text.
+    fn generate(&self, model: &str, prompt: &str) -> Result<String>;
+}
+
+/// Talks to a local Ollama install through the `ollama` command-line tool.
+pub struct OllamaCli {
+    /// `ollama run` is killed once it has run this long.
+    pub timeout: Duration,
+}
+
+impl Default for OllamaCli {
+    fn default() -> Self {
+        OllamaCli {
+            timeout: Duration::from_secs(DEFAULT_OLLAMA_TIMEOUT_SECS),
+        }
+    }
+}
+
+/// Runs `cmd` to completion like [`Command::output`], killing it once `timeout` elapses.
+/// Output is drained on helper threads so a chatty child cannot block on a full pipe.
+fn output_with_timeout(cmd: &mut Command, timeout: Duration) -> Result<Output> {
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut buf = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buf);
+            }
+            buf
+        })
+    };
+    let stdout = drain(
+        child
+            .stdout
+            .take()
+            .map(|p| Box::new(p) as Box<dyn Read + Send>),
+    );
+    let stderr = drain(
+        child
+            .stderr
+            .take()
+            .map(|p| Box::new(p) as Box<dyn Read + Send>),
+    );
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!("timed out after {}s", timeout.as_secs_f32()));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+impl LlmBackend for OllamaCli {
+    fn is_available(&self) -> bool {
+        Command::new("ollama")
+            .arg("list")
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false)
+    }
+
+    fn probe_model(&self) -> Option<String> {
+        let out = Command::new("ollama").arg("list").output().ok()?;
+        if !out.status.success() {
+            return None;
+        }
+        parse_smallest_model(&String::from_utf8_lossy(&out.stdout))
+    }
+
+    fn generate(&self, model: &str, prompt: &str) -> Result<String> {
+        let o = output_with_timeout(
+            Command::new("ollama").arg("run").arg(model).arg(prompt),
+            self.timeout,
+        )
+        .map_err(|e| anyhow!("ollama run failed ({}): {}", model, e))?;
+        if o.status.success() {
+            Ok(String::from_utf8_lossy(&o.stdout).into_owned())
+        } else {
+            Err(anyhow!(
+                "ollama run failed ({}): {}",
+                model,
+                String::from_utf8_lossy(&o.stderr)
+            ))
+        }
+    }
+}
+
+/// Talks to an Ollama server over its HTTP API (`--ollama-url`), e.g. one running on
+/// another machine or in a container without the `ollama` binary on `PATH`.
+pub struct OllamaHttp {
+    base_url: String,
+    agent: ureq::Agent,
+    timeout: Duration,
+}
+
+#[derive(Deserialize)]
+struct TagsResponse {
+    models: Vec<ModelTag>,
+}
+
+#[derive(Deserialize)]
+struct ModelTag {
+    name: String,
+    #[serde(default)]
+    size: u64,
+    #[serde(default)]
+    modified_at: String,
+    #[serde(default)]
+    details: ModelDetails,
+}
+
+#[derive(Deserialize, Default)]
+struct ModelDetails {
+    family: Option<String>,
+    parameter_size: Option<String>,
+}
+
+/// An installed model, as listed by `voltai model-list`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct OllamaModel {
+    pub name: String,
+    pub size_bytes: u64,
+    pub modified_at: String,
+    pub family: Option<String>,
+    pub parameter_size: Option<String>,
+    /// The model `voltai query` picks when `--model` is not given.
+    pub default: bool,
+}
+
+#[derive(Deserialize)]
+struct GenerateResponse {
+    response: String,
+}
+
+/// One line of the NDJSON stream returned by `POST /api/pull`.
+#[derive(Deserialize)]
+struct PullChunk {
+    #[serde(default)]
+    status: String,
+    total: Option<u64>,
+    completed: Option<u64>,
+    error: Option<String>,
+}
+
+impl OllamaHttp {
+    /// `base_url` is the server root, e.g. `http://localhost:11434`. Each request is
+    /// abandoned after `timeout`.
+    pub fn new(base_url: &str, timeout: Duration) -> Self {
+        OllamaHttp {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            agent: ureq::AgentBuilder::new()
+                .timeout_connect(Duration::from_secs(2))
+                .timeout(timeout)
+                .build(),
+            timeout,
+        }
+    }
+
+    fn tags(&self) -> Result<TagsResponse> {
+        Ok(self
+            .agent
+            .get(&format!("{}/api/tags", self.base_url))
+            .call()?
+            .into_json()?)
+    }
+
+    /// Installed models, smallest first. The default is `preferred` (normally
+    /// `OLLAMA_MODEL`) when installed, otherwise the smallest model, as in `voltai query`.
+    pub fn list_models(&self, preferred: Option<&str>) -> Result<Vec<OllamaModel>> {
+        let tags = self
+            .tags()
+            .map_err(|e| anyhow!("cannot list models at {}: {}", self.base_url, e))?;
+        let mut models: Vec<OllamaModel> = tags
+            .models
+            .into_iter()
+            .map(|m| OllamaModel {
+                name: m.name,
+                size_bytes: m.size,
+                modified_at: m.modified_at,
+                family: m.details.family,
+                parameter_size: m.details.parameter_size,
+                default: false,
+            })
+            .collect();
+        models.sort_by(|a, b| a.size_bytes.cmp(&b.size_bytes).then(a.name.cmp(&b.name)));
+        let default = preferred
+            .and_then(|p| models.iter().position(|m| m.name == p))
+            .unwrap_or(0);
+        if let Some(m) = models.get_mut(default) {
+            m.default = true;
+        }
+        Ok(models)
+    }
+}
+
+impl OllamaHttp {
+    /// Whether `model` is installed; a name without a tag means `:latest`, as in Ollama.
+    pub fn has_model(&self, model: &str) -> Result<bool> {
+        let tagged = if model.contains(':') {
+            model.to_string()
+        } else {
+            format!("{}:latest", model)
+        };
+        Ok(self
+            .list_models(None)?
+            .iter()
+            .any(|m| m.name == model || m.name == tagged))
+    }
+
+    /// Downloads `model` via `POST /api/pull`, mapping the `completed` / `total` byte
+    /// counts of each progress chunk onto `bar`. Downloads can take far longer than a
+    /// generation, so the timeout only bounds how long the stream may stall.
+    pub fn pull_model(&self, model: &str, bar: &ProgressBar) -> Result<()> {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(Duration::from_secs(2))
+            .timeout_read(self.timeout)
+            .build();
+        let resp = agent
+            .post(&format!("{}/api/pull", self.base_url))
+            .send_json(serde_json::json!({ "name": model, "stream": true }))
+            .map_err(|e| match e {
+                ureq::Error::Status(code, r) => anyhow!(
+                    "ollama pull failed ({}): HTTP {}: {}",
+                    model,
+                    code,
+                    r.into_string().unwrap_or_default()
+                ),
+                e => anyhow!("failed to reach ollama at {}: {}", self.base_url, e),
+            })?;
+
+        for line in BufReader::new(resp.into_reader()).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let chunk: PullChunk = serde_json::from_str(&line)
+                .map_err(|e| anyhow!("unexpected pull progress {:?}: {}", line, e))?;
+            if let Some(err) = chunk.error {
+                return Err(anyhow!("ollama pull failed ({}): {}", model, err));
+            }
+            if let Some(total) = chunk.total {
+                // Each layer reports its own total; the bar follows the current layer.
+                if bar.length() != Some(total) {
+                    bar.set_length(total);
+                    bar.set_position(0);
+                }
+            }
+            if let Some(done) = chunk.completed {
+                bar.set_position(done);
+            }
+            bar.set_message(chunk.status.clone());
+            if chunk.status == "success" {
+                if let Some(len) = bar.length() {
+                    bar.set_position(len);
+                }
+                bar.finish_with_message(format!("pulled {}", model));
+                return Ok(());
+            }
+        }
+        Err(anyhow!(
+            "ollama pull ({}) ended before reporting success",
+            model
+        ))
+    }
+}
+
+/// `1.2 GB`-style size, matching `ollama list`.
+fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut v = bytes as f64;
+    let mut unit = 0;
+    while v >= 1000.0 && unit + 1 < UNITS.len() {
+        v /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", v, UNITS[unit])
+    }
+}
+
+/// Writes `models` for `voltai model-list`. In text mode the default model is starred
+/// (and its name coloured when `printer` colours output).
+pub fn write_model_list(
+    models: &[OllamaModel],
+    format: OutputFormat,
+    printer: &ColorPrinter,
+    out: &mut dyn Write,
+) -> Result<()> {
+    let opt = |v: &Option<String>| v.clone().unwrap_or_else(|| "-".to_string());
+    match format {
+        OutputFormat::Json | OutputFormat::PrettyJson => {
+            serde_json::to_writer_pretty(&mut *out, models)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Jsonl => {
+            for m in models {
+                serde_json::to_writer(&mut *out, m)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv => {
+            writeln!(
+                out,
+                "name,size_bytes,modified_at,family,parameter_size,default"
+            )?;
+            for m in models {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{}",
+                    csv_field(&m.name),
+                    m.size_bytes,
+                    csv_field(&m.modified_at),
+                    csv_field(m.family.as_deref().unwrap_or_default()),
+                    csv_field(m.parameter_size.as_deref().unwrap_or_default()),
+                    m.default
+                )?;
+            }
+        }
+        OutputFormat::Markdown => return Err(markdown_unsupported()),
+        OutputFormat::Text => {
+            if models.is_empty() {
+                writeln!(out, "No models installed.")?;
+                return Ok(());
+            }
+            let width = models
+                .iter()
+                .map(|m| m.name.len())
+                .max()
+                .unwrap_or(0)
+                .max(4);
+            writeln!(
+                out,
+                "  {:<width$} {:>9} {:>7} {:<10} MODIFIED",
+                "NAME", "SIZE", "PARAMS", "FAMILY"
+            )?;
+            for m in models {
+                write!(out, "{} ", if m.default { "*" } else { " " })?;
+                let name = format!("{:<width$}", m.name);
+                if m.default {
+                    printer.print_keyword(out, &name)?;
+                } else {
+                    write!(out, "{}", name)?;
+                }
+                writeln!(
+                    out,
+                    " {:>9} {:>7} {:<10} {}",
+                    human_size(m.size_bytes),
+                    opt(&m.parameter_size),
+                    opt(&m.family),
+                    m.modified_at
+                )?;
+            }
+            writeln!(out, "* default model for `voltai query`")?;
+        }
+    }
+    Ok(())
+}
+
+impl LlmBackend for OllamaHttp {
+    fn is_available(&self) -> bool {
+        self.tags().is_ok()
+    }
+
+    fn probe_model(&self) -> Option<String> {
+        self.tags()
+            .ok()?
+            .models
+            .into_iter()
+            .min_by_key(|m| m.size)
+            .map(|m| m.name)
+    }
+
+    fn generate(&self, model: &str, prompt: &str) -> Result<String> {
+        let resp = self
+            .agent
+            .post(&format!("{}/api/generate", self.base_url))
+            .send_json(serde_json::json!({
+                "model": model,
+                "prompt": prompt,
+                "stream": false,
+            }))
+            .map_err(|e| match e {
+                ureq::Error::Status(code, r) => anyhow!(
+                    "ollama generate failed ({}): HTTP {}: {}",
+                    model,
+                    code,
+                    r.into_string().unwrap_or_default()
+                ),
+                e => anyhow!("failed to reach ollama at {}: {}", self.base_url, e),
+            })?;
+        Ok(resp.into_json::<GenerateResponse>()?.response)
+    }
+}
+
+/// Picks the smallest model from `ollama list` output.
+///
+/// Lines look like `NAME  ID  SIZE  MODIFIED`, where SIZE is two tokens such as
+/// `3.3 GB` or `700 MB`. The header line and lines without a size are skipped.
+pub fn parse_smallest_model(list_output: &str) -> Option<String> {
+    let mut best: Option<(String, f32)> = None;
+    for line in list_output.lines() {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        if cols.len() < 3 {
+            continue;
+        }
+        let size = cols.windows(2).skip(1).find_map(|pair| {
+            let v = pair[0].parse::<f32>().ok()?;
+            let bytes = match pair[1].to_uppercase().as_str() {
+                "GB" => v * 1024.0 * 1024.0 * 1024.0,
+                "MB" => v * 1024.0 * 1024.0,
+                "KB" => v * 1024.0,
+                _ => v,
+            };
+            Some(bytes)
+        });
+        if let Some(sz) = size {
+            match &best {
+                Some((_, bsz)) if *bsz <= sz => {}
+                _ => best = Some((cols[0].to_string(), sz)),
+            }
+        }
+    }
+    best.map(|(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_smallest_model_picks_smallest_size() {
+        let out = "NAME            ID              SIZE      MODIFIED\n\
+                   mistral:latest  abc123          4.1 GB    2 days ago\n\
+                   gemma3:1b       def456          815 MB    5 hours ago\n\
+                   llama3:8b       0a1b2c          4.7 GB    3 weeks ago\n";
+        assert_eq!(parse_smallest_model(out), Some("gemma3:1b".to_string()));
+    }
+
+    #[test]
+    fn test_http_generate_times_out_on_slow_server() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/api/generate")
+            .with_chunked_body(|w| {
+                std::thread::sleep(Duration::from_secs(2));
+                w.write_all(br#"{"response":"SLOW ANSWER"}"#)
+            })
+            .create();
+
+        let impatient = OllamaHttp::new(&server.url(), Duration::from_secs(1));
+        assert!(impatient.generate("m", "hi").is_err());
+        let patient = OllamaHttp::new(&server.url(), Duration::from_secs(3));
+        assert_eq!(patient.generate("m", "hi").unwrap(), "SLOW ANSWER");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_watchdog_kills_slow_command() {
+        let started = Instant::now();
+        let err = output_with_timeout(Command::new("sleep").arg("5"), Duration::from_millis(200))
+            .unwrap_err();
+        assert!(err.to_string().contains("timed out"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(4));
+
+        let out = output_with_timeout(
+            Command::new("sh").args(["-c", "sleep 0.3; echo done"]),
+            Duration::from_secs(3),
+        )
+        .unwrap();
+        assert!(out.status.success());
+        assert_eq!(String::from_utf8_lossy(&out.stdout), "done\n");
+    }
+
+    #[test]
+    fn test_timeout_flag_wins_over_default() {
+        assert_eq!(resolve_timeout(Some(5)), Duration::from_secs(5));
+    }
+
+    const TAGS: &str = r#"{"models":[
+        {"name":"llama3:8b","size":4661224676,"modified_at":"2024-05-01T10:00:00Z",
+         "details":{"family":"llama","parameter_size":"8.0B"}},
+        {"name":"gemma3:1b","size":815319791,"modified_at":"2024-06-02T09:30:00Z",
+         "details":{"family":"gemma3","parameter_size":"999.89M"}},
+        {"name":"mistral:latest","size":4113301824}]}"#;
+
+    #[test]
+    fn test_list_models_parses_and_sorts_by_size() {
+        let mut server = mockito::Server::new();
+        server.mock("GET", "/api/tags").with_body(TAGS).create();
+        let http = OllamaHttp::new(&server.url(), Duration::from_secs(5));
+
+        let models = http.list_models(None).unwrap();
+        let names: Vec<&str> = models.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["gemma3:1b", "mistral:latest", "llama3:8b"]);
+        assert_eq!(
+            models[0],
+            OllamaModel {
+                name: "gemma3:1b".into(),
+                size_bytes: 815319791,
+                modified_at: "2024-06-02T09:30:00Z".into(),
+                family: Some("gemma3".into()),
+                parameter_size: Some("999.89M".into()),
+                default: true,
+            }
+        );
+        // Models without details still parse.
+        assert_eq!(models[1].family, None);
+        assert!(!models[1].default && !models[2].default);
+
+        assert!(http.has_model("mistral").unwrap());
+        assert!(!http.has_model("llama3").unwrap());
+
+        // A preferred model overrides the smallest one only when installed.
+        let models = http.list_models(Some("llama3:8b")).unwrap();
+        assert!(models[2].default && !models[0].default);
+        let models = http.list_models(Some("phi3")).unwrap();
+        assert!(models[0].default);
+    }
+
+    #[test]
+    fn test_model_list_text_stars_default() {
+        let mut server = mockito::Server::new();
+        server.mock("GET", "/api/tags").with_body(TAGS).create();
+        let models = OllamaHttp::new(&server.url(), Duration::from_secs(5))
+            .list_models(None)
+            .unwrap();
+        let mut out = Vec::new();
+        write_model_list(
+            &models,
+            OutputFormat::Text,
+            &ColorPrinter::default(),
+            &mut out,
+        )
+        .unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[1].starts_with("* gemma3:1b"), "{}", text);
+        assert!(lines[1].contains("815.3 MB") && lines[1].contains("999.89M"));
+        assert!(lines[2].starts_with("  mistral:latest"));
+    }
+
+    #[test]
+    fn test_pull_model_drives_progress_bar_to_completion() {
+        let mut server = mockito::Server::new();
+        let pull = server
+            .mock("POST", "/api/pull")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"name":"gemma3:1b","stream":true}"#.into(),
+            ))
+            .with_body(
+                "{\"status\":\"pulling manifest\"}\n\
+                 {\"status\":\"pulling aa11\",\"digest\":\"sha256:aa11\",\"total\":1000,\"completed\":400}\n\
+                 {\"status\":\"pulling aa11\",\"digest\":\"sha256:aa11\",\"total\":1000,\"completed\":1000}\n\
+                 {\"status\":\"success\"}\n",
+            )
+            .create();
+
+        let bar = ProgressBar::hidden();
+        OllamaHttp::new(&server.url(), Duration::from_secs(5))
+            .pull_model("gemma3:1b", &bar)
+            .unwrap();
+        pull.assert();
+        assert_eq!(bar.length(), Some(1000));
+        assert_eq!(bar.position(), 1000);
+        assert!(bar.is_finished());
+    }
+
+    #[test]
+    fn test_pull_model_reports_stream_errors() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("POST", "/api/pull")
+            .with_body("{\"status\":\"pulling manifest\"}\n{\"error\":\"file does not exist\"}\n")
+            .create();
+        let err = OllamaHttp::new(&server.url(), Duration::from_secs(5))
+            .pull_model("nope", &ProgressBar::hidden())
+            .unwrap_err();
+        assert!(err.to_string().contains("file does not exist"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_smallest_model_handles_empty_listing() {
+        assert_eq!(
+            parse_smallest_model("NAME    ID    SIZE    MODIFIED\n"),
+            None
+        );
+        assert_eq!(parse_smallest_model(""), None);
+    }
+}
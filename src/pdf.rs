@@ -0,0 +1,149 @@
+// PDF document-information metadata (title, author, subject) read with lopdf, the parser
+// pdf-extract already uses for text extraction.
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use lopdf::{decode_text_string, Document, Object};
+
+/// Fields of interest from a PDF's Info dictionary, plus the page count.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PdfMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub page_count: Option<u32>,
+}
+
+impl PdfMetadata {
+    /// Flattens the present fields into `Doc::metadata` entries
+    /// (`title`, `author`, `subject`, `page_count`).
+    pub fn to_map(&self) -> HashMap<String, String> {
+        let mut map = HashMap::new();
+        for (key, value) in [
+            ("title", &self.title),
+            ("author", &self.author),
+            ("subject", &self.subject),
+        ] {
+            if let Some(v) = value {
+                map.insert(key.to_string(), v.clone());
+            }
+        }
+        if let Some(n) = self.page_count {
+            map.insert("page_count".to_string(), n.to_string());
+        }
+        map
+    }
+}
+
+/// Reads the Info dictionary of the PDF at `path`. Missing or blank fields are `None`;
+/// a PDF without an Info dictionary yields only the page count.
+pub fn read_pdf_metadata(path: &Path) -> Result<PdfMetadata> {
+    let doc = Document::load(path)
+        .map_err(|e| anyhow!("failed to parse PDF {}: {}", path.display(), e))?;
+    let page_count = Some(doc.get_pages().len() as u32);
+
+    let info = doc
+        .trailer
+        .get(b"Info")
+        .ok()
+        .and_then(|obj| doc.dereference(obj).ok())
+        .and_then(|(_, obj)| obj.as_dict().ok());
+    let field = |key: &[u8]| -> Option<String> {
+        let obj = info?.get(key).ok()?;
+        let (_, obj): (_, &Object) = doc.dereference(obj).ok()?;
+        let text = decode_text_string(obj).ok()?;
+        let text = text.trim();
+        (!text.is_empty()).then(|| text.to_string())
+    };
+
+    Ok(PdfMetadata {
+        title: field(b"Title"),
+        author: field(b"Author"),
+        subject: field(b"Subject"),
+        page_count,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use lopdf::{dictionary, Stream};
+
+    /// Writes a one-page PDF whose Info dictionary holds `info` (key, value) pairs.
+    pub(crate) fn write_pdf(path: &Path, info: &[(&str, &str)]) {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let content_id = doc.add_object(Stream::new(dictionary! {}, Vec::new()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+            "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+        });
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        if !info.is_empty() {
+            let mut dict = lopdf::Dictionary::new();
+            for (k, v) in info {
+                dict.set(*k, Object::string_literal(*v));
+            }
+            let info_id = doc.add_object(dict);
+            doc.trailer.set("Info", info_id);
+        }
+        doc.save(path).unwrap();
+    }
+
+    #[test]
+    fn test_reads_title_author_subject_and_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("report.pdf");
+        write_pdf(
+            &path,
+            &[
+                ("Title", "Quarterly Report"),
+                ("Author", "Jane Doe"),
+                ("Subject", "Finance"),
+            ],
+        );
+        let meta = read_pdf_metadata(&path).unwrap();
+        assert_eq!(meta.title.as_deref(), Some("Quarterly Report"));
+        assert_eq!(meta.author.as_deref(), Some("Jane Doe"));
+        assert_eq!(meta.subject.as_deref(), Some("Finance"));
+        assert_eq!(meta.page_count, Some(1));
+        assert_eq!(
+            meta.to_map().get("title").map(String::as_str),
+            Some("Quarterly Report")
+        );
+    }
+
+    #[test]
+    fn test_missing_info_dictionary_yields_no_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bare.pdf");
+        write_pdf(&path, &[("Title", "   ")]);
+        let meta = read_pdf_metadata(&path).unwrap();
+        assert_eq!(meta.title, None);
+        assert_eq!(meta.author, None);
+        assert_eq!(meta.page_count, Some(1));
+    }
+
+    #[test]
+    fn test_invalid_pdf_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("broken.pdf");
+        std::fs::write(&path, b"not a pdf").unwrap();
+        assert!(read_pdf_metadata(&path).is_err());
+    }
+}
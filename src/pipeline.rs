@@ -0,0 +1,134 @@
+// `voltai pipeline`: index a directory and run the analysis subcommands over it in one go,
+// writing each step's output as a JSON file under a single output directory.
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use clap::ValueEnum;
+
+use crate::{
+    index_dir_with_options, index_entities, progress_bar, stats, IndexOptions, OutputFormat,
+};
+
+/// One stage of `voltai pipeline`, named after the subcommand it runs.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PipelineStep {
+    /// `voltai index` → `index.json`.
+    Index,
+    /// `voltai index-entities` over the index from the `index` step → `entities.json`.
+    Ner,
+    /// `voltai text-stats --format json` → `text_stats.json`.
+    Stats,
+}
+
+impl PipelineStep {
+    /// File the step writes inside the pipeline's output directory.
+    pub fn output_file(self) -> &'static str {
+        match self {
+            PipelineStep::Index => "index.json",
+            PipelineStep::Ner => "entities.json",
+            PipelineStep::Stats => "text_stats.json",
+        }
+    }
+}
+
+/// Runs `steps` in order over `dir`, returning the files written to `out_dir`.
+///
+/// Steps that consume another step's output (`ner` reads the index) fail up front when that
+/// output is neither produced earlier in the pipeline nor already present in `out_dir`.
+/// `quiet` hides the progress bar and the index step's status message.
+pub fn run_pipeline(
+    dir: &Path,
+    out_dir: &Path,
+    steps: &[PipelineStep],
+    quiet: bool,
+) -> Result<Vec<PathBuf>> {
+    let index_path = out_dir.join(PipelineStep::Index.output_file());
+    if let Some(ner) = steps.iter().position(|&s| s == PipelineStep::Ner) {
+        let indexed_before = steps[..ner].contains(&PipelineStep::Index);
+        if !indexed_before && !index_path.exists() {
+            bail!(
+                "the ner step needs an index: run the index step before it or place one at {}",
+                index_path.display()
+            );
+        }
+    }
+    std::fs::create_dir_all(out_dir)?;
+
+    let pb = progress_bar(steps.len() as u64, quiet)?;
+
+    let mut written = Vec::with_capacity(steps.len());
+    for &step in steps {
+        pb.set_message(format!("{:?}", step).to_lowercase());
+        let out = out_dir.join(step.output_file());
+        match step {
+            PipelineStep::Index => index_dir_with_options(
+                dir,
+                &out,
+                &IndexOptions {
+                    quiet,
+                    ..Default::default()
+                },
+            )?,
+            PipelineStep::Ner => index_entities(&index_path, &out, false, None, quiet)?,
+            PipelineStep::Stats => {
+                let mut w = BufWriter::new(File::create(&out)?);
+                stats::write_report(&stats::analyze_dir(dir, false), OutputFormat::Json, &mut w)?;
+            }
+        }
+        written.push(out);
+        pb.inc(1);
+    }
+    pb.finish_with_message("pipeline complete");
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const ALL_STEPS: [PipelineStep; 3] =
+        [PipelineStep::Index, PipelineStep::Ner, PipelineStep::Stats];
+
+    #[test]
+    fn test_full_pipeline_writes_every_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let docs = dir.path().join("docs");
+        std::fs::create_dir(&docs).unwrap();
+        std::fs::write(docs.join("a.txt"), "Alice flew to Paris on Monday.").unwrap();
+        std::fs::write(docs.join("b.md"), "The museum in Paris opens early.").unwrap();
+        let out_dir = dir.path().join("out");
+
+        let written = run_pipeline(&docs, &out_dir, &ALL_STEPS, true).unwrap();
+        assert_eq!(written.len(), 3);
+        assert!(written.iter().all(|p| p.exists()));
+
+        let idx = crate::load_index(&out_dir.join("index.json")).unwrap();
+        assert_eq!(idx.docs.len(), 2);
+
+        let entities: HashMap<String, Vec<String>> =
+            serde_json::from_reader(File::open(out_dir.join("entities.json")).unwrap()).unwrap();
+        assert_eq!(entities["Paris"].len(), 2);
+
+        let report: serde_json::Value =
+            serde_json::from_reader(File::open(out_dir.join("text_stats.json")).unwrap()).unwrap();
+        assert_eq!(report["corpus"]["documents"], 2);
+    }
+
+    #[test]
+    fn test_ner_without_index_fails_before_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().join("out");
+        let err = run_pipeline(
+            dir.path(),
+            &out_dir,
+            &[PipelineStep::Ner, PipelineStep::Index],
+            true,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("needs an index"));
+        assert!(!out_dir.exists());
+    }
+}
@@ -0,0 +1,114 @@
+// Text clean-up applied to each document before tokenization (`voltai index --pre-process`).
+use clap::ValueEnum;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+static SCRIPT_STYLE_PATTERN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?is)<(script|style)\b[^>]*>.*?</(script|style)>").unwrap());
+static HTML_TAG_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<!--.*?-->|<[^>]+>").unwrap());
+static URL_PATTERN: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\b(?:https?|ftp)://\S+|\bwww\.\S+").unwrap());
+static BLOCK_COMMENT_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)/\*.*?\*/").unwrap());
+// `//` only starts a comment at the start of a line or after whitespace, so `http://` survives.
+static LINE_COMMENT_PATTERN: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)(^|[ \t])//.*$").unwrap());
+
+/// One transformation of `voltai index --pre-process`.
+#[derive(ValueEnum, Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PreprocessStep {
+    /// Lowercase the whole text.
+    Lowercase,
+    /// Remove HTML tags and comments, and `<script>`/`<style>` elements with their content.
+    StripHtml,
+    /// Remove `http://`, `https://`, `ftp://` and `www.` links.
+    RemoveUrls,
+    /// Remove `/* ... */` block comments and `// ...` line comments.
+    StripCodeComments,
+    /// Trim every line and drop the leading and trailing blank lines.
+    Trim,
+}
+
+impl PreprocessStep {
+    fn apply(self, text: String) -> String {
+        match self {
+            PreprocessStep::Lowercase => text.to_lowercase(),
+            PreprocessStep::StripHtml => {
+                let text = SCRIPT_STYLE_PATTERN.replace_all(&text, " ");
+                HTML_TAG_PATTERN.replace_all(&text, " ").into_owned()
+            }
+            PreprocessStep::RemoveUrls => URL_PATTERN.replace_all(&text, "").into_owned(),
+            PreprocessStep::StripCodeComments => {
+                let text = BLOCK_COMMENT_PATTERN.replace_all(&text, "");
+                LINE_COMMENT_PATTERN.replace_all(&text, "$1").into_owned()
+            }
+            PreprocessStep::Trim => text
+                .lines()
+                .map(str::trim)
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim()
+                .to_string(),
+        }
+    }
+}
+
+/// Applies `steps` to `text` in order.
+pub fn apply_preprocessing(text: String, steps: &[PreprocessStep]) -> String {
+    steps.iter().fold(text, |text, step| step.apply(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use PreprocessStep::*;
+
+    #[test]
+    fn test_strip_html_removes_tags_and_scripts() {
+        let html = "<html><head><style>p { color: red }</style></head>\
+                    <body><!-- nav --><p>Tide <b>tables</b></p><script>track()</script></body></html>";
+        let text = apply_preprocessing(html.to_string(), &[StripHtml]);
+        assert_eq!(
+            text.split_whitespace().collect::<Vec<_>>(),
+            ["Tide", "tables"]
+        );
+    }
+
+    #[test]
+    fn test_remove_urls() {
+        let text = apply_preprocessing(
+            "see http://example.com/a?b=1 or https://x.org, and www.voltai.dev now".to_string(),
+            &[RemoveUrls],
+        );
+        assert_eq!(text, "see  or  and  now");
+    }
+
+    #[test]
+    fn test_strip_code_comments() {
+        let code = "/* licence\n   header */\nfn main() { // entry point\n    // setup\n    let url = \"http://example.com\";\n}";
+        let text = apply_preprocessing(code.to_string(), &[StripCodeComments]);
+        assert!(
+            !text.contains("licence") && !text.contains("entry point"),
+            "{}",
+            text
+        );
+        assert!(!text.contains("setup"), "{}", text);
+        assert!(text.contains("fn main() {"), "{}", text);
+        assert!(text.contains("\"http://example.com\";"), "{}", text);
+    }
+
+    #[test]
+    fn test_steps_apply_in_order() {
+        let text = "  <P>Visit HTTP://EXAMPLE.COM</P>  ".to_string();
+        // The URL pattern is case-sensitive, so it only matches after lowercasing.
+        assert_eq!(
+            apply_preprocessing(text.clone(), &[StripHtml, Lowercase, RemoveUrls, Trim]),
+            "visit"
+        );
+        assert_eq!(
+            apply_preprocessing(text, &[StripHtml, RemoveUrls, Lowercase, Trim]),
+            "visit http://example.com"
+        );
+    }
+}
@@ -0,0 +1,156 @@
+// Turns files on disk into plain text. Indexing and `voltai text-stats` both go through
+// `FileReader`, so the set of readable formats is decided in one place.
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+/// Extensions `DefaultFileReader` understands. `voltai index` only walks files with one of
+/// these, which keeps binaries (git internals, images, build artifacts) out of the index.
+pub const SUPPORTED_EXTENSIONS: [&str; 8] =
+    ["txt", "md", "csv", "json", "pdf", "yaml", "yml", "toml"];
+
+/// Extracts the text of a file. Indexing is generic over the reader, so tests can swap in
+/// one that does not touch the filesystem.
+pub trait FileReader {
+    fn read(path: &Path) -> Result<String>;
+}
+
+/// Reads plain-text formats as UTF-8, extracts PDFs with `pdf-extract`, and keeps only the
+/// string values of YAML and TOML files.
+pub struct DefaultFileReader;
+
+impl FileReader for DefaultFileReader {
+    fn read(path: &Path) -> Result<String> {
+        let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+        match ext {
+            "pdf" => {
+                pdf_extract::extract_text(path).map_err(|e| anyhow!("PDF extraction failed: {}", e))
+            }
+            "txt" | "md" | "csv" | "json" => read_text_file(path),
+            "yaml" | "yml" => read_yaml(path),
+            "toml" => read_toml(path),
+            _ => Err(anyhow!(
+                "unsupported file type '{}' for {} (supported: {})",
+                ext,
+                path.display(),
+                SUPPORTED_EXTENSIONS.join(", ")
+            )),
+        }
+    }
+}
+
+/// True when `path` has an extension `DefaultFileReader` can read.
+pub fn is_supported(path: &Path) -> bool {
+    path.extension()
+        .and_then(|s| s.to_str())
+        .is_some_and(|ext| SUPPORTED_EXTENSIONS.contains(&ext))
+}
+
+fn read_text_file(p: &Path) -> Result<String> {
+    let mut s = String::new();
+    let mut f = File::open(p)?;
+    f.read_to_string(&mut s)?;
+    Ok(s)
+}
+
+/// The string values of a YAML file, one per line; keys, numbers and booleans are left out.
+pub fn read_yaml(path: &Path) -> Result<String> {
+    let strings = crate::yaml::string_leaves(&read_text_file(path)?)
+        .map_err(|e| anyhow!("invalid YAML in {}: {}", path.display(), e))?;
+    Ok(strings.join("\n"))
+}
+
+/// The string values of a TOML file, one per line; keys, numbers, booleans and dates are
+/// left out.
+pub fn read_toml(path: &Path) -> Result<String> {
+    fn walk(value: &toml::Value, out: &mut Vec<String>) {
+        match value {
+            toml::Value::String(s) => out.push(s.clone()),
+            toml::Value::Array(items) => items.iter().for_each(|v| walk(v, out)),
+            toml::Value::Table(table) => table.values().for_each(|v| walk(v, out)),
+            _ => {}
+        }
+    }
+    let table: toml::Table = read_text_file(path)?
+        .parse()
+        .map_err(|e| anyhow!("invalid TOML in {}: {}", path.display(), e))?;
+    let mut strings = Vec::new();
+    table.values().for_each(|v| walk(v, &mut strings));
+    Ok(strings.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reads_every_text_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        for ext in ["txt", "md", "csv", "json"] {
+            let path = dir.path().join(format!("doc.{ext}"));
+            std::fs::write(&path, format!("content of {ext}")).unwrap();
+            assert_eq!(
+                DefaultFileReader::read(&path).unwrap(),
+                format!("content of {ext}")
+            );
+        }
+    }
+
+    #[test]
+    fn test_yaml_and_toml_keep_only_string_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = dir.path().join("deploy.yml");
+        std::fs::write(
+            &yaml,
+            "app:\n  name: tide-api\n  port: 8080\n  regions:\n    - north sea\n    - baltic\n  notes:\n    owner: harbour ops\n",
+        )
+        .unwrap();
+        let text = DefaultFileReader::read(&yaml).unwrap();
+        assert_eq!(text, "tide-api\nnorth sea\nbaltic\nharbour ops");
+
+        let toml = dir.path().join("Cargo.toml");
+        std::fs::write(
+            &toml,
+            "[package]\nname = \"voltai\"\nedition = \"2021\"\nversion = 1\n\n[package.metadata]\nkeywords = [\"search\", \"rag\"]\n",
+        )
+        .unwrap();
+        let text = DefaultFileReader::read(&toml).unwrap();
+        for expected in ["voltai", "2021", "search", "rag"] {
+            assert!(text.lines().any(|l| l == expected), "{expected}: {text}");
+        }
+        assert!(
+            !text.contains("package") && !text.contains("name"),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn test_reads_pdf() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("doc.pdf");
+        crate::pdf::tests::write_pdf(&path, &[]);
+        assert!(DefaultFileReader::read(&path).unwrap().trim().is_empty());
+    }
+
+    #[test]
+    fn test_unsupported_extension_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["image.png", "notes.docx", "README"] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, "data").unwrap();
+            let err = DefaultFileReader::read(&path).unwrap_err().to_string();
+            assert!(err.contains("unsupported file type"), "{name}: {err}");
+        }
+    }
+
+    #[test]
+    fn test_is_supported_matches_reader() {
+        assert!(is_supported(Path::new("a/b.md")));
+        assert!(is_supported(Path::new("paper.pdf")));
+        assert!(is_supported(Path::new("config/app.yaml")));
+        assert!(!is_supported(Path::new("photo.jpg")));
+        assert!(!is_supported(Path::new("Makefile")));
+    }
+}
@@ -0,0 +1,177 @@
+// Shareable HTML reports for `voltai report`: runs a batch of queries and renders a
+// self-contained page (summary table, per-query results, inline SVG score bars).
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::{retrieve, Index, QueryOptions};
+
+/// Width in pixels of the longest score bar.
+const BAR_WIDTH: f32 = 360.0;
+/// Vertical space per bar in the score chart.
+const BAR_PITCH: usize = 22;
+
+/// One retrieved document in a report section.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ReportResult {
+    pub path: String,
+    pub score: f32,
+    /// Query-centred excerpt, or the document's keywords when no query term occurs verbatim.
+    pub snippet: String,
+    /// Bar length relative to the best score of this query.
+    pub bar_width: f32,
+    pub bar_y: usize,
+}
+
+/// Results for one query of the batch.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct QueryReport {
+    pub query: String,
+    pub results: Vec<ReportResult>,
+    pub chart_height: usize,
+}
+
+/// Runs every query against `idx`.
+pub fn build_report(idx: &Index, queries: &[String], opts: &QueryOptions) -> Vec<QueryReport> {
+    queries
+        .iter()
+        .map(|q| {
+            let results = retrieve(idx, q, opts);
+            let best = results.iter().map(|r| r.score).fold(0.0_f32, f32::max);
+            QueryReport {
+                query: q.clone(),
+                chart_height: results.len() * BAR_PITCH,
+                results: results
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, r)| ReportResult {
+                        bar_width: if best > 0.0 {
+                            r.score / best * BAR_WIDTH
+                        } else {
+                            0.0
+                        },
+                        bar_y: i * BAR_PITCH,
+                        snippet: if r.excerpt.is_empty() {
+                            r.keywords.join(", ")
+                        } else {
+                            r.excerpt
+                        },
+                        path: r.path,
+                        score: r.score,
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+const REPORT_TEMPLATE: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>VoltAI query report</title>
+<style>
+body { font-family: system-ui, sans-serif; margin: 2rem auto; max-width: 60rem; color: #222; }
+table { border-collapse: collapse; width: 100%; }
+th, td { border: 1px solid #ccc; padding: .4rem .6rem; text-align: left; }
+th { background: #f4f4f4; }
+section { margin-top: 2.5rem; }
+.snippet { color: #555; font-size: .9rem; }
+.none { color: #888; font-style: italic; }
+svg text { font-size: 12px; }
+</style>
+</head>
+<body>
+<h1>VoltAI query report</h1>
+<p>Index: <code>{{ index }}</code> &middot; {{ reports | length }} queries</p>
+<table>
+<tr><th>#</th><th>Query</th><th>Top document</th><th>Score</th></tr>
+{% for r in reports %}<tr><td><a href="#q{{ loop.index }}">{{ loop.index }}</a></td><td>{{ r.query }}</td>{% if r.results %}<td>{{ r.results.0.path }}</td><td>{{ r.results.0.score | round(precision=4) }}</td>{% else %}<td class="none" colspan="2">no matching documents</td>{% endif %}</tr>
+{% endfor %}</table>
+{% for r in reports %}
+<section id="q{{ loop.index }}">
+<h2>{{ loop.index }}. {{ r.query }}</h2>
+{% if r.results %}<svg width="600" height="{{ r.chart_height }}" role="img" aria-label="Scores for {{ r.query }}">
+{% for d in r.results %}<rect x="0" y="{{ d.bar_y }}" width="{{ d.bar_width | round(precision=1) }}" height="16" fill="#4a7fd4"></rect><text x="{{ d.bar_width | round(precision=1) }}" dx="6" y="{{ d.bar_y + 12 }}">{{ d.score | round(precision=4) }}</text>
+{% endfor %}</svg>
+<ol>
+{% for d in r.results %}<li><strong>{{ d.path }}</strong> ({{ d.score | round(precision=4) }})<div class="snippet">{{ d.snippet }}</div></li>
+{% endfor %}</ol>
+{% else %}<p class="none">No matching documents.</p>
+{% endif %}</section>
+{% endfor %}
+</body>
+</html>
+"##;
+
+/// Renders `reports` as a standalone HTML page. Queries, paths and snippets are escaped.
+pub fn render_html(index_name: &str, reports: &[QueryReport]) -> Result<String> {
+    let mut ctx = tera::Context::new();
+    ctx.insert("index", index_name);
+    ctx.insert("reports", reports);
+    tera::Tera::one_off(REPORT_TEMPLATE, &ctx, true)
+        .map_err(|e| anyhow!("cannot render report: {}", e))
+}
+
+/// Queries in `contents`, one per line; blank lines and surrounding whitespace are ignored.
+pub fn parse_queries(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_index, tokenize, Doc, RankingMethod};
+    use regex::Regex;
+
+    fn index() -> Index {
+        let docs: Vec<Doc> = [
+            ("lakes.txt", "freshwater lakes freeze in winter"),
+            ("oceans.txt", "salt water oceans and deep currents"),
+        ]
+        .iter()
+        .map(|(path, text)| Doc {
+            id: path.to_string(),
+            path: path.to_string(),
+            text: text.to_string(),
+            ..Default::default()
+        })
+        .collect();
+        let tokens: Vec<Vec<String>> = docs.iter().map(|d| tokenize(&d.text)).collect();
+        build_index(docs, &tokens, RankingMethod::Bm25, true)
+    }
+
+    #[test]
+    fn test_report_is_html_with_every_query() -> Result<()> {
+        let queries = parse_queries("frozen lakes\n\n  ocean currents \nvolcanoes <b>\n");
+        assert_eq!(queries.len(), 3);
+        let reports = build_report(&index(), &queries, &QueryOptions::default());
+        let html = render_html("idx.json", &reports)?;
+
+        assert!(Regex::new(r"^<!DOCTYPE html>\s*<html")?.is_match(&html));
+        assert!(html.trim_end().ends_with("</html>"));
+        assert!(html.contains("frozen lakes") && html.contains("ocean currents"));
+        // Queries are escaped, and one without matches is reported as such.
+        assert!(html.contains("volcanoes &lt;b&gt;"));
+        assert!(html.contains("No matching documents."));
+        assert!(html.contains("<td>lakes.txt</td>"));
+        assert!(html.contains("<rect"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_bars_are_relative_to_best_score() {
+        let reports = build_report(
+            &index(),
+            &["water lakes".to_string()],
+            &QueryOptions::default(),
+        );
+        let bars: Vec<f32> = reports[0].results.iter().map(|r| r.bar_width).collect();
+        assert_eq!(bars[0], BAR_WIDTH);
+        assert!(bars.iter().all(|&w| w > 0.0 && w <= BAR_WIDTH));
+    }
+}
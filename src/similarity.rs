@@ -0,0 +1,360 @@
+// Document-to-document similarity: cosine over the index's IDF-weighted BM25 vectors, and
+// over ad-hoc TF-IDF vectors for two files or sentences outside any index (`voltai
+// text-diff`, `voltai sentence-similarity`).
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::{
+    dot_product, excerpt_for_query, sparse_cosine, term_counts, tokenize, top_k, Index,
+    QueryResult, EXCERPT_CHARS, RESULT_KEYWORDS,
+};
+
+/// Number of top-weighted terms per document considered by `compare_docs`.
+const COMPARE_TERMS: usize = 10;
+
+/// Result of `voltai compare`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DocComparison {
+    pub similarity: f32,
+    /// Terms in both documents' top terms, in document A's rank order.
+    pub shared_terms: Vec<String>,
+    pub unique_to_a: Vec<String>,
+    pub unique_to_b: Vec<String>,
+}
+
+/// Resolves `key` to a document position: an exact id or path match first, then a
+/// file-name match (`notes.txt` for `/corpus/notes.txt`).
+pub fn find_doc(idx: &Index, key: &str) -> Result<usize> {
+    idx.docs
+        .iter()
+        .position(|d| d.id == key || d.path == key)
+        .or_else(|| {
+            idx.docs.iter().position(|d| {
+                Path::new(&d.path)
+                    .file_name()
+                    .map(|n| n == key)
+                    .unwrap_or(false)
+            })
+        })
+        .ok_or_else(|| anyhow!("no document with id or path '{}' in the index", key))
+}
+
+/// `vectors[di]` scaled by IDF: the document's full BM25 term weights.
+pub fn weighted_vector(idx: &Index, di: usize) -> Vec<f32> {
+    idx.vectors[di]
+        .iter()
+        .zip(idx.idf.iter())
+        .map(|(w, idf)| w * idf)
+        .collect()
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let na = dot_product(a, a).sqrt();
+    let nb = dot_product(b, b).sqrt();
+    if na == 0.0 || nb == 0.0 {
+        0.0
+    } else {
+        dot_product(a, b) / (na * nb)
+    }
+}
+
+/// Up to `n` terms with the highest weight in `weights`, best first (ties by term).
+fn top_terms(idx: &Index, weights: &[f32], n: usize) -> Vec<String> {
+    let mut ranked: Vec<(usize, f32)> = weights
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|&(_, w)| w > 0.0)
+        .collect();
+    ranked.sort_by(|a, b| {
+        b.1.partial_cmp(&a.1)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| idx.terms[a.0].cmp(&idx.terms[b.0]))
+    });
+    ranked
+        .into_iter()
+        .take(n)
+        .map(|(ti, _)| idx.terms[ti].clone())
+        .collect()
+}
+
+/// Compares two indexed documents, each given by id or path.
+pub fn compare_docs(index: &Index, id_a: &str, id_b: &str) -> Result<DocComparison> {
+    let a = weighted_vector(index, find_doc(index, id_a)?);
+    let b = weighted_vector(index, find_doc(index, id_b)?);
+    let terms_a = top_terms(index, &a, COMPARE_TERMS);
+    let terms_b = top_terms(index, &b, COMPARE_TERMS);
+    let (shared_terms, unique_to_a): (Vec<String>, Vec<String>) =
+        terms_a.iter().cloned().partition(|t| terms_b.contains(t));
+    let unique_to_b = terms_b
+        .into_iter()
+        .filter(|t| !terms_a.contains(t))
+        .collect();
+    Ok(DocComparison {
+        similarity: cosine_similarity(&a, &b),
+        shared_terms,
+        unique_to_a,
+        unique_to_b,
+    })
+}
+
+/// Compares two texts without an index: TF-IDF over the pair, with the smoothed IDF
+/// `ln(3 / (1 + df)) + 1` so shared terms keep some weight. Every term is listed, heaviest
+/// first (shared terms by their weight in A), ties alphabetical.
+pub fn diff_texts(text_a: &str, text_b: &str) -> DocComparison {
+    let counts_a = term_counts(&tokenize(text_a));
+    let counts_b = term_counts(&tokenize(text_b));
+    let idf = |term: &str| {
+        let df = u8::from(counts_a.contains_key(term)) + u8::from(counts_b.contains_key(term));
+        (3.0 / (1.0 + f32::from(df))).ln() + 1.0
+    };
+    let weigh = |counts: &HashMap<String, f32>| -> HashMap<String, f32> {
+        counts
+            .iter()
+            .map(|(t, c)| (t.clone(), c * idf(t)))
+            .collect()
+    };
+    let (a, b) = (weigh(&counts_a), weigh(&counts_b));
+    let ranked = |vec: &HashMap<String, f32>, keep: &dyn Fn(&str) -> bool| -> Vec<String> {
+        let mut terms: Vec<(&String, f32)> = vec
+            .iter()
+            .filter(|(t, _)| keep(t))
+            .map(|(t, &w)| (t, w))
+            .collect();
+        terms.sort_by(|x, y| y.1.total_cmp(&x.1).then_with(|| x.0.cmp(y.0)));
+        terms.into_iter().map(|(t, _)| t.clone()).collect()
+    };
+    DocComparison {
+        similarity: sparse_cosine(&a, &b),
+        shared_terms: ranked(&a, &|t| b.contains_key(t)),
+        unique_to_a: ranked(&a, &|t| !b.contains_key(t)),
+        unique_to_b: ranked(&b, &|t| !a.contains_key(t)),
+    }
+}
+
+/// Result of `voltai sentence-similarity`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SentenceSimilarity {
+    pub similarity: f32,
+    /// Tokens occurring in both sentences, alphabetical.
+    pub overlap: Vec<String>,
+}
+
+/// Cosine similarity of the TF-IDF vectors of two sentences. IDF comes from `idx` when
+/// given (terms it has never seen count as its rarest), otherwise from the pair itself as
+/// in [`diff_texts`].
+pub fn sentence_similarity(text_a: &str, text_b: &str, idx: Option<&Index>) -> SentenceSimilarity {
+    let counts_a = term_counts(&tokenize(text_a));
+    let counts_b = term_counts(&tokenize(text_b));
+    let idf: HashMap<String, f32> = match idx {
+        Some(idx) => {
+            let known: HashMap<&str, f32> = idx
+                .terms
+                .iter()
+                .map(String::as_str)
+                .zip(idx.idf.iter().copied())
+                .collect();
+            let unseen = idx.idf.iter().copied().fold(1.0_f32, f32::max);
+            counts_a
+                .keys()
+                .chain(counts_b.keys())
+                .map(|t| (t.clone(), known.get(t.as_str()).copied().unwrap_or(unseen)))
+                .collect()
+        }
+        None => counts_a
+            .keys()
+            .chain(counts_b.keys())
+            .map(|t| {
+                let df = u8::from(counts_a.contains_key(t)) + u8::from(counts_b.contains_key(t));
+                (t.clone(), (3.0 / (1.0 + f32::from(df))).ln() + 1.0)
+            })
+            .collect(),
+    };
+    let weigh = |counts: &HashMap<String, f32>| -> HashMap<String, f32> {
+        counts
+            .iter()
+            .map(|(t, c)| (t.clone(), c * idf[t]))
+            .collect()
+    };
+    let mut overlap: Vec<String> = counts_a
+        .keys()
+        .filter(|t| counts_b.contains_key(*t))
+        .cloned()
+        .collect();
+    overlap.sort();
+    SentenceSimilarity {
+        // Cosine normalises both vectors, so no separate L2 step is needed.
+        similarity: sparse_cosine(&weigh(&counts_a), &weigh(&counts_b)),
+        overlap,
+    }
+}
+
+/// Ranks the other documents by cosine similarity to the document `doc_id` (id or path),
+/// best first, and packages the top `k` like query results. Only documents sharing at
+/// least one term with it are scored.
+pub fn find_similar_docs(index: &Index, doc_id: &str, k: usize) -> Result<Vec<QueryResult>> {
+    let source = find_doc(index, doc_id)?;
+    let source_vec = weighted_vector(index, source);
+
+    let candidates: BTreeSet<usize> = source_vec
+        .iter()
+        .enumerate()
+        .filter(|&(_, &w)| w > 0.0)
+        .filter_map(|(ti, _)| index.inverted.get(&index.terms[ti]))
+        .flatten()
+        .copied()
+        .filter(|&di| di != source)
+        .collect();
+    let mut scores = vec![0.0_f32; index.docs.len()];
+    for di in candidates {
+        scores[di] = cosine_similarity(&source_vec, &weighted_vector(index, di));
+    }
+
+    // Excerpts are centred on the source document's most distinctive terms.
+    let source_terms = top_terms(index, &source_vec, COMPARE_TERMS);
+    Ok(top_k(&scores, k)
+        .into_iter()
+        .map(|(di, score)| {
+            let doc = &index.docs[di];
+            QueryResult {
+                path: doc.path.clone(),
+                score,
+                keywords: index.keywords(&doc.text, RESULT_KEYWORDS),
+                excerpt: excerpt_for_query(&doc.text, &source_terms, EXCERPT_CHARS),
+                sentence_excerpt: None,
+                last_modified_secs: doc.last_modified_secs,
+                context_used: false,
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{index_dir, load_index, IndexFormat};
+
+    fn overlapping_fixture(dir: &Path) -> Result<Index> {
+        std::fs::write(
+            dir.join("a.txt"),
+            "rust compiler borrow checker ownership lifetimes",
+        )?;
+        std::fs::write(dir.join("b.txt"), "rust compiler macros traits generics")?;
+        std::fs::write(dir.join("c.txt"), "tomato basil pasta sauce")?;
+        let out = dir.join("idx.json");
+        index_dir(dir, &out, IndexFormat::Json)?;
+        load_index(&out)
+    }
+
+    #[test]
+    fn test_diff_texts_splits_vocabulary() {
+        let diff = diff_texts(
+            "The harbour ferry departs at dawn; the ferry carries bicycles.",
+            "The harbour ferry departs at dusk and carries cars.",
+        );
+        let sorted = |terms: &[String]| {
+            let mut t = terms.to_vec();
+            t.sort();
+            t
+        };
+        assert_eq!(
+            sorted(&diff.shared_terms),
+            vec!["carries", "departs", "ferry", "harbour"]
+        );
+        assert_eq!(diff.unique_to_a, vec!["bicycles", "dawn"]);
+        assert_eq!(diff.unique_to_b, vec!["cars", "dusk"]);
+        // "ferry" occurs twice in A, so it leads A's shared terms.
+        assert_eq!(diff.shared_terms[0], "ferry");
+        assert!(diff.similarity > 0.5 && diff.similarity < 1.0);
+        assert!((diff_texts("same words here", "same words here").similarity - 1.0).abs() < 1e-6);
+        assert_eq!(diff_texts("alpha", "beta").similarity, 0.0);
+    }
+
+    #[test]
+    fn test_sentence_similarity_bounds() -> Result<()> {
+        let same = sentence_similarity("the cat sat on the mat", "the cat sat on the mat", None);
+        assert!((same.similarity - 1.0).abs() < 1e-6);
+        let disjoint = sentence_similarity("quantum physics lecture", "banana bread recipe", None);
+        assert_eq!(disjoint.similarity, 0.0);
+        assert!(disjoint.overlap.is_empty());
+        let partial = sentence_similarity("rust compiler errors", "rust compiler warnings", None);
+        assert!(partial.similarity > 0.0 && partial.similarity < 1.0);
+        assert_eq!(partial.overlap, vec!["compiler", "rust"]);
+
+        // With an index, a term common in the corpus counts for less than a rare one.
+        let dir = tempfile::tempdir()?;
+        let idx = overlapping_fixture(dir.path())?;
+        let common = sentence_similarity("rust pasta", "rust tomato", Some(&idx));
+        let rare = sentence_similarity("rust pasta", "basil pasta", Some(&idx));
+        assert!(
+            rare.similarity > common.similarity,
+            "{:?} {:?}",
+            rare,
+            common
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_docs_reports_shared_vocabulary() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let idx = overlapping_fixture(dir.path())?;
+        let cmp = compare_docs(&idx, "a.txt", "b.txt")?;
+
+        let mut shared = cmp.shared_terms.clone();
+        shared.sort();
+        assert_eq!(shared, vec!["compiler".to_string(), "rust".to_string()]);
+        assert!(cmp.unique_to_a.contains(&"ownership".to_string()));
+        assert!(cmp.unique_to_b.contains(&"macros".to_string()));
+        assert!(cmp.similarity > 0.0 && cmp.similarity < 1.0);
+
+        let unrelated = compare_docs(&idx, "a.txt", "c.txt")?;
+        assert_eq!(unrelated.similarity, 0.0);
+        assert!(unrelated.shared_terms.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_docs_accepts_ids_and_paths() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let idx = overlapping_fixture(dir.path())?;
+        let path_a = dir.path().join("a.txt").to_string_lossy().to_string();
+        let by_path = compare_docs(&idx, &path_a, "doc-b.txt")?;
+        let by_name = compare_docs(&idx, "a.txt", "b.txt")?;
+        assert_eq!(by_path, by_name);
+        assert!(compare_docs(&idx, "a.txt", "missing.txt").is_err());
+
+        let self_cmp = compare_docs(&idx, "a.txt", "a.txt")?;
+        assert!((self_cmp.similarity - 1.0).abs() < 1e-5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_similar_docs_returns_thematic_neighbour_first() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("espresso.txt"),
+            "espresso coffee beans roast grinder crema",
+        )?;
+        std::fs::write(
+            dir.path().join("latte.txt"),
+            "latte coffee beans milk espresso foam",
+        )?;
+        std::fs::write(
+            dir.path().join("tides.txt"),
+            "ocean tides moon gravity coastline beans",
+        )?;
+        let out = dir.path().join("idx.json");
+        index_dir(dir.path(), &out, IndexFormat::Json)?;
+        let idx = load_index(&out)?;
+
+        let results = find_similar_docs(&idx, "espresso.txt", 2)?;
+        assert!(results[0].path.ends_with("latte.txt"), "{:?}", results);
+        assert!(results.iter().all(|r| !r.path.ends_with("espresso.txt")));
+        assert!(results.windows(2).all(|w| w[0].score >= w[1].score));
+        Ok(())
+    }
+}
@@ -0,0 +1,645 @@
+// Corpus analytics for `voltai text-stats` and `voltai word-count`: per-file
+// readability/vocabulary metrics plus corpus-level aggregates, computed straight from the
+// files without building an index.
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::reader::{DefaultFileReader, FileReader};
+use crate::{
+    indexable_files, markdown_unsupported, top_keywords, OutputFormat, SENTENCE_PATTERN, WORD_RE,
+};
+
+/// Number of keywords reported per file.
+const STATS_KEYWORDS: usize = 5;
+
+/// Metrics for a single file.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FileStats {
+    pub path: String,
+    pub word_count: usize,
+    pub sentence_count: usize,
+    pub unique_words: usize,
+    pub avg_sentence_length: f32,
+    /// Unique words / total words (type-token ratio); 0 for an empty file.
+    pub lexical_diversity: f32,
+    pub top_keywords: Vec<String>,
+}
+
+/// Aggregates over every analysed file.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CorpusStats {
+    pub documents: usize,
+    pub total_words: usize,
+    pub avg_words_per_doc: f32,
+    pub vocabulary_size: usize,
+    /// Share of the corpus vocabulary that occurs in more than one document.
+    pub vocabulary_overlap: f32,
+}
+
+/// Document-frequency ranges of `text-stats --histogram` and `top-terms --histogram`,
+/// inclusive; the last is open.
+const DF_BUCKETS: [(usize, Option<usize>); 5] = [
+    (1, Some(5)),
+    (6, Some(20)),
+    (21, Some(100)),
+    (101, Some(500)),
+    (501, None),
+];
+
+/// Width, in characters, of the longest histogram bar.
+const HISTOGRAM_BAR: usize = 40;
+
+/// Number of distinct words whose document frequency falls in `range`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DfBucket {
+    pub range: String,
+    pub terms: usize,
+}
+
+/// Shape of the corpus vocabulary, from `text-stats --histogram` or
+/// `top-terms --histogram`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TermDistribution {
+    pub document_frequency: Vec<DfBucket>,
+    /// Least-squares slope of log(frequency) against log(rank), over corpus word counts
+    /// for `text-stats` and document frequencies for `top-terms` (an index keeps no word
+    /// counts); about -1 for natural-language text. `None` with fewer than two distinct
+    /// words.
+    pub zipf_coefficient: Option<f64>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TextStatsReport {
+    pub files: Vec<FileStats>,
+    pub corpus: CorpusStats,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub term_distribution: Option<TermDistribution>,
+}
+
+/// Lower-cased words of `text`, stop words included: these are raw text statistics.
+fn words(text: &str) -> Vec<String> {
+    WORD_RE
+        .find_iter(text)
+        .map(|m| m.as_str().to_lowercase())
+        .collect()
+}
+
+/// Sentences containing at least one word, so stray punctuation is not counted.
+fn sentence_count(text: &str) -> usize {
+    SENTENCE_PATTERN
+        .find_iter(text)
+        .filter(|m| WORD_RE.is_match(m.as_str()))
+        .count()
+}
+
+pub fn file_stats(path: &str, text: &str) -> FileStats {
+    let words = words(text);
+    let word_count = words.len();
+    let unique_words = words.iter().collect::<HashSet<_>>().len();
+    let sentence_count = sentence_count(text);
+    let ratio = |num: usize, den: usize| {
+        if den == 0 {
+            0.0
+        } else {
+            num as f32 / den as f32
+        }
+    };
+    FileStats {
+        path: path.to_string(),
+        word_count,
+        sentence_count,
+        unique_words,
+        avg_sentence_length: ratio(word_count, sentence_count),
+        lexical_diversity: ratio(unique_words, word_count),
+        top_keywords: top_keywords(text, STATS_KEYWORDS),
+    }
+}
+
+/// Slope of the least-squares line through `(ln rank, ln frequency)` for `frequencies`
+/// ranked from most to least frequent.
+pub fn zipf_coefficient(frequencies: &[usize]) -> Option<f64> {
+    let mut sorted: Vec<usize> = frequencies.iter().copied().filter(|&f| f > 0).collect();
+    if sorted.len() < 2 {
+        return None;
+    }
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+    let points: Vec<(f64, f64)> = sorted
+        .iter()
+        .enumerate()
+        .map(|(rank, &f)| (((rank + 1) as f64).ln(), (f as f64).ln()))
+        .collect();
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    Some(covariance / variance)
+}
+
+/// Buckets each word's document frequency into [`DF_BUCKETS`].
+fn df_histogram<'a>(doc_freqs: impl Iterator<Item = &'a usize>) -> Vec<DfBucket> {
+    let mut counts = [0usize; DF_BUCKETS.len()];
+    for &df in doc_freqs {
+        if let Some(b) = DF_BUCKETS
+            .iter()
+            .position(|&(lo, hi)| df >= lo && hi.is_none_or(|hi| df <= hi))
+        {
+            counts[b] += 1;
+        }
+    }
+    DF_BUCKETS
+        .iter()
+        .zip(counts)
+        .map(|(&(lo, hi), terms)| DfBucket {
+            range: match hi {
+                Some(hi) => format!("{}-{}", lo, hi),
+                None => format!("{}+", lo - 1),
+            },
+            terms,
+        })
+        .collect()
+}
+
+/// The distribution of a vocabulary known only by each term's document frequency, as in
+/// an index.
+pub fn doc_freq_distribution(doc_freqs: &[usize]) -> TermDistribution {
+    TermDistribution {
+        document_frequency: df_histogram(doc_freqs.iter()),
+        zipf_coefficient: zipf_coefficient(doc_freqs),
+    }
+}
+
+/// Reads and analyses `files` in parallel. Unreadable files are reported as empty, matching
+/// how `voltai index` treats them. With `histogram`, the report includes the corpus
+/// [`TermDistribution`].
+pub fn analyze_files(files: &[PathBuf], histogram: bool) -> TextStatsReport {
+    let analysed: Vec<(FileStats, HashMap<String, usize>)> = files
+        .par_iter()
+        .map(|p| {
+            let text = DefaultFileReader::read(p).unwrap_or_default();
+            let mut counts: HashMap<String, usize> = HashMap::new();
+            for w in words(&text) {
+                *counts.entry(w).or_insert(0) += 1;
+            }
+            (file_stats(&p.to_string_lossy(), &text), counts)
+        })
+        .collect();
+
+    // Per word: documents containing it, and occurrences across the corpus.
+    let mut doc_freq: HashMap<&str, (usize, usize)> = HashMap::new();
+    for (_, counts) in &analysed {
+        for (w, &n) in counts {
+            let entry = doc_freq.entry(w.as_str()).or_insert((0, 0));
+            entry.0 += 1;
+            entry.1 += n;
+        }
+    }
+    let term_distribution = histogram.then(|| TermDistribution {
+        document_frequency: df_histogram(doc_freq.values().map(|(df, _)| df)),
+        zipf_coefficient: zipf_coefficient(
+            &doc_freq.values().map(|&(_, cf)| cf).collect::<Vec<_>>(),
+        ),
+    });
+    let shared = doc_freq.values().filter(|&&(df, _)| df > 1).count();
+    let total_words: usize = analysed.iter().map(|(f, _)| f.word_count).sum();
+    let documents = analysed.len();
+    let corpus = CorpusStats {
+        documents,
+        total_words,
+        avg_words_per_doc: if documents == 0 {
+            0.0
+        } else {
+            total_words as f32 / documents as f32
+        },
+        vocabulary_size: doc_freq.len(),
+        vocabulary_overlap: if doc_freq.is_empty() {
+            0.0
+        } else {
+            shared as f32 / doc_freq.len() as f32
+        },
+    };
+    TextStatsReport {
+        files: analysed.into_iter().map(|(f, _)| f).collect(),
+        corpus,
+        term_distribution,
+    }
+}
+
+/// Quotes a CSV field when it contains a delimiter, quote or newline (RFC 4180).
+pub(crate) fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Renders `report`. CSV and JSONL carry one row per file; the corpus aggregates and term
+/// distribution are only part of the text and JSON outputs.
+pub fn write_report(
+    report: &TextStatsReport,
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    match format {
+        OutputFormat::Json | OutputFormat::PrettyJson => {
+            serde_json::to_writer_pretty(&mut *out, report)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Jsonl => {
+            for f in &report.files {
+                serde_json::to_writer(&mut *out, f)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv => {
+            writeln!(
+                out,
+                "path,word_count,sentence_count,unique_words,avg_sentence_length,lexical_diversity,top_keywords"
+            )?;
+            for f in &report.files {
+                writeln!(
+                    out,
+                    "{},{},{},{},{:.2},{:.4},{}",
+                    csv_field(&f.path),
+                    f.word_count,
+                    f.sentence_count,
+                    f.unique_words,
+                    f.avg_sentence_length,
+                    f.lexical_diversity,
+                    csv_field(&f.top_keywords.join(";"))
+                )?;
+            }
+        }
+        OutputFormat::Markdown => return Err(markdown_unsupported()),
+        OutputFormat::Text => {
+            for f in &report.files {
+                writeln!(out, "File: {}", f.path)?;
+                writeln!(out, "  words:               {}", f.word_count)?;
+                writeln!(out, "  sentences:           {}", f.sentence_count)?;
+                writeln!(out, "  unique words:        {}", f.unique_words)?;
+                writeln!(out, "  avg sentence length: {:.2}", f.avg_sentence_length)?;
+                writeln!(out, "  lexical diversity:   {:.4}", f.lexical_diversity)?;
+                writeln!(out, "  top keywords:        {}", f.top_keywords.join(", "))?;
+            }
+            let c = &report.corpus;
+            writeln!(out, "Corpus:")?;
+            writeln!(out, "  documents:           {}", c.documents)?;
+            writeln!(out, "  total words:         {}", c.total_words)?;
+            writeln!(out, "  avg words/doc:       {:.2}", c.avg_words_per_doc)?;
+            writeln!(out, "  vocabulary:          {}", c.vocabulary_size)?;
+            writeln!(out, "  vocabulary overlap:  {:.4}", c.vocabulary_overlap)?;
+            if let Some(dist) = &report.term_distribution {
+                write_term_distribution(dist, out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Document-frequency histogram with bars scaled to the largest bucket, then the Zipf
+/// coefficient.
+pub fn write_term_distribution(dist: &TermDistribution, out: &mut dyn Write) -> Result<()> {
+    let largest = dist
+        .document_frequency
+        .iter()
+        .map(|b| b.terms)
+        .max()
+        .unwrap_or(0);
+    writeln!(out, "Document frequency (words per range of documents):")?;
+    for b in &dist.document_frequency {
+        let bar = if largest == 0 {
+            0
+        } else {
+            (b.terms * HISTOGRAM_BAR).div_ceil(largest)
+        };
+        writeln!(out, "  {:<8} {:>8} {}", b.range, b.terms, "#".repeat(bar))?;
+    }
+    match dist.zipf_coefficient {
+        Some(z) => writeln!(out, "Zipf coefficient:      {:.3}", z)?,
+        None => writeln!(out, "Zipf coefficient:      n/a")?,
+    }
+    Ok(())
+}
+
+/// Counts for one file of `voltai word-count`, or the totals over all of them.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct WordCount {
+    pub path: String,
+    pub words: usize,
+    /// Distinct lower-cased words; for the totals, the corpus vocabulary size.
+    pub unique_words: usize,
+    pub sentences: usize,
+    /// Blank-line separated blocks containing at least one word.
+    pub paragraphs: usize,
+}
+
+/// Path of the totals row in `voltai word-count` output.
+pub const WORD_COUNT_TOTAL: &str = "TOTAL";
+
+fn paragraph_count(text: &str) -> usize {
+    let mut count = 0;
+    let mut in_paragraph = false;
+    for line in text.lines() {
+        let has_words = WORD_RE.is_match(line);
+        if line.trim().is_empty() {
+            in_paragraph = false;
+        } else if has_words && !in_paragraph {
+            count += 1;
+            in_paragraph = true;
+        }
+    }
+    count
+}
+
+/// Per-file counts for every file under `dir` that `voltai index` would pick up, read in
+/// parallel, followed by a [`WORD_COUNT_TOTAL`] row.
+pub fn word_counts(dir: &Path) -> Vec<WordCount> {
+    let counted: Vec<(WordCount, HashSet<String>)> = indexable_files(dir, false)
+        .par_iter()
+        .map(|p| {
+            let text = DefaultFileReader::read(p).unwrap_or_default();
+            let words = words(&text);
+            let word_count = words.len();
+            let vocab: HashSet<String> = words.into_iter().collect();
+            let count = WordCount {
+                path: p.to_string_lossy().into_owned(),
+                words: word_count,
+                unique_words: vocab.len(),
+                sentences: sentence_count(&text),
+                paragraphs: paragraph_count(&text),
+            };
+            (count, vocab)
+        })
+        .collect();
+
+    let vocabulary: HashSet<&String> = counted.iter().flat_map(|(_, v)| v).collect();
+    let total = WordCount {
+        path: WORD_COUNT_TOTAL.to_string(),
+        words: counted.iter().map(|(c, _)| c.words).sum(),
+        unique_words: vocabulary.len(),
+        sentences: counted.iter().map(|(c, _)| c.sentences).sum(),
+        paragraphs: counted.iter().map(|(c, _)| c.paragraphs).sum(),
+    };
+    counted
+        .into_iter()
+        .map(|(c, _)| c)
+        .chain(std::iter::once(total))
+        .collect()
+}
+
+/// Renders `counts` (ending with the totals row). JSON is an array of rows and JSONL one
+/// row per line; CSV has the columns `path,words,unique_words,sentences,paragraphs`.
+pub fn write_word_counts(
+    counts: &[WordCount],
+    format: OutputFormat,
+    out: &mut dyn Write,
+) -> Result<()> {
+    match format {
+        OutputFormat::Json | OutputFormat::PrettyJson => {
+            serde_json::to_writer_pretty(&mut *out, counts)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Jsonl => {
+            for c in counts {
+                serde_json::to_writer(&mut *out, c)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv => {
+            writeln!(out, "path,words,unique_words,sentences,paragraphs")?;
+            for c in counts {
+                writeln!(
+                    out,
+                    "{},{},{},{},{}",
+                    csv_field(&c.path),
+                    c.words,
+                    c.unique_words,
+                    c.sentences,
+                    c.paragraphs
+                )?;
+            }
+        }
+        OutputFormat::Markdown => return Err(markdown_unsupported()),
+        OutputFormat::Text => {
+            writeln!(
+                out,
+                "{:>9} {:>9} {:>9} {:>10}  path",
+                "words", "unique", "sentences", "paragraphs"
+            )?;
+            for c in counts {
+                writeln!(
+                    out,
+                    "{:>9} {:>9} {:>9} {:>10}  {}",
+                    c.words, c.unique_words, c.sentences, c.paragraphs, c.path
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Analyses every file under `dir` that `voltai index` would pick up.
+pub fn analyze_dir(dir: &Path, histogram: bool) -> TextStatsReport {
+    analyze_files(&indexable_files(dir, false), histogram)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_count_fixture() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("story.txt"),
+            "The fox ran. The fox hid!\n\nA dog barked? Then silence.\n\n\n---\n",
+        )?;
+        std::fs::write(dir.path().join("note.md"), "one two two\n")?;
+        let counts = word_counts(dir.path());
+        assert_eq!(counts.len(), 3);
+        let story = counts
+            .iter()
+            .find(|c| c.path.ends_with("story.txt"))
+            .unwrap();
+        assert_eq!(
+            (
+                story.words,
+                story.unique_words,
+                story.sentences,
+                story.paragraphs
+            ),
+            (11, 9, 4, 2)
+        );
+        let total = counts.last().unwrap();
+        assert_eq!(total.path, WORD_COUNT_TOTAL);
+        assert_eq!(
+            (
+                total.words,
+                total.unique_words,
+                total.sentences,
+                total.paragraphs
+            ),
+            (14, 11, 5, 3)
+        );
+
+        let mut out = Vec::new();
+        write_word_counts(&counts, OutputFormat::Csv, &mut out)?;
+        let csv = String::from_utf8(out)?;
+        assert!(csv.starts_with("path,words,unique_words,sentences,paragraphs\n"));
+        assert!(csv.ends_with("\nTOTAL,14,11,5,3\n"), "{}", csv);
+        Ok(())
+    }
+
+    #[test]
+    fn test_word_counts_match_known_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "The cat sat. The cat ran!").unwrap();
+        std::fs::write(
+            dir.path().join("b.md"),
+            "Dogs bark loudly at night and sleep by day",
+        )
+        .unwrap();
+
+        let report = analyze_dir(dir.path(), false);
+        let by_name = |name: &str| {
+            report
+                .files
+                .iter()
+                .find(|f| f.path.ends_with(name))
+                .unwrap()
+                .clone()
+        };
+        let a = by_name("a.txt");
+        assert_eq!(a.word_count, 6);
+        assert_eq!(a.sentence_count, 2);
+        assert_eq!(a.unique_words, 4);
+        assert_eq!(a.avg_sentence_length, 3.0);
+
+        let b = by_name("b.md");
+        assert_eq!(b.word_count, 9);
+        assert_eq!(b.sentence_count, 1);
+
+        assert_eq!(report.corpus.documents, 2);
+        assert_eq!(report.corpus.total_words, 15);
+        assert_eq!(report.corpus.avg_words_per_doc, 7.5);
+    }
+
+    #[test]
+    fn test_vocabulary_overlap_counts_shared_words() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "red green").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "green blue").unwrap();
+        let report = analyze_dir(dir.path(), false);
+        assert_eq!(report.corpus.vocabulary_size, 3);
+        assert!((report.corpus.vocabulary_overlap - 1.0 / 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_zipf_corpus_has_coefficient_near_minus_one() {
+        // The word of rank r occurs 1200 / r times in total, spread over the documents.
+        let dir = tempfile::tempdir().unwrap();
+        let mut docs = vec![String::new(); 4];
+        for rank in 1..=60usize {
+            let word: String = (0..3)
+                .map(|i| (b'a' + ((rank - 1) / 26usize.pow(i) % 26) as u8) as char)
+                .collect();
+            for n in 0..1200 / rank {
+                let doc = &mut docs[n % 4];
+                doc.push_str(&word);
+                doc.push(' ');
+            }
+        }
+        for (i, text) in docs.iter().enumerate() {
+            std::fs::write(dir.path().join(format!("{}.txt", i)), text).unwrap();
+        }
+
+        let report = analyze_dir(dir.path(), true);
+        let dist = report.term_distribution.unwrap();
+        let zipf = dist.zipf_coefficient.unwrap();
+        assert!((zipf + 1.0).abs() < 0.05, "{}", zipf);
+        assert_eq!(zipf_coefficient(&[7]), None);
+    }
+
+    #[test]
+    fn test_document_frequency_histogram() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        for i in 0..8 {
+            let text = if i < 2 { "common rare" } else { "common" };
+            std::fs::write(dir.path().join(format!("{}.txt", i)), text)?;
+        }
+        let report = analyze_dir(dir.path(), true);
+        let buckets: Vec<(&str, usize)> = report
+            .term_distribution
+            .as_ref()
+            .unwrap()
+            .document_frequency
+            .iter()
+            .map(|b| (b.range.as_str(), b.terms))
+            .collect();
+        assert_eq!(
+            buckets,
+            [
+                ("1-5", 1),
+                ("6-20", 1),
+                ("21-100", 0),
+                ("101-500", 0),
+                ("500+", 0)
+            ]
+        );
+
+        let mut out: Vec<u8> = Vec::new();
+        write_report(&report, OutputFormat::Text, &mut out)?;
+        let text = String::from_utf8(out)?;
+        assert!(
+            text.contains(&format!("  1-5             1 {}\n", "#".repeat(40))),
+            "{}",
+            text
+        );
+        assert!(text.contains("  21-100          0 \n"), "{}", text);
+        assert!(text.contains("Zipf coefficient:"), "{}", text);
+        assert!(analyze_dir(dir.path(), false).term_distribution.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_file_has_zero_ratios() {
+        let s = file_stats("empty.txt", "");
+        assert_eq!(s.word_count, 0);
+        assert_eq!(s.sentence_count, 0);
+        assert_eq!(s.lexical_diversity, 0.0);
+        assert_eq!(s.avg_sentence_length, 0.0);
+    }
+
+    #[test]
+    fn test_csv_output_quotes_fields() {
+        let report = TextStatsReport {
+            files: vec![file_stats("dir, with comma/a.txt", "alpha beta. gamma")],
+            corpus: CorpusStats {
+                documents: 1,
+                total_words: 3,
+                avg_words_per_doc: 3.0,
+                vocabulary_size: 3,
+                vocabulary_overlap: 0.0,
+            },
+            term_distribution: None,
+        };
+        let mut out: Vec<u8> = Vec::new();
+        write_report(&report, OutputFormat::Csv, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let mut lines = out.lines();
+        assert!(lines.next().unwrap().starts_with("path,word_count,"));
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("\"dir, with comma/a.txt\",3,2,3,1.50,1.0000,"));
+    }
+}
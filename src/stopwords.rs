@@ -0,0 +1,72 @@
+// Stop word lists per language, embedded from `data/stopwords/<lang>.txt` (one word per line).
+use std::collections::{HashMap, HashSet};
+
+use once_cell::sync::Lazy;
+
+/// Languages with an embedded list, as accepted by `voltai index --lang`.
+pub const LANGUAGES: [&str; 6] = ["en", "fr", "de", "es", "it", "pt"];
+
+const LISTS: [(&str, &str); 6] = [
+    ("en", include_str!("../data/stopwords/en.txt")),
+    ("fr", include_str!("../data/stopwords/fr.txt")),
+    ("de", include_str!("../data/stopwords/de.txt")),
+    ("es", include_str!("../data/stopwords/es.txt")),
+    ("it", include_str!("../data/stopwords/it.txt")),
+    ("pt", include_str!("../data/stopwords/pt.txt")),
+];
+
+static STOP_WORD_SETS: Lazy<HashMap<&'static str, HashSet<&'static str>>> = Lazy::new(|| {
+    LISTS
+        .iter()
+        .map(|(lang, list)| {
+            (
+                *lang,
+                list.lines()
+                    .map(str::trim)
+                    .filter(|w| !w.is_empty())
+                    .collect(),
+            )
+        })
+        .collect()
+});
+
+/// Stop words for the ISO 639-1 code `lang` (e.g. `fr`), or `None` if no list is embedded.
+pub fn load_stop_words(lang: &str) -> Option<&'static HashSet<&'static str>> {
+    STOP_WORD_SETS.get(lang.to_ascii_lowercase().as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tokenize_with_config, TokenizerConfig};
+
+    #[test]
+    fn test_every_language_has_a_list() {
+        for lang in LANGUAGES {
+            assert!(
+                load_stop_words(lang).is_some_and(|s| s.len() > 50),
+                "{}",
+                lang
+            );
+        }
+        assert!(load_stop_words("FR").is_some());
+        assert!(load_stop_words("xx").is_none());
+    }
+
+    #[test]
+    fn test_french_stop_words_are_filtered() {
+        let fr = load_stop_words("fr").unwrap();
+        assert!(["le", "la", "les"].iter().all(|w| fr.contains(w)));
+
+        let cfg = TokenizerConfig {
+            lang: Some("fr".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            tokenize_with_config("Le chat mange les souris dans la cuisine", &cfg),
+            ["chat", "mange", "souris", "cuisine"]
+        );
+        // English stop words are no longer dropped for French text.
+        assert_eq!(tokenize_with_config("the chat", &cfg), ["the", "chat"]);
+    }
+}
@@ -0,0 +1,187 @@
+// Prompt templates for `voltai query --template` / `--context-template`, rendered with
+// Tera (Jinja2-style `{{ var }}` / `{% for %}` syntax): user files or built-in ones.
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+/// Templates shipped in the binary, selectable by name.
+const BUILTIN_TEMPLATES: [(&str, &str); 2] = [
+    ("default", include_str!("../templates/default.tera")),
+    ("few-shot", include_str!("../templates/few-shot.tera")),
+];
+
+/// One retrieved document as exposed to templates.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PromptDocument {
+    pub id: String,
+    pub path: String,
+    /// Last component of `path`.
+    pub filename: String,
+    pub keywords: Vec<String>,
+}
+
+/// A worked example for few-shot prompts, read from `--examples-file`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PromptExample {
+    pub query: String,
+    pub expected_answer: String,
+}
+
+/// Variables available to a prompt template: `{{ context }}` (the same per-document
+/// block the built-in prompts use), `{{ query }}`, `{{ documents }}`, `{{ examples }}`
+/// and `{{ model }}`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PromptVars {
+    pub context: String,
+    pub query: String,
+    pub documents: Vec<PromptDocument>,
+    pub examples: Vec<PromptExample>,
+    pub model: String,
+}
+
+/// Source of the template `spec`: the file at that path if there is one, otherwise the
+/// built-in template of that name (`default` or `few-shot`).
+pub fn load_template(spec: &str) -> Result<String> {
+    let path = Path::new(spec);
+    if path.is_file() {
+        return std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("cannot read template {}: {}", path.display(), e));
+    }
+    BUILTIN_TEMPLATES
+        .iter()
+        .find(|(name, _)| *name == spec)
+        .map(|(_, source)| source.to_string())
+        .ok_or_else(|| {
+            anyhow!(
+                "no template file {} and no built-in template of that name (built-in: {})",
+                spec,
+                BUILTIN_TEMPLATES.map(|(name, _)| name).join(", ")
+            )
+        })
+}
+
+/// Few-shot examples from a JSON array of `{"query", "expected_answer"}` objects.
+pub fn load_examples(path: &Path) -> Result<Vec<PromptExample>> {
+    let data = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("cannot read examples {}: {}", path.display(), e))?;
+    serde_json::from_str(&data)
+        .map_err(|e| anyhow!("invalid examples file {}: {}", path.display(), e))
+}
+
+/// Renders `template` with `vars`. Output is not HTML-escaped: prompts are plain text.
+pub fn render_prompt(template: &str, vars: &PromptVars) -> Result<String> {
+    let ctx = tera::Context::from_serialize(vars)?;
+    tera::Tera::one_off(template, &ctx, false).map_err(|e| {
+        // Tera keeps the useful detail (line, unknown variable) in the error source.
+        let detail = std::error::Error::source(&e)
+            .map(|s| format!(": {}", s))
+            .unwrap_or_default();
+        anyhow!("invalid prompt template: {}{}", e, detail)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> PromptVars {
+        PromptVars {
+            context: "Filename: a.txt\nKeywords: rust, memory\n---\n".to_string(),
+            query: "how is memory managed?".to_string(),
+            documents: vec![
+                PromptDocument {
+                    id: "doc-a.txt".to_string(),
+                    path: "/docs/a.txt".to_string(),
+                    filename: "a.txt".to_string(),
+                    keywords: vec!["rust".to_string(), "memory".to_string()],
+                },
+                PromptDocument {
+                    id: "doc-b.txt".to_string(),
+                    path: "/docs/b.txt".to_string(),
+                    filename: "b.txt".to_string(),
+                    keywords: vec![],
+                },
+            ],
+            examples: Vec::new(),
+            model: "gemma3:1b".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_render_prompt_query_before_context() {
+        let out = render_prompt("Question: {{ query }}\nContext:\n{{ context }}", &vars()).unwrap();
+        assert_eq!(
+            out,
+            "Question: how is memory managed?\nContext:\nFilename: a.txt\nKeywords: rust, memory\n---\n"
+        );
+    }
+
+    #[test]
+    fn test_render_prompt_documents_and_model() {
+        let template = "[{{ model }}]{% for d in documents %} {{ d.path }}({{ d.keywords | join(sep=\",\") }}){% endfor %}";
+        assert_eq!(
+            render_prompt(template, &vars()).unwrap(),
+            "[gemma3:1b] /docs/a.txt(rust,memory) /docs/b.txt()"
+        );
+    }
+
+    #[test]
+    fn test_render_prompt_does_not_escape_html() {
+        let mut v = vars();
+        v.query = "a < b & c".to_string();
+        assert_eq!(render_prompt("{{ query }}", &v).unwrap(), "a < b & c");
+    }
+
+    #[test]
+    fn test_few_shot_template_includes_examples_and_documents() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let examples_file = dir.path().join("examples.json");
+        std::fs::write(
+            &examples_file,
+            r#"[{"query": "what is borrowing?", "expected_answer": "Temporary access to a value."},
+                {"query": "what is a lifetime?", "expected_answer": "How long a reference is valid."}]"#,
+        )?;
+        let mut v = vars();
+        v.examples = load_examples(&examples_file)?;
+        let out = render_prompt(&load_template("few-shot")?, &v)?;
+
+        assert!(out.contains(
+            "Example question: what is borrowing?\nExample answer: Temporary access to a value.\n"
+        ));
+        assert!(out.contains(
+            "Example question: what is a lifetime?\nExample answer: How long a reference is valid.\n"
+        ));
+        assert!(out.contains("Filename: a.txt\nKeywords: rust, memory\n---\n"));
+        assert!(out.contains("Filename: b.txt\n"));
+        assert!(
+            out.ends_with("Question: how is memory managed?\nAnswer:\n"),
+            "{}",
+            out
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_template_prefers_files_over_builtins() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let custom = dir.path().join("mine.tera");
+        std::fs::write(&custom, "{{ query }}")?;
+        assert_eq!(load_template(&custom.to_string_lossy())?, "{{ query }}");
+        assert!(load_template("default")?.contains("Question: {{ query }}"));
+        let err = load_template("no-such-template").unwrap_err();
+        assert!(err.to_string().contains("default, few-shot"), "{}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_prompt_reports_template_errors() {
+        let err = render_prompt("{{ unknown_var }}", &vars()).unwrap_err();
+        assert!(
+            err.to_string().contains("invalid prompt template"),
+            "{}",
+            err
+        );
+        assert!(render_prompt("{% for d in documents %}", &vars()).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+// Term rewriting after tokenization, such as expanding abbreviations from an alias file
+// (`voltai index --alias-file`).
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+
+/// Rewrites a document's or query's token list.
+pub trait TermFilter: std::fmt::Debug + Send + Sync {
+    fn filter(&self, terms: &[String]) -> Vec<String>;
+}
+
+/// Leaves terms unchanged.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IdentityFilter;
+
+impl TermFilter for IdentityFilter {
+    fn filter(&self, terms: &[String]) -> Vec<String> {
+        terms.to_vec()
+    }
+}
+
+/// Replaces each term that has an alias with it, e.g. `ml` with `machine_learning`.
+#[derive(Debug, Clone, Default)]
+pub struct AliasFilter {
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl AliasFilter {
+    /// Reads `term = "replacement"` pairs from a TOML file. Terms are lowercased, as tokens
+    /// are.
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("cannot read alias file {}: {}", path.display(), e))?;
+        let aliases: BTreeMap<String, String> = toml::from_str(&contents).map_err(|e| {
+            anyhow!(
+                "invalid alias file {}: {}\nEach line must be `term = \"replacement\"`.",
+                path.display(),
+                e
+            )
+        })?;
+        Ok(AliasFilter {
+            aliases: aliases
+                .into_iter()
+                .map(|(term, alias)| (term.to_lowercase(), alias))
+                .collect(),
+        })
+    }
+}
+
+impl TermFilter for AliasFilter {
+    fn filter(&self, terms: &[String]) -> Vec<String> {
+        terms
+            .iter()
+            .map(|t| self.aliases.get(t).unwrap_or(t).clone())
+            .collect()
+    }
+}
+
+/// Applies its filters in order, each to the previous one's output.
+#[derive(Debug, Default)]
+pub struct FilterChain(pub Vec<Box<dyn TermFilter>>);
+
+impl TermFilter for FilterChain {
+    fn filter(&self, terms: &[String]) -> Vec<String> {
+        self.0
+            .iter()
+            .fold(terms.to_vec(), |terms, filter| filter.filter(&terms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terms(list: &[&str]) -> Vec<String> {
+        list.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_alias_file_and_chain() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("aliases.toml");
+        std::fs::write(
+            &path,
+            "# abbreviations\nML = \"machine_learning\"\nnlp = \"ml\"\n",
+        )?;
+        let aliases = AliasFilter::load(&path)?;
+        assert_eq!(aliases.aliases["ml"], "machine_learning");
+
+        let input = terms(&["ml", "nlp", "rust"]);
+        assert_eq!(
+            aliases.filter(&input),
+            terms(&["machine_learning", "ml", "rust"])
+        );
+        // Each filter sees the previous one's output, so aliases can be applied twice.
+        let chain = FilterChain(vec![
+            Box::new(IdentityFilter),
+            Box::new(aliases.clone()),
+            Box::new(aliases),
+        ]);
+        assert_eq!(
+            chain.filter(&input),
+            terms(&["machine_learning", "machine_learning", "rust"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_alias_file_is_explained() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("aliases.toml");
+        std::fs::write(&path, "ml = 3\n")?;
+        let err = AliasFilter::load(&path).unwrap_err().to_string();
+        assert!(err.contains("term = \"replacement\""), "{}", err);
+        Ok(())
+    }
+}
@@ -0,0 +1,376 @@
+// Vocabulary inspection: `voltai top-terms` shows which terms dominate an index, by how many
+// documents they occur in and by their TF-IDF weight; `voltai doc-terms` shows the term
+// weights of a single document; `query --show-term-coverage` checks a query against the
+// vocabulary.
+use std::collections::HashSet;
+use std::io::Write;
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::similarity::weighted_vector;
+use crate::stats::{csv_field, doc_freq_distribution, TermDistribution};
+use crate::{markdown_unsupported, Index, OutputFormat, STOP_WORDS};
+
+/// Ranking used by `voltai top-terms --sort-by`.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TermSortBy {
+    /// Number of documents containing the term.
+    #[default]
+    DocFreq,
+    /// Average TF-IDF weight over the corpus.
+    TfIdf,
+}
+
+/// Corpus-wide statistics for one index term. TF-IDF weights are the stored term weight
+/// times the term's IDF.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TermStats {
+    pub term: String,
+    pub doc_freq: usize,
+    /// Mean weight over every document, counting documents without the term as 0.
+    pub avg_tfidf: f32,
+    pub max_tfidf: f32,
+}
+
+/// Statistics for every term of `index`, in vocabulary order.
+pub fn compute_term_stats(index: &Index) -> Vec<TermStats> {
+    let n_docs = index.vectors.len();
+    index
+        .terms
+        .iter()
+        .enumerate()
+        .map(|(t, term)| {
+            let idf = index.idf.get(t).copied().unwrap_or(0.0);
+            let (mut doc_freq, mut sum, mut max) = (0, 0.0_f32, 0.0_f32);
+            // Presence is decided by the stored weight: a term in every document can have
+            // an IDF of zero and still occur.
+            for v in &index.vectors {
+                let weight = v.get(t).copied().unwrap_or(0.0);
+                if weight != 0.0 {
+                    doc_freq += 1;
+                    sum += weight * idf;
+                    max = max.max(weight * idf);
+                }
+            }
+            TermStats {
+                term: term.clone(),
+                doc_freq,
+                avg_tfidf: if n_docs == 0 {
+                    0.0
+                } else {
+                    sum / n_docs as f32
+                },
+                max_tfidf: max,
+            }
+        })
+        .collect()
+}
+
+/// The `n` highest-ranked terms by `sort_by`, ties broken alphabetically. With
+/// `exclude_stop_words` the built-in stop-word list is dropped first; `voltai index` already
+/// omits those words, so this only matters for indexes written by other tools.
+pub fn top_terms(
+    index: &Index,
+    n: usize,
+    sort_by: TermSortBy,
+    exclude_stop_words: bool,
+) -> Vec<TermStats> {
+    let mut stats: Vec<TermStats> = compute_term_stats(index)
+        .into_iter()
+        .filter(|s| !exclude_stop_words || !STOP_WORDS.contains(s.term.as_str()))
+        .collect();
+    stats.sort_by(|a, b| {
+        let primary = match sort_by {
+            TermSortBy::DocFreq => b
+                .doc_freq
+                .cmp(&a.doc_freq)
+                .then(b.avg_tfidf.total_cmp(&a.avg_tfidf)),
+            TermSortBy::TfIdf => b.avg_tfidf.total_cmp(&a.avg_tfidf),
+        };
+        primary.then_with(|| a.term.cmp(&b.term))
+    });
+    stats.truncate(n);
+    stats
+}
+
+/// Document-frequency histogram and Zipf fit of the whole vocabulary of `index`
+/// (`voltai top-terms --histogram`).
+pub fn index_term_distribution(index: &Index) -> TermDistribution {
+    let doc_freqs: Vec<usize> = compute_term_stats(index)
+        .iter()
+        .map(|s| s.doc_freq)
+        .collect();
+    doc_freq_distribution(&doc_freqs)
+}
+
+pub fn write_term_stats(stats: &[TermStats], out: &mut dyn Write) -> Result<()> {
+    writeln!(
+        out,
+        "{:<24} {:>8} {:>10} {:>10}",
+        "term", "doc_freq", "avg_tfidf", "max_tfidf"
+    )?;
+    for s in stats {
+        writeln!(
+            out,
+            "{:<24} {:>8} {:>10.4} {:>10.4}",
+            s.term, s.doc_freq, s.avg_tfidf, s.max_tfidf
+        )?;
+    }
+    Ok(())
+}
+
+/// One `(term, weight)` entry of a document vector.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TermWeight {
+    pub term: String,
+    pub weight: f32,
+}
+
+/// The heaviest terms of one document, plus summary figures for its whole vector.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocTerms {
+    pub path: String,
+    pub terms: Vec<TermWeight>,
+    /// Euclidean norm of the full IDF-weighted vector.
+    pub norm: f32,
+    pub non_zero: usize,
+}
+
+/// The `top_n` terms of document `di` by IDF-weighted BM25 weight (the weight queries
+/// score against), heaviest first and ties broken alphabetically.
+pub fn doc_terms(index: &Index, di: usize, top_n: usize) -> DocTerms {
+    let weights = weighted_vector(index, di);
+    let mut terms: Vec<TermWeight> = weights
+        .iter()
+        .zip(&index.terms)
+        .filter(|(w, _)| **w != 0.0)
+        .map(|(&weight, term)| TermWeight {
+            term: term.clone(),
+            weight,
+        })
+        .collect();
+    let non_zero = terms.len();
+    terms.sort_by(|a, b| {
+        b.weight
+            .total_cmp(&a.weight)
+            .then_with(|| a.term.cmp(&b.term))
+    });
+    terms.truncate(top_n);
+    DocTerms {
+        path: index.docs[di].path.clone(),
+        terms,
+        norm: weights.iter().map(|w| w * w).sum::<f32>().sqrt(),
+        non_zero,
+    }
+}
+
+/// Renders `report`. JSON is an array of `{term, weight}`, JSONL one object per line and
+/// CSV one row per term; only the text output includes the norm and non-zero count.
+pub fn write_doc_terms(report: &DocTerms, format: OutputFormat, out: &mut dyn Write) -> Result<()> {
+    match format {
+        OutputFormat::Json | OutputFormat::PrettyJson => {
+            serde_json::to_writer_pretty(&mut *out, &report.terms)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Jsonl => {
+            for t in &report.terms {
+                serde_json::to_writer(&mut *out, t)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv => {
+            writeln!(out, "term,weight")?;
+            for t in &report.terms {
+                writeln!(out, "{},{:.4}", csv_field(&t.term), t.weight)?;
+            }
+        }
+        OutputFormat::Markdown => return Err(markdown_unsupported()),
+        OutputFormat::Text => {
+            writeln!(out, "Document: {}", report.path)?;
+            writeln!(out, "  vector norm:     {:.4}", report.norm)?;
+            writeln!(out, "  non-zero terms:  {}", report.non_zero)?;
+            for t in &report.terms {
+                writeln!(out, "  {:<24} {:.4}", t.term, t.weight)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Which query tokens the index vocabulary knows, from [`terms_overlap`].
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TermsOverlapResult {
+    pub matched: Vec<String>,
+    pub missing: Vec<String>,
+    /// `matched.len()` over the number of distinct query tokens; 0.0 for a query with no
+    /// tokens.
+    pub coverage: f32,
+}
+
+/// Splits the distinct tokens of `query` (as tokenized for retrieval, so stop words are
+/// left out) into those in `index`'s vocabulary and those missing, in query order.
+pub fn terms_overlap(query: &str, index: &Index) -> TermsOverlapResult {
+    let vocabulary: HashSet<&str> = index.terms.iter().map(String::as_str).collect();
+    let mut seen = HashSet::new();
+    let (matched, missing): (Vec<String>, Vec<String>) = index
+        .tokenize_query(query)
+        .into_iter()
+        .filter(|t| seen.insert(t.clone()))
+        .partition(|t| vocabulary.contains(t.as_str()));
+    let total = matched.len() + missing.len();
+    TermsOverlapResult {
+        coverage: if total == 0 {
+            0.0
+        } else {
+            matched.len() as f32 / total as f32
+        },
+        matched,
+        missing,
+    }
+}
+
+/// Text report for `query --show-term-coverage`, with a warning when no term matches.
+pub fn write_terms_overlap(overlap: &TermsOverlapResult, out: &mut dyn Write) -> Result<()> {
+    writeln!(
+        out,
+        "Term coverage: {:.0}% (matched: [{}]; missing: [{}])",
+        overlap.coverage * 100.0,
+        overlap.matched.join(", "),
+        overlap.missing.join(", ")
+    )?;
+    if overlap.coverage == 0.0 {
+        writeln!(
+            out,
+            "None of your query terms are in the index vocabulary \u{2014} results may be poor."
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_index, tokenize, Doc, RankingMethod};
+
+    fn index_of(texts: &[&str]) -> Index {
+        let docs: Vec<Doc> = texts
+            .iter()
+            .enumerate()
+            .map(|(i, t)| Doc {
+                id: format!("doc-{i}"),
+                path: format!("{i}.txt"),
+                text: t.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let tokens: Vec<Vec<String>> = docs.iter().map(|d| tokenize(&d.text)).collect();
+        build_index(docs, &tokens, RankingMethod::Bm25, true)
+    }
+
+    #[test]
+    fn test_terms_overlap_coverage() -> Result<()> {
+        let idx = index_of(&["rust compiler borrow checker", "python interpreter"]);
+        let half = terms_overlap("rust borrow haskell monads", &idx);
+        assert_eq!(half.matched, vec!["rust", "borrow"]);
+        assert_eq!(half.missing, vec!["haskell", "monads"]);
+        assert_eq!(half.coverage, 0.5);
+        // Stop words and repeated tokens do not count.
+        let third = terms_overlap("the python and the python gc tuning", &idx);
+        assert_eq!(third.matched, vec!["python"]);
+        assert!((third.coverage - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!(terms_overlap("compiler interpreter", &idx).coverage, 1.0);
+
+        let none = terms_overlap("sourdough starter", &idx);
+        assert_eq!(none.coverage, 0.0);
+        let mut out = Vec::new();
+        write_terms_overlap(&none, &mut out)?;
+        assert!(String::from_utf8(out)?.contains("None of your query terms"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_top_term_by_doc_freq_is_most_widespread() {
+        let idx = index_of(&[
+            "rust compiler borrow checker",
+            "rust cargo crates",
+            "rust async runtime",
+            "python interpreter",
+        ]);
+        let top = top_terms(&idx, 3, TermSortBy::DocFreq, false);
+        assert_eq!(top[0].term, "rust");
+        assert_eq!(top[0].doc_freq, 3);
+        assert!(top[0].max_tfidf >= top[0].avg_tfidf);
+    }
+
+    #[test]
+    fn test_tfidf_sort_prefers_rare_repeated_terms() {
+        let idx = index_of(&["common zebra zebra zebra", "common apple", "common banana"]);
+        let top = top_terms(&idx, 1, TermSortBy::TfIdf, false);
+        assert_eq!(top[0].term, "zebra");
+    }
+
+    #[test]
+    fn test_exclude_stop_words_drops_listed_terms() {
+        let mut idx = index_of(&["alpha beta", "beta gamma"]);
+        idx.terms[0] = "the".to_string();
+        let all = top_terms(&idx, usize::MAX, TermSortBy::DocFreq, false);
+        let filtered = top_terms(&idx, usize::MAX, TermSortBy::DocFreq, true);
+        assert!(all.iter().any(|s| s.term == "the"));
+        assert!(filtered.iter().all(|s| s.term != "the"));
+        assert_eq!(filtered.len(), all.len() - 1);
+    }
+
+    #[test]
+    fn test_index_term_distribution_follows_zipf() {
+        // Term `i` occurs in every `i`-th document, so its document frequency is 120 / i.
+        const WORDS: [&str; 8] = [
+            "alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel",
+        ];
+        let texts: Vec<String> = (1..=120)
+            .map(|d| {
+                WORDS
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| d % (i + 1) == 0)
+                    .map(|(_, w)| *w)
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        let idx = index_of(&texts.iter().map(String::as_str).collect::<Vec<_>>());
+        let dist = index_term_distribution(&idx);
+        let zipf = dist.zipf_coefficient.unwrap();
+        assert!((zipf + 1.0).abs() < 0.05, "{}", zipf);
+        let terms: Vec<usize> = dist.document_frequency.iter().map(|b| b.terms).collect();
+        // 20, 17, 15 | 60, 40, 30, 24 | 120
+        assert_eq!(terms, [0, 3, 4, 1, 0]);
+    }
+
+    #[test]
+    fn test_doc_terms_top_term_is_most_frequent_word() {
+        let idx = index_of(&[
+            "lighthouse keeper watched the lighthouse beam; the lighthouse stood tall",
+            "keeper of the garden",
+        ]);
+        let report = doc_terms(&idx, 0, 3);
+        assert_eq!(report.terms[0].term, "lighthouse");
+        assert_eq!(report.terms.len(), 3);
+        // lighthouse, keeper, watched, beam, stood, tall
+        assert_eq!(report.non_zero, 6);
+        assert!(report.norm > report.terms[0].weight);
+    }
+
+    #[test]
+    fn test_doc_terms_json_is_an_array_of_pairs() {
+        let idx = index_of(&["solar solar wind", "tidal power"]);
+        let mut out = Vec::new();
+        write_doc_terms(&doc_terms(&idx, 0, 10), OutputFormat::Json, &mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let arr = json.as_array().unwrap();
+        assert_eq!(arr.len(), 2);
+        assert_eq!(arr[0]["term"], "solar");
+        assert!(arr[0]["weight"].as_f64().unwrap() > arr[1]["weight"].as_f64().unwrap());
+    }
+}
@@ -0,0 +1,151 @@
+// Topic labels for `voltai index --assign-topics`: k-means over the IDF-weighted document
+// vectors, with each cluster labelled by the heaviest terms of its centroid.
+use rand::{rngs::StdRng, seq::index::sample, SeedableRng};
+
+use crate::similarity::{cosine_similarity, weighted_vector};
+use crate::Index;
+
+/// Labels taken from each cluster centroid.
+pub const TOPIC_LABELS: usize = 5;
+const MAX_ITERATIONS: usize = 20;
+
+fn mean(vectors: &[Vec<f32>], members: impl Iterator<Item = usize>, dim: usize) -> Vec<f32> {
+    let mut sum = vec![0.0_f32; dim];
+    let mut n = 0;
+    for i in members {
+        for (s, w) in sum.iter_mut().zip(&vectors[i]) {
+            *s += w;
+        }
+        n += 1;
+    }
+    if n > 0 {
+        sum.iter_mut().for_each(|s| *s /= n as f32);
+    }
+    sum
+}
+
+/// Spherical k-means (cosine similarity) over the index's IDF-weighted document vectors,
+/// seeded with `k` distinct documents chosen by `seed`. Returns each document's cluster and
+/// the cluster centroids; `k` is capped at the number of documents.
+pub fn cluster_documents(index: &Index, k: usize, seed: u64) -> (Vec<usize>, Vec<Vec<f32>>) {
+    let n = index.docs.len();
+    let k = k.min(n);
+    if k == 0 {
+        return (vec![0; n], Vec::new());
+    }
+    let dim = index.terms.len();
+    let vectors: Vec<Vec<f32>> = (0..n).map(|di| weighted_vector(index, di)).collect();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut centroids: Vec<Vec<f32>> = sample(&mut rng, n, k)
+        .into_iter()
+        .map(|i| vectors[i].clone())
+        .collect();
+    let mut assignments = vec![usize::MAX; n];
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (v, assigned) in vectors.iter().zip(assignments.iter_mut()) {
+            let best = (0..k)
+                .map(|c| (c, cosine_similarity(v, &centroids[c])))
+                .fold(
+                    (0, f32::MIN),
+                    |best, cur| if cur.1 > best.1 { cur } else { best },
+                )
+                .0;
+            changed |= *assigned != best;
+            *assigned = best;
+        }
+        if !changed {
+            break;
+        }
+        for (c, centroid) in centroids.iter_mut().enumerate() {
+            let members = assignments.iter().enumerate().filter(|&(_, &a)| a == c);
+            // An emptied cluster keeps its previous centroid.
+            if members.clone().next().is_some() {
+                *centroid = mean(&vectors, members.map(|(i, _)| i), dim);
+            }
+        }
+    }
+    (assignments, centroids)
+}
+
+/// The [`TOPIC_LABELS`] heaviest terms of each centroid, handed to every document in that
+/// cluster. `cluster_assignments` is parallel to the index's documents.
+pub fn assign_topics(
+    index: &Index,
+    cluster_assignments: &[usize],
+    centroids: &[Vec<f32>],
+) -> Vec<Vec<String>> {
+    let labels: Vec<Vec<String>> = centroids
+        .iter()
+        .map(|centroid| {
+            let mut weighted: Vec<(usize, f32)> = centroid
+                .iter()
+                .copied()
+                .enumerate()
+                .filter(|&(_, w)| w > 0.0)
+                .collect();
+            weighted.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+            weighted
+                .into_iter()
+                .take(TOPIC_LABELS)
+                .map(|(t, _)| index.terms[t].clone())
+                .collect()
+        })
+        .collect();
+    cluster_assignments
+        .iter()
+        .map(|&c| labels.get(c).cloned().unwrap_or_default())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_index, tokenize, Doc, RankingMethod};
+
+    fn index_of(texts: &[&str]) -> Index {
+        let docs: Vec<Doc> = texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| Doc {
+                id: format!("doc{}", i),
+                path: format!("doc{}.txt", i),
+                text: text.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let tokens: Vec<Vec<String>> = texts.iter().map(|t| tokenize(t)).collect();
+        build_index(docs, &tokens, RankingMethod::Bm25, true)
+    }
+
+    #[test]
+    fn test_every_doc_gets_topics_from_its_cluster() {
+        let idx = index_of(&[
+            "rust borrow checker lifetimes ownership",
+            "rust ownership borrow lifetimes traits",
+            "sourdough bread flour yeast oven",
+            "bread yeast flour starter oven",
+        ]);
+        let (assignments, centroids) = cluster_documents(&idx, 2, 7);
+        assert_eq!(centroids.len(), 2);
+        assert_eq!(assignments[0], assignments[1]);
+        assert_eq!(assignments[2], assignments[3]);
+        assert_ne!(assignments[0], assignments[2]);
+
+        let topics = assign_topics(&idx, &assignments, &centroids);
+        assert!(topics
+            .iter()
+            .all(|t| !t.is_empty() && t.len() <= TOPIC_LABELS));
+        assert!(topics[0].iter().any(|t| t == "rust" || t == "borrow"));
+        assert!(topics[2].iter().any(|t| t == "bread" || t == "yeast"));
+        assert_eq!(topics[2], topics[3]);
+    }
+
+    #[test]
+    fn test_cluster_count_is_capped_at_doc_count() {
+        let idx = index_of(&["alpha beta", "gamma delta"]);
+        let (assignments, centroids) = cluster_documents(&idx, 10, 0);
+        assert_eq!(centroids.len(), 2);
+        assert_eq!(assignments.len(), 2);
+    }
+}
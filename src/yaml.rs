@@ -0,0 +1,102 @@
+// String values of YAML files for indexing: the text is kept, keys, numbers, booleans and
+// nulls are not.
+use anyhow::Result;
+use serde::Deserialize;
+use serde_yaml::Value;
+
+/// The string scalars of every document in `text`, in document order.
+pub fn string_leaves(text: &str) -> Result<Vec<String>> {
+    let mut leaves = Vec::new();
+    for document in serde_yaml::Deserializer::from_str(text) {
+        value_strings(&Value::deserialize(document)?, &mut leaves);
+    }
+    Ok(leaves)
+}
+
+/// Appends the string scalars under `value` to `out`; mapping keys are skipped.
+pub fn value_strings(value: &Value, out: &mut Vec<String>) {
+    match value {
+        Value::String(s) => out.push(s.clone()),
+        Value::Sequence(items) => items.iter().for_each(|v| value_strings(v, out)),
+        Value::Mapping(mapping) => mapping.values().for_each(|v| value_strings(v, out)),
+        Value::Tagged(tagged) => value_strings(&tagged.value, out),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_values_without_keys_or_numbers() -> Result<()> {
+        let yaml = "\
+# service settings
+service:
+  name: tide-api
+  replicas: 3
+  enabled: true
+  owner:
+    team: \"harbour ops\"  # on call
+    contact: 'dock''s desk'
+  tags: [tides, charts]
+  endpoints:
+    - path: /forecast
+      summary: Hourly forecast
+    - /health
+  description: |
+    Serves tide tables
+    for the harbour.
+limits: {region: north sea, max: 10}
+";
+        assert_eq!(
+            string_leaves(yaml)?,
+            [
+                "tide-api",
+                "harbour ops",
+                "dock's desk",
+                "tides",
+                "charts",
+                "/forecast",
+                "Hourly forecast",
+                "/health",
+                "Serves tide tables\nfor the harbour.\n",
+                "north sea",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_urls_and_hashes_inside_values_are_kept() -> Result<()> {
+        assert_eq!(
+            string_leaves("url: https://example.com/a#top\ncolor: \"#ff0000\"\n")?,
+            ["https://example.com/a#top", "#ff0000"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_quoted_commas_and_multi_line_scalars() -> Result<()> {
+        let yaml = "\
+tags: [\"a, b\", c,
+  d]
+note: a plain scalar
+  folded over two lines
+---
+second: document
+";
+        assert_eq!(
+            string_leaves(yaml)?,
+            [
+                "a, b",
+                "c",
+                "d",
+                "a plain scalar folded over two lines",
+                "document"
+            ]
+        );
+        assert!(string_leaves("key: [unclosed").is_err());
+        Ok(())
+    }
+}